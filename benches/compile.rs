@@ -0,0 +1,143 @@
+// Compile time of large synthetic programs, run with `cargo bench`. Criterion compares each
+// run with the previous one, so a refactor that slows down a phase shows up as a regression.
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use ezlang::{
+    compiler::{Compiler, CompilerOptions},
+    lexer::{Edition, Lexer},
+    parser::{Limits, Parser},
+};
+
+const FUNCTIONS: usize = 10_000;
+// Operations nested in each expression, below the default expression depth limit
+const DEPTH: usize = 200;
+const DEEP_FUNCTIONS: usize = 100;
+
+// Public so code is generated for every function, not only the ones main calls
+fn write_functions(count: usize) -> String {
+    let mut source = String::new();
+
+    for index in 0..count {
+        source.push_str(&format!(
+            "pub fn f{}: (a: i64, b: i64) -> i64 {{
+    var c: i64 = a * 3 + b - (a & b) ^ {};
+    var d: i64 = c + a * b + c * 2;
+    return d + c;
+}}
+",
+            index, index
+        ));
+    }
+
+    source.push_str("fn main: () -> i64 {\n    return 0;\n}\n");
+
+    return source;
+}
+
+// Right nested operations like `a + (b * (a - ...))`, each level a binary expression
+fn write_deep_expressions(count: usize, depth: usize) -> String {
+    let operators = ["+", "*", "-", "&", "|", "^"];
+    let mut source = String::new();
+
+    for index in 0..count {
+        let mut expression = String::from("b");
+
+        for level in 0..depth {
+            let operator = operators[(index + level) % operators.len()];
+            expression = format!("a {} ({})", operator, expression);
+        }
+
+        source.push_str(&format!(
+            "pub fn g{}: (a: i64, b: i64) -> i64 {{\n    return {};\n}}\n",
+            index, expression
+        ));
+    }
+
+    source.push_str("fn main: () -> i64 {\n    return 0;\n}\n");
+
+    return source;
+}
+
+fn get_limits() -> Limits {
+    return Limits {
+        functions: FUNCTIONS * 2,
+        ..Default::default()
+    };
+}
+
+fn lex(source: Vec<u8>) -> usize {
+    let mut lexer = Lexer::from_source("bench.ez", source, Edition::English);
+    let mut count = 0;
+
+    while lexer.next().is_some() {
+        count += 1;
+    }
+
+    return count;
+}
+
+fn parse(source: Vec<u8>) -> usize {
+    let mut parser = Parser::from_source("bench.ez", source, Edition::English);
+    parser.set_limits(get_limits());
+    parser.generate_tokens();
+
+    return parser.generate_program().functions.len();
+}
+
+fn generate(source: Vec<u8>) -> String {
+    let options = CompilerOptions {
+        limits: get_limits(),
+        ..Default::default()
+    };
+
+    let mut compiler = Compiler::from_source("bench.ez", source, options);
+    compiler.generate();
+
+    return compiler.get_assembly();
+}
+
+fn bench_phases(criterion: &mut Criterion, name: &str, source: &str) {
+    let mut group = criterion.benchmark_group(name);
+    group.sample_size(10);
+
+    group.bench_function("lex", |bencher| {
+        bencher.iter_batched(
+            || source.as_bytes().to_vec(),
+            |source| black_box(lex(source)),
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.bench_function("parse", |bencher| {
+        bencher.iter_batched(
+            || source.as_bytes().to_vec(),
+            |source| black_box(parse(source)),
+            BatchSize::LargeInput,
+        )
+    });
+
+    // Parsing included, the code generator needs the whole program
+    group.bench_function("generate", |bencher| {
+        bencher.iter_batched(
+            || source.as_bytes().to_vec(),
+            |source| black_box(generate(source)),
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.finish();
+}
+
+fn bench_functions(criterion: &mut Criterion) {
+    bench_phases(criterion, "functions", &write_functions(FUNCTIONS));
+}
+
+fn bench_deep_expressions(criterion: &mut Criterion) {
+    bench_phases(
+        criterion,
+        "deep_expressions",
+        &write_deep_expressions(DEEP_FUNCTIONS, DEPTH),
+    );
+}
+
+criterion_group!(benches, bench_functions, bench_deep_expressions);
+criterion_main!(benches);
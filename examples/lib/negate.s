@@ -0,0 +1,9 @@
+;# Runtime support routine using the ez calling convention:
+;# arguments are pushed on the stack and the result is returned in rax. Results narrower
+;# than 64 bits only need their low bits set, the caller extends them.
+;# Comments start with ;# and operands leave out their size, so both NASM and GNU as in
+;# Intel syntax assemble the file.
+negate:
+	mov rax, [rsp + 0x8]
+	neg rax
+	ret
@@ -0,0 +1,17 @@
+;# Writes a null terminated string to the standard output.
+;# Takes the string address as its only argument and returns the written length.
+;# Written for both NASM and GNU as in Intel syntax, like negate.s.
+puts:
+	mov rsi, [rsp + 0x8]
+	xor rdx, rdx
+.count:
+	mov al, [rsi + rdx]
+	test al, al
+	jz .write
+	inc rdx
+	jmp .count
+.write:
+	mov rax, 0x1
+	mov rdi, 0x1
+	syscall
+	ret
@@ -0,0 +1,492 @@
+use crate::{
+    diagnostics::Fix,
+    lexer::{Edition, TokenType},
+    parser::{Function, FunctionId, Intrinsic, Program, Reference, Struct, Type},
+    span::{Position, Spanned},
+    syntax::{SyntaxElement, SyntaxKind, SyntaxToken, SyntaxTree},
+};
+
+// Description of the symbol under a position, covering `length` bytes of its identifier
+#[derive(Debug)]
+pub struct Hover {
+    pub position: Position,
+    pub length: usize,
+    pub contents: String,
+}
+
+// Classes of tokens for highlighting, named like the standard LSP token types
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SemanticKind {
+    Keyword,
+    Number,
+    String,
+    Operator,
+    Variable,
+    Parameter,
+    Function,
+    Namespace,
+    Struct,
+    Property,
+    // Built in types like i64
+    Type,
+}
+
+impl SemanticKind {
+    pub fn get_name(&self) -> &'static str {
+        return match self {
+            SemanticKind::Keyword => "keyword",
+            SemanticKind::Number => "number",
+            SemanticKind::String => "string",
+            SemanticKind::Operator => "operator",
+            SemanticKind::Variable => "variable",
+            SemanticKind::Parameter => "parameter",
+            SemanticKind::Function => "function",
+            SemanticKind::Namespace => "namespace",
+            SemanticKind::Struct => "struct",
+            SemanticKind::Property => "property",
+            SemanticKind::Type => "type",
+        };
+    }
+}
+
+#[derive(Debug)]
+pub struct SemanticToken {
+    pub position: Position,
+    pub length: usize,
+    pub kind: SemanticKind,
+}
+
+// Type or definition of the identifier at a position, which may be anywhere inside it
+pub fn hover(program: &Program, position: &Position) -> Option<Hover> {
+    let (function, start, reference) = find_reference(program, position)?;
+    let function = &program.functions[function];
+
+    return Some(Hover {
+        position: start.clone(),
+        length: get_name(program, function, reference)?.len(),
+        contents: get_contents(program, function, reference)?,
+    });
+}
+
+// Edits renaming the local or function at a position everywhere it is declared or used.
+// Names that would change what other identifiers resolve to are rejected.
+pub fn rename(program: &Program, position: &Position, new_name: &str) -> Result<Vec<Fix>, String> {
+    let (function_index, _, reference) = match find_reference(program, position) {
+        Some(found) => found,
+        None => return Err(String::from("No variable or function at this position.")),
+    };
+
+    let function = &program.functions[function_index];
+
+    let mut characters = new_name.chars();
+    let valid = match characters.next() {
+        Some(first) => {
+            (first.is_ascii_alphabetic() || first == '_')
+                && characters.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        None => false,
+    };
+
+    let keyword = Edition::English
+        .get_keywords()
+        .iter()
+        .chain(Edition::Spanish.get_keywords())
+        .any(|(keyword, _)| *keyword == new_name);
+
+    if !valid || keyword {
+        return Err(format!("{} is not a valid identifier.", new_name));
+    }
+
+    // Every reference of the same symbol, which for locals only appear in their function
+    let positions: Vec<&Position> = match reference {
+        Reference::Local(index) => {
+            if function
+                .locals
+                .locals
+                .iter()
+                .any(|local| local.label == new_name)
+            {
+                return Err(format!(
+                    "A variable named {} already exists in {}.",
+                    new_name,
+                    function.get_display_name()
+                ));
+            }
+
+            function
+                .references
+                .iter()
+                .filter(|reference| reference.value == Reference::Local(*index))
+                .map(|reference| &reference.position)
+                .collect()
+        }
+        Reference::Function(id) => {
+            // Calls without a module could resolve to another function with the same name
+            if !program.table.find(new_name).is_empty()
+                || program
+                    .generics
+                    .iter()
+                    .any(|(_, generic)| generic.name == new_name)
+            {
+                return Err(format!("A function named {} already exists.", new_name));
+            }
+
+            if is_builtin(new_name) {
+                return Err(format!("{} is a built in function.", new_name));
+            }
+
+            // Instances of a generic function are written with its name, so all of them change
+            let target = &program.functions[*id];
+            let renamed: Vec<FunctionId> = match target.type_arguments.is_empty() {
+                true => vec![*id],
+                false => program
+                    .table
+                    .find(&target.name)
+                    .iter()
+                    .filter(|other| program.functions[**other].module == target.module)
+                    .copied()
+                    .collect(),
+            };
+
+            program
+                .functions
+                .iter()
+                .flat_map(|function| function.references.iter())
+                .filter(|reference| match reference.value {
+                    Reference::Function(other) => renamed.contains(&other),
+                    _ => false,
+                })
+                .map(|reference| &reference.position)
+                .collect()
+        }
+        _ => return Err(String::from("Only variables and functions can be renamed.")),
+    };
+
+    let length = match get_name(program, function, reference) {
+        Some(name) => name.len(),
+        None => return Err(String::from("No variable or function at this position.")),
+    };
+
+    let mut edits: Vec<Fix> = positions
+        .into_iter()
+        .map(|position| Fix {
+            position: position.clone(),
+            length,
+            replacement: new_name.to_owned(),
+        })
+        .collect();
+
+    edits.sort_by_key(|edit| {
+        (
+            edit.position.file.to_owned(),
+            edit.position.line,
+            edit.position.column,
+        )
+    });
+
+    // The declaration of a generic function is recorded again by each of its instances
+    edits.dedup_by(|a, b| {
+        a.position.file == b.position.file
+            && a.position.line == b.position.line
+            && a.position.column == b.position.column
+    });
+
+    return Ok(edits);
+}
+
+// Every token of the tree that has a class, in source order. Identifiers are classified
+// by what the program resolved them to, so the tree and program must come from the same source.
+pub fn semantic_tokens(program: &Program, tree: &SyntaxTree) -> Vec<SemanticToken> {
+    let references: Vec<(&Function, &Position, &Reference)> = program
+        .functions
+        .iter()
+        .filter(|function| function.position.file == tree.filename)
+        .flat_map(|function| {
+            function
+                .references
+                .iter()
+                .map(move |reference| (function, &reference.position, &reference.value))
+        })
+        .collect();
+
+    let mut tokens: Vec<SemanticToken> = Vec::new();
+
+    for item in tree.root.children.iter() {
+        let (kind, item_tokens) = match item {
+            SyntaxElement::Node(node) => (node.kind, node.get_tokens()),
+            SyntaxElement::Token(token) => (SyntaxKind::File, vec![token]),
+        };
+
+        for (index, token) in item_tokens.iter().enumerate() {
+            let semantic_kind = match &token.token.token_type {
+                TokenType::Identifier(name) => {
+                    let position = &token.token.position;
+
+                    let reference = references.iter().find(|(_, start, _)| {
+                        start.line == position.line && start.column == position.column
+                    });
+
+                    match reference {
+                        Some((function, _, reference)) => Some(classify(function, reference)),
+                        None if kind == SyntaxKind::Struct => Some(classify_declaration(
+                            program,
+                            &name.get_name(),
+                            &item_tokens,
+                            index,
+                        )),
+                        None => classify_name(program, &name.get_name()),
+                    }
+                }
+                token_type => classify_token(token_type),
+            };
+
+            if let Some(kind) = semantic_kind {
+                tokens.push(SemanticToken {
+                    position: token.token.position.clone(),
+                    length: token.text.len(),
+                    kind,
+                });
+            }
+        }
+    }
+
+    return tokens;
+}
+
+fn classify(function: &Function, reference: &Reference) -> SemanticKind {
+    return match reference {
+        Reference::Local(index) if function.arguments.contains(index) => SemanticKind::Parameter,
+        Reference::Local(_) => SemanticKind::Variable,
+        Reference::Function(_) => SemanticKind::Function,
+        Reference::Module(_) => SemanticKind::Namespace,
+        Reference::Struct(_) => SemanticKind::Struct,
+        Reference::Field(..) => SemanticKind::Property,
+        Reference::Constant(_) => SemanticKind::Variable,
+    };
+}
+
+// Struct declarations are outside of functions, their names and fields are never referenced
+fn classify_declaration(
+    program: &Program,
+    name: &str,
+    tokens: &[&SyntaxToken],
+    index: usize,
+) -> SemanticKind {
+    let get = |offset: Option<usize>| {
+        return offset
+            .and_then(|offset| tokens.get(offset))
+            .map(|token| &token.token.token_type);
+    };
+
+    if let Some(TokenType::Struct) = get(index.checked_sub(1)) {
+        return SemanticKind::Struct;
+    }
+
+    if let Some(TokenType::Colon) = get(Some(index + 1)) {
+        return SemanticKind::Property;
+    }
+
+    return classify_name(program, name).unwrap_or(SemanticKind::Type);
+}
+
+// Names the parser resolves without recording a reference
+fn classify_name(program: &Program, name: &str) -> Option<SemanticKind> {
+    if Type::from_name(name).is_some() {
+        return Some(SemanticKind::Type);
+    }
+
+    if program
+        .structs
+        .iter()
+        .any(|(_, layout)| layout.name == name)
+    {
+        return Some(SemanticKind::Struct);
+    }
+
+    if program
+        .constants
+        .iter()
+        .any(|(_, constant)| constant.name == name)
+    {
+        return Some(SemanticKind::Variable);
+    }
+
+    if is_builtin(name) {
+        return Some(SemanticKind::Function);
+    }
+
+    return None;
+}
+
+// Calls built into the compiler, addr and printf are parsed apart from the intrinsics
+fn is_builtin(name: &str) -> bool {
+    return name == "addr" || name == "printf" || Intrinsic::from_name(name).is_some();
+}
+
+fn classify_token(token_type: &TokenType) -> Option<SemanticKind> {
+    return match token_type {
+        TokenType::Function
+        | TokenType::Pub
+        | TokenType::Extern
+        | TokenType::Var
+        | TokenType::Struct
+        | TokenType::Const
+        | TokenType::StaticAssert
+        | TokenType::Return
+        | TokenType::Goto
+        | TokenType::As
+        | TokenType::Include
+        | TokenType::IncludeAsm
+        | TokenType::If
+        | TokenType::While
+        | TokenType::For
+        | TokenType::True
+        | TokenType::False => Some(SemanticKind::Keyword),
+        TokenType::NumberLiteral(_)
+        | TokenType::TypedNumberLiteral(..)
+        | TokenType::FloatLiteral(_) => Some(SemanticKind::Number),
+        TokenType::StringLiteral(_) | TokenType::Character(_) => Some(SemanticKind::String),
+        TokenType::Equals
+        | TokenType::Arrow
+        | TokenType::UnaryNot
+        | TokenType::UnaryInc
+        | TokenType::UnaryDec
+        | TokenType::BinaryOperation(_) => Some(SemanticKind::Operator),
+        _ => None,
+    };
+}
+
+// Index of the function recording the identifier at a position, with where the identifier
+// starts and what it refers to
+fn find_reference<'a>(
+    program: &'a Program,
+    position: &Position,
+) -> Option<(usize, &'a Position, &'a Reference)> {
+    for (index, function) in program.functions.iter().enumerate() {
+        for Spanned {
+            position: start,
+            value: reference,
+        } in function.references.iter()
+        {
+            let name = match get_name(program, function, reference) {
+                Some(name) => name,
+                None => continue,
+            };
+
+            if start.file == position.file
+                && start.line == position.line
+                && start.column <= position.column
+                && position.column < start.column + name.len()
+            {
+                return Some((index, start, reference));
+            }
+        }
+    }
+
+    return None;
+}
+
+// Identifier as written in the source for the reference
+fn get_name(program: &Program, function: &Function, reference: &Reference) -> Option<String> {
+    return match reference {
+        Reference::Local(index) => Some(function.locals.get(*index)?.label.to_string()),
+        Reference::Function(id) => Some(program.functions.get(id.0)?.name.to_owned()),
+        Reference::Module(module) => Some(module.to_owned()),
+        Reference::Struct(name) => Some(name.to_owned()),
+        Reference::Field(name, field) => Some(
+            find_struct(program, name)?
+                .fields
+                .get(*field)?
+                .name
+                .to_owned(),
+        ),
+        Reference::Constant(name) => Some(name.to_owned()),
+    };
+}
+
+fn get_contents(program: &Program, function: &Function, reference: &Reference) -> Option<String> {
+    return match reference {
+        Reference::Local(index) => {
+            let local = function.locals.get(*index)?;
+
+            Some(match function.arguments.contains(index) {
+                true => format!("(argument) {}: {}", local.label, local.local_type),
+                false => format!("var {}: {}", local.label, local.local_type),
+            })
+        }
+        Reference::Function(id) => Some(get_signature(program.functions.get(id.0)?)),
+        Reference::Module(module) => Some(format!("module {}", module)),
+        Reference::Struct(name) => {
+            let layout = find_struct(program, name)?;
+
+            let fields: Vec<String> = layout
+                .fields
+                .iter()
+                .map(|field| format!("    {}: {},\n", field.name, field.field_type))
+                .collect();
+
+            Some(format!(
+                "struct {} {{\n{}}}\nsize {}, alignment {}",
+                layout.name,
+                fields.concat(),
+                layout.size,
+                layout.alignment
+            ))
+        }
+        Reference::Field(name, field) => {
+            let field = find_struct(program, name)?.fields.get(*field)?;
+
+            Some(format!(
+                "{}.{}: {}\noffset {}",
+                name, field.name, field.field_type, field.offset
+            ))
+        }
+        Reference::Constant(name) => {
+            let (_, constant) = program
+                .constants
+                .iter()
+                .find(|(_, constant)| constant.name == *name)?;
+
+            Some(format!(
+                "const {}: {} = {}",
+                constant.name,
+                constant.constant_type,
+                match constant.constant_type.is_signed() {
+                    true => (constant.value as i64).to_string(),
+                    false => constant.value.to_string(),
+                }
+            ))
+        }
+    };
+}
+
+fn get_signature(function: &Function) -> String {
+    let arguments: Vec<String> = function
+        .arguments
+        .iter()
+        .filter_map(|argument| function.locals.get(*argument))
+        .map(|local| format!("{}: {}", local.label, local.local_type))
+        .collect();
+
+    let prefix = match (function.public, function.external) {
+        (_, true) => "extern ",
+        (true, false) => "pub ",
+        (false, false) => "",
+    };
+
+    return format!(
+        "{}fn {}: ({}) -> {}",
+        prefix,
+        function.get_display_name(),
+        arguments.join(", "),
+        function.return_type
+    );
+}
+
+fn find_struct<'a>(program: &'a Program, name: &str) -> Option<&'a Struct> {
+    return program
+        .structs
+        .iter()
+        .map(|(_, layout)| layout)
+        .find(|layout| layout.name == name);
+}
@@ -0,0 +1,288 @@
+use std::ops::Range;
+
+use clap::ValueEnum;
+
+use crate::{
+    parser::{Function, Program, Scope, Statement},
+    trace::describe_statement,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum CfgFormat {
+    /// Blocks with their statements and successors, one per line
+    #[default]
+    Text,
+    /// Graphviz digraph with a cluster per function
+    Dot,
+}
+
+// Statements that always run together, only entered at the first one
+#[derive(Debug)]
+pub struct Block {
+    // Indices into the statements of the body
+    pub statements: Range<usize>,
+    pub successors: Vec<usize>,
+}
+
+// Basic blocks of a function body, the first one is the entry and the last one the empty exit
+#[derive(Debug)]
+pub struct Graph {
+    pub blocks: Vec<Block>,
+}
+
+impl Graph {
+    pub fn get_exit(&self) -> usize {
+        return self.blocks.len() - 1;
+    }
+
+    // Blocks reachable from the entry
+    pub fn find_reachable(&self) -> Vec<bool> {
+        let mut reachable = vec![false; self.blocks.len()];
+        let mut pending = vec![0];
+
+        while let Some(block) = pending.pop() {
+            if reachable[block] {
+                continue;
+            }
+
+            reachable[block] = true;
+            pending.extend(self.blocks[block].successors.iter());
+        }
+
+        return reachable;
+    }
+}
+
+// Returns, aborts and gotos end a block, the statements after one only start a block nobody
+// jumps to unless they start at a label. Aborts never reach the exit.
+pub fn build_graph(body: &Scope) -> Graph {
+    let mut blocks: Vec<Block> = Vec::new();
+    let mut start = 0;
+
+    for (index, statement) in body.statements.iter().enumerate() {
+        if let (Statement::Label(_), true) = (statement, index > start) {
+            blocks.push(Block {
+                statements: start..index,
+                successors: Vec::new(),
+            });
+            start = index;
+        }
+
+        if matches!(statement, Statement::Return(_) | Statement::Goto(_))
+            || statement.is_diverging()
+        {
+            blocks.push(Block {
+                statements: start..index + 1,
+                successors: Vec::new(),
+            });
+            start = index + 1;
+        }
+    }
+
+    // Falling off the end of the body also leaves the function
+    if start < body.statements.len() || blocks.len() == 0 {
+        blocks.push(Block {
+            statements: start..body.statements.len(),
+            successors: Vec::new(),
+        });
+    }
+
+    let exit = blocks.len();
+
+    // Labels always start a block
+    let find_label = |label| {
+        return blocks.iter().position(|block: &Block| {
+            return match body.statements.get(block.statements.start) {
+                Some(Statement::Label(other)) => *other == label,
+                _ => false,
+            };
+        });
+    };
+
+    let successors: Vec<Vec<usize>> = blocks
+        .iter()
+        .enumerate()
+        .map(|(index, block)| {
+            let last = block
+                .statements
+                .clone()
+                .last()
+                .map(|last| &body.statements[last]);
+
+            return match last {
+                Some(Statement::Return(_)) => vec![exit],
+                Some(Statement::Goto(label)) => find_label(*label).into_iter().collect(),
+                Some(statement) if statement.is_diverging() => Vec::new(),
+                // Falls through to the next block, the exit after the last one
+                _ => vec![index + 1],
+            };
+        })
+        .collect();
+
+    for (block, successors) in blocks.iter_mut().zip(successors) {
+        block.successors = successors;
+    }
+
+    blocks.push(Block {
+        statements: body.statements.len()..body.statements.len(),
+        successors: Vec::new(),
+    });
+
+    return Graph { blocks };
+}
+
+// Functions that can fall off the end of their body, which leaves whatever was in rax as the
+// result. Constructors and destructors have nobody reading it.
+pub fn find_missing_returns(program: &Program) -> Vec<String> {
+    let mut warnings: Vec<String> = Vec::new();
+
+    for function in program.functions.iter() {
+        let body = match &function.body {
+            Some(body) => body,
+            None => continue,
+        };
+
+        if function.attributes.constructor || function.attributes.destructor {
+            continue;
+        }
+
+        let graph = build_graph(body);
+        let reachable = graph.find_reachable();
+
+        let missing = graph.blocks.iter().enumerate().any(|(index, block)| {
+            let returns = block.statements.clone().last().map_or(false, |last| {
+                matches!(body.statements[last], Statement::Return(_))
+            });
+
+            return reachable[index] && block.successors.contains(&graph.get_exit()) && !returns;
+        });
+
+        // Instances of a generic function share its position
+        let warning = format!(
+            "{}: warning: Function {} can reach the end of its body without returning a value.",
+            function.position, function.name
+        );
+
+        if missing && !warnings.contains(&warning) {
+            warnings.push(warning);
+        }
+    }
+
+    return warnings;
+}
+
+// Graph of every function with a body, as printed by --dump-cfg
+pub fn write_cfg(program: &Program, format: CfgFormat) -> String {
+    let functions = program.functions.iter().filter_map(|function| {
+        let body = function.body.as_ref()?;
+        return Some((function, body, build_graph(body)));
+    });
+
+    let mut output = String::new();
+
+    match format {
+        CfgFormat::Text => {
+            for (function, body, graph) in functions {
+                output.push_str(&format!("fn {}\n", function.get_display_name()));
+                output.push_str(&write_text(program, function, body, &graph));
+                output.push('\n');
+            }
+        }
+        CfgFormat::Dot => {
+            output.push_str("digraph cfg {\n    node [shape=box, fontname=monospace];\n");
+
+            for (function, body, graph) in functions {
+                output.push_str(&write_dot(program, function, body, &graph));
+            }
+
+            output.push_str("}\n");
+        }
+    }
+
+    return output;
+}
+
+fn get_block_name(graph: &Graph, block: usize) -> String {
+    return match block == graph.get_exit() {
+        true => "exit".to_owned(),
+        false => format!("bb{}", block),
+    };
+}
+
+fn write_text(program: &Program, function: &Function, body: &Scope, graph: &Graph) -> String {
+    let reachable = graph.find_reachable();
+    let mut text = String::new();
+
+    for (index, block) in graph.blocks.iter().enumerate() {
+        let unreachable = match reachable[index] {
+            true => "",
+            false => " (unreachable)",
+        };
+
+        text.push_str(&format!(
+            "  {}{}:\n",
+            get_block_name(graph, index),
+            unreachable
+        ));
+
+        for statement in block.statements.clone() {
+            let position = &body.spans[statement].position;
+
+            text.push_str(&format!(
+                "    {}:{}  {}\n",
+                position.line,
+                position.column,
+                describe_statement(&body.statements[statement], function, program)
+            ));
+        }
+
+        if block.successors.len() > 0 {
+            let successors: Vec<String> = block
+                .successors
+                .iter()
+                .map(|successor| get_block_name(graph, *successor))
+                .collect();
+
+            text.push_str(&format!("    -> {}\n", successors.join(", ")));
+        }
+    }
+
+    return text;
+}
+
+fn write_dot(program: &Program, function: &Function, body: &Scope, graph: &Graph) -> String {
+    let name = function.get_display_name();
+    let id = |block: usize| format!("\"{}.{}\"", name, get_block_name(graph, block));
+    let escape = |text: &str| text.replace('\\', "\\\\").replace('"', "\\\"");
+
+    let mut dot = format!(
+        "    subgraph \"cluster_{}\" {{\n        label=\"fn {}\";\n",
+        name, name
+    );
+
+    for (index, block) in graph.blocks.iter().enumerate() {
+        // \l ends a left aligned line
+        let mut label = format!("{}:\\l", get_block_name(graph, index));
+
+        for statement in block.statements.clone() {
+            label.push_str(&escape(&describe_statement(
+                &body.statements[statement],
+                function,
+                program,
+            )));
+            label.push_str("\\l");
+        }
+
+        dot.push_str(&format!("        {} [label=\"{}\"];\n", id(index), label));
+    }
+
+    for (index, block) in graph.blocks.iter().enumerate() {
+        for successor in block.successors.iter() {
+            dot.push_str(&format!("        {} -> {};\n", id(index), id(*successor)));
+        }
+    }
+
+    dot.push_str("    }\n");
+
+    return dot;
+}
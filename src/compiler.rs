@@ -1,9 +1,38 @@
 use core::fmt;
-use std::{fs::File, io::Write, path::Path, process::Command};
+use std::{
+    cell::RefCell,
+    fs::{read, read_to_string, write, File},
+    io::{self, BufWriter, Write},
+    panic::panic_any,
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+#[cfg(feature = "native")]
+use std::process::Command;
 
 use crate::{
-    lexer::BinaryOperator,
-    parser::{Expression, Function, Local, LocalStack, Parser, Program, Scope, Statement},
+    cfg::{find_missing_returns, write_cfg, CfgFormat},
+    constants::{get_constant, propagate_constants},
+    dataflow::{assign_slots, find_dead_stores},
+    diagnostics::set_processing,
+    explain::write_explanation,
+    gas::{translate_to_gas, AsmSyntax},
+    header::{get_c_name, is_exportable, write_header, FLOAT_REGISTERS, INTEGER_REGISTERS},
+    lexer::{BinaryOperator, Edition},
+    lint::{run_lints, LintConfig, LintLevel},
+    logging::{log, Level, Span},
+    metrics::{measure_function, write_metrics, write_stack_analysis, FunctionMetrics},
+    parser::{
+        BinaryExpression, Expression, Function, FunctionId, Intrinsic, Limits, Local, LocalStack,
+        Parser, Program, Scope, Statement, Type,
+    },
+    pass::Pass,
+    peephole::remove_redundant_jumps,
+    runtime::Routine,
+    span::Position,
+    target::{write_linker_script, Target, BOOT_HALT, BOOT_STUB},
+    validator::{validate_library, validate_program, validate_target},
 };
 
 #[derive(Clone)]
@@ -16,6 +45,25 @@ enum Register {
     R6(usize),
     R7(usize),
     R8(usize),
+    X1,
+    X2,
+}
+
+impl Register {
+    fn resize(&self, size: usize) -> Register {
+        return match self {
+            Register::R1(_) => Register::R1(size),
+            Register::R2(_) => Register::R2(size),
+            Register::R3(_) => Register::R3(size),
+            Register::R4(_) => Register::R4(size),
+            Register::R5(_) => Register::R5(size),
+            Register::R6(_) => Register::R6(size),
+            Register::R7(_) => Register::R7(size),
+            Register::R8(_) => Register::R8(size),
+            Register::X1 => Register::X1,
+            Register::X2 => Register::X2,
+        };
+    }
 }
 
 impl fmt::Display for Register {
@@ -77,6 +125,8 @@ impl fmt::Display for Register {
                 64 => write!(f, "rdi"),
                 _ => panic!("Invalid register size"),
             },
+            Register::X1 => write!(f, "xmm0"),
+            Register::X2 => write!(f, "xmm1"),
         }
     }
 }
@@ -105,12 +155,67 @@ impl BinaryOperator {
             BinaryOperator::Add => "add",
             BinaryOperator::Sub => "sub",
             BinaryOperator::Mul => "imul",
-            BinaryOperator::Div => todo!("Division instruction"),
+            // Written by write_division, which needs rax and rdx
+            BinaryOperator::Div => panic!("Unreachable"),
             BinaryOperator::BitwiseOr => "or",
             BinaryOperator::BitwiseAnd => "and",
             BinaryOperator::BitwiseXor => "xor",
         }
     }
+
+    pub fn get_float_instruction(&self) -> &str {
+        match self {
+            BinaryOperator::Add => "addsd",
+            BinaryOperator::Sub => "subsd",
+            BinaryOperator::Mul => "mulsd",
+            BinaryOperator::Div => "divsd",
+            BinaryOperator::BitwiseOr | BinaryOperator::BitwiseAnd | BinaryOperator::BitwiseXor => {
+                panic!("Unreachable")
+            }
+        }
+    }
+}
+
+impl Intrinsic {
+    pub fn get_routine(&self, arguments: &[Type]) -> Option<Routine> {
+        return match (self, arguments) {
+            (Intrinsic::ToStr, [Type::F64, _]) => Some(Routine::Ftoa),
+            (Intrinsic::ToStr, _) => Some(Routine::Itoa),
+            (Intrinsic::ParseInt, _) => Some(Routine::Atoi),
+            // Seeding only writes the state kept next to the routine
+            (Intrinsic::Random | Intrinsic::Seed, _) => Some(Routine::Random),
+            (
+                Intrinsic::Write
+                | Intrinsic::ToF64
+                | Intrinsic::ToInt
+                | Intrinsic::DivMod
+                | Intrinsic::PortIn
+                | Intrinsic::PortOut
+                | Intrinsic::StackAlloc
+                | Intrinsic::Memset
+                | Intrinsic::Memcpy
+                | Intrinsic::Memcmp
+                | Intrinsic::Trap
+                | Intrinsic::Unreachable
+                | Intrinsic::Time
+                | Intrinsic::ClockNs
+                | Intrinsic::SleepMs,
+                _,
+            ) => None,
+        };
+    }
+}
+
+impl Type {
+    pub fn get_word_type(&self) -> TypeSize {
+        match self.get_size() {
+            1 => TypeSize::Byte,
+            2 => TypeSize::Word,
+            4 => TypeSize::Double,
+            8 => TypeSize::Quad,
+            _ => panic!("Unkown size"),
+        }
+    }
 }
 
 impl Local {
@@ -123,308 +228,2947 @@ impl Local {
             _ => panic!("Unkown size"),
         }
     }
+
+    // Memory operand of the local, static locals are addressed relative to rip
+    pub fn get_address(&self) -> String {
+        return match &self.static_label {
+            Some(label) => format!("rel {}", label),
+            None => format!("{} - {:#x}", Register::R6(64), self.offset + self.size),
+        };
+    }
+}
+
+// Calls deeper than this still run, they are only left out of backtraces
+const SHADOW_CAPACITY: usize = 0x400;
+
+#[derive(Debug, Clone, Default)]
+pub struct CompilerOptions {
+    // Check indices into arrays against their length at runtime
+    pub checked_bounds: bool,
+    // Embed the compiler version, source hash and timestamp as comments
+    pub metadata: bool,
+    // Leave out anything that changes between runs with the same input
+    pub reproducible: bool,
+    // Write a C header and C callable wrappers for public functions
+    pub header: bool,
+    // Link a position independent shared library instead of an executable
+    pub shared: bool,
+    // Write an object for the executables of other packages to link, without an entry point
+    pub library: bool,
+    // Archive the library object into lib<stem>.a, for C and Rust projects to link
+    pub archive: bool,
+    // Extra objects providing the extern functions
+    pub link: Vec<String>,
+    // Script given to the linker, deciding where each section is placed
+    pub linker_script: Option<String>,
+    // Label every statement and write a map from the labels back to source positions
+    pub source_map: bool,
+    // Write the parsed program as JSON, only available with the serde feature
+    pub ast_json: bool,
+    // Comment every statement with its source line and have the assembler write a listing
+    pub listing: bool,
+    // Only assemble the output to check it, without keeping the object or linking
+    pub check_assembly: bool,
+    // Keep a shadow call stack and print it when the program aborts or crashes
+    pub backtraces: bool,
+    // Count how often each statement runs and write the counts at exit
+    pub coverage: bool,
+    // Count calls and cycles spent in each function and write them at exit
+    pub profile: bool,
+    // Print the tokens, syntax tree and instructions of every statement
+    pub explain: bool,
+    // Print the control flow graph of every function
+    pub dump_cfg: Option<CfgFormat>,
+    // Print the instructions, estimated cycles, stack usage and calls of every function
+    pub analyze: bool,
+    // Print the worst case stack of every function through the calls it makes
+    pub stack_analysis: bool,
+    // Keywords of files without an edition pragma
+    pub edition: Edition,
+    // Optimizations beyond the ones always applied, 0 keeps every function frame
+    pub opt_level: u8,
+    // Bounds on the input checked while parsing
+    pub limits: Limits,
+    // Level of each lint, denied ones fail the build
+    pub lints: LintConfig,
+    // Assembler the output is written for
+    pub asm_syntax: AsmSyntax,
+    // System the program runs on, which decides how it starts and ends
+    pub target: Target,
+    // Path of the outputs without their extension, the stem of the source file by default
+    pub output: Option<String>,
 }
 
 pub struct Compiler {
     filename: String,
     parser: Parser,
+    options: CompilerOptions,
     buffer: Vec<u8>,
+    // Messages of the failed bounds checks and the traps, with their position
+    aborts: RefCell<Vec<String>>,
+    header: Option<String>,
+    // Parsed program as JSON, for tools outside the compiler
+    ast: Option<String>,
+    // Source map lines, collected while the functions are written
+    locations: RefCell<Vec<String>>,
+    // Statement of each coverage counter
+    counters: RefCell<Vec<Position>>,
+    // Function of each profile entry
+    profiled: RefCell<Vec<String>>,
+    // Function label, statement index and instructions of each explained statement
+    explained: RefCell<Vec<(String, usize, String)>>,
+    // Metrics of each function written, in order
+    analyzed: RefCell<Vec<FunctionMetrics>>,
+    // Lines of each source file the listing quotes, read once
+    source_lines: RefCell<Vec<(String, Vec<String>)>>,
+    // Steps added by crates using the compiler, in the order they run
+    passes: Vec<Box<dyn Pass>>,
+    // Files written so far and how long each phase took, for the build summary
+    pub artifacts: Vec<String>,
+    pub warnings: Vec<String>,
+    pub timings: Vec<(&'static str, Duration)>,
 }
 
 impl Compiler {
-    pub fn from_file(filename: &str) -> Self {
+    pub fn from_file(filename: &str, options: CompilerOptions) -> Self {
+        let parser = Parser::from_file(filename, options.edition);
+        return Self::from_parser(filename, parser, options);
+    }
+
+    // Source given in memory, the filename only names it in diagnostics and labels
+    pub fn from_source(filename: &str, source: Vec<u8>, options: CompilerOptions) -> Self {
+        let parser = Parser::from_source(filename, source, options.edition);
+        return Self::from_parser(filename, parser, options);
+    }
+
+    fn from_parser(filename: &str, mut parser: Parser, options: CompilerOptions) -> Self {
+        parser.set_limits(options.limits.clone());
+
         Self {
             filename: filename.to_owned(),
-            parser: Parser::from_file(filename),
+            parser,
+            options,
             buffer: Vec::new(),
+            aborts: RefCell::new(Vec::new()),
+            header: None,
+            ast: None,
+            locations: RefCell::new(Vec::new()),
+            counters: RefCell::new(Vec::new()),
+            profiled: RefCell::new(Vec::new()),
+            explained: RefCell::new(Vec::new()),
+            analyzed: RefCell::new(Vec::new()),
+            source_lines: RefCell::new(Vec::new()),
+            passes: Vec::new(),
+            artifacts: Vec::new(),
+            warnings: Vec::new(),
+            timings: Vec::new(),
         }
     }
 
-    pub fn compile(&mut self) {
-        self.parser.generate_tokens();
+    // Runs after the passes added before it, once the program is parsed
+    pub fn add_pass(&mut self, pass: Box<dyn Pass>) {
+        self.passes.push(pass);
+    }
+
+    // Compile errors panic with their diagnostic, only tool failures are returned
+    pub fn compile(&mut self) -> Result<(), String> {
+        let program = self.parse();
+        let stem = self.get_stem();
+
+        let file = match File::create(format!("{}.s", stem)) {
+            Ok(file) => file,
+            Err(error) => return Err(format!("Can not create {}.s: {}", stem, error)),
+        };
+
+        let mut writer = BufWriter::new(file);
+
+        if let Err(error) = self
+            .write(&program, &mut writer)
+            .and_then(|_| writer.flush())
+        {
+            return Err(format!("Can not write {}.s: {}", stem, error));
+        }
 
-        let program = self.parser.generate_program();
+        self.artifacts.push(format!("{}.s", stem));
 
-        self.buffer.extend(self.write_program(&program));
+        for warning in self.warnings.iter() {
+            eprintln!("{}", warning);
+        }
 
-        self.save_buffer();
+        return self.save_artifacts();
     }
 
-    fn write_program(&self, program: &Program) -> Vec<u8> {
+    // Parses the program and writes its assembly without writing any file,
+    // compile errors panic like in compile
+    pub fn generate(&mut self) {
+        let program = self.parse();
         let mut buffer: Vec<u8> = Vec::new();
 
-        buffer.extend(format!("; Source File: {}", self.filename).as_bytes());
+        self.write(&program, &mut buffer)
+            .expect("Writing to memory can not fail");
 
-        buffer.extend("\nsection .text".as_bytes());
-        buffer.extend("\n\tglobal _start".as_bytes());
+        self.buffer = buffer;
+    }
 
-        buffer.extend("\n_start:".as_bytes());
-        buffer.extend("\n\tcall main".as_bytes());
-        buffer.extend(format!("\n\tmov {}, {}", Register::R8(64), Register::R1(64)).as_bytes());
-        buffer.extend(format!("\n\tmov {}, 0x3c", Register::R1(64)).as_bytes());
-        buffer.extend("\n\tsyscall".as_bytes());
+    // Checks the parsed program after the passes ran on it, compile errors panic
+    fn parse(&mut self) -> Program {
+        let span = Span::enter("parse");
+
+        self.parser.generate_tokens();
 
-        for function in program.functions.iter() {
-            buffer.extend(self.write_function(function, &program.functions));
+        let mut program = self.parser.generate_program();
+
+        log(
+            Level::Debug,
+            format_args!(
+                "{} tokens, {} functions, {} strings from {}",
+                program.tokens.len(),
+                program.functions.len(),
+                program.strings.len(),
+                program.sources.join(", ")
+            ),
+        );
+
+        for pass in self.passes.iter_mut() {
+            log(
+                Level::Debug,
+                format_args!("running pass {}", pass.get_name()),
+            );
+
+            if let Err(diagnostic) = pass.run(&mut program) {
+                panic_any(diagnostic);
+            }
         }
 
-        buffer.push(b'\n');
+        validate_program(
+            &program,
+            &self.filename,
+            self.options.shared || self.options.library,
+        );
+        validate_target(&program, self.options.target);
 
-        return buffer;
-    }
+        if self.options.library {
+            validate_library(&program);
+        }
 
-    fn write_function(&self, function: &Function, functions: &Vec<Function>) -> Vec<u8> {
-        let mut buffer: Vec<u8> = Vec::new();
+        self.warnings = find_dead_stores(&program);
+        self.warnings.extend(find_missing_returns(&program));
 
-        buffer.extend(format!("\n{}:", function.name).as_bytes());
+        let lints = run_lints(&program, &self.options.lints);
 
-        let locals = &function.locals;
+        let denied: Vec<&str> = lints
+            .iter()
+            .filter(|(level, _)| *level == LintLevel::Deny)
+            .map(|(_, message)| message.as_str())
+            .collect();
 
-        // add 8 because future calls aligments
-        let mut stack_size = locals.get_size() + 8;
+        if denied.len() > 0 {
+            panic!("{}", denied.join("\n"));
+        }
 
-        // force 16 bytes aligment
-        stack_size += stack_size % 16;
+        self.warnings
+            .extend(lints.into_iter().map(|(_, message)| message));
 
-        buffer.extend(format!("\n\tpush {}", Register::R6(64)).as_bytes());
-        buffer.extend(format!("\n\tmov {}, {}", Register::R6(64), Register::R5(64)).as_bytes());
+        #[cfg(feature = "serde")]
+        if self.options.ast_json {
+            self.ast = Some(serde_json::to_string_pretty(&program).expect("Unreachable"));
+        }
 
-        buffer.extend(format!("\n\tsub {}, {:#x}", Register::R5(64), stack_size).as_bytes());
+        self.timings.push(("parse", span.elapsed()));
 
-        for index in function.arguments.iter() {
-            let argument = function.locals.get(*index).expect("Unreachable");
+        return program;
+    }
 
-            buffer.extend(
-                format!(
-                    "\n\tmov {}, {} [{} + {:#x}]",
-                    Register::R1(64),
-                    argument.get_word_type(),
-                    Register::R6(64),
-                    16 + argument.offset
-                )
-                .as_bytes(),
-            );
+    fn write(&mut self, program: &Program, output: &mut dyn Write) -> io::Result<()> {
+        let span = Span::enter("codegen");
 
-            buffer.extend(
-                format!(
-                    "\n\tmov {} [{} - {:#x}], {}\t; {}",
-                    argument.get_word_type(),
-                    Register::R6(64),
-                    argument.offset + argument.size,
-                    Register::R1(64),
-                    argument.label,
-                )
-                .as_bytes(),
-            );
+        self.write_program(program, output)?;
+
+        if self.options.header {
+            let guard = format!("{}_H", self.get_stem().to_uppercase())
+                .replace(|character: char| !character.is_ascii_alphanumeric(), "_");
+
+            self.header = Some(write_header(program, &self.filename, &guard));
         }
 
-        buffer.extend(self.write_body(&function.name, &function.body, &function.locals, functions));
+        self.timings.push(("codegen", span.elapsed()));
+        drop(span);
 
-        buffer.extend(format!("\n.return_{}:", function.name).as_bytes());
+        if self.options.explain {
+            print!("{}", write_explanation(program, &self.explained.borrow()));
+        }
 
-        buffer.extend(format!("\n\tmov {}, {}", Register::R5(64), Register::R6(64)).as_bytes());
-        buffer.extend(format!("\n\tpop {}", Register::R6(64)).as_bytes());
+        if let Some(format) = self.options.dump_cfg {
+            print!("{}", write_cfg(program, format));
+        }
 
-        buffer.extend(format!("\n\tret").as_bytes());
+        if self.options.analyze {
+            print!("{}", write_metrics(program, &self.analyzed.borrow()));
+        }
 
-        return buffer;
+        if self.options.stack_analysis {
+            print!("{}", write_stack_analysis(program, &self.analyzed.borrow()));
+        }
+
+        return Ok(());
     }
 
-    fn write_body(
-        &self,
-        name: &str,
-        body: &Scope,
-        locals: &LocalStack,
-        functions: &Vec<Function>,
-    ) -> Vec<u8> {
+    pub fn get_assembly(&self) -> String {
+        return String::from_utf8_lossy(&self.buffer).to_string();
+    }
+
+    // Written to the output a function at a time, the assembly of a large program is never
+    // held in memory as a whole
+    fn write_program(&self, program: &Program, output: &mut dyn Write) -> io::Result<()> {
+        // Included assembly is copied as is, so it has to be written for the chosen assembler
+        let prologue = self.options.asm_syntax.get_prologue();
+
+        if !prologue.is_empty() {
+            output.write_all(format!("{}\n", prologue).as_bytes())?;
+        }
+
         let mut buffer: Vec<u8> = Vec::new();
 
-        for statement in body.statements.iter() {
-            match statement {
-                Statement::Assign(local, expression) => {
-                    let local = locals.get(*local).expect("Unreachable");
+        buffer.extend(format!("; Source File: {}", self.filename).as_bytes());
 
-                    buffer.extend(self.write_expression(
-                        expression,
-                        &Register::R2(64),
-                        &Register::R3(64),
-                        locals,
-                        functions,
-                    ));
+        if self.options.metadata {
+            buffer.extend(self.write_metadata(program));
+        }
 
-                    buffer.extend(
-                        format!(
-                            "\n\tmov {} [{} - {:#x}], {}\t; {}",
-                            local.get_word_type(),
-                            Register::R6(64),
-                            local.offset + local.size,
-                            Register::R2(64),
-                            local.label
-                        )
-                        .as_bytes(),
-                    );
-                }
-                Statement::Return(expression) => {
-                    buffer.extend(self.write_expression(
-                        expression,
-                        &Register::R2(64),
-                        &Register::R3(64),
-                        locals,
-                        functions,
-                    ));
+        if program.strings.len() > 0 {
+            buffer.extend(self.write_strings(program));
+        }
 
-                    buffer.extend(
-                        format!("\n\tmov {}, {}", Register::R1(64), Register::R2(64)).as_bytes(),
-                    );
+        buffer.extend("\nsection .text".as_bytes());
 
-                    buffer.extend(format!("\n\tjmp .return_{}", name).as_bytes());
-                }
-                Statement::Call(expression) => {
-                    // FIXME: idk
-                    buffer.extend(self.write_expression(
-                        expression,
-                        &Register::R2(64),
-                        &Register::R3(64),
-                        locals,
-                        functions,
-                    ));
-                }
+        // The executable linking a library starts it
+        let entry = !self.options.shared && !self.options.library;
+
+        if entry && self.options.target == Target::Baremetal {
+            buffer.extend(BOOT_STUB.as_bytes());
+            buffer.extend(self.write_main_call(program));
+            buffer.extend(BOOT_HALT.as_bytes());
+        } else if entry {
+            buffer.extend("\n\tglobal _start".as_bytes());
+
+            buffer.extend("\n_start:".as_bytes());
+
+            if self.options.backtraces {
+                buffer.extend(format!("\n\tcall {}", Routine::CrashHandler.get_label()).as_bytes());
             }
-        }
 
-        return buffer;
-    }
+            buffer.extend(self.write_main_call(program));
 
-    fn write_expression(
-        &self,
-        expression: &Expression,
-        register: &Register,
-        alt: &Register,
-        locals: &LocalStack,
-        functions: &Vec<Function>,
-    ) -> Vec<u8> {
-        let mut buffer: Vec<u8> = Vec::new();
+            // Counters are written once main returns, keeping its exit code
+            let dumps = [
+                (self.options.coverage, "ez_coverage"),
+                (self.options.profile, "ez_profile"),
+            ];
 
-        match expression {
-            Expression::Binary(binary_expression) => {
-                let left = &*binary_expression.left;
-                let right = &*binary_expression.right;
+            for (_, label) in dumps.iter().filter(|(enabled, _)| *enabled) {
+                buffer.extend(format!("\n\tpush {}", Register::R1(64)).as_bytes());
+                buffer.extend(
+                    format!("\n\tlea {}, [rel {}_file]", Register::R8(64), label).as_bytes(),
+                );
+                buffer.extend(format!("\n\tlea {}, [rel {}]", Register::R7(64), label).as_bytes());
+                buffer.extend(format!("\n\tmov {}, {}_size", Register::R3(64), label).as_bytes());
+                buffer.extend(format!("\n\tcall {}", Routine::DumpFile.get_label()).as_bytes());
+                buffer.extend(format!("\n\tpop {}", Register::R1(64)).as_bytes());
+            }
 
-                if let Expression::Binary(_) = left {
-                    buffer.extend(self.write_expression(left, register, alt, locals, functions));
-                    buffer.extend(self.write_expression(right, alt, register, locals, functions));
-                    buffer.extend(
-                        format!(
-                            "\n\t{} {}, {}",
-                            binary_expression.operator.get_instruction(),
-                            register,
-                            alt
-                        )
-                        .as_bytes(),
-                    );
-                } else if let Expression::Binary(_) = right {
-                    buffer.extend(self.write_expression(right, alt, register, locals, functions));
-                    buffer.extend(self.write_expression(left, register, alt, locals, functions));
-                    buffer.extend(
-                        format!(
-                            "\n\t{} {}, {}",
-                            binary_expression.operator.get_instruction(),
-                            register,
-                            alt
-                        )
-                        .as_bytes(),
-                    );
-                } else {
-                    buffer.extend(self.write_expression(left, register, alt, locals, functions));
-                    buffer.extend(self.write_expression(right, alt, register, locals, functions));
-                    buffer.extend(
-                        format!(
-                            "\n\t{} {}, {}",
-                            binary_expression.operator.get_instruction(),
-                            register,
-                            alt
-                        )
-                        .as_bytes(),
-                    );
+            buffer.extend(format!("\n\tmov {}, {}", Register::R8(64), Register::R1(64)).as_bytes());
+            buffer.extend(format!("\n\tmov {}, 0x3c", Register::R1(64)).as_bytes());
+            buffer.extend("\n\tsyscall".as_bytes());
+        }
+
+        // Shared libraries are loaded without _start, the dynamic loader runs their hooks
+        if self.options.shared {
+            let hooks = [
+                (".init_array", program.get_constructors()),
+                // The loader runs them back to front itself
+                (
+                    ".fini_array",
+                    program.get_destructors().into_iter().rev().collect(),
+                ),
+            ];
+
+            for (section, functions) in hooks.iter().filter(|(_, functions)| functions.len() > 0) {
+                buffer.extend(format!("\nsection {} write align=8", section).as_bytes());
+
+                for id in functions.iter() {
+                    buffer.extend(format!("\n\tdq {}", program.functions[*id].label).as_bytes());
                 }
+
+                buffer.extend("\nsection .text".as_bytes());
             }
-            Expression::NumberLiteral(number) => {
-                buffer.extend(format!("\n\tmov {}, {:#x}", register, number).as_bytes());
+        }
+
+        let reachable = self.find_reachable(&program.functions);
+
+        for (function, _) in program
+            .functions
+            .iter()
+            .zip(&reachable)
+            .filter(|(_, r)| **r)
+        {
+            if function.external {
+                buffer.extend(format!("\n\textern {}", function.label).as_bytes());
             }
-            Expression::Local(index) => {
-                if let Some(local) = locals.get(*index) {
-                    buffer.extend(
-                        format!(
-                            "\n\tmov {}, {} [{} - {:#x}]\t; {}",
-                            register,
-                            local.get_word_type(),
-                            Register::R6(64),
-                            local.offset + local.size,
-                            local.label
-                        )
-                        .as_bytes(),
-                    );
-                } else {
-                    panic!("Unreachable");
+        }
+
+        for (function, _) in program
+            .functions
+            .iter()
+            .zip(&reachable)
+            .filter(|(_, r)| **r)
+        {
+            // Functions without body are provided by included assembly
+            if let Some(body) = &function.body {
+                if function.public {
+                    buffer.extend(format!("\n\tglobal {}", function.label).as_bytes());
                 }
-            }
-            Expression::Call(index, expressions) => {
-                let function = match functions.get(*index) {
-                    Some(function) => function,
-                    None => panic!("No function found"),
-                };
 
-                if function.arguments.len() != expressions.len() {
-                    panic!("Argument mismath");
+                if let Some(section) = &function.attributes.section {
+                    buffer.extend(format!("\nsection {} exec align=16", section).as_bytes());
                 }
 
-                for (i, expression) in expressions.iter().enumerate() {
-                    buffer.extend(self.write_expression(
-                        expression,
-                        &Register::R2(64),
-                        &Register::R3(64),
-                        locals,
-                        functions,
-                    ));
+                buffer.extend(self.write_function(function, body, &program.functions));
 
-                    let argument = function
-                        .locals
-                        .get(*function.arguments.get(i).unwrap())
-                        .unwrap();
+                if function.attributes.section.is_some() {
+                    buffer.extend("\nsection .text".as_bytes());
+                }
+            }
 
-                    buffer.extend(
-                        format!("\n\tpush {};\t{}", Register::R2(64), argument.label).as_bytes(),
-                    );
+            if self.options.header && is_exportable(function) {
+                buffer.extend(self.write_c_wrapper(function));
+            }
+
+            self.flush_buffer(&mut buffer, output)?;
+        }
+
+        // Runtime routines are only emitted once and only when referenced
+        let mut routines: Vec<Routine> = Vec::new();
+
+        for (function, _) in program
+            .functions
+            .iter()
+            .zip(&reachable)
+            .filter(|(_, r)| **r)
+        {
+            if let Some(body) = &function.body {
+                for statement in body.statements.iter() {
+                    match statement {
+                        Statement::Assign(_, expression)
+                        | Statement::Destructure(_, expression)
+                        | Statement::Return(expression)
+                        | Statement::Call(expression) => {
+                            self.collect_routines(
+                                expression,
+                                &function.locals,
+                                &program.functions,
+                                &mut routines,
+                            );
+                        }
+                        Statement::Store(target, expression) => {
+                            for expression in [target, expression] {
+                                self.collect_routines(
+                                    expression,
+                                    &function.locals,
+                                    &program.functions,
+                                    &mut routines,
+                                );
+                            }
+                        }
+                        Statement::Declare(_) | Statement::Label(_) | Statement::Goto(_) => {}
+                    }
                 }
+            }
+        }
 
-                buffer.extend(format!("\n\tcall {}", function.name).as_bytes());
-                buffer.extend(format!("\n\tmov {}, {}", register, Register::R1(64)).as_bytes());
+        if self.options.coverage || self.options.profile {
+            routines.push(Routine::DumpFile);
+        }
+
+        if self.options.backtraces {
+            let routine = match self.options.shared {
+                true => Routine::Backtrace,
+                false => Routine::CrashHandler,
+            };
+
+            for routine in routine.get_dependencies().into_iter().chain([routine]) {
+                if !routines.contains(&routine) {
+                    routines.push(routine);
+                }
             }
         }
 
-        return buffer;
-    }
+        for routine in routines.iter() {
+            buffer.extend(routine.get_source().as_bytes());
+
+            // Calls into the runtime count towards the stack of their callers
+            if self.options.analyze || self.options.stack_analysis {
+                self.analyzed
+                    .borrow_mut()
+                    .push(measure_function(routine.get_label(), routine.get_source()));
+            }
+        }
+
+        self.flush_buffer(&mut buffer, output)?;
+
+        for assembly in program.assembly.iter() {
+            let comment = self.options.asm_syntax.get_comment();
+
+            output.write_all(
+                format!("\n{} Included assembly: {}\n", comment, assembly.filename).as_bytes(),
+            )?;
+            output.write_all(assembly.source.trim_end().as_bytes())?;
+        }
+
+        if !prologue.is_empty() && !program.assembly.is_empty() {
+            output.write_all(format!("\n{}", prologue).as_bytes())?;
+        }
+
+        // Messages are only known after every function has been written
+        let aborts = self.aborts.borrow();
+
+        if aborts.len() > 0 {
+            buffer.extend("\nsection .rodata".as_bytes());
+
+            for (index, message) in aborts.iter().enumerate() {
+                buffer.extend(self.write_data(&format!("abort.{}", index), message));
+            }
+        }
+
+        if self.options.backtraces {
+            buffer.extend(self.write_shadow_stack(&program.functions, &reachable));
+        }
+
+        if self.options.coverage {
+            buffer.extend(self.write_counters());
+        }
+
+        if self.options.profile {
+            buffer.extend(self.write_profile());
+        }
+
+        // Zero until the first number or a seed, like the statics
+        if routines.contains(&Routine::Random) {
+            buffer.extend("\nsection .bss".as_bytes());
+            buffer.extend("\nez_rand_state resq 1".as_bytes());
+        }
+
+        buffer.extend(self.write_statics(&program.functions, &reachable));
+
+        // Linkers of C programs make the stack executable for objects that do not say otherwise
+        if self.options.library {
+            buffer.extend("\nsection .note.GNU-stack noalloc noexec nowrite progbits".as_bytes());
+        }
+
+        self.flush_buffer(&mut buffer, output)?;
+
+        return output.write_all(b"\n");
+    }
+
+    // Moves what was written so far to the output, chunks always end before a new line so
+    // they are translated line by line like the whole program
+    fn flush_buffer(&self, buffer: &mut Vec<u8>, output: &mut dyn Write) -> io::Result<()> {
+        return output.write_all(&self.write_syntax(std::mem::take(buffer)));
+    }
+
+    // The compiler writes NASM, translated afterwards when another assembler is chosen
+    fn write_syntax(&self, buffer: Vec<u8>) -> Vec<u8> {
+        return match self.options.asm_syntax {
+            AsmSyntax::Nasm => buffer,
+            AsmSyntax::Gas => translate_to_gas(&String::from_utf8_lossy(&buffer)).into_bytes(),
+        };
+    }
+
+    // Calls main between the constructors and the destructors, leaving its result in rax
+    fn write_main_call(&self, program: &Program) -> Vec<u8> {
+        let mut buffer: Vec<u8> = Vec::new();
+
+        for id in program.get_constructors() {
+            buffer.extend(format!("\n\tcall {}", program.functions[id].label).as_bytes());
+        }
+
+        buffer.extend("\n\tcall main".as_bytes());
+
+        let destructors = program.get_destructors();
+
+        if destructors.len() > 0 {
+            buffer.extend(format!("\n\tpush {}", Register::R1(64)).as_bytes());
+
+            for id in destructors {
+                buffer.extend(format!("\n\tcall {}", program.functions[id].label).as_bytes());
+            }
+
+            buffer.extend(format!("\n\tpop {}", Register::R1(64)).as_bytes());
+        }
+
+        return buffer;
+    }
+
+    // Private functions are only emitted when called from main, a public function or a hook
+    fn find_reachable(&self, functions: &Vec<Function>) -> Vec<bool> {
+        let mut reachable = vec![false; functions.len()];
+
+        let mut pending: Vec<FunctionId> = functions
+            .iter()
+            .enumerate()
+            .filter(|(_, function)| {
+                function.public
+                    || function.label == "main"
+                    || function.attributes.constructor
+                    || function.attributes.destructor
+            })
+            .map(|(index, _)| FunctionId(index))
+            .collect();
+
+        while let Some(id) = pending.pop() {
+            if reachable[id.0] {
+                continue;
+            }
+
+            reachable[id.0] = true;
+
+            pending.extend(functions[id].get_calls());
+        }
+
+        return reachable;
+    }
+
+    fn collect_routines(
+        &self,
+        expression: &Expression,
+        locals: &LocalStack,
+        functions: &Vec<Function>,
+        routines: &mut Vec<Routine>,
+    ) {
+        match expression {
+            Expression::Binary(binary_expression) => {
+                self.collect_routines(&binary_expression.left, locals, functions, routines);
+                self.collect_routines(&binary_expression.right, locals, functions, routines);
+            }
+            Expression::Call(_, expressions) => {
+                for expression in expressions.iter() {
+                    self.collect_routines(expression, locals, functions, routines);
+                }
+            }
+            Expression::Cast(_, expression)
+            | Expression::Address(expression)
+            | Expression::Field(expression, _) => {
+                self.collect_routines(expression, locals, functions, routines);
+            }
+            Expression::Initializer(_, expressions) | Expression::Tuple(expressions) => {
+                for expression in expressions.iter() {
+                    self.collect_routines(expression, locals, functions, routines);
+                }
+            }
+            Expression::Index(base, index, _) => {
+                if let (true, true, Type::Array(..)) = (
+                    self.options.checked_bounds,
+                    self.options.target.has_os(),
+                    base.get_type(locals, functions),
+                ) {
+                    let routine = self.get_trap_routine();
+
+                    for routine in routine.get_dependencies().into_iter().chain([routine]) {
+                        if !routines.contains(&routine) {
+                            routines.push(routine);
+                        }
+                    }
+                }
+
+                self.collect_routines(base, locals, functions, routines);
+                self.collect_routines(index, locals, functions, routines);
+            }
+            Expression::Intrinsic(intrinsic, expressions) => {
+                let types: Vec<Type> = expressions
+                    .iter()
+                    .map(|expression| expression.get_type(locals, functions))
+                    .collect();
+
+                let routine = match intrinsic.get_abort_message() {
+                    Some(_) if self.options.target.has_os() => Some(self.get_trap_routine()),
+                    Some(_) => None,
+                    None => intrinsic.get_routine(&types),
+                };
+
+                if let Some(routine) = routine {
+                    for routine in routine.get_dependencies().into_iter().chain([routine]) {
+                        if !routines.contains(&routine) {
+                            routines.push(routine);
+                        }
+                    }
+                }
+
+                for expression in expressions.iter() {
+                    self.collect_routines(expression, locals, functions, routines);
+                }
+            }
+            Expression::Format(_, expressions) => {
+                for expression in expressions.iter() {
+                    let routine = match expression.get_type(locals, functions) {
+                        Type::F64 => Routine::Ftoa,
+                        _ => Routine::Itoa,
+                    };
+
+                    for routine in routine.get_dependencies().into_iter().chain([routine]) {
+                        if !routines.contains(&routine) {
+                            routines.push(routine);
+                        }
+                    }
+
+                    self.collect_routines(expression, locals, functions, routines);
+                }
+            }
+            Expression::NumberLiteral(_)
+            | Expression::FloatLiteral(_)
+            | Expression::StringLiteral(_)
+            | Expression::Local(_) => {}
+        }
+    }
+
+    // One line per function for the backtrace, the stack itself and the crash messages
+    fn write_shadow_stack(&self, functions: &Vec<Function>, reachable: &Vec<bool>) -> Vec<u8> {
+        let mut buffer: Vec<u8> = Vec::new();
+
+        buffer.extend("\nsection .rodata".as_bytes());
+        buffer.extend(self.write_data("ez_backtrace_header", "backtrace:\n"));
+
+        if !self.options.shared {
+            buffer.extend(self.write_data("ez_crash_segv", "Segmentation fault.\n"));
+            buffer.extend(self.write_data("ez_crash_fpe", "Arithmetic exception.\n"));
+            buffer.extend(self.write_data("ez_crash_bus", "Bus error.\n"));
+        }
+
+        for (function, _) in functions.iter().zip(reachable).filter(|(_, r)| **r) {
+            if function.body.is_none() {
+                continue;
+            }
+
+            let line = format!(
+                "  at {} ({})\n",
+                function.get_display_name(),
+                function.position
+            );
+
+            buffer.extend(self.write_data(&format!("ezfn.{}", function.label), &line));
+        }
+
+        buffer.extend("\nsection .bss".as_bytes());
+        buffer.extend(format!("\nez_shadow_capacity equ {:#x}", SHADOW_CAPACITY).as_bytes());
+        buffer.extend("\nez_shadow_stack resq ez_shadow_capacity".as_bytes());
+        buffer.extend("\nez_shadow_depth resq 1".as_bytes());
+
+        return buffer;
+    }
+
+    // Static locals of the written functions, zeroed by the loader
+    fn write_statics(&self, functions: &Vec<Function>, reachable: &Vec<bool>) -> Vec<u8> {
+        let mut buffer: Vec<u8> = Vec::new();
+
+        let statics: Vec<&Local> = functions
+            .iter()
+            .zip(reachable)
+            .filter(|(_, reachable)| **reachable)
+            .flat_map(|(function, _)| function.locals.locals.iter())
+            .filter(|local| local.static_label.is_some())
+            .collect();
+
+        if statics.is_empty() {
+            return buffer;
+        }
+
+        buffer.extend("\nsection .bss".as_bytes());
+
+        for local in statics {
+            let label = local.static_label.as_ref().expect("Unreachable");
+
+            buffer.extend(format!("\n\talignb {}", local.local_type.get_alignment()).as_bytes());
+            buffer.extend(format!("\n{} resb {:#x}", label, local.size).as_bytes());
+        }
+
+        return buffer;
+    }
+
+    // The counters are dumped as is, one little endian quad word per statement
+    fn write_counters(&self) -> Vec<u8> {
+        let mut buffer: Vec<u8> = Vec::new();
+
+        buffer.extend("\nsection .rodata".as_bytes());
+        buffer.extend(self.write_data("ez_coverage_file", &format!("{}.cov", self.get_stem())));
+
+        let counters = self.counters.borrow().len();
+
+        buffer.extend("\nsection .bss".as_bytes());
+        buffer.extend(format!("\nez_coverage_size equ {:#x}", counters * 8).as_bytes());
+        buffer.extend(format!("\nez_coverage resq {:#x}", counters.max(1)).as_bytes());
+
+        return buffer;
+    }
+
+    // Calls and cycles of each function, as two little endian quad words
+    fn write_profile(&self) -> Vec<u8> {
+        let mut buffer: Vec<u8> = Vec::new();
+
+        buffer.extend("\nsection .rodata".as_bytes());
+        buffer.extend(self.write_data("ez_profile_file", &format!("{}.prof", self.get_stem())));
+
+        let functions = self.profiled.borrow().len();
+
+        buffer.extend("\nsection .bss".as_bytes());
+        buffer.extend(format!("\nez_profile_size equ {:#x}", functions * 16).as_bytes());
+        buffer.extend(format!("\nez_profile resq {:#x}", (functions * 2).max(1)).as_bytes());
+
+        return buffer;
+    }
+
+    fn write_metadata(&self, program: &Program) -> Vec<u8> {
+        let mut buffer: Vec<u8> = Vec::new();
+
+        buffer.extend(format!("\n; Compiler: ezlang {}", env!("CARGO_PKG_VERSION")).as_bytes());
+        buffer.extend(format!("\n; Source Hash: {:016x}", self.hash_sources(program)).as_bytes());
+
+        if !self.options.reproducible {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_secs())
+                .unwrap_or(0);
+
+            buffer.extend(format!("\n; Timestamp: {}", timestamp).as_bytes());
+        }
+
+        return buffer;
+    }
+
+    // FNV-1a over every source and included assembly, stable across runs and platforms
+    fn hash_sources(&self, program: &Program) -> u64 {
+        let mut hash: u64 = 0xcbf29ce484222325;
+
+        let sources = program
+            .sources
+            .iter()
+            .map(|source| read(source).unwrap_or_default())
+            .chain(
+                program
+                    .assembly
+                    .iter()
+                    .map(|assembly| assembly.source.clone().into_bytes()),
+            );
+
+        for source in sources {
+            for byte in source {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(0x100000001b3);
+            }
+        }
+
+        return hash;
+    }
+
+    // Read-only strings share the storage of an identical one, and with -O1 of a longer one
+    // ending with them since every string ends with the same terminator
+    fn write_strings(&self, program: &Program) -> Vec<u8> {
+        let mut buffer: Vec<u8> = Vec::new();
+
+        let strings = &program.strings;
+        let read_only = program.get_read_only_strings();
+
+        // Sorted by their reversed bytes a string is followed by the ones ending with it
+        let mut order: Vec<usize> = (0..strings.len())
+            .filter(|index| read_only[*index])
+            .collect();
+        order.sort_by(|a, b| strings[*a].bytes().rev().cmp(strings[*b].bytes().rev()));
+
+        let mut hosts: Vec<usize> = (0..strings.len()).collect();
+
+        for position in (0..order.len().saturating_sub(1)).rev() {
+            let (string, next) = (&strings[order[position]], &strings[order[position + 1]]);
+
+            let shared = match self.options.opt_level {
+                0 => string == next,
+                _ => next.ends_with(string.as_str()),
+            };
+
+            if shared {
+                hosts[order[position]] = hosts[order[position + 1]];
+            }
+        }
+
+        log(
+            Level::Debug,
+            format_args!(
+                "{} of {} strings share the storage of another",
+                (0..strings.len())
+                    .filter(|index| hosts[*index] != *index)
+                    .count(),
+                strings.len()
+            ),
+        );
+
+        // Literals the program may write to stay writable
+        for (section, writable) in [(".rodata", false), (".data", true)] {
+            let indices: Vec<usize> = (0..strings.len())
+                .filter(|index| read_only[*index] != writable)
+                .collect();
+
+            if indices.len() > 0 {
+                buffer.extend(format!("\nsection {}", section).as_bytes());
+            }
+
+            for index in indices {
+                buffer.extend(self.write_string(strings, index, hosts[index]));
+            }
+        }
+
+        return buffer;
+    }
+
+    fn write_string(&self, strings: &Vec<String>, index: usize, host: usize) -> Vec<u8> {
+        let mut buffer: Vec<u8> = Vec::new();
+
+        let label = format!("strltr.{}", index);
+        let string = &strings[index];
+
+        if host == index {
+            return self.write_data(&label, string);
+        }
+
+        buffer.extend(
+            format!(
+                "\n{} equ strltr.{} + {}\t; \"{}\"",
+                label,
+                host,
+                strings[host].len() - string.len(),
+                string.escape_debug()
+            )
+            .as_bytes(),
+        );
+        buffer.extend(format!("\n{}.len equ {}", label, string.len()).as_bytes());
+
+        return buffer;
+    }
+
+    fn write_data(&self, label: &str, string: &str) -> Vec<u8> {
+        let mut buffer: Vec<u8> = Vec::new();
+
+        // Raw bytes are safe for any content, unlike quoted NASM strings
+        let bytes: Vec<String> = string
+            .bytes()
+            .chain(std::iter::once(0))
+            .map(|byte| format!("{:#x}", byte))
+            .collect();
+
+        buffer.extend(
+            format!(
+                "\n{} db {}\t; \"{}\"",
+                label,
+                bytes.join(", "),
+                string.escape_debug()
+            )
+            .as_bytes(),
+        );
+
+        // Length without the null terminator
+        buffer.extend(format!("\n{}.len equ $-{}-1", label, label).as_bytes());
+
+        return buffer;
+    }
+
+    fn write_function(
+        &self,
+        function: &Function,
+        body: &Scope,
+        functions: &Vec<Function>,
+    ) -> Vec<u8> {
+        set_processing(&function.position);
+
+        let mut buffer: Vec<u8> = Vec::new();
+
+        buffer.extend(format!("\n{}:", function.label).as_bytes());
+
+        let body = &propagate_constants(function, body, functions);
+
+        // Locals that are never live at the same time share a slot
+        let locals = &assign_slots(function, body);
+
+        // add 8 because future calls aligments
+        let mut stack_size = locals.get_size() + 8;
+
+        // The cycle counter at entry is kept right below the locals
+        let profile = match self.options.profile {
+            true => {
+                let mut profiled = self.profiled.borrow_mut();
+                profiled.push(format!(
+                    "{}\t{}",
+                    function.get_display_name(),
+                    function.position
+                ));
+
+                stack_size += 8;
+                Some(((profiled.len() - 1) * 16, locals.get_size() + 8))
+            }
+            false => None,
+        };
+
+        // force 16 bytes aligment
+        stack_size = (stack_size + 15) / 16 * 16;
+
+        // Without locals or calls nothing addresses the frame, so it is never set up
+        let frameless = self.options.opt_level >= 1
+            && locals.get_size() == 0
+            && profile.is_none()
+            && self.is_leaf(body);
+
+        log(
+            Level::Debug,
+            format_args!(
+                "{}: {} statements, {:#x} bytes of locals from {:#x}{}",
+                function.get_display_name(),
+                body.statements.len(),
+                locals.get_size(),
+                function.locals.get_size(),
+                if frameless { ", frameless" } else { "" }
+            ),
+        );
+
+        if !frameless {
+            buffer.extend(format!("\n\tpush {}", Register::R6(64)).as_bytes());
+            buffer.extend(format!("\n\tmov {}, {}", Register::R6(64), Register::R5(64)).as_bytes());
+
+            buffer.extend(format!("\n\tsub {}, {:#x}", Register::R5(64), stack_size).as_bytes());
+        }
+
+        if let Some((entry, slot)) = profile {
+            buffer.extend(format!("\n\tinc qword [rel ez_profile + {:#x}]", entry).as_bytes());
+            buffer.extend(self.write_timestamp());
+            buffer.extend(
+                format!(
+                    "\n\tmov [{} - {:#x}], {}",
+                    Register::R6(64),
+                    slot,
+                    Register::R1(64)
+                )
+                .as_bytes(),
+            );
+        }
+
+        // Arguments are read after this, so the scratch registers are free
+        if self.options.backtraces {
+            buffer
+                .extend(format!("\n\tmov {}, [rel ez_shadow_depth]", Register::R1(64)).as_bytes());
+            buffer.extend(format!("\n\tcmp {}, ez_shadow_capacity", Register::R1(64)).as_bytes());
+            buffer.extend(format!("\n\tjae .shadow_{}", function.label).as_bytes());
+            buffer.extend(
+                format!(
+                    "\n\tlea {}, [rel ezfn.{}]",
+                    Register::R2(64),
+                    function.label
+                )
+                .as_bytes(),
+            );
+            buffer
+                .extend(format!("\n\tlea {}, [rel ez_shadow_stack]", Register::R3(64)).as_bytes());
+            buffer.extend(
+                format!(
+                    "\n\tmov [{} + {} * 8], {}",
+                    Register::R3(64),
+                    Register::R1(64),
+                    Register::R2(64)
+                )
+                .as_bytes(),
+            );
+            buffer.extend(format!("\n.shadow_{}:", function.label).as_bytes());
+            buffer.extend("\n\tinc qword [rel ez_shadow_depth]".as_bytes());
+        }
+
+        for (i, index) in function.arguments.iter().enumerate() {
+            let argument = locals.get(*index).expect("Unreachable");
+
+            // Arguments are pushed in order as full quad words, so the last one is closest
+            buffer.extend(
+                format!(
+                    "\n\tmov {}, {} [{} + {:#x}]",
+                    Register::R1(64),
+                    TypeSize::Quad,
+                    Register::R6(64),
+                    16 + (function.arguments.len() - 1 - i) * 8
+                )
+                .as_bytes(),
+            );
+
+            if argument.local_type.is_struct() {
+                buffer.extend(
+                    format!("\n\tmov {}, {}", Register::R7(64), Register::R1(64)).as_bytes(),
+                );
+                buffer.extend(
+                    format!(
+                        "\n\tlea {}, [{} - {:#x}]\t; {}",
+                        Register::R8(64),
+                        Register::R6(64),
+                        argument.offset + argument.size,
+                        argument.label
+                    )
+                    .as_bytes(),
+                );
+                buffer.extend(self.write_copy(argument.size));
+                continue;
+            }
+
+            buffer.extend(
+                format!(
+                    "\n\tmov {} [{} - {:#x}], {}\t; {}",
+                    argument.get_word_type(),
+                    Register::R6(64),
+                    argument.offset + argument.size,
+                    Register::R1(argument.size * 8),
+                    argument.label,
+                )
+                .as_bytes(),
+            );
+        }
+
+        buffer.extend(self.write_body(&function.label, body, locals, functions));
+
+        buffer.extend(format!("\n.return_{}:", function.label).as_bytes());
+
+        if self.options.backtraces {
+            buffer.extend("\n\tdec qword [rel ez_shadow_depth]".as_bytes());
+        }
+
+        // rax and rdx hold the return value
+        if let Some((entry, slot)) = profile {
+            buffer.extend(format!("\n\tpush {}", Register::R1(64)).as_bytes());
+            buffer.extend(format!("\n\tpush {}", Register::R3(64)).as_bytes());
+            buffer.extend(self.write_timestamp());
+            buffer.extend(
+                format!(
+                    "\n\tsub {}, [{} - {:#x}]",
+                    Register::R1(64),
+                    Register::R6(64),
+                    slot
+                )
+                .as_bytes(),
+            );
+            buffer.extend(
+                format!(
+                    "\n\tadd [rel ez_profile + {:#x}], {}",
+                    entry + 8,
+                    Register::R1(64)
+                )
+                .as_bytes(),
+            );
+            buffer.extend(format!("\n\tpop {}", Register::R3(64)).as_bytes());
+            buffer.extend(format!("\n\tpop {}", Register::R1(64)).as_bytes());
+        }
+
+        if !frameless {
+            buffer.extend(format!("\n\tmov {}, {}", Register::R5(64), Register::R6(64)).as_bytes());
+            buffer.extend(format!("\n\tpop {}", Register::R6(64)).as_bytes());
+        }
+
+        buffer.extend(format!("\n\tret").as_bytes());
+
+        // Marks where the function ends, so addresses can be attributed to it
+        if self.options.source_map {
+            buffer.extend(format!("\n..@ez.end.{}:", function.label).as_bytes());
+
+            self.locations.borrow_mut().push(format!(
+                "fn\t{}\t..@ez.end.{}\t{}",
+                function.label, function.label, function.position
+            ));
+        }
+
+        // The last statement usually jumps to the epilogue right after it
+        let assembly = remove_redundant_jumps(&String::from_utf8_lossy(&buffer));
+
+        if self.options.analyze || self.options.stack_analysis {
+            self.analyzed
+                .borrow_mut()
+                .push(measure_function(&function.label, &assembly));
+        }
+
+        return assembly.into_bytes();
+    }
+
+    // Functions that never call anything, runtime routines included
+    fn is_leaf(&self, body: &Scope) -> bool {
+        let mut expressions: Vec<&Expression> = body
+            .statements
+            .iter()
+            .flat_map(|statement| statement.get_expressions())
+            .collect();
+
+        while let Some(expression) = expressions.pop() {
+            match expression {
+                Expression::Call(..) | Expression::Intrinsic(..) | Expression::Format(..) => {
+                    return false;
+                }
+                Expression::Index(..) if self.options.checked_bounds => return false,
+                _ => {}
+            }
+
+            expressions.extend(expression.get_children());
+        }
+
+        return true;
+    }
+
+    fn get_source_line(&self, position: &Position) -> Option<String> {
+        let mut source_lines = self.source_lines.borrow_mut();
+
+        let index = match source_lines
+            .iter()
+            .position(|(file, _)| **file == *position.file)
+        {
+            Some(index) => index,
+            None => {
+                let lines = read_to_string(&*position.file)
+                    .map(|source| source.lines().map(str::to_owned).collect())
+                    .unwrap_or_default();
+
+                source_lines.push((position.file.to_string(), lines));
+                source_lines.len() - 1
+            }
+        };
+
+        return source_lines[index].1.get(position.line - 1).cloned();
+    }
+
+    // Global symbols can be interposed in shared libraries so they are called through the PLT
+    fn get_call_target(&self, function: &Function) -> String {
+        if self.options.shared && (function.public || function.external) {
+            return format!("{} wrt ..plt", function.label);
+        }
+
+        return function.label.to_owned();
+    }
+
+    // Moves System V register arguments to the stack and calls the ez function
+    fn write_c_wrapper(&self, function: &Function) -> Vec<u8> {
+        let mut buffer: Vec<u8> = Vec::new();
+
+        let name = get_c_name(function);
+
+        buffer.extend(format!("\n\tglobal {}", name).as_bytes());
+        buffer.extend(format!("\n{}:", name).as_bytes());
+        buffer.extend(format!("\n\tpush {}", Register::R6(64)).as_bytes());
+        buffer.extend(format!("\n\tmov {}, {}", Register::R6(64), Register::R5(64)).as_bytes());
+
+        let (mut integers, mut floats) = (0, 0);
+
+        for index in function.arguments.iter() {
+            let argument = function.locals.get(*index).expect("Unreachable");
+
+            if argument.local_type == Type::F64 {
+                buffer.extend(format!("\n\tmovq {}, xmm{}", Register::R1(64), floats).as_bytes());
+                buffer.extend(format!("\n\tpush {}", Register::R1(64)).as_bytes());
+                floats += 1;
+            } else {
+                buffer.extend(format!("\n\tpush {}", INTEGER_REGISTERS[integers]).as_bytes());
+                integers += 1;
+            }
+        }
+
+        assert!(floats <= FLOAT_REGISTERS);
+
+        buffer.extend(format!("\n\tcall {}", self.get_call_target(function)).as_bytes());
+        buffer.extend(format!("\n\tmov {}, {}", Register::R5(64), Register::R6(64)).as_bytes());
+        buffer.extend(format!("\n\tpop {}", Register::R6(64)).as_bytes());
+
+        if function.return_type == Type::F64 {
+            buffer.extend(format!("\n\tmovq {}, {}", Register::X1, Register::R1(64)).as_bytes());
+        }
+
+        buffer.extend("\n\tret".as_bytes());
+
+        return buffer;
+    }
+
+    // Moves the pushed arguments to their System V registers and calls the extern function on
+    // a 16 byte aligned stack, the result is left in rax like ez functions do
+    fn write_c_call(&self, function: &Function) -> Vec<u8> {
+        let mut buffer: Vec<u8> = Vec::new();
+
+        let floats: Vec<bool> = function
+            .arguments
+            .iter()
+            .map(|index| function.locals.get(*index).expect("Unreachable").local_type == Type::F64)
+            .collect();
+
+        let mut integers = floats.iter().filter(|float| !**float).count();
+        let mut vectors = floats.len() - integers;
+
+        // The last argument was pushed last
+        for float in floats.iter().rev() {
+            if *float {
+                vectors -= 1;
+                buffer.extend(format!("\n\tpop {}", Register::R1(64)).as_bytes());
+                buffer.extend(format!("\n\tmovq xmm{}, {}", vectors, Register::R1(64)).as_bytes());
+            } else {
+                integers -= 1;
+                buffer.extend(format!("\n\tpop {}", INTEGER_REGISTERS[integers]).as_bytes());
+            }
+        }
+
+        // The old stack pointer is pushed twice to keep the alignment, then popped back
+        buffer.extend(format!("\n\tmov {}, {}", Register::R1(64), Register::R5(64)).as_bytes());
+        buffer.extend(format!("\n\tand {}, -0x10", Register::R5(64)).as_bytes());
+        buffer.extend(format!("\n\tpush {}", Register::R1(64)).as_bytes());
+        buffer.extend(format!("\n\tpush {}", Register::R1(64)).as_bytes());
+
+        // Variadic functions like printf read the number of float arguments from al
+        let vectors = floats.iter().filter(|float| **float).count();
+        buffer.extend(format!("\n\tmov {}, {:#x}", Register::R1(32), vectors).as_bytes());
+
+        buffer.extend(format!("\n\tcall {}", self.get_call_target(function)).as_bytes());
+        buffer.extend(format!("\n\tpop {}", Register::R5(64)).as_bytes());
+
+        if function.return_type == Type::F64 {
+            buffer.extend(format!("\n\tmovq {}, {}", Register::R1(64), Register::X1).as_bytes());
+        }
+
+        return buffer;
+    }
+
+    fn write_body(
+        &self,
+        name: &str,
+        body: &Scope,
+        locals: &LocalStack,
+        functions: &Vec<Function>,
+    ) -> Vec<u8> {
+        let mut buffer: Vec<u8> = Vec::new();
+
+        let mut listed: Option<&Position> = None;
+
+        for (index, (statement, span)) in body.statements.iter().zip(body.spans.iter()).enumerate()
+        {
+            let position = &span.position;
+
+            set_processing(position);
+
+            // Statements sharing a line are listed under it once
+            if self.options.listing
+                && listed.map_or(true, |listed| {
+                    listed.line != position.line || listed.file != position.file
+                })
+            {
+                if let Some(line) = self.get_source_line(position) {
+                    buffer.extend(format!("\n; {:>4} | {}", position.line, line.trim()).as_bytes());
+                }
+
+                listed = Some(position);
+            }
+
+            let start = buffer.len();
+
+            // ..@ labels do not start a new scope for the local labels that follow
+            if self.options.source_map {
+                let label = format!("..@ez.loc.{}.{}", name, index);
+
+                buffer.extend(format!("\n{}:", label).as_bytes());
+
+                self.locations
+                    .borrow_mut()
+                    .push(format!("loc\t{}\t{}\t{}", label, name, position));
+            }
+
+            if self.options.coverage {
+                let mut counters = self.counters.borrow_mut();
+
+                buffer.extend(
+                    format!(
+                        "\n\tinc qword [rel ez_coverage + {:#x}]",
+                        counters.len() * 8
+                    )
+                    .as_bytes(),
+                );
+
+                counters.push(position.clone());
+            }
+
+            match statement {
+                // Static locals are zeroed once, in .bss
+                Statement::Declare(local) if locals.locals[*local].static_label.is_some() => {}
+                Statement::Declare(local) => {
+                    let local = locals.get(*local).expect("Unreachable");
+
+                    buffer.extend(
+                        format!(
+                            "\n\tlea {}, [{} - {:#x}]\t; {}",
+                            Register::R8(64),
+                            Register::R6(64),
+                            local.offset + local.size,
+                            local.label
+                        )
+                        .as_bytes(),
+                    );
+                    buffer.extend(
+                        format!("\n\tmov {}, {:#x}", Register::R2(64), local.size).as_bytes(),
+                    );
+                    buffer.extend(
+                        format!("\n\txor {}, {}", Register::R1(32), Register::R1(32)).as_bytes(),
+                    );
+                    buffer.extend("\n\trep stosb".as_bytes());
+                }
+                // Structs are copied from the address of the value to the target
+                Statement::Store(target, expression)
+                    if target.get_type(locals, functions).is_aggregate() =>
+                {
+                    buffer.extend(self.write_address(
+                        expression,
+                        &Register::R2(64),
+                        &Register::R3(64),
+                        locals,
+                        functions,
+                    ));
+
+                    buffer.extend(format!("\n\tpush {}", Register::R2(64)).as_bytes());
+
+                    buffer.extend(self.write_address(
+                        target,
+                        &Register::R2(64),
+                        &Register::R3(64),
+                        locals,
+                        functions,
+                    ));
+
+                    buffer.extend(
+                        format!("\n\tmov {}, {}", Register::R8(64), Register::R2(64)).as_bytes(),
+                    );
+                    buffer.extend(format!("\n\tpop {}", Register::R7(64)).as_bytes());
+                    buffer.extend(self.write_copy(target.get_type(locals, functions).get_size()));
+                }
+                Statement::Store(target, expression) => {
+                    let target_type = target.get_type(locals, functions);
+
+                    buffer.extend(self.write_expression(
+                        expression,
+                        &Register::R2(64),
+                        &Register::R3(64),
+                        locals,
+                        functions,
+                    ));
+
+                    buffer.extend(format!("\n\tpush {}", Register::R2(64)).as_bytes());
+
+                    buffer.extend(self.write_address(
+                        target,
+                        &Register::R2(64),
+                        &Register::R3(64),
+                        locals,
+                        functions,
+                    ));
+
+                    buffer.extend(format!("\n\tpop {}", Register::R3(64)).as_bytes());
+                    buffer.extend(
+                        format!(
+                            "\n\tmov {} [{}], {}",
+                            target_type.get_word_type(),
+                            Register::R2(64),
+                            Register::R3(target_type.get_size() * 8)
+                        )
+                        .as_bytes(),
+                    );
+                }
+                Statement::Assign(local, Expression::Initializer(_, elements)) => {
+                    let local = locals.get(*local).expect("Unreachable");
+
+                    buffer.extend(self.write_initializer(
+                        &local.local_type,
+                        elements,
+                        local.offset + local.size,
+                        locals,
+                        functions,
+                    ));
+                }
+                Statement::Assign(local, expression)
+                    if locals
+                        .get(*local)
+                        .expect("Unreachable")
+                        .local_type
+                        .is_struct() =>
+                {
+                    let local = locals.get(*local).expect("Unreachable");
+
+                    buffer.extend(self.write_address(
+                        expression,
+                        &Register::R2(64),
+                        &Register::R3(64),
+                        locals,
+                        functions,
+                    ));
+
+                    buffer.extend(
+                        format!("\n\tmov {}, {}", Register::R7(64), Register::R2(64)).as_bytes(),
+                    );
+                    buffer.extend(
+                        format!(
+                            "\n\tlea {}, [{}]\t; {}",
+                            Register::R8(64),
+                            local.get_address(),
+                            local.label
+                        )
+                        .as_bytes(),
+                    );
+                    buffer.extend(self.write_copy(local.size));
+                }
+                Statement::Assign(local, expression) => {
+                    let local = locals.get(*local).expect("Unreachable");
+
+                    buffer.extend(self.write_expression(
+                        expression,
+                        &Register::R2(64),
+                        &Register::R3(64),
+                        locals,
+                        functions,
+                    ));
+
+                    buffer.extend(
+                        format!(
+                            "\n\tmov {} [{}], {}\t; {}",
+                            local.get_word_type(),
+                            local.get_address(),
+                            Register::R2(local.size * 8),
+                            local.label
+                        )
+                        .as_bytes(),
+                    );
+                }
+                // Tuples are returned with the first value in rax and the second in rdx
+                Statement::Return(Expression::Tuple(elements)) => {
+                    buffer.extend(self.write_expression(
+                        &elements[1],
+                        &Register::R2(64),
+                        &Register::R3(64),
+                        locals,
+                        functions,
+                    ));
+
+                    buffer.extend(format!("\n\tpush {}", Register::R2(64)).as_bytes());
+
+                    buffer.extend(self.write_expression(
+                        &elements[0],
+                        &Register::R2(64),
+                        &Register::R3(64),
+                        locals,
+                        functions,
+                    ));
+
+                    buffer.extend(format!("\n\tpop {}", Register::R3(64)).as_bytes());
+                    buffer.extend(
+                        format!("\n\tmov {}, {}", Register::R1(64), Register::R2(64)).as_bytes(),
+                    );
+
+                    buffer.extend(format!("\n\tjmp .return_{}", name).as_bytes());
+                }
+                Statement::Destructure(indices, expression) => {
+                    buffer.extend(self.write_expression(
+                        expression,
+                        &Register::R2(64),
+                        &Register::R3(64),
+                        locals,
+                        functions,
+                    ));
+
+                    for (index, register) in indices.iter().zip([Register::R1, Register::R3]) {
+                        let local = locals.get(*index).expect("Unreachable");
+
+                        buffer.extend(
+                            format!(
+                                "\n\tmov {} [{}], {}\t; {}",
+                                local.get_word_type(),
+                                local.get_address(),
+                                register(local.size * 8),
+                                local.label
+                            )
+                            .as_bytes(),
+                        );
+                    }
+                }
+                Statement::Return(expression) => {
+                    // Constants, usually left by propagation, go straight to rax
+                    let constant = matches!(expression, Expression::NumberLiteral(_));
+                    let register = match constant {
+                        true => Register::R1(64),
+                        false => Register::R2(64),
+                    };
+
+                    buffer.extend(self.write_expression(
+                        expression,
+                        &register,
+                        &Register::R3(64),
+                        locals,
+                        functions,
+                    ));
+
+                    if !constant {
+                        buffer.extend(
+                            format!("\n\tmov {}, {}", Register::R1(64), register).as_bytes(),
+                        );
+                    }
+
+                    buffer.extend(format!("\n\tjmp .return_{}", name).as_bytes());
+                }
+                // The message takes the position of the statement
+                Statement::Call(Expression::Intrinsic(intrinsic, _))
+                    if statement.is_diverging() =>
+                {
+                    let message = intrinsic.get_abort_message().expect("Unreachable");
+
+                    buffer.extend(self.write_abort(position, message));
+                }
+                // Labels are local to the function label, like the one of the epilogue
+                Statement::Label(label) => {
+                    buffer.extend(format!("\n.label_{}_{}:", name, label).as_bytes());
+                }
+                Statement::Goto(label) => {
+                    buffer.extend(format!("\n\tjmp .label_{}_{}", name, label).as_bytes());
+                }
+                Statement::Call(expression) => {
+                    // FIXME: idk
+                    buffer.extend(self.write_expression(
+                        expression,
+                        &Register::R2(64),
+                        &Register::R3(64),
+                        locals,
+                        functions,
+                    ));
+                }
+            }
+
+            if self.options.explain {
+                self.explained.borrow_mut().push((
+                    name.to_owned(),
+                    index,
+                    String::from_utf8_lossy(&buffer[start..]).to_string(),
+                ));
+            }
+        }
+
+        return buffer;
+    }
+
+    fn write_expression(
+        &self,
+        expression: &Expression,
+        register: &Register,
+        alt: &Register,
+        locals: &LocalStack,
+        functions: &Vec<Function>,
+    ) -> Vec<u8> {
+        let mut buffer: Vec<u8> = Vec::new();
+
+        match expression {
+            Expression::Binary(binary_expression) => {
+                let left = &*binary_expression.left;
+                let right = &*binary_expression.right;
+
+                let operation_type = expression.get_type(locals, functions);
+
+                if let Some((operand, value)) = self.get_immediate_operand(
+                    binary_expression,
+                    &operation_type,
+                    locals,
+                    functions,
+                ) {
+                    buffer.extend(self.write_expression(operand, register, alt, locals, functions));
+                    buffer.extend(self.write_immediate_operation(
+                        &binary_expression.operator,
+                        &operation_type,
+                        register,
+                        value,
+                    ));
+
+                    return buffer;
+                }
+
+                // The complex side goes first so the other one only needs its own register
+                let (first, first_register, second, second_register) =
+                    match !self.is_complex(left) && self.is_complex(right) {
+                        true => (right, alt, left, register),
+                        false => (left, register, right, alt),
+                    };
+
+                buffer.extend(self.write_expression(
+                    first,
+                    first_register,
+                    second_register,
+                    locals,
+                    functions,
+                ));
+
+                // The second side needs both registers or calls a function, either way the first
+                // value waits on the stack
+                if self.is_complex(second) || self.clobbers_registers(second) {
+                    buffer.extend(format!("\n\tpush {}", first_register).as_bytes());
+                    buffer.extend(self.write_expression(
+                        second,
+                        second_register,
+                        first_register,
+                        locals,
+                        functions,
+                    ));
+                    buffer.extend(format!("\n\tpop {}", first_register).as_bytes());
+                } else {
+                    buffer.extend(self.write_expression(
+                        second,
+                        second_register,
+                        first_register,
+                        locals,
+                        functions,
+                    ));
+                }
+
+                // The integer side of pointer arithmetic counts elements, not bytes
+                match (
+                    left.get_type(locals, functions),
+                    right.get_type(locals, functions),
+                ) {
+                    (Type::Pointer(element), _) => {
+                        buffer.extend(self.write_scale(alt, element.get_size()));
+                    }
+                    (_, Type::Pointer(element)) => {
+                        buffer.extend(self.write_scale(register, element.get_size()));
+                    }
+                    _ => {}
+                }
+
+                buffer.extend(self.write_operation(
+                    &binary_expression.operator,
+                    &operation_type,
+                    register,
+                    alt,
+                ));
+            }
+            Expression::NumberLiteral(number) => {
+                buffer.extend(format!("\n\tmov {}, {:#x}", register, number).as_bytes());
+            }
+            Expression::FloatLiteral(number) => {
+                buffer.extend(
+                    format!(
+                        "\n\tmov {}, {:#x}\t; {:?}",
+                        register,
+                        number.to_bits(),
+                        number
+                    )
+                    .as_bytes(),
+                );
+            }
+            Expression::StringLiteral(index) => {
+                // Addresses are relative to rip so the code works at any load address
+                buffer.extend(format!("\n\tlea {}, [rel strltr.{}]", register, index).as_bytes());
+            }
+            Expression::Local(index) => {
+                if let Some(local) = locals.get(*index) {
+                    buffer.extend(self.write_load(
+                        &local.local_type,
+                        register,
+                        &local.get_address(),
+                    ));
+                    buffer.extend(format!("\t; {}", local.label).as_bytes());
+                } else {
+                    panic!("Unreachable");
+                }
+            }
+            Expression::Initializer(..) | Expression::Tuple(_) => panic!("Unreachable"),
+            Expression::Index(..) | Expression::Field(..) => {
+                let element_type = expression.get_type(locals, functions);
+
+                buffer.extend(self.write_address(expression, register, alt, locals, functions));
+                buffer.extend(self.write_load(&element_type, register, &register.to_string()));
+            }
+            Expression::Address(expression) => {
+                buffer.extend(self.write_address(expression, register, alt, locals, functions));
+            }
+            Expression::Cast(cast_type, expression) => {
+                let expression_type = expression.get_type(locals, functions);
+
+                buffer.extend(self.write_expression(expression, register, alt, locals, functions));
+
+                match (&expression_type, cast_type) {
+                    (Type::F64, Type::F64) => {}
+                    (_, Type::F64) => {
+                        buffer.extend(
+                            format!("\n\tcvtsi2sd {}, {}", Register::X1, register).as_bytes(),
+                        );
+                        buffer
+                            .extend(format!("\n\tmovq {}, {}", register, Register::X1).as_bytes());
+                    }
+                    (Type::F64, _) => {
+                        buffer
+                            .extend(format!("\n\tmovq {}, {}", Register::X1, register).as_bytes());
+                        buffer.extend(
+                            format!("\n\tcvttsd2si {}, {}", register, Register::X1).as_bytes(),
+                        );
+                        buffer.extend(self.write_truncation(cast_type, register));
+                    }
+                    _ => {
+                        buffer.extend(self.write_truncation(cast_type, register));
+                    }
+                }
+            }
+            Expression::Call(id, expressions) => {
+                let function = match functions.get(id.0) {
+                    Some(function) => function,
+                    None => panic!("No function found"),
+                };
+
+                if function.arguments.len() != expressions.len() {
+                    panic!("Argument mismath");
+                }
+
+                for (i, expression) in expressions.iter().enumerate() {
+                    // Structs are passed by address and copied by the called function
+                    if expression.get_type(locals, functions).is_struct() {
+                        buffer.extend(self.write_address(
+                            expression,
+                            &Register::R2(64),
+                            &Register::R3(64),
+                            locals,
+                            functions,
+                        ));
+                    } else {
+                        buffer.extend(self.write_expression(
+                            expression,
+                            &Register::R2(64),
+                            &Register::R3(64),
+                            locals,
+                            functions,
+                        ));
+                    }
+
+                    let argument = function
+                        .locals
+                        .get(*function.arguments.get(i).unwrap())
+                        .unwrap();
+
+                    buffer.extend(
+                        format!("\n\tpush {};\t{}", Register::R2(64), argument.label).as_bytes(),
+                    );
+                }
+
+                if function.external {
+                    buffer.extend(self.write_c_call(function));
+                } else {
+                    buffer
+                        .extend(format!("\n\tcall {}", self.get_call_target(function)).as_bytes());
+
+                    if expressions.len() > 0 {
+                        buffer.extend(
+                            format!("\n\tadd {}, {:#x}", Register::R5(64), expressions.len() * 8)
+                                .as_bytes(),
+                        );
+                    }
+                }
+
+                buffer.extend(format!("\n\tmov {}, {}", register, Register::R1(64)).as_bytes());
+
+                // ez functions return values extended by their type like every register holds
+                // them, assembly and extern functions may only set the low bits like C does
+                if function.body.is_none() {
+                    buffer.extend(self.write_truncation(&function.return_type, register));
+                }
+            }
+            Expression::Format(chunks, expressions) => {
+                buffer.extend(self.write_format(chunks, expressions, locals, functions));
+                buffer.extend(format!("\n\tmov {}, {}", register, Register::R1(64)).as_bytes());
+            }
+            Expression::Intrinsic(intrinsic, expressions) => {
+                for expression in expressions.iter() {
+                    buffer.extend(self.write_expression(
+                        expression,
+                        &Register::R2(64),
+                        &Register::R3(64),
+                        locals,
+                        functions,
+                    ));
+
+                    buffer.extend(format!("\n\tpush {}", Register::R2(64)).as_bytes());
+                }
+
+                let types: Vec<Type> = expressions
+                    .iter()
+                    .map(|expression| expression.get_type(locals, functions))
+                    .collect();
+
+                buffer.extend(self.write_intrinsic(intrinsic, &types));
+                buffer.extend(format!("\n\tmov {}, {}", register, Register::R1(64)).as_bytes());
+            }
+        }
+
+        return buffer;
+    }
+
+    // Complex expressions use the other register as scratch
+    fn is_complex(&self, expression: &Expression) -> bool {
+        return match expression {
+            Expression::Binary(_) => true,
+            Expression::Cast(_, expression)
+            | Expression::Address(expression)
+            | Expression::Field(expression, _) => self.is_complex(expression),
+            Expression::Index(base, index, _) => self.is_complex(base) || self.is_complex(index),
+            Expression::Initializer(..) | Expression::Tuple(_) | Expression::Format(..) => true,
+            _ => false,
+        };
+    }
+
+    // Calls and intrinsics take every register, wherever they are in the expression
+    fn clobbers_registers(&self, expression: &Expression) -> bool {
+        return match expression {
+            Expression::Call(..) | Expression::Intrinsic(..) | Expression::Format(..) => true,
+            Expression::Binary(binary_expression) => {
+                self.clobbers_registers(&binary_expression.left)
+                    || self.clobbers_registers(&binary_expression.right)
+            }
+            Expression::Cast(_, expression)
+            | Expression::Address(expression)
+            | Expression::Field(expression, _) => self.clobbers_registers(expression),
+            Expression::Index(base, index, _) => {
+                self.clobbers_registers(base) || self.clobbers_registers(index)
+            }
+            Expression::Initializer(_, expressions) | Expression::Tuple(expressions) => expressions
+                .iter()
+                .any(|expression| self.clobbers_registers(expression)),
+            _ => false,
+        };
+    }
+
+    // Stores each element in its slot, frame_offset is the distance from rbp to the aggregate
+    fn write_initializer(
+        &self,
+        slot_type: &Type,
+        elements: &Vec<Expression>,
+        frame_offset: usize,
+        locals: &LocalStack,
+        functions: &Vec<Function>,
+    ) -> Vec<u8> {
+        let mut buffer: Vec<u8> = Vec::new();
+
+        let slots: Vec<(&Type, usize)> = match slot_type {
+            Type::Array(element, _) => (0..elements.len())
+                .map(|index| (&**element, index * element.get_size()))
+                .collect(),
+            Type::Struct(layout) => layout
+                .fields
+                .iter()
+                .map(|field| (&field.field_type, field.offset))
+                .collect(),
+            _ => panic!("Unreachable"),
+        };
+
+        for ((element_type, offset), element) in slots.into_iter().zip(elements.iter()) {
+            if let Expression::Initializer(_, elements) = element {
+                buffer.extend(self.write_initializer(
+                    element_type,
+                    elements,
+                    frame_offset - offset,
+                    locals,
+                    functions,
+                ));
+                continue;
+            }
+
+            if element_type.is_struct() {
+                buffer.extend(self.write_address(
+                    element,
+                    &Register::R2(64),
+                    &Register::R3(64),
+                    locals,
+                    functions,
+                ));
+
+                buffer.extend(
+                    format!("\n\tmov {}, {}", Register::R7(64), Register::R2(64)).as_bytes(),
+                );
+                buffer.extend(
+                    format!(
+                        "\n\tlea {}, [{} - {:#x}]",
+                        Register::R8(64),
+                        Register::R6(64),
+                        frame_offset - offset
+                    )
+                    .as_bytes(),
+                );
+                buffer.extend(self.write_copy(element_type.get_size()));
+                continue;
+            }
+
+            buffer.extend(self.write_expression(
+                element,
+                &Register::R2(64),
+                &Register::R3(64),
+                locals,
+                functions,
+            ));
+
+            buffer.extend(
+                format!(
+                    "\n\tmov {} [{} - {:#x}], {}",
+                    element_type.get_word_type(),
+                    Register::R6(64),
+                    frame_offset - offset,
+                    Register::R2(element_type.get_size() * 8)
+                )
+                .as_bytes(),
+            );
+        }
+
+        return buffer;
+    }
+
+    fn write_load(&self, load_type: &Type, register: &Register, address: &str) -> Vec<u8> {
+        // Narrow values are extended so registers always hold the full value
+        let instruction = match load_type {
+            Type::U8 | Type::U16 => "movzx",
+            Type::I8 | Type::I16 => "movsx",
+            Type::I32 => "movsxd",
+            Type::U32 | Type::U64 | Type::I64 | Type::F64 | Type::Pointer(_) => "mov",
+            Type::Array(..) | Type::Struct(_) | Type::Tuple(_) => panic!("Unreachable"),
+        };
+
+        let destination = match load_type {
+            Type::U32 => register.resize(32),
+            _ => register.clone(),
+        };
+
+        return format!(
+            "\n\t{} {}, {} [{}]",
+            instruction,
+            destination,
+            load_type.get_word_type(),
+            address
+        )
+        .into_bytes();
+    }
+
+    fn write_address(
+        &self,
+        expression: &Expression,
+        register: &Register,
+        alt: &Register,
+        locals: &LocalStack,
+        functions: &Vec<Function>,
+    ) -> Vec<u8> {
+        let mut buffer: Vec<u8> = Vec::new();
+
+        match expression {
+            Expression::Local(index) => {
+                let local = locals.get(*index).expect("Unreachable");
+
+                buffer.extend(
+                    format!(
+                        "\n\tlea {}, [{}]\t; {}",
+                        register,
+                        local.get_address(),
+                        local.label
+                    )
+                    .as_bytes(),
+                );
+            }
+            Expression::Index(base, index, position) => {
+                let base_type = base.get_type(locals, functions);
+                let element_size = base_type.get_element().expect("Unreachable").get_size();
+
+                buffer.extend(self.write_expression(index, register, alt, locals, functions));
+
+                if let (true, Type::Array(_, length)) = (self.options.checked_bounds, &base_type) {
+                    buffer.extend(self.write_bounds_check(register, *length, position));
+                }
+                buffer.extend(self.write_scale(register, element_size));
+                buffer.extend(format!("\n\tpush {}", register).as_bytes());
+
+                // Arrays live in place while pointers hold the address as value
+                if let Type::Array(..) = base_type {
+                    buffer.extend(self.write_address(base, register, alt, locals, functions));
+                } else {
+                    buffer.extend(self.write_expression(base, register, alt, locals, functions));
+                }
+
+                buffer.extend(format!("\n\tpop {}", Register::R7(64)).as_bytes());
+                buffer.extend(format!("\n\tadd {}, {}", register, Register::R7(64)).as_bytes());
+            }
+            Expression::Field(base, field) => {
+                let offset = match base.get_type(locals, functions) {
+                    Type::Struct(layout) => layout.fields[*field].offset,
+                    _ => panic!("Unreachable"),
+                };
+
+                buffer.extend(self.write_address(base, register, alt, locals, functions));
+
+                if offset > 0 {
+                    buffer.extend(format!("\n\tadd {}, {:#x}", register, offset).as_bytes());
+                }
+            }
+            _ => panic!("Unreachable"),
+        }
+
+        return buffer;
+    }
+
+    // Copies size bytes from rsi to rdi, through a register when they fit in one
+    fn write_copy(&self, size: usize) -> Vec<u8> {
+        let mut buffer: Vec<u8> = Vec::new();
+
+        if [1, 2, 4, 8].contains(&size) {
+            buffer.extend(
+                format!("\n\tmov {}, [{}]", Register::R1(size * 8), Register::R7(64)).as_bytes(),
+            );
+            buffer.extend(
+                format!("\n\tmov [{}], {}", Register::R8(64), Register::R1(size * 8)).as_bytes(),
+            );
+        } else {
+            buffer.extend(format!("\n\tmov {}, {:#x}", Register::R2(64), size).as_bytes());
+            buffer.extend("\n\trep movsb".as_bytes());
+        }
+
+        return buffer;
+    }
+
+    fn write_bounds_check(
+        &self,
+        register: &Register,
+        length: usize,
+        position: &Position,
+    ) -> Vec<u8> {
+        let mut buffer: Vec<u8> = Vec::new();
+
+        // Numbered like the message written next, so labels stay unique
+        let check = self.aborts.borrow().len();
+
+        // Unsigned comparison also catches negative indices
+        buffer.extend(format!("\n\tcmp {}, {:#x}", register, length).as_bytes());
+        buffer.extend(format!("\n\tjb .bounds_ok_{}", check).as_bytes());
+        buffer.extend(self.write_abort(position, "Index out of bounds."));
+        buffer.extend(format!("\n.bounds_ok_{}:", check).as_bytes());
+
+        return buffer;
+    }
+
+    // Writes the message to stderr and stops at an invalid instruction. Without an operating
+    // system there is nowhere to write, the invalid instruction is left to the exception
+    // handlers of the kernel.
+    fn write_abort(&self, position: &Position, message: &str) -> Vec<u8> {
+        if !self.options.target.has_os() {
+            return "\n\tud2".as_bytes().to_vec();
+        }
+
+        let mut buffer: Vec<u8> = Vec::new();
+
+        let abort = {
+            let mut aborts = self.aborts.borrow_mut();
+            aborts.push(format!("{}: {}\n", position, message));
+            aborts.len() - 1
+        };
+
+        buffer.extend(format!("\n\tlea {}, [rel abort.{}]", Register::R8(64), abort).as_bytes());
+        buffer.extend(format!("\n\tmov {}, abort.{}.len", Register::R7(64), abort).as_bytes());
+        buffer.extend(format!("\n\tcall {}", self.get_trap_routine().get_label()).as_bytes());
+
+        return buffer;
+    }
+
+    // Full 64 bit time stamp counter in rax, clobbering rdx
+    fn write_timestamp(&self) -> Vec<u8> {
+        let mut buffer: Vec<u8> = Vec::new();
+
+        buffer.extend("\n\trdtsc".as_bytes());
+        buffer.extend(format!("\n\tshl {}, 0x20", Register::R3(64)).as_bytes());
+        buffer.extend(format!("\n\tor {}, {}", Register::R1(64), Register::R3(64)).as_bytes());
+
+        return buffer;
+    }
+
+    // Aborting with a backtrace needs the shadow stack routines
+    fn get_trap_routine(&self) -> Routine {
+        return match self.options.backtraces {
+            true => Routine::TrapTrace,
+            false => Routine::Trap,
+        };
+    }
+
+    fn write_scale(&self, register: &Register, size: usize) -> Vec<u8> {
+        if size == 1 {
+            return Vec::new();
+        }
+
+        if size.is_power_of_two() {
+            return format!("\n\tshl {}, {}", register, size.trailing_zeros()).into_bytes();
+        }
+
+        return format!("\n\timul {}, {}, {:#x}", register, register, size).into_bytes();
+    }
+
+    // Operand left to compute when the other one is a constant fitting an immediate, with the
+    // constant already scaled for pointer arithmetic. Constants on the left only move to the
+    // instruction when the operator commutes.
+    fn get_immediate_operand<'a>(
+        &self,
+        binary_expression: &'a BinaryExpression,
+        operation_type: &Type,
+        locals: &LocalStack,
+        functions: &Vec<Function>,
+    ) -> Option<(&'a Expression, u64)> {
+        let integer = operation_type.is_integer() || matches!(operation_type, Type::Pointer(_));
+
+        // Division has no immediate form
+        if !integer || matches!(binary_expression.operator, BinaryOperator::Div) {
+            return None;
+        }
+
+        let left = &*binary_expression.left;
+        let right = &*binary_expression.right;
+
+        let commutes = !matches!(binary_expression.operator, BinaryOperator::Sub);
+
+        let (operand, constant) = match (get_constant(left), get_constant(right)) {
+            (_, Some(value)) => (left, value),
+            (Some(value), None) if commutes => (right, value),
+            _ => return None,
+        };
+
+        let scale = match operand.get_type(locals, functions) {
+            Type::Pointer(element) => element.get_size() as u64,
+            _ => 1,
+        };
+
+        let value = constant.wrapping_mul(scale);
+
+        // Immediates are 32 bits, sign extended to the width of the register
+        return match i32::try_from(value as i64) {
+            Ok(_) => Some((operand, value)),
+            Err(_) => None,
+        };
+    }
+
+    fn write_immediate_operation(
+        &self,
+        operator: &BinaryOperator,
+        operation_type: &Type,
+        register: &Register,
+        value: u64,
+    ) -> Vec<u8> {
+        let mut buffer: Vec<u8> = Vec::new();
+
+        let immediate = match value as i64 {
+            value if value < 0 => format!("-{:#x}", -value),
+            value => format!("{:#x}", value),
+        };
+
+        let instruction = match operator {
+            // Only the three operand form of imul takes an immediate
+            BinaryOperator::Mul => format!("\n\timul {}, {}, {}", register, register, immediate),
+            operator => format!(
+                "\n\t{} {}, {}",
+                operator.get_instruction(),
+                register,
+                immediate
+            ),
+        };
+
+        buffer.extend(instruction.as_bytes());
+        buffer.extend(self.write_truncation(operation_type, register));
+
+        return buffer;
+    }
+
+    fn write_operation(
+        &self,
+        operator: &BinaryOperator,
+        operation_type: &Type,
+        register: &Register,
+        alt: &Register,
+    ) -> Vec<u8> {
+        let mut buffer: Vec<u8> = Vec::new();
+
+        match operation_type {
+            Type::U8
+            | Type::U16
+            | Type::U32
+            | Type::U64
+            | Type::I8
+            | Type::I16
+            | Type::I32
+            | Type::I64
+            | Type::Pointer(_) => {
+                match operator {
+                    BinaryOperator::Div => {
+                        buffer.extend(self.write_division(operation_type, register, alt));
+                    }
+                    operator => buffer.extend(
+                        format!("\n\t{} {}, {}", operator.get_instruction(), register, alt)
+                            .as_bytes(),
+                    ),
+                }
+
+                // Wrap around at the width of the type
+                buffer.extend(self.write_truncation(operation_type, register));
+            }
+            Type::F64 => {
+                buffer.extend(format!("\n\tmovq {}, {}", Register::X1, register).as_bytes());
+                buffer.extend(format!("\n\tmovq {}, {}", Register::X2, alt).as_bytes());
+                buffer.extend(
+                    format!(
+                        "\n\t{} {}, {}",
+                        operator.get_float_instruction(),
+                        Register::X1,
+                        Register::X2
+                    )
+                    .as_bytes(),
+                );
+                buffer.extend(format!("\n\tmovq {}, {}", register, Register::X1).as_bytes());
+            }
+            Type::Array(..) | Type::Struct(_) | Type::Tuple(_) => panic!("Unreachable"),
+        }
+
+        return buffer;
+    }
+
+    // Registers hold values extended to 64 bits by their type, so the 64 bit division gives the
+    // quotient of every width. The operands are rcx and rdx in either order, rax is free and
+    // rsi keeps the divisor away from the sign extension in rdx. Dividing by zero or the most
+    // negative value by -1 raises SIGFPE like in C.
+    fn write_division(&self, integer_type: &Type, register: &Register, alt: &Register) -> Vec<u8> {
+        let mut buffer: Vec<u8> = Vec::new();
+
+        buffer.extend(format!("\n\tmov {}, {}", Register::R1(64), register).as_bytes());
+        buffer.extend(format!("\n\tmov {}, {}", Register::R7(64), alt).as_bytes());
+
+        if integer_type.is_signed() {
+            buffer.extend("\n\tcqo".as_bytes());
+            buffer.extend(format!("\n\tidiv {}", Register::R7(64)).as_bytes());
+        } else {
+            buffer.extend(format!("\n\txor {}, {}", Register::R3(32), Register::R3(32)).as_bytes());
+            buffer.extend(format!("\n\tdiv {}", Register::R7(64)).as_bytes());
+        }
+
+        buffer.extend(format!("\n\tmov {}, {}", register, Register::R1(64)).as_bytes());
+
+        return buffer;
+    }
+
+    fn write_truncation(&self, integer_type: &Type, register: &Register) -> Vec<u8> {
+        let mut buffer: Vec<u8> = Vec::new();
+
+        let size = integer_type.get_size() * 8;
+
+        match integer_type {
+            Type::U8 | Type::U16 => {
+                buffer.extend(
+                    format!("\n\tmovzx {}, {}", register, register.resize(size)).as_bytes(),
+                );
+            }
+            Type::I8 | Type::I16 => {
+                buffer.extend(
+                    format!("\n\tmovsx {}, {}", register, register.resize(size)).as_bytes(),
+                );
+            }
+            Type::U32 => {
+                // Writing the 32 bit register clears the upper half
+                buffer.extend(
+                    format!(
+                        "\n\tmov {}, {}",
+                        register.resize(size),
+                        register.resize(size)
+                    )
+                    .as_bytes(),
+                );
+            }
+            Type::I32 => {
+                buffer.extend(
+                    format!("\n\tmovsxd {}, {}", register, register.resize(size)).as_bytes(),
+                );
+            }
+            Type::U64 | Type::I64 | Type::F64 | Type::Pointer(_) => {}
+            Type::Array(..) | Type::Struct(_) | Type::Tuple(_) => {}
+        }
+
+        return buffer;
+    }
+
+    // Writes the chunks and values in order, returning the total written length in rax
+    fn write_format(
+        &self,
+        chunks: &Vec<Option<usize>>,
+        expressions: &Vec<Expression>,
+        locals: &LocalStack,
+        functions: &Vec<Function>,
+    ) -> Vec<u8> {
+        let mut buffer: Vec<u8> = Vec::new();
+
+        // Written length counter followed by the conversion buffer
+        buffer.extend("\n\tpush 0x0".as_bytes());
+        buffer.extend(format!("\n\tsub {}, 0x20", Register::R5(64)).as_bytes());
+
+        for (index, chunk) in chunks.iter().enumerate() {
+            if let Some(chunk) = chunk {
+                buffer.extend(
+                    format!("\n\tlea {}, [rel strltr.{}]", Register::R7(64), chunk).as_bytes(),
+                );
+                buffer.extend(
+                    format!("\n\tmov {}, strltr.{}.len", Register::R3(64), chunk).as_bytes(),
+                );
+                buffer.extend(self.write_format_syscall());
+            }
+
+            if let Some(expression) = expressions.get(index) {
+                let routine = match expression.get_type(locals, functions) {
+                    Type::F64 => Routine::Ftoa,
+                    _ => Routine::Itoa,
+                };
+
+                buffer.extend(self.write_expression(
+                    expression,
+                    &Register::R2(64),
+                    &Register::R3(64),
+                    locals,
+                    functions,
+                ));
+
+                buffer.extend(
+                    format!("\n\tmov {}, {}", Register::R8(64), Register::R2(64)).as_bytes(),
+                );
+                buffer.extend(
+                    format!("\n\tmov {}, {}", Register::R7(64), Register::R5(64)).as_bytes(),
+                );
+                buffer.extend(format!("\n\tcall {}", routine.get_label()).as_bytes());
+                buffer.extend(
+                    format!("\n\tmov {}, {}", Register::R3(64), Register::R1(64)).as_bytes(),
+                );
+                buffer.extend(
+                    format!("\n\tmov {}, {}", Register::R7(64), Register::R5(64)).as_bytes(),
+                );
+                buffer.extend(self.write_format_syscall());
+            }
+        }
+
+        buffer.extend(format!("\n\tadd {}, 0x20", Register::R5(64)).as_bytes());
+        buffer.extend(format!("\n\tpop {}", Register::R1(64)).as_bytes());
+
+        return buffer;
+    }
+
+    // Writes rdx bytes from rsi to stdout and adds them to the counter
+    fn write_format_syscall(&self) -> Vec<u8> {
+        let mut buffer: Vec<u8> = Vec::new();
+
+        buffer.extend(format!("\n\tmov {}, 0x1", Register::R8(64)).as_bytes());
+        buffer.extend(format!("\n\tmov {}, 0x1", Register::R1(64)).as_bytes());
+        buffer.extend("\n\tsyscall".as_bytes());
+        buffer.extend(
+            format!(
+                "\n\tadd [{} + 0x20], {}",
+                Register::R5(64),
+                Register::R1(64)
+            )
+            .as_bytes(),
+        );
+
+        return buffer;
+    }
+
+    fn write_intrinsic(&self, intrinsic: &Intrinsic, types: &[Type]) -> Vec<u8> {
+        let mut buffer: Vec<u8> = Vec::new();
+
+        match intrinsic {
+            Intrinsic::Write => {
+                buffer.extend(format!("\n\tpop {}\t; length", Register::R3(64)).as_bytes());
+                buffer.extend(format!("\n\tpop {}\t; buffer", Register::R7(64)).as_bytes());
+                buffer
+                    .extend(format!("\n\tpop {}\t; file descriptor", Register::R8(64)).as_bytes());
+                buffer.extend(format!("\n\tmov {}, 0x1", Register::R1(64)).as_bytes());
+                buffer.extend("\n\tsyscall".as_bytes());
+            }
+            Intrinsic::ToStr => {
+                let routine = intrinsic.get_routine(types).expect("Unreachable");
+
+                buffer.extend(format!("\n\tpop {}\t; buffer", Register::R7(64)).as_bytes());
+                buffer.extend(format!("\n\tpop {}\t; number", Register::R8(64)).as_bytes());
+                buffer.extend(format!("\n\tcall {}", routine.get_label()).as_bytes());
+            }
+            Intrinsic::ParseInt => {
+                buffer.extend(format!("\n\tpop {}\t; buffer", Register::R8(64)).as_bytes());
+                buffer.extend(format!("\n\tcall {}", Routine::Atoi.get_label()).as_bytes());
+            }
+            Intrinsic::ToF64 => {
+                buffer.extend(format!("\n\tpop {}", Register::R1(64)).as_bytes());
+                buffer.extend(
+                    format!("\n\tcvtsi2sd {}, {}", Register::X1, Register::R1(64)).as_bytes(),
+                );
+                buffer
+                    .extend(format!("\n\tmovq {}, {}", Register::R1(64), Register::X1).as_bytes());
+            }
+            Intrinsic::ToInt => {
+                buffer.extend(format!("\n\tpop {}", Register::R1(64)).as_bytes());
+                buffer
+                    .extend(format!("\n\tmovq {}, {}", Register::X1, Register::R1(64)).as_bytes());
+                buffer.extend(
+                    format!("\n\tcvttsd2si {}, {}", Register::R1(64), Register::X1).as_bytes(),
+                );
+            }
+            // Quotient ends up in rax and remainder in rdx, exactly where tuples live
+            Intrinsic::DivMod => {
+                buffer.extend(format!("\n\tpop {}\t; divisor", Register::R2(64)).as_bytes());
+                buffer.extend(format!("\n\tpop {}\t; dividend", Register::R1(64)).as_bytes());
+
+                if types[0].is_signed() {
+                    buffer.extend("\n\tcqo".as_bytes());
+                    buffer.extend(format!("\n\tidiv {}", Register::R2(64)).as_bytes());
+                } else {
+                    buffer.extend(
+                        format!("\n\txor {}, {}", Register::R3(32), Register::R3(32)).as_bytes(),
+                    );
+                    buffer.extend(format!("\n\tdiv {}", Register::R2(64)).as_bytes());
+                }
+            }
+            // in and out only take the port in dx and the value in al
+            Intrinsic::PortIn => {
+                buffer.extend(format!("\n\tpop {}\t; port", Register::R3(64)).as_bytes());
+                buffer.extend(
+                    format!("\n\txor {}, {}", Register::R1(32), Register::R1(32)).as_bytes(),
+                );
+                buffer
+                    .extend(format!("\n\tin {}, {}", Register::R1(8), Register::R3(16)).as_bytes());
+            }
+            Intrinsic::PortOut => {
+                buffer.extend(format!("\n\tpop {}\t; value", Register::R1(64)).as_bytes());
+                buffer.extend(format!("\n\tpop {}\t; port", Register::R3(64)).as_bytes());
+                buffer.extend(
+                    format!("\n\tout {}, {}", Register::R3(16), Register::R1(8)).as_bytes(),
+                );
+            }
+            // Only ever the whole value of an assignment, so nothing else is on the stack above.
+            // Rounded up to keep calls aligned, rbp restores rsp when the function returns.
+            Intrinsic::StackAlloc => {
+                buffer.extend(format!("\n\tpop {}\t; size", Register::R1(64)).as_bytes());
+                buffer.extend(format!("\n\tadd {}, 0xf", Register::R1(64)).as_bytes());
+                buffer.extend(format!("\n\tand {}, -0x10", Register::R1(64)).as_bytes());
+                buffer.extend(
+                    format!("\n\tsub {}, {}", Register::R5(64), Register::R1(64)).as_bytes(),
+                );
+                buffer.extend(
+                    format!("\n\tmov {}, {}", Register::R1(64), Register::R5(64)).as_bytes(),
+                );
+            }
+            // rep takes the count in rcx, stos and movs write to rdi and movs and cmps read rsi
+            Intrinsic::Memset => {
+                buffer.extend(format!("\n\tpop {}\t; length", Register::R2(64)).as_bytes());
+                buffer.extend(format!("\n\tpop {}\t; value", Register::R1(64)).as_bytes());
+                buffer.extend(format!("\n\tpop {}\t; destination", Register::R8(64)).as_bytes());
+                buffer.extend(
+                    format!("\n\tmov {}, {}", Register::R3(64), Register::R8(64)).as_bytes(),
+                );
+                buffer.extend("\n\trep stosb".as_bytes());
+                buffer.extend(
+                    format!("\n\tmov {}, {}", Register::R1(64), Register::R3(64)).as_bytes(),
+                );
+            }
+            Intrinsic::Memcpy => {
+                buffer.extend(format!("\n\tpop {}\t; length", Register::R2(64)).as_bytes());
+                buffer.extend(format!("\n\tpop {}\t; source", Register::R7(64)).as_bytes());
+                buffer.extend(format!("\n\tpop {}\t; destination", Register::R8(64)).as_bytes());
+                buffer.extend(
+                    format!("\n\tmov {}, {}", Register::R1(64), Register::R8(64)).as_bytes(),
+                );
+                buffer.extend("\n\trep movsb".as_bytes());
+            }
+            // Flags are left by the last bytes compared, or by the xor when there are none
+            Intrinsic::Memcmp => {
+                buffer.extend(format!("\n\tpop {}\t; length", Register::R2(64)).as_bytes());
+                buffer.extend(format!("\n\tpop {}\t; b", Register::R8(64)).as_bytes());
+                buffer.extend(format!("\n\tpop {}\t; a", Register::R7(64)).as_bytes());
+                buffer.extend(
+                    format!("\n\txor {}, {}", Register::R1(32), Register::R1(32)).as_bytes(),
+                );
+                buffer.extend(
+                    format!("\n\txor {}, {}", Register::R3(32), Register::R3(32)).as_bytes(),
+                );
+                buffer.extend("\n\trepe cmpsb".as_bytes());
+                buffer.extend(format!("\n\tseta {}", Register::R1(8)).as_bytes());
+                buffer.extend(format!("\n\tsetb {}", Register::R3(8)).as_bytes());
+                buffer.extend(
+                    format!("\n\tsub {}, {}", Register::R1(64), Register::R3(64)).as_bytes(),
+                );
+            }
+            Intrinsic::Random => {
+                buffer.extend(format!("\n\tcall {}", Routine::Random.get_label()).as_bytes());
+            }
+            Intrinsic::Seed => {
+                buffer.extend(format!("\n\tpop {}\t; seed", Register::R1(64)).as_bytes());
+                buffer.extend(
+                    format!("\n\tmov [rel ez_rand_state], {}", Register::R1(64)).as_bytes(),
+                );
+            }
+            // clock_gettime fills seconds and nanoseconds on the stack
+            Intrinsic::Time | Intrinsic::ClockNs => {
+                let clock = match intrinsic {
+                    Intrinsic::Time => "0x0\t; CLOCK_REALTIME",
+                    _ => "0x1\t; CLOCK_MONOTONIC",
+                };
+
+                buffer.extend(format!("\n\tsub {}, 0x10", Register::R5(64)).as_bytes());
+                buffer.extend(format!("\n\tmov {}, {}", Register::R8(64), clock).as_bytes());
+                buffer.extend(
+                    format!("\n\tmov {}, {}", Register::R7(64), Register::R5(64)).as_bytes(),
+                );
+                buffer.extend(format!("\n\tmov {}, 0xe4", Register::R1(64)).as_bytes());
+                buffer.extend("\n\tsyscall".as_bytes());
+                buffer.extend(
+                    format!("\n\tmov {}, [{}]", Register::R1(64), Register::R5(64)).as_bytes(),
+                );
+
+                if let Intrinsic::ClockNs = intrinsic {
+                    buffer.extend(
+                        format!(
+                            "\n\timul {}, {}, 0x3b9aca00",
+                            Register::R1(64),
+                            Register::R1(64)
+                        )
+                        .as_bytes(),
+                    );
+                    buffer.extend(
+                        format!("\n\tadd {}, [{} + 0x8]", Register::R1(64), Register::R5(64))
+                            .as_bytes(),
+                    );
+                }
+
+                buffer.extend(format!("\n\tadd {}, 0x10", Register::R5(64)).as_bytes());
+            }
+            // nanosleep takes seconds and nanoseconds, negative durations fail with EINVAL
+            Intrinsic::SleepMs => {
+                buffer.extend(format!("\n\tpop {}\t; milliseconds", Register::R1(64)).as_bytes());
+                buffer.extend(format!("\n\tmov {}, 0x3e8", Register::R2(64)).as_bytes());
+                buffer.extend("\n\tcqo".as_bytes());
+                buffer.extend(format!("\n\tidiv {}", Register::R2(64)).as_bytes());
+                buffer.extend(
+                    format!(
+                        "\n\timul {}, {}, 0xf4240",
+                        Register::R3(64),
+                        Register::R3(64)
+                    )
+                    .as_bytes(),
+                );
+                buffer.extend(format!("\n\tpush {}", Register::R3(64)).as_bytes());
+                buffer.extend(format!("\n\tpush {}", Register::R1(64)).as_bytes());
+                buffer.extend(
+                    format!("\n\tmov {}, {}", Register::R8(64), Register::R5(64)).as_bytes(),
+                );
+                buffer.extend(
+                    format!("\n\txor {}, {}", Register::R7(32), Register::R7(32)).as_bytes(),
+                );
+                buffer.extend(format!("\n\tmov {}, 0x23", Register::R1(64)).as_bytes());
+                buffer.extend("\n\tsyscall".as_bytes());
+                buffer.extend(format!("\n\tadd {}, 0x10", Register::R5(64)).as_bytes());
+            }
+            // Statements of their own, written by write_body
+            Intrinsic::Trap | Intrinsic::Unreachable => panic!("Unreachable"),
+        }
+
+        return buffer;
+    }
+
+    fn get_stem(&self) -> String {
+        if let Some(output) = &self.options.output {
+            return output.to_owned();
+        }
 
-    fn save_buffer(&self) {
         let path = Path::new(&self.filename);
-        let stem = path.file_stem().expect("Error").to_str().unwrap();
-
-        let mut file = File::create(format!("{}.s", stem)).expect("Can not create file");
-        file.write(&self.buffer).expect("Can not write to file");
-
-        Command::new("nasm")
-            .arg("-felf64")
-            .arg(format!("{}.s", stem))
-            .arg("-o")
-            .arg(format!("{}.o", stem))
-            .output()
-            .expect("failed to assemble");
-
-        Command::new("ld")
-            .arg(format!("{}.o", stem))
-            .arg("-o")
-            .arg(stem)
-            .output()
-            .expect("failed to link");
+        return path
+            .file_stem()
+            .expect("Error")
+            .to_str()
+            .unwrap()
+            .to_owned();
+    }
+
+    fn save_artifacts(&mut self) -> Result<(), String> {
+        let stem = self.get_stem();
+
+        if self.options.source_map {
+            let mut source_map = format!("# ezlang source map for {}\n", self.filename);
+
+            for location in self.locations.borrow().iter() {
+                source_map.push_str(location);
+                source_map.push('\n');
+            }
+
+            if let Err(error) = write(format!("{}.map", stem), source_map) {
+                return Err(format!("Can not write {}.map: {}", stem, error));
+            }
+
+            self.artifacts.push(format!("{}.map", stem));
+        }
+
+        if self.options.coverage {
+            let mut map = format!("# ezlang coverage map for {}\n", self.filename);
+
+            for position in self.counters.borrow().iter() {
+                map.push_str(&format!("{}\n", position));
+            }
+
+            if let Err(error) = write(format!("{}.covmap", stem), map) {
+                return Err(format!("Can not write {}.covmap: {}", stem, error));
+            }
+
+            self.artifacts.push(format!("{}.covmap", stem));
+        }
+
+        if self.options.profile {
+            let mut map = format!("# ezlang profile map for {}\n", self.filename);
+
+            for function in self.profiled.borrow().iter() {
+                map.push_str(function);
+                map.push('\n');
+            }
+
+            if let Err(error) = write(format!("{}.profmap", stem), map) {
+                return Err(format!("Can not write {}.profmap: {}", stem, error));
+            }
+
+            self.artifacts.push(format!("{}.profmap", stem));
+        }
+
+        if let Some(header) = &self.header {
+            if let Err(error) = write(format!("{}.h", stem), header) {
+                return Err(format!("Can not write {}.h: {}", stem, error));
+            }
+
+            self.artifacts.push(format!("{}.h", stem));
+        }
+
+        if let Some(ast) = &self.ast {
+            if let Err(error) = write(format!("{}.ast.json", stem), ast) {
+                return Err(format!("Can not write {}.ast.json: {}", stem, error));
+            }
+
+            self.artifacts.push(format!("{}.ast.json", stem));
+        }
+
+        // Without a script of the user, the kernel is linked at the address the loader expects
+        if self.options.target == Target::Baremetal && self.options.linker_script.is_none() {
+            if let Err(error) = write(format!("{}.ld", stem), write_linker_script()) {
+                return Err(format!("Can not write {}.ld: {}", stem, error));
+            }
+
+            self.artifacts.push(format!("{}.ld", stem));
+        }
+
+        return self.assemble(&stem);
+    }
+
+    #[cfg(not(feature = "native"))]
+    fn assemble(&mut self, stem: &str) -> Result<(), String> {
+        return Err(format!(
+            "Built without the native feature, {}.s has to be assembled and linked by hand.",
+            stem
+        ));
+    }
+
+    #[cfg(feature = "native")]
+    fn assemble(&mut self, stem: &str) -> Result<(), String> {
+        let mut assembler = match self.options.asm_syntax {
+            AsmSyntax::Nasm => {
+                let mut nasm = Command::new("nasm");
+                nasm.arg("-felf64");
+                nasm
+            }
+            AsmSyntax::Gas => {
+                // GNU as drops symbols starting with .. as local, NASM keeps the ..@ labels the
+                // debugger looks up
+                let mut gas = Command::new("as");
+                gas.arg("-L");
+                gas
+            }
+        };
+
+        // Each line of the assembly next to the address and bytes it encodes to
+        if self.options.listing {
+            match self.options.asm_syntax {
+                AsmSyntax::Nasm => assembler.arg("-l").arg(format!("{}.lst", stem)),
+                AsmSyntax::Gas => assembler.arg(format!("-aln={}.lst", stem)),
+            };
+        }
+
+        let object = match self.options.check_assembly {
+            true => String::from("/dev/null"),
+            false => format!("{}.o", stem),
+        };
+
+        let assembled = self.run_tool(
+            "assemble",
+            assembler.arg(format!("{}.s", stem)).arg("-o").arg(&object),
+        );
+
+        // Code written by the compiler should always assemble, so the error is a bug of its own
+        if let Err(error) = assembled {
+            let assembly = read_to_string(format!("{}.s", stem)).unwrap_or_default();
+
+            return Err(
+                match find_rejected_function(&format!("{}.s", stem), &assembly, &error) {
+                    Some(label) => format!(
+                        "Bug in ezlang, the assembler rejected the code written for {}. Please \
+                         report it with the source that produced it.\n{}",
+                        label, error
+                    ),
+                    None => error,
+                },
+            );
+        }
+
+        if self.options.check_assembly {
+            return Ok(());
+        }
+
+        self.artifacts.push(object);
+
+        if self.options.listing {
+            self.artifacts.push(format!("{}.lst", stem));
+        }
+
+        if self.options.archive {
+            let path = Path::new(stem);
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let archive = path
+                .with_file_name(format!("lib{}.a", name))
+                .to_string_lossy()
+                .to_string();
+
+            // Objects of the dependencies go in with it, so linking the archive is enough
+            let objects = self.options.link.iter().filter(|link| link.ends_with(".o"));
+
+            self.run_tool(
+                "archive",
+                Command::new("ar")
+                    .arg("rcs")
+                    .arg(&archive)
+                    .arg(format!("{}.o", stem))
+                    .args(objects),
+            )?;
+
+            self.artifacts.push(archive);
+        }
+
+        if self.options.library {
+            return Ok(());
+        }
+
+        let output = match (self.options.shared, self.options.target) {
+            (true, _) => {
+                let path = Path::new(stem);
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+
+                path.with_file_name(format!("lib{}.so", name))
+                    .to_string_lossy()
+                    .to_string()
+            }
+            (false, Target::Baremetal) => format!("{}.elf", stem),
+            (false, Target::Linux) => stem.to_owned(),
+        };
+
+        let script = match (&self.options.linker_script, self.options.target) {
+            (Some(script), _) => Some(script.to_owned()),
+            (None, Target::Baremetal) => Some(format!("{}.ld", stem)),
+            (None, Target::Linux) => None,
+        };
+
+        let mut linker = Command::new("ld");
+
+        if self.options.shared {
+            linker.arg("-shared");
+        }
+
+        if let Some(script) = script {
+            linker.arg("-T").arg(script);
+        }
+
+        self.run_tool(
+            "link",
+            linker
+                .arg(format!("{}.o", stem))
+                .args(&self.options.link)
+                .arg("-o")
+                .arg(&output),
+        )?;
+
+        self.artifacts.push(output.clone());
+
+        // Loaders without ELF support get the image as it is laid out in memory
+        if self.options.target == Target::Baremetal {
+            self.run_tool(
+                "objcopy",
+                Command::new("objcopy")
+                    .arg("-O")
+                    .arg("binary")
+                    .arg(&output)
+                    .arg(format!("{}.bin", stem)),
+            )?;
+
+            self.artifacts.push(format!("{}.bin", stem));
+        }
+
+        return Ok(());
+    }
+
+    #[cfg(feature = "native")]
+    fn run_tool(&mut self, phase: &'static str, command: &mut Command) -> Result<(), String> {
+        let span = Span::enter(phase);
+        let tool = command.get_program().to_string_lossy().to_string();
+
+        let arguments: Vec<String> = command
+            .get_args()
+            .map(|argument| argument.to_string_lossy().to_string())
+            .collect();
+
+        log(
+            Level::Debug,
+            format_args!("running {} {}", tool, arguments.join(" ")),
+        );
+
+        let output = match command.output() {
+            Ok(output) => output,
+            Err(error) => return Err(format!("Can not run {}: {}", tool, error)),
+        };
+
+        if !output.status.success() {
+            return Err(format!(
+                "{} failed with {}:\n{}",
+                tool,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim_end()
+            ));
+        }
+
+        self.timings.push((phase, span.elapsed()));
+
+        return Ok(());
+    }
+}
+
+// Label of the function holding the first line the assembler reports, like
+// `program.s:12: error: ...`. Included assembly is written by hand, so it has none.
+#[cfg(feature = "native")]
+fn find_rejected_function(filename: &str, assembly: &str, error: &str) -> Option<String> {
+    let prefix = format!("{}:", filename);
+
+    let line: usize = error.lines().find_map(|line| {
+        return line.strip_prefix(&prefix)?.split(':').next()?.parse().ok();
+    })?;
+
+    let mut function: Option<String> = None;
+
+    for text in assembly.lines().take(line) {
+        // Included assembly is written by the user, even its lines ending in a colon
+        if text.contains("Included assembly:") {
+            return None;
+        } else if let Some(label) = text.strip_suffix(':') {
+            // Local labels and the ones of the source map belong to the function before them
+            if !text.starts_with(char::is_whitespace) && !label.starts_with('.') {
+                function = Some(label.to_owned());
+            }
+        }
     }
+
+    return function;
 }
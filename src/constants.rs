@@ -0,0 +1,307 @@
+use crate::{
+    cfg::build_graph,
+    dataflow::compute_liveness,
+    lexer::BinaryOperator,
+    parser::{BinaryExpression, Expression, Function, LocalStack, Scope, Statement, Type},
+};
+
+// Body with the locals known to hold a constant replaced by it and the integer operations
+// on constants computed, wrapping at the width of their type like the generated code.
+// Only locals whose address is never taken are followed, from the entry of the function
+// until the first return.
+pub fn propagate_constants(function: &Function, body: &Scope, functions: &Vec<Function>) -> Scope {
+    let escaped = compute_liveness(function, body).escaped;
+    let graph = build_graph(body);
+
+    let tracked: Vec<bool> = function
+        .locals
+        .locals
+        .iter()
+        .enumerate()
+        .map(|(index, local)| local.local_type.is_integer() && !escaped[index])
+        .collect();
+
+    let mut statements: Vec<Statement> = Vec::new();
+
+    for block in graph.blocks.iter() {
+        // Blocks after the entry follow a return, an abort or start at a label, nothing is
+        // known when they start
+        let mut known: Vec<Option<u64>> = vec![None; tracked.len()];
+
+        for statement in body.statements[block.statements.clone()].iter() {
+            let folder = Folder {
+                function,
+                functions,
+                known: &known,
+            };
+
+            let statement = match statement {
+                Statement::Declare(local) => Statement::Declare(*local),
+                Statement::Assign(local, expression) => {
+                    Statement::Assign(*local, folder.fold(expression))
+                }
+                Statement::Store(target, expression) => {
+                    Statement::Store(folder.fold_place(target), folder.fold(expression))
+                }
+                Statement::Destructure(locals, expression) => {
+                    Statement::Destructure(locals.clone(), folder.fold(expression))
+                }
+                Statement::Return(expression) => Statement::Return(folder.fold(expression)),
+                Statement::Call(expression) => Statement::Call(folder.fold(expression)),
+                Statement::Label(label) => Statement::Label(*label),
+                Statement::Goto(label) => Statement::Goto(*label),
+            };
+
+            match &statement {
+                // Declared locals start zeroed
+                Statement::Declare(local) if tracked[*local] => known[*local] = Some(0),
+                Statement::Assign(local, expression) if tracked[*local] => {
+                    let local_type = &function.locals.locals[*local].local_type;
+
+                    known[*local] = get_constant(expression).map(|value| wrap(value, local_type));
+                }
+                Statement::Declare(local) | Statement::Assign(local, _) => known[*local] = None,
+                Statement::Destructure(locals, _) => {
+                    for local in locals.iter() {
+                        known[*local] = None;
+                    }
+                }
+                Statement::Store(..)
+                | Statement::Return(_)
+                | Statement::Call(_)
+                | Statement::Label(_)
+                | Statement::Goto(_) => {}
+            }
+
+            statements.push(statement);
+        }
+    }
+
+    return Scope {
+        statements,
+        spans: body.spans.clone(),
+    };
+}
+
+struct Folder<'a> {
+    function: &'a Function,
+    functions: &'a Vec<Function>,
+    // Constant value of each local followed so far
+    known: &'a [Option<u64>],
+}
+
+impl<'a> Folder<'a> {
+    fn fold(&self, expression: &Expression) -> Expression {
+        return match expression {
+            Expression::Local(index) => match self.known.get(*index) {
+                Some(Some(value)) => {
+                    let local_type = &self.function.locals.locals[*index].local_type;
+                    get_literal(*value, local_type)
+                }
+                _ => expression.clone(),
+            },
+            Expression::Binary(binary_expression) => {
+                let operation_type = expression.get_type(&self.function.locals, self.functions);
+                self.fold_binary(binary_expression, &operation_type)
+            }
+            Expression::Cast(cast_type, operand) => {
+                let operand = self.fold(operand);
+
+                let operand_type = operand.get_type(&self.function.locals, self.functions);
+
+                match get_constant(&operand) {
+                    Some(value) if cast_type.is_integer() && operand_type.is_integer() => {
+                        get_literal(wrap(value, cast_type), cast_type)
+                    }
+                    _ => Expression::Cast(cast_type.clone(), Box::new(operand)),
+                }
+            }
+            Expression::Index(base, index, position) => Expression::Index(
+                Box::new(self.fold_place(base)),
+                Box::new(self.fold(index)),
+                position.clone(),
+            ),
+            Expression::Field(base, field) => {
+                Expression::Field(Box::new(self.fold_place(base)), *field)
+            }
+            Expression::Address(place) => Expression::Address(Box::new(self.fold_place(place))),
+            Expression::Call(index, arguments) => Expression::Call(
+                *index,
+                arguments
+                    .iter()
+                    .map(|argument| self.fold(argument))
+                    .collect(),
+            ),
+            Expression::Intrinsic(intrinsic, arguments) => Expression::Intrinsic(
+                intrinsic.clone(),
+                arguments
+                    .iter()
+                    .map(|argument| self.fold(argument))
+                    .collect(),
+            ),
+            Expression::Initializer(initializer_type, elements) => Expression::Initializer(
+                initializer_type.clone(),
+                elements.iter().map(|element| self.fold(element)).collect(),
+            ),
+            Expression::Tuple(elements) => {
+                Expression::Tuple(elements.iter().map(|element| self.fold(element)).collect())
+            }
+            Expression::Format(chunks, arguments) => Expression::Format(
+                chunks.clone(),
+                arguments
+                    .iter()
+                    .map(|argument| self.fold(argument))
+                    .collect(),
+            ),
+            Expression::NumberLiteral(_)
+            | Expression::FloatLiteral(_)
+            | Expression::StringLiteral(_) => expression.clone(),
+        };
+    }
+
+    // Locals used as a place keep their slot, only the indices inside are folded
+    fn fold_place(&self, expression: &Expression) -> Expression {
+        return match expression {
+            Expression::Local(_) => expression.clone(),
+            expression => self.fold(expression),
+        };
+    }
+
+    fn fold_binary(&self, original: &BinaryExpression, operation_type: &Type) -> Expression {
+        let mut binary_expression = BinaryExpression {
+            operator: original.operator.clone(),
+            left: Box::new(self.fold(&original.left)),
+            right: Box::new(self.fold(&original.right)),
+        };
+
+        let constants = (
+            get_constant(&binary_expression.left),
+            get_constant(&binary_expression.right),
+        );
+
+        // Divisions that trap are left to the generated code
+        if let ((Some(left), Some(right)), true) = (constants, operation_type.is_integer()) {
+            let operator = &binary_expression.operator;
+
+            if let Ok(value) = compute(operator, operation_type, left, right) {
+                return get_literal(value, operation_type);
+            }
+        }
+
+        // An untyped left operand takes the type of the right one, a left operand that
+        // became a plain literal keeps the type it had
+        if !original.left.is_untyped() && binary_expression.left.is_untyped() {
+            let left_type = original
+                .left
+                .get_type(&self.function.locals, self.functions);
+
+            binary_expression.left = Box::new(Expression::Cast(left_type, binary_expression.left));
+        }
+
+        return Expression::Binary(binary_expression);
+    }
+}
+
+// Value of an integer expression known at compile time, for the places of the language that
+// need a constant like array lengths. Fails with the reason it has no value.
+pub fn evaluate(
+    expression: &Expression,
+    locals: &LocalStack,
+    functions: &Vec<Function>,
+) -> Result<u64, &'static str> {
+    let expression_type = expression.get_type(locals, functions);
+
+    if !expression_type.is_integer() {
+        return Err("only integers are known at compile time");
+    }
+
+    return match expression {
+        Expression::NumberLiteral(value) => Ok(*value),
+        Expression::Cast(cast_type, operand) => {
+            if !operand.get_type(locals, functions).is_integer() {
+                return Err("only integers are known at compile time");
+            }
+
+            Ok(wrap(evaluate(operand, locals, functions)?, cast_type))
+        }
+        Expression::Binary(binary_expression) => {
+            let left = evaluate(&binary_expression.left, locals, functions)?;
+            let right = evaluate(&binary_expression.right, locals, functions)?;
+
+            compute(
+                &binary_expression.operator,
+                &expression_type,
+                left,
+                right,
+            )
+        }
+        _ => Err("only literals, constants, @sizeof, casts, integer arithmetic and calls of pure functions are known at compile time"),
+    };
+}
+
+// Integer operation on constants, wrapping at the width of the type like the generated code
+pub fn compute(
+    operator: &BinaryOperator,
+    operation_type: &Type,
+    left: u64,
+    right: u64,
+) -> Result<u64, &'static str> {
+    let value = match operator {
+        BinaryOperator::Add => left.wrapping_add(right),
+        BinaryOperator::Sub => left.wrapping_sub(right),
+        BinaryOperator::Mul => left.wrapping_mul(right),
+        BinaryOperator::BitwiseAnd => left & right,
+        BinaryOperator::BitwiseOr => left | right,
+        BinaryOperator::BitwiseXor => left ^ right,
+        BinaryOperator::Div if right == 0 => return Err("it divides by zero"),
+        BinaryOperator::Div if operation_type.is_signed() => {
+            match (left as i64).checked_div(right as i64) {
+                Some(quotient) => quotient as u64,
+                None => return Err("the division overflows"),
+            }
+        }
+        BinaryOperator::Div => left / right,
+    };
+
+    return Ok(wrap(value, operation_type));
+}
+
+// Integer constant of a folded expression, as the bits the generated code computes
+pub fn get_constant(expression: &Expression) -> Option<u64> {
+    return match expression {
+        Expression::NumberLiteral(value) => Some(*value),
+        Expression::Cast(cast_type, operand) if cast_type.is_integer() => match &**operand {
+            Expression::NumberLiteral(value) => Some(wrap(*value, cast_type)),
+            _ => None,
+        },
+        _ => None,
+    };
+}
+
+// Plain literals have type i64, other types keep a cast so the operations around stay the same
+pub fn get_literal(value: u64, literal_type: &Type) -> Expression {
+    return match literal_type {
+        Type::I64 => Expression::NumberLiteral(value),
+        _ => Expression::Cast(
+            literal_type.clone(),
+            Box::new(Expression::NumberLiteral(value)),
+        ),
+    };
+}
+
+// Truncates to the width of the type, extending the sign of signed types like movsx
+pub fn wrap(value: u64, integer_type: &Type) -> u64 {
+    let bits = integer_type.get_size() * 8;
+
+    if bits >= 64 {
+        return value;
+    }
+
+    let mask = (1u64 << bits) - 1;
+    let negative = integer_type.is_signed() && value & (1 << (bits - 1)) != 0;
+
+    return match negative {
+        true => value | !mask,
+        false => value & mask,
+    };
+}
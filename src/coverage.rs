@@ -0,0 +1,103 @@
+use std::{
+    collections::BTreeMap,
+    fs::{read, read_to_string},
+    path::Path,
+};
+
+// Counters written at exit by instrumented programs, as little endian quad words
+pub fn read_counters(path: &str) -> Result<Vec<u64>, String> {
+    let counters = match read(path) {
+        Ok(counters) => counters,
+        Err(error) => {
+            return Err(format!(
+                "Can not read {}, run the program first: {}",
+                path, error
+            ))
+        }
+    };
+
+    return Ok(counters
+        .chunks_exact(8)
+        .map(|counter| u64::from_le_bytes(counter.try_into().expect("Unreachable")))
+        .collect());
+}
+
+// Annotates every instrumented source file with the execution count of its lines
+pub fn write_report(filename: &str) -> Result<String, String> {
+    let stem = match Path::new(filename).file_stem() {
+        Some(stem) => stem.to_string_lossy().to_string(),
+        None => return Err(format!("Invalid source file {}.", filename)),
+    };
+
+    let map = match read_to_string(format!("{}.covmap", stem)) {
+        Ok(map) => map,
+        Err(error) => {
+            return Err(format!(
+                "Can not read {}.covmap, build with --coverage first: {}",
+                stem, error
+            ))
+        }
+    };
+
+    let counts = read_counters(&format!("{}.cov", stem))?;
+
+    let statements: Vec<&str> = map.lines().filter(|line| !line.starts_with('#')).collect();
+
+    if statements.len() != counts.len() {
+        return Err(format!(
+            "{}.cov does not match {}.covmap, rebuild and run the program again.",
+            stem, stem
+        ));
+    }
+
+    // Statements sharing a line are reported with the highest count
+    let mut files: BTreeMap<String, BTreeMap<usize, u64>> = BTreeMap::new();
+
+    for (statement, count) in statements.iter().zip(counts.iter()) {
+        let mut parts = statement.rsplitn(3, ':');
+
+        let (line, file) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(_), Some(line), Some(file)) => (line, file),
+            _ => return Err(format!("Invalid coverage map line: {}", statement)),
+        };
+
+        let line: usize = match line.parse() {
+            Ok(line) => line,
+            Err(_) => return Err(format!("Invalid coverage map line: {}", statement)),
+        };
+
+        let lines = files.entry(file.to_owned()).or_default();
+        let entry = lines.entry(line).or_insert(0);
+        *entry = (*entry).max(*count);
+    }
+
+    let mut report = String::new();
+
+    for (file, lines) in files.iter() {
+        let source = match read_to_string(file) {
+            Ok(source) => source,
+            Err(error) => return Err(format!("Can not read {}: {}", file, error)),
+        };
+
+        let executed = lines.values().filter(|count| **count > 0).count();
+
+        report.push_str(&format!(
+            "{}: {} of {} lines executed\n",
+            file,
+            executed,
+            lines.len()
+        ));
+
+        for (index, text) in source.lines().enumerate() {
+            let count = match lines.get(&(index + 1)) {
+                Some(0) => "#####".to_owned(),
+                Some(count) => count.to_string(),
+                None => "-".to_owned(),
+            };
+
+            report.push_str(&format!("{:>9} | {:>4} | {}\n", count, index + 1, text));
+        }
+    }
+
+    return Ok(report);
+}
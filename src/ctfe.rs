@@ -0,0 +1,98 @@
+use crate::{
+    interpreter::{Interpreter, InterpreterOptions},
+    parser::{Expression, Function, FunctionId, Intrinsic, Program},
+};
+
+// Bounds of a single evaluation besides its steps, which come from the limits of the parser
+const MAX_DEPTH: usize = 256;
+const MAX_MEMORY: usize = 16 * 1024 * 1024;
+
+// Runs a function of the program being parsed with constant arguments, for constants like
+// `const SIZE = @next_size(100);`. Only pure functions are run, so evaluating the call at
+// compile time gives the value running it would.
+pub fn evaluate_call(
+    program: &Program,
+    id: FunctionId,
+    arguments: Vec<u64>,
+    max_steps: u64,
+) -> Result<u64, String> {
+    check_pure(&program.functions, id)?;
+
+    let options = InterpreterOptions {
+        max_steps,
+        max_depth: MAX_DEPTH,
+        max_memory: MAX_MEMORY,
+        trace: false,
+    };
+
+    return Interpreter::new(program, options).call_function(id, arguments);
+}
+
+// Fails with the first effect the function or one it calls has besides its result: output,
+// port I/O, static locals kept between calls or code outside of the program
+fn check_pure(functions: &Vec<Function>, id: FunctionId) -> Result<(), String> {
+    let mut visited = vec![false; functions.len()];
+    let mut pending = vec![id];
+
+    while let Some(id) = pending.pop() {
+        if visited[id.0] {
+            continue;
+        }
+
+        visited[id.0] = true;
+
+        let function = &functions[id];
+        let name = function.get_display_name();
+
+        let body = match &function.body {
+            Some(body) => body,
+            None => return Err(format!("@{} is not implemented in ez", name)),
+        };
+
+        if function
+            .locals
+            .locals
+            .iter()
+            .any(|local| local.static_label.is_some())
+        {
+            return Err(format!("@{} is not pure, it keeps static variables", name));
+        }
+
+        let mut expressions: Vec<&Expression> = body
+            .statements
+            .iter()
+            .flat_map(|statement| statement.get_expressions())
+            .collect();
+
+        while let Some(expression) = expressions.pop() {
+            match expression {
+                Expression::Format(..) => {
+                    return Err(format!("@{} is not pure, it calls @printf", name))
+                }
+                Expression::Intrinsic(
+                    intrinsic @ (Intrinsic::Write
+                    | Intrinsic::PortIn
+                    | Intrinsic::PortOut
+                    | Intrinsic::Random
+                    | Intrinsic::Seed
+                    | Intrinsic::Time
+                    | Intrinsic::ClockNs
+                    | Intrinsic::SleepMs),
+                    _,
+                ) => {
+                    return Err(format!(
+                        "@{} is not pure, it calls @{}",
+                        name,
+                        intrinsic.get_name()
+                    ))
+                }
+                Expression::Call(callee, _) => pending.push(*callee),
+                _ => {}
+            }
+
+            expressions.extend(expression.get_children());
+        }
+    }
+
+    return Ok(());
+}
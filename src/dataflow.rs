@@ -0,0 +1,260 @@
+use crate::{
+    cfg::{build_graph, Graph},
+    parser::{Expression, Function, Local, LocalStack, Program, Scope, Statement},
+};
+
+// Solves a backward problem over sets of locals, joined by union where control flow merges.
+// The transfer turns the set after a statement into the set before it, the result holds
+// the set after every statement once nothing changes anymore.
+pub fn solve_backward(
+    graph: &Graph,
+    body: &Scope,
+    size: usize,
+    transfer: impl Fn(&Statement, &mut Vec<bool>),
+) -> Vec<Vec<bool>> {
+    let mut after: Vec<Vec<bool>> = vec![vec![false; size]; body.statements.len()];
+    let mut before_blocks: Vec<Vec<bool>> = vec![vec![false; size]; graph.blocks.len()];
+    let mut changed = true;
+
+    while changed {
+        changed = false;
+
+        for (index, block) in graph.blocks.iter().enumerate().rev() {
+            let mut fact = vec![false; size];
+
+            for successor in block.successors.iter() {
+                for (local, live) in before_blocks[*successor].iter().enumerate() {
+                    fact[local] |= *live;
+                }
+            }
+
+            for statement in block.statements.clone().rev() {
+                after[statement] = fact.clone();
+                transfer(&body.statements[statement], &mut fact);
+            }
+
+            if fact != before_blocks[index] {
+                before_blocks[index] = fact;
+                changed = true;
+            }
+        }
+    }
+
+    return after;
+}
+
+// Locals whose value may still be read after each statement
+pub struct Liveness {
+    pub live_after: Vec<Vec<bool>>,
+    // Locals with their address taken, which can be read and written through pointers,
+    // and static locals
+    pub escaped: Vec<bool>,
+}
+
+pub fn compute_liveness(function: &Function, body: &Scope) -> Liveness {
+    let size = function.locals.locals.len();
+    let graph = build_graph(body);
+
+    // Static locals outlive the call, their value is read by the next one
+    let mut escaped: Vec<bool> = function
+        .locals
+        .locals
+        .iter()
+        .map(|local| local.static_label.is_some())
+        .collect();
+
+    for statement in body.statements.iter() {
+        for expression in statement.get_expressions() {
+            mark_escaped(expression, false, &mut escaped);
+        }
+    }
+
+    let live_after = solve_backward(&graph, body, size, |statement, live| {
+        for local in get_definitions(statement) {
+            live[local] = false;
+        }
+
+        for local in get_uses(statement) {
+            live[local] = true;
+        }
+
+        for (local, escaped) in escaped.iter().enumerate() {
+            live[local] |= *escaped;
+        }
+    });
+
+    return Liveness {
+        live_after,
+        escaped,
+    };
+}
+
+// Locals a statement overwrites as a whole
+fn get_definitions(statement: &Statement) -> Vec<usize> {
+    return match statement {
+        Statement::Declare(index) | Statement::Assign(index, _) => vec![*index],
+        Statement::Destructure(indices, _) => indices.clone(),
+        _ => Vec::new(),
+    };
+}
+
+// Locals a statement reads, stores into an element or field also count as reads of the local
+fn get_uses(statement: &Statement) -> Vec<usize> {
+    let mut uses: Vec<usize> = Vec::new();
+    let mut expressions = statement.get_expressions();
+
+    while let Some(expression) = expressions.pop() {
+        if let Expression::Local(index) = expression {
+            uses.push(*index);
+        }
+
+        expressions.extend(expression.get_children());
+    }
+
+    return uses;
+}
+
+fn mark_escaped(expression: &Expression, addressed: bool, escaped: &mut Vec<bool>) {
+    if let Expression::Local(index) = expression {
+        escaped[*index] |= addressed;
+    }
+
+    let addressed = addressed || matches!(expression, Expression::Address(_));
+
+    for child in expression.get_children() {
+        mark_escaped(child, addressed, escaped);
+    }
+}
+
+// Assignments whose value is overwritten or dropped before anything reads it
+pub fn find_dead_stores(program: &Program) -> Vec<String> {
+    let mut warnings: Vec<String> = Vec::new();
+
+    for function in program.functions.iter() {
+        let body = match &function.body {
+            Some(body) => body,
+            None => continue,
+        };
+
+        let liveness = compute_liveness(function, body);
+
+        for (index, statement) in body.statements.iter().enumerate() {
+            let assigned = match statement {
+                Statement::Assign(local, _) => vec![*local],
+                Statement::Destructure(locals, _) => locals.clone(),
+                _ => continue,
+            };
+
+            for local in assigned {
+                let label = &function.locals.locals[local].label;
+
+                // Like in Rust, a leading underscore marks values dropped on purpose
+                if liveness.live_after[index][local] || label.get_name().starts_with('_') {
+                    continue;
+                }
+
+                let position = &body.spans[index].position;
+
+                warnings.push(format!(
+                    "{}: warning: Value assigned to {} is never read afterwards.",
+                    position, label
+                ));
+            }
+        }
+    }
+
+    return warnings;
+}
+
+// Locals of the function laid out again so locals never live at the same time share a slot.
+// Arguments and escaped locals keep a slot of their own, static locals have none.
+pub fn assign_slots(function: &Function, body: &Scope) -> LocalStack {
+    let liveness = compute_liveness(function, body);
+    let size = function.locals.locals.len();
+
+    // Two locals interfere when one is written while the other may still be read
+    let mut interferes = vec![vec![false; size]; size];
+
+    for (index, statement) in body.statements.iter().enumerate() {
+        let definitions = get_definitions(statement);
+        let uses = get_uses(statement);
+
+        for definition in definitions.iter() {
+            let live = liveness.live_after[index]
+                .iter()
+                .enumerate()
+                .filter(|(_, live)| **live)
+                .map(|(local, _)| local);
+
+            // Operands may be read while the result is being written
+            for other in live
+                .chain(uses.iter().copied())
+                .chain(definitions.iter().copied())
+            {
+                interferes[*definition][other] = true;
+                interferes[other][*definition] = true;
+            }
+        }
+    }
+
+    let fixed: Vec<bool> = (0..size)
+        .map(|local| function.arguments.contains(&local) || liveness.escaped[local])
+        .collect();
+
+    // Locals sharing each slot, with the offset of the slot
+    let mut slots: Vec<(Vec<usize>, usize)> = Vec::new();
+    let mut offsets: Vec<usize> = vec![0; size];
+    let mut end = 0;
+
+    // Fixed locals first so arguments stay where the parser put them
+    let order = (0..size)
+        .filter(|local| fixed[*local])
+        .chain((0..size).filter(|local| !fixed[*local]));
+
+    for local in order {
+        let original = &function.locals.locals[local];
+
+        if original.static_label.is_some() {
+            continue;
+        }
+
+        let shared = slots.iter_mut().find(|(sharing, _)| {
+            let first = &function.locals.locals[sharing[0]];
+
+            return !fixed[local]
+                && !fixed[sharing[0]]
+                && first.size == original.size
+                && first.local_type.get_alignment() == original.local_type.get_alignment()
+                && sharing.iter().all(|other| !interferes[local][*other]);
+        });
+
+        offsets[local] = match shared {
+            Some((sharing, offset)) => {
+                sharing.push(local);
+                *offset
+            }
+            None => {
+                // Same layout as the parser, slots are aligned by their distance to rbp
+                let alignment = original.local_type.get_alignment();
+                let offset = (end + original.size).next_multiple_of(alignment) - original.size;
+
+                end = offset + original.size;
+                slots.push((vec![local], offset));
+                offset
+            }
+        };
+    }
+
+    let locals = function
+        .locals
+        .locals
+        .iter()
+        .zip(offsets)
+        .map(|(original, offset)| Local {
+            offset,
+            ..original.clone()
+        })
+        .collect();
+
+    return LocalStack::from_locals(locals);
+}
@@ -0,0 +1,497 @@
+use std::{
+    collections::HashMap,
+    fs::{read, read_to_string},
+    io::{self, BufRead, Write},
+    os::unix::process::CommandExt,
+    process::Command,
+};
+
+use ezlang::span::Position;
+
+// Requests and the rip slot of user_regs_struct from <sys/ptrace.h> and <sys/user.h>
+const PTRACE_TRACEME: i32 = 0;
+const PTRACE_PEEKTEXT: i32 = 1;
+const PTRACE_POKETEXT: i32 = 4;
+const PTRACE_CONT: i32 = 7;
+const PTRACE_KILL: i32 = 8;
+const PTRACE_SINGLESTEP: i32 = 9;
+const PTRACE_GETREGS: i32 = 12;
+const PTRACE_SETREGS: i32 = 13;
+const REGISTERS: usize = 27;
+const RIP: usize = 16;
+const SIGTRAP: i32 = 5;
+
+// The standard library already links against libc
+extern "C" {
+    fn ptrace(request: i32, ...) -> i64;
+    fn waitpid(pid: i32, status: *mut i32, options: i32) -> i32;
+}
+
+struct Location {
+    address: u64,
+    function: String,
+    position: Position,
+}
+
+struct FunctionRange {
+    label: String,
+    start: u64,
+    end: u64,
+    position: Position,
+}
+
+enum Stop {
+    Trapped,
+    Signaled(i32),
+    Exited(u8),
+    Killed(i32),
+}
+
+pub struct Debugger {
+    pid: i32,
+    functions: Vec<FunctionRange>,
+    // Sorted by address, one per statement
+    locations: Vec<Location>,
+    // Address and the original word before the int3 was written
+    breakpoints: Vec<(u64, u64)>,
+    // Signal to deliver when the program resumes
+    pending_signal: i32,
+}
+
+impl Debugger {
+    // Starts the executable stopped right after exec
+    pub fn launch(executable: &str, source_map: &str) -> Result<Self, String> {
+        let symbols = read_symbols(executable)?;
+
+        let source_map = match read_to_string(source_map) {
+            Ok(source_map) => source_map,
+            Err(error) => return Err(format!("Can not read {}: {}", source_map, error)),
+        };
+
+        let resolve = |symbol: &str| match symbols.get(symbol) {
+            Some(address) => Ok(*address),
+            None => Err(format!("Symbol {} is missing from {}.", symbol, executable)),
+        };
+
+        let mut functions: Vec<FunctionRange> = Vec::new();
+        let mut locations: Vec<Location> = Vec::new();
+
+        for line in source_map.lines().filter(|line| !line.starts_with('#')) {
+            match line.split('\t').collect::<Vec<&str>>().as_slice() {
+                ["fn", label, end, position] => functions.push(FunctionRange {
+                    label: label.to_string(),
+                    start: resolve(label)?,
+                    end: resolve(end)?,
+                    position: parse_position(position)?,
+                }),
+                ["loc", symbol, function, position] => locations.push(Location {
+                    address: resolve(symbol)?,
+                    function: function.to_string(),
+                    position: parse_position(position)?,
+                }),
+                _ => return Err(format!("Invalid source map line: {}", line)),
+            }
+        }
+
+        locations.sort_by_key(|location| location.address);
+
+        let mut command = Command::new(executable);
+
+        // Runs in the child between fork and exec
+        unsafe {
+            command.pre_exec(|| {
+                ptrace(PTRACE_TRACEME, 0, 0, 0);
+                return Ok(());
+            });
+        }
+
+        let child = match command.spawn() {
+            Ok(child) => child,
+            Err(error) => return Err(format!("Can not run {}: {}", executable, error)),
+        };
+
+        let mut debugger = Self {
+            pid: child.id() as i32,
+            functions,
+            locations,
+            breakpoints: Vec::new(),
+            pending_signal: 0,
+        };
+
+        // exec stops the traced child with SIGTRAP
+        match debugger.wait()? {
+            Stop::Trapped => {}
+            _ => return Err(format!("{} did not start under ptrace.", executable)),
+        }
+
+        return Ok(debugger);
+    }
+
+    // Reads commands from stdin until the program exits, returning its exit code
+    pub fn run(&mut self) -> Result<u8, String> {
+        println!("Stopped before main, `break <function>` then `continue`, or `step`.");
+
+        let mut lines = io::stdin().lock().lines();
+
+        loop {
+            print!("(ezdb) ");
+            let _ = io::stdout().flush();
+
+            let line = match lines.next() {
+                Some(Ok(line)) => line,
+                _ => {
+                    self.kill();
+                    return Ok(0);
+                }
+            };
+
+            let words: Vec<&str> = line.split_whitespace().collect();
+
+            let stop = match words.as_slice() {
+                [] => continue,
+                ["break" | "b", function] => {
+                    match self.add_breakpoint(function) {
+                        Ok(position) => println!("Breakpoint at {}.", position),
+                        Err(error) => println!("{}", error),
+                    }
+                    continue;
+                }
+                ["continue" | "c"] => self.resume()?,
+                ["step" | "s"] => self.step_line()?,
+                ["where" | "w"] => {
+                    self.print_location()?;
+                    continue;
+                }
+                ["quit" | "q"] => {
+                    self.kill();
+                    return Ok(0);
+                }
+                ["help" | "h"] => {
+                    println!("break <function>  stop when the function is entered");
+                    println!("continue          run until the next breakpoint");
+                    println!("step              run until the next source line");
+                    println!("where             show the current source line");
+                    println!("quit              kill the program and exit");
+                    continue;
+                }
+                _ => {
+                    println!("Unknown command, try `help`.");
+                    continue;
+                }
+            };
+
+            match stop {
+                Stop::Trapped => self.print_location()?,
+                Stop::Signaled(signal) => {
+                    println!("Program received signal {}.", signal);
+                    self.pending_signal = signal;
+                    self.print_location()?;
+                }
+                Stop::Exited(code) => {
+                    println!("Program exited with code {}.", code);
+                    return Ok(code);
+                }
+                Stop::Killed(signal) => {
+                    println!("Program terminated by signal {}.", signal);
+                    return Ok(128 + signal as u8);
+                }
+            }
+        }
+    }
+
+    // Function entry is its first statement, after the arguments are stored
+    fn add_breakpoint(&mut self, name: &str) -> Result<Position, String> {
+        let label = name.replace("::", ".");
+
+        let function = match self
+            .functions
+            .iter()
+            .find(|function| function.label == label)
+        {
+            Some(function) => function,
+            None => return Err(format!("No function named {}.", name)),
+        };
+
+        let (address, position) = match self
+            .locations
+            .iter()
+            .find(|location| location.function == function.label)
+        {
+            Some(location) => (location.address, location.position.clone()),
+            None => (function.start, function.position.clone()),
+        };
+
+        if !self
+            .breakpoints
+            .iter()
+            .any(|(breakpoint, _)| *breakpoint == address)
+        {
+            let word = self.peek(address)?;
+            self.poke(address, (word & !0xff) | 0xcc)?;
+            self.breakpoints.push((address, word));
+        }
+
+        return Ok(position);
+    }
+
+    fn resume(&mut self) -> Result<Stop, String> {
+        let stop = self.step_instruction()?;
+
+        let Stop::Trapped = stop else {
+            return Ok(stop);
+        };
+
+        self.request(PTRACE_CONT, 0, self.pending_signal as u64)?;
+        self.pending_signal = 0;
+
+        let stop = self.wait()?;
+
+        // Back up over the int3 so the original instruction runs next
+        if let Stop::Trapped = stop {
+            let mut registers = self.get_registers()?;
+            let address = registers[RIP] - 1;
+
+            if self
+                .breakpoints
+                .iter()
+                .any(|(breakpoint, _)| *breakpoint == address)
+            {
+                registers[RIP] = address;
+                self.set_registers(&registers)?;
+            }
+        }
+
+        return Ok(stop);
+    }
+
+    // Steps instructions until rip reaches the start of a statement, entering calls
+    fn step_line(&mut self) -> Result<Stop, String> {
+        loop {
+            let stop = self.step_instruction()?;
+
+            let Stop::Trapped = stop else {
+                return Ok(stop);
+            };
+
+            let rip = self.get_registers()?[RIP];
+
+            if self
+                .locations
+                .binary_search_by_key(&rip, |location| location.address)
+                .is_ok()
+            {
+                return Ok(stop);
+            }
+        }
+    }
+
+    // Breakpoints are lifted while their own instruction runs
+    fn step_instruction(&mut self) -> Result<Stop, String> {
+        let rip = self.get_registers()?[RIP];
+
+        let breakpoint = self
+            .breakpoints
+            .iter()
+            .find(|(address, _)| *address == rip)
+            .cloned();
+
+        if let Some((address, word)) = breakpoint {
+            self.poke(address, word)?;
+        }
+
+        self.request(PTRACE_SINGLESTEP, 0, self.pending_signal as u64)?;
+        self.pending_signal = 0;
+
+        let stop = self.wait()?;
+
+        if let (Some((address, word)), Stop::Trapped | Stop::Signaled(_)) = (breakpoint, &stop) {
+            self.poke(address, (word & !0xff) | 0xcc)?;
+        }
+
+        return Ok(stop);
+    }
+
+    fn print_location(&self) -> Result<(), String> {
+        let rip = self.get_registers()?[RIP];
+
+        let function = match self
+            .functions
+            .iter()
+            .find(|function| function.start <= rip && rip < function.end)
+        {
+            Some(function) => function,
+            None => {
+                println!("Stopped at {:#x}, outside of ez code.", rip);
+                return Ok(());
+            }
+        };
+
+        // Before the first statement the function itself is the best match
+        let position = match self
+            .locations
+            .iter()
+            .filter(|location| location.function == function.label && location.address <= rip)
+            .last()
+        {
+            Some(location) => &location.position,
+            None => &function.position,
+        };
+
+        println!("{}: in {}", position, function.label);
+
+        if let Some(line) = read_to_string(&position.file)
+            .ok()
+            .and_then(|source| source.lines().nth(position.line - 1).map(str::to_owned))
+        {
+            println!("{:>5} | {}", position.line, line);
+        }
+
+        return Ok(());
+    }
+
+    fn kill(&mut self) {
+        let _ = self.request(PTRACE_KILL, 0, 0);
+        let _ = self.wait();
+    }
+
+    fn wait(&mut self) -> Result<Stop, String> {
+        let mut status: i32 = 0;
+
+        if unsafe { waitpid(self.pid, &mut status, 0) } < 0 {
+            return Err(format!("waitpid failed: {}", io::Error::last_os_error()));
+        }
+
+        return Ok(match (status & 0x7f, (status >> 8) & 0xff) {
+            (0, code) => Stop::Exited(code as u8),
+            (0x7f, SIGTRAP) => Stop::Trapped,
+            (0x7f, signal) => Stop::Signaled(signal),
+            (signal, _) => Stop::Killed(signal),
+        });
+    }
+
+    fn request(&self, request: i32, address: u64, data: u64) -> Result<i64, String> {
+        clear_errno();
+
+        let result = unsafe { ptrace(request, self.pid, address, data) };
+
+        if result == -1 {
+            let error = io::Error::last_os_error();
+
+            // PEEKTEXT returns the word read, which can be -1 itself
+            if error.raw_os_error() != Some(0) {
+                return Err(format!("ptrace failed: {}", error));
+            }
+        }
+
+        return Ok(result);
+    }
+
+    fn peek(&self, address: u64) -> Result<u64, String> {
+        return Ok(self.request(PTRACE_PEEKTEXT, address, 0)? as u64);
+    }
+
+    fn poke(&self, address: u64, word: u64) -> Result<(), String> {
+        self.request(PTRACE_POKETEXT, address, word)?;
+        return Ok(());
+    }
+
+    fn get_registers(&self) -> Result<[u64; REGISTERS], String> {
+        let mut registers = [0u64; REGISTERS];
+        self.request(PTRACE_GETREGS, 0, registers.as_mut_ptr() as u64)?;
+        return Ok(registers);
+    }
+
+    fn set_registers(&self, registers: &[u64; REGISTERS]) -> Result<(), String> {
+        self.request(PTRACE_SETREGS, 0, registers.as_ptr() as u64)?;
+        return Ok(());
+    }
+}
+
+fn clear_errno() {
+    extern "C" {
+        fn __errno_location() -> *mut i32;
+    }
+
+    unsafe { *__errno_location() = 0 };
+}
+
+fn parse_position(position: &str) -> Result<Position, String> {
+    let mut parts = position.rsplitn(3, ':');
+
+    let (column, line, file) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(column), Some(line), Some(file)) => (column, line, file),
+        _ => return Err(format!("Invalid source map position: {}", position)),
+    };
+
+    return match (line.parse(), column.parse()) {
+        (Ok(line), Ok(column)) => Ok(Position {
+            file: file.to_owned(),
+            line,
+            column,
+        }),
+        _ => Err(format!("Invalid source map position: {}", position)),
+    };
+}
+
+// Symbol addresses from the ELF symbol table, static executables are not relocated
+fn read_symbols(executable: &str) -> Result<HashMap<String, u64>, String> {
+    let elf = match read(executable) {
+        Ok(elf) => elf,
+        Err(error) => return Err(format!("Can not read {}: {}", executable, error)),
+    };
+
+    let invalid = || format!("{} is not a valid ELF64 executable.", executable);
+
+    let u16_at = |offset: usize| -> Option<u64> {
+        return Some(u16::from_le_bytes(elf.get(offset..offset + 2)?.try_into().ok()?) as u64);
+    };
+    let u32_at = |offset: usize| -> Option<u64> {
+        return Some(u32::from_le_bytes(elf.get(offset..offset + 4)?.try_into().ok()?) as u64);
+    };
+    let u64_at = |offset: usize| -> Option<u64> {
+        return Some(u64::from_le_bytes(
+            elf.get(offset..offset + 8)?.try_into().ok()?,
+        ));
+    };
+
+    if elf.get(..5) != Some(b"\x7fELF\x02".as_slice()) {
+        return Err(invalid());
+    }
+
+    let section_headers = u64_at(0x28).ok_or_else(invalid)? as usize;
+    let section_size = u16_at(0x3a).ok_or_else(invalid)? as usize;
+    let section_count = u16_at(0x3c).ok_or_else(invalid)? as usize;
+
+    let mut symbols: HashMap<String, u64> = HashMap::new();
+
+    for index in 0..section_count {
+        let header = section_headers + index * section_size;
+
+        // SHT_SYMTAB
+        if u32_at(header + 0x4).ok_or_else(invalid)? != 2 {
+            continue;
+        }
+
+        let offset = u64_at(header + 0x18).ok_or_else(invalid)? as usize;
+        let size = u64_at(header + 0x20).ok_or_else(invalid)? as usize;
+        let link = u32_at(header + 0x28).ok_or_else(invalid)? as usize;
+        let strings = u64_at(section_headers + link * section_size + 0x18).ok_or_else(invalid)?;
+
+        for symbol in (offset..offset + size).step_by(24) {
+            let name = strings as usize + u32_at(symbol).ok_or_else(invalid)? as usize;
+            let value = u64_at(symbol + 0x8).ok_or_else(invalid)?;
+
+            let end = match elf
+                .get(name..)
+                .and_then(|name| name.iter().position(|byte| *byte == 0))
+            {
+                Some(length) => name + length,
+                None => return Err(invalid()),
+            };
+
+            symbols.insert(String::from_utf8_lossy(&elf[name..end]).to_string(), value);
+        }
+    }
+
+    return Ok(symbols);
+}
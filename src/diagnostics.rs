@@ -0,0 +1,294 @@
+use std::{any::Any, cell::RefCell};
+
+use crate::{
+    lexer::BinaryOperator,
+    parser::{BinaryExpression, Expression, Program, Statement},
+    span::Position,
+    syntax::{SyntaxTree, Trivia, TriviaKind},
+};
+
+// Compile error raised with panic_any when the compiler knows an edit fixing it
+pub struct Diagnostic {
+    pub message: String,
+    pub fix: Option<Fix>,
+}
+
+// Message of a compile error raised as a Diagnostic or with panic!, None for other payloads
+pub fn get_panic_message(payload: &(dyn Any + Send)) -> Option<String> {
+    if let Some(diagnostic) = payload.downcast_ref::<Diagnostic>() {
+        return Some(diagnostic.message.to_owned());
+    }
+
+    return match (
+        payload.downcast_ref::<&str>(),
+        payload.downcast_ref::<String>(),
+    ) {
+        (Some(message), _) => Some(message.to_string()),
+        (_, Some(message)) => Some(message.to_owned()),
+        _ => None,
+    };
+}
+
+thread_local! {
+    // Last place in the source a phase started working on, for internal compiler errors
+    static PROCESSING: RefCell<Option<Position>> = RefCell::new(None);
+}
+
+pub fn set_processing(position: &Position) {
+    PROCESSING.with(|processing| *processing.borrow_mut() = Some(position.clone()));
+}
+
+// Compile errors start with the position they are about, like `file:1:1: ...`. Any other
+// message the compiler panics with, `Unreachable` included, is a bug in the compiler.
+pub fn is_internal_error(message: &str) -> bool {
+    let position = match message.split_once(": ") {
+        Some((position, _)) => position,
+        None => return true,
+    };
+
+    // The filename may contain colons, the line and column are the last two fields
+    let mut fields = position.rsplitn(3, ':');
+
+    let column = fields.next().and_then(|field| field.parse::<usize>().ok());
+    let line = fields.next().and_then(|field| field.parse::<usize>().ok());
+
+    return column.is_none() || line.is_none() || fields.next().is_none();
+}
+
+// Report printed in place of the panic and its backtrace
+pub fn write_internal_error(message: &str) -> String {
+    let processing = PROCESSING.with(|processing| processing.borrow().clone());
+
+    let place = match processing {
+        Some(position) => format!("while processing {}", position),
+        None => String::from("before reading any source"),
+    };
+
+    return format!(
+        "error: internal compiler error: {}\n\
+         note: ezlang {} failed {}.\n\
+         note: This is a bug in ezlang, please file an issue with the source that triggers it.",
+        message,
+        env!("CARGO_PKG_VERSION"),
+        place
+    );
+}
+
+// Replaces `length` bytes at a position, safe to apply without review
+#[derive(Debug, Clone)]
+pub struct Fix {
+    pub position: Position,
+    pub length: usize,
+    pub replacement: String,
+}
+
+// Number of single character insertions, deletions, substitutions and swaps of
+// adjacent characters turning a into b
+pub fn get_edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+
+    for i in 0..=a.len() {
+        distances[i][0] = i;
+    }
+
+    for j in 0..=b.len() {
+        distances[0][j] = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = (a[i - 1] != b[j - 1]) as usize;
+
+            distances[i][j] = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + cost);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distances[i][j] = distances[i][j].min(distances[i - 2][j - 2] + 1);
+            }
+        }
+    }
+
+    return distances[a.len()][b.len()];
+}
+
+// Closest name within a third of the misspelled length, ready to append to a diagnostic.
+// Ties prefer names with the same letters, then names extending the misspelled one.
+pub fn get_suggestion<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> String {
+    let limit = (name.chars().count() / 3).max(1);
+
+    let sorted = |text: &str| {
+        let mut characters: Vec<char> = text.chars().collect();
+        characters.sort();
+        return characters;
+    };
+
+    let closest = candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (get_edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= limit)
+        .min_by_key(|(distance, candidate)| {
+            (
+                *distance,
+                sorted(candidate) != sorted(name),
+                !candidate.starts_with(name) && !name.starts_with(candidate),
+            )
+        });
+
+    return match closest {
+        Some((_, candidate)) => format!(" Did you mean `{}`?", candidate),
+        None => String::new(),
+    };
+}
+
+// Byte offset of a position, whose column counts bytes from 1 after any byte order mark
+pub fn get_offset(source: &str, position: &Position) -> Option<usize> {
+    let (mut offset, source) = match source.strip_prefix('\u{feff}') {
+        Some(rest) => ('\u{feff}'.len_utf8(), rest),
+        None => (0, source),
+    };
+
+    for (index, line) in source.split_inclusive('\n').enumerate() {
+        if index + 1 == position.line {
+            return match position.column - 1 <= line.len() {
+                true => Some(offset + position.column - 1),
+                false => None,
+            };
+        }
+
+        offset += line.len();
+    }
+
+    return None;
+}
+
+// Whether evaluating the expression can only produce a value, so dropping it changes nothing
+fn is_pure(expression: &Expression) -> bool {
+    return match expression {
+        Expression::Call(..)
+        | Expression::Intrinsic(..)
+        | Expression::Format(..)
+        | Expression::Index(..) => false,
+        Expression::Binary(BinaryExpression {
+            operator: BinaryOperator::Div,
+            ..
+        }) => false,
+        expression => expression.get_children().into_iter().all(is_pure),
+    };
+}
+
+// Variables of the file that are only ever assigned, with a fix deleting each assignment.
+// Assignments with side effects or traps keep the variable.
+pub fn find_unused_variables(program: &Program, tree: &SyntaxTree) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+
+    for (function_index, function) in program.functions.iter().enumerate() {
+        let body = match &function.body {
+            Some(body) if function.position.file == tree.filename => body,
+            _ => continue,
+        };
+
+        let mut used: Vec<bool> = function
+            .locals
+            .locals
+            .iter()
+            .enumerate()
+            .map(|(index, _)| function.arguments.contains(&index))
+            .collect();
+
+        for statement in body.statements.iter() {
+            if let Statement::Destructure(indices, _) = statement {
+                for index in indices.iter() {
+                    used[*index] = true;
+                }
+            }
+
+            let mut expressions = statement.get_expressions();
+
+            while let Some(expression) = expressions.pop() {
+                if let Expression::Local(index) = expression {
+                    used[*index] = true;
+                }
+
+                expressions.extend(expression.get_children());
+            }
+        }
+
+        for (index, local) in function.locals.locals.iter().enumerate() {
+            if used[index] {
+                continue;
+            }
+
+            let statements: Vec<(usize, &Statement)> = body
+                .statements
+                .iter()
+                .enumerate()
+                .filter(|(_, statement)| match statement {
+                    Statement::Declare(declared) => *declared == index,
+                    Statement::Assign(assigned, _) => *assigned == index,
+                    _ => false,
+                })
+                .collect();
+
+            let removable = statements.iter().all(|(_, statement)| match statement {
+                Statement::Assign(_, expression) => is_pure(expression),
+                _ => true,
+            });
+
+            if !removable {
+                continue;
+            }
+
+            for (statement, _) in statements {
+                let position = &body.spans[statement].position;
+
+                diagnostics.push(Diagnostic {
+                    message: format!(
+                        "{}: Variable {} is assigned but never used, remove it.",
+                        position, local.label
+                    ),
+                    fix: get_removal(tree, function_index, statement),
+                });
+            }
+        }
+    }
+
+    return diagnostics;
+}
+
+// Deletes the text of a statement, with its whole line when nothing else is on it
+fn get_removal(tree: &SyntaxTree, function: usize, statement: usize) -> Option<Fix> {
+    let tokens = tree.find_statement(function, statement)?.get_tokens();
+    let (first, last) = (tokens.first()?, tokens.last()?);
+
+    // Only whitespace goes away with the statement
+    let whitespace = |trivia: Option<&Trivia>| match trivia {
+        Some(trivia) if trivia.kind == TriviaKind::Whitespace => trivia.text.to_owned(),
+        _ => String::new(),
+    };
+
+    let before = whitespace(first.leading.last());
+    let after = whitespace(tree.get_following_trivia(last).first());
+
+    // Alone on its line the indentation and line break go too, otherwise the spaces
+    // separating it from the next statement or, at the end of a line, from the previous one
+    let (indentation, line_break) = match (before.rfind('\n'), after.find('\n')) {
+        (Some(start), Some(end)) => (before.len() - start - 1, end + 1),
+        (_, None) => (0, after.len()),
+        (None, Some(_)) => (before.len(), 0),
+    };
+
+    return Some(Fix {
+        position: Position {
+            file: first.token.position.file.to_owned(),
+            line: first.token.position.line,
+            column: first.token.position.column - indentation,
+        },
+        length: indentation + last.range.end - first.range.start + line_break,
+        replacement: String::new(),
+    });
+}
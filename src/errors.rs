@@ -0,0 +1,561 @@
+// Stable codes of compile errors, shown as `error[E0001]` in their diagnostic.
+// Codes are never reused, retired errors keep their entry.
+pub struct ErrorCode {
+    pub code: &'static str,
+    pub title: &'static str,
+    pub explanation: &'static str,
+}
+
+pub static ERROR_CODES: &[ErrorCode] = &[
+    ErrorCode {
+        code: "E0001",
+        title: "undeclared variable",
+        explanation: "A variable was used before being declared with `var`.
+
+Erroneous example:
+
+    fn main: () {
+        count = 1;
+        return count;
+    }
+
+Variables only exist after their declaration, and assigning does not declare
+them. Declare the variable first:
+
+    fn main: () {
+        var count = 1;
+        return count;
+    }
+",
+    },
+    ErrorCode {
+        code: "E0002",
+        title: "arity mismatch",
+        explanation: "A function or intrinsic was called with the wrong number of arguments.
+
+Erroneous example:
+
+    fn sum: (a, b) {
+        return a + b;
+    }
+
+    fn main: () {
+        return @sum(1);
+    }
+
+Every argument of a function must be passed, there are no default values.
+Pass as many arguments as the function declares:
+
+    fn main: () {
+        return @sum(1, 2);
+    }
+",
+    },
+    ErrorCode {
+        code: "E0003",
+        title: "undefined function",
+        explanation: "A called function is not declared in any module of the program.
+
+Erroneous example:
+
+    fn main: () {
+        return @double(21);
+    }
+
+Declare the function, or include the file declaring it:
+
+    fn double: (a) {
+        return 2 * a;
+    }
+
+    fn main: () {
+        return @double(21);
+    }
+",
+    },
+    ErrorCode {
+        code: "E0004",
+        title: "duplicated variable declaration",
+        explanation: "A variable was declared twice in the same function.
+
+Erroneous example:
+
+    fn main: () {
+        var total = 1;
+        var total = 2;
+        return total;
+    }
+
+Variables live until the end of their function and can not be shadowed.
+Assign to the existing variable or pick another name:
+
+    fn main: () {
+        var total = 1;
+        total = 2;
+        return total;
+    }
+",
+    },
+    ErrorCode {
+        code: "E0005",
+        title: "mismatched types",
+        explanation: "A value of one type was used where another type is required.
+
+Erroneous example:
+
+    fn main: () {
+        var ratio: f64 = 1.5;
+        var count: i64 = ratio;
+        return count;
+    }
+
+Values are never converted between unrelated types implicitly. Convert them
+with `as` when a conversion exists:
+
+    fn main: () {
+        var ratio: f64 = 1.5;
+        var count: i64 = ratio as i64;
+        return count;
+    }
+",
+    },
+    ErrorCode {
+        code: "E0006",
+        title: "lossy implicit conversion",
+        explanation: "An integer was assigned to a type that can not hold all of its values.
+
+Erroneous example:
+
+    fn main: () {
+        var wide: i64 = 300;
+        var narrow: u8 = wide;
+        return narrow;
+    }
+
+Only conversions that keep every value happen implicitly. Make a truncating
+conversion explicit with `as`:
+
+    fn main: () {
+        var wide: i64 = 300;
+        var narrow: u8 = wide as u8;
+        return narrow;
+    }
+",
+    },
+    ErrorCode {
+        code: "E0007",
+        title: "private function",
+        explanation: "A function of another module was called without being public.
+
+Erroneous example, with math.ez included by the main file:
+
+    fn square: (a) {
+        return a * a;
+    }
+
+    fn main: () {
+        return @math::square(3);
+    }
+
+Functions are only visible inside their own module unless declared with
+`pub fn`:
+
+    pub fn square: (a) {
+        return a * a;
+    }
+",
+    },
+    ErrorCode {
+        code: "E0008",
+        title: "ambiguous call",
+        explanation: "A function name is declared public by several included modules.
+
+Erroneous example, with both shapes.ez and colors.ez declaring `pub fn area`:
+
+    include \"shapes.ez\";
+    include \"colors.ez\";
+
+    fn main: () {
+        return @area(2);
+    }
+
+Unqualified calls only work when a single module provides the function.
+Qualify the call with the module, named after the file stem:
+
+    fn main: () {
+        return @shapes::area(2);
+    }
+",
+    },
+    ErrorCode {
+        code: "E0009",
+        title: "unknown type",
+        explanation: "A type annotation names neither a builtin type nor a declared struct.
+
+Erroneous example:
+
+    fn main: () {
+        var count: int = 0;
+        return count;
+    }
+
+The builtin types are u8, u16, u32, u64, i8, i16, i32, i64 and f64, plus
+pointers like *u8 and arrays like [i64; 4]. Use one of them, or declare the
+struct before using it:
+
+    fn main: () {
+        var count: i64 = 0;
+        return count;
+    }
+",
+    },
+    ErrorCode {
+        code: "E0010",
+        title: "unknown field",
+        explanation: "A field was accessed or initialized that its struct does not declare.
+
+Erroneous example:
+
+    struct Point { x: i64, y: i64 }
+
+    fn main: () {
+        var point = Point { x: 1, y: 2 };
+        return point.z;
+    }
+
+Only the fields listed in the struct declaration exist. Use one of them, or
+add the field to the struct:
+
+    fn main: () {
+        var point = Point { x: 1, y: 2 };
+        return point.y;
+    }
+",
+    },
+    ErrorCode {
+        code: "E0011",
+        title: "invalid cast",
+        explanation: "A value was cast to a type it can not be converted to.
+
+Erroneous example:
+
+    fn main: () {
+        var ratio: f64 = 1.5;
+        var pointer = ratio as *u8;
+        return 0;
+    }
+
+Integers, f64 and pointers can be cast to each other, except f64 to pointers
+and back. Arrays, structs and tuples can not be cast at all. Go through a
+supported conversion instead:
+
+    fn main: () {
+        var ratio: f64 = 1.5;
+        var whole = ratio as i64;
+        return whole;
+    }
+",
+    },
+    ErrorCode {
+        code: "E0012",
+        title: "literal out of range",
+        explanation: "An integer literal does not fit in the type it is given.
+
+Erroneous example:
+
+    fn main: () {
+        var small: u8 = 256;
+        return small;
+    }
+
+Literals take the type they are assigned to and must fit in it. No literal can
+be larger than 18446744073709551615, the largest u64. Use a wider type, or a
+literal in range:
+
+    fn main: () {
+        var small: u16 = 256;
+        return small;
+    }
+",
+    },
+    ErrorCode {
+        code: "E0013",
+        title: "duplicated function",
+        explanation: "Two functions with the same name were declared in the same module.
+
+Erroneous example:
+
+    fn value: () {
+        return 1;
+    }
+
+    fn value: () {
+        return 2;
+    }
+
+Functions can not be overloaded. Rename one of them:
+
+    fn first_value: () {
+        return 1;
+    }
+
+    fn second_value: () {
+        return 2;
+    }
+",
+    },
+    ErrorCode {
+        code: "E0014",
+        title: "missing entry point",
+        explanation: "An executable was built from a program without a main function.
+
+Erroneous example:
+
+    fn start: () {
+        return 0;
+    }
+
+Executables start at `fn main: ()`, which takes no arguments and whose value
+becomes the exit code. Declare it, or build a library with --shared:
+
+    fn main: () {
+        return 0;
+    }
+",
+    },
+    ErrorCode {
+        code: "E0015",
+        title: "unresolved function",
+        explanation: "A function was declared without body but no included assembly defines it.
+
+Erroneous example:
+
+    fn fast_sum: (a, b);
+
+    fn main: () {
+        return @fast_sum(1, 2);
+    }
+
+Declarations without body are implemented by assembly files included with
+include_asm, which must define a label with the function name. Include the
+file, or declare the function with `extern` when another object provides it:
+
+    include_asm \"fast_sum.asm\";
+
+    fn fast_sum: (a, b);
+
+Included assembly takes its arguments on the stack like ez functions do. Extern
+functions are called like C instead, with their arguments in registers, so an
+object compiled from C can be linked with --link:
+
+    extern fn fast_sum: (a: i64, b: i64) -> i64;
+",
+    },
+    ErrorCode {
+        code: "E0016",
+        title: "expression too complex",
+        explanation: "An expression nests more parentheses and calls, or chains more operations,
+than the compiler accepts.
+
+Erroneous example:
+
+    fn main: () {
+        return @id(@id(@id(@id(1))));
+    }
+
+with thousands of levels instead of four, usually from generated code. The
+limit is 256 levels of parentheses and calls and can be changed with
+--max-expression-depth. Operations chained like `1 + 1 + 1` do not nest, but
+the compiler walks them one at a time, so a single expression may chain at most
+1024 of them, counting the ones inside parentheses and calls. That limit can be
+changed with --max-expression-length. Split the expression into variables
+holding its parts:
+
+    fn main: () {
+        var inner = @id(@id(1));
+        return @id(@id(inner));
+    }
+",
+    },
+    ErrorCode {
+        code: "E0017",
+        title: "program too large",
+        explanation: "A program exceeds one of the limits on the size of the input.
+
+The compiler bounds the tokens of all files together, the functions, the
+variables of each function and the bytes of all string literals, so compiling
+untrusted programs can not exhaust it. Each limit can be raised on the command
+line:
+
+    --max-tokens        tokens of the program and its includes
+    --max-functions     functions of the program
+    --max-locals        variables of a single function
+    --max-string-bytes  bytes of every string literal together
+
+The variables of a function can also take at most 1073741824 bytes together,
+since the frame is addressed with 32 bit displacements. That bound is fixed.
+
+Programs this large are usually generated. Splitting a large function or moving
+big strings into included assembly keeps them within the limits.
+",
+    },
+    ErrorCode {
+        code: "E0018",
+        title: "write through a string literal",
+        explanation: "A pointer to a string literal was written through.
+
+Erroneous example:
+
+    fn main: () {
+        var name = \"ezlang\" as *u8;
+        name[0] = 69 as u8;
+        return 0;
+    }
+
+String literals used as pointers are placed in read-only memory, writing to them
+would crash the program. Write into an array holding a copy instead:
+
+    fn main: () {
+        var name: [u8; 7];
+        name[0] = 69 as u8;
+        return 0;
+    }
+",
+    },
+    ErrorCode {
+        code: "E0019",
+        title: "expected a constant expression",
+        explanation: "A value needed while compiling can not be computed at compile time.
+
+Erroneous example:
+
+    const STARTED = @time();
+
+Array lengths and constants are computed by the compiler, so they can only use
+number literals, other constants, @sizeof, casts, integer arithmetic and calls
+of pure functions, which the compiler runs (see E0020). Floats and intrinsics
+other than @sizeof, like @time, are only known when the program runs. Keep the
+value in a variable instead:
+
+    fn main: () {
+        var started = @time();
+        return started;
+    }
+
+Values the compiler can compute still go in constants and array lengths:
+
+    const SIZE = 16;
+
+    fn main: () {
+        var buffer: [u8; SIZE * @sizeof(i64)];
+        return 0;
+    }
+
+Divisions by zero and signed divisions that overflow are reported with this
+error as well.
+",
+    },
+    ErrorCode {
+        code: "E0020",
+        title: "compile-time evaluation failed",
+        explanation: "A call inside a constant could not be run at compile time.
+
+Erroneous example:
+
+    fn get_size: (count: i64) {
+        @printf(\"{}\\n\", count);
+        return count * 8;
+    }
+
+    const SIZE = @get_size(16);
+
+Calls in constants and array lengths are run by the interpreter while
+compiling, with constant arguments. Only pure functions can be run: the called
+function and every function it calls must have an ez body and can not print,
+call @write, use port I/O, draw random numbers, read clocks, sleep or keep
+static variables. Running the function also fails when it crashes or runs more
+statements than --max-const-steps allows.
+
+Move the output out of the function so it only computes its result:
+
+    fn get_size: (count: i64) {
+        return count * 8;
+    }
+
+    const SIZE = @get_size(16);
+",
+    },
+    ErrorCode {
+        code: "E0021",
+        title: "static assertion failed",
+        explanation: "The condition of a static_assert is zero.
+
+Erroneous example:
+
+    const SLOTS = 12;
+
+    static_assert(SLOTS / 16, \"SLOTS must be at least 16\");
+
+static_assert checks a constant expression while compiling and stops with its
+message when the expression is zero, any other value passes. The condition can
+use everything a constant can, calls to pure functions included, so properties
+of sizes and layouts are checked before the program ever runs:
+
+    struct Header {
+        magic: u32,
+        length: u32,
+    }
+
+    static_assert(@sizeof(Header) / 8, \"Header must fill at least a word\");
+
+Fix the constant or the types the assertion is about, the assertion itself
+documents what the rest of the program relies on.
+",
+    },
+    ErrorCode {
+        code: "E0022",
+        title: "operands without a common type",
+        explanation: "The operands of an arithmetic operation have integer types that can not
+hold each other's values.
+
+Erroneous example:
+
+    fn main: () {
+        var count: u32 = 3;
+        var offset: i32 = 7;
+        return count + offset;
+    }
+
+When the operands have different integer types, the one whose values all fit
+in the type of the other is widened to it: a u8 added to an i32 is zero
+extended and the sum is an i32. Neither of u32 and i32 holds every value of the
+other, so the conversion has to be written with `as`, choosing where the values
+that do not fit wrap:
+
+    fn main: () {
+        var count: u32 = 3;
+        var offset: i32 = 7;
+        return count as i64 + offset as i64;
+    }
+
+Operands of the same type keep it, so the sum of two u8 wraps at 256. Widen one
+of them first to keep the carry.
+",
+    },
+];
+
+pub fn find_error_code(code: &str) -> Option<&'static ErrorCode> {
+    let code = code.to_uppercase();
+
+    return ERROR_CODES
+        .iter()
+        .find(|error_code| error_code.code == code);
+}
+
+// Code of a diagnostic like `file:1:1: error[E0001]: ...`
+pub fn get_error_code(message: &str) -> Option<&str> {
+    let start = message.find("error[")? + "error[".len();
+    let end = start + message[start..].find(']')?;
+
+    return Some(&message[start..end]);
+}
@@ -0,0 +1,204 @@
+use crate::{
+    lexer::TokenType,
+    parser::{Expression, Function, Program, Statement, Type},
+    trace::describe_statement,
+};
+
+// Kind of a token followed by its text as written
+fn describe_token(token_type: &TokenType) -> String {
+    return match token_type {
+        TokenType::NumberLiteral(number) => format!("NumberLiteral {}", number),
+        TokenType::TypedNumberLiteral(number, suffix) => {
+            format!("NumberLiteral {}{}", number, suffix)
+        }
+        TokenType::FloatLiteral(number) => format!("FloatLiteral {:?}", number),
+        TokenType::StringLiteral(string) => format!("StringLiteral \"{}\"", string.escape_debug()),
+        TokenType::Character(character) => {
+            format!("Character '{}'", character.escape_debug())
+        }
+        TokenType::Identifier(name) => format!("Identifier {}", name),
+        TokenType::BinaryOperation(operator) => {
+            format!("BinaryOperation {}", operator.get_symbol())
+        }
+        TokenType::Call(_) => "Call @".to_owned(),
+        token_type => format!("{:?}", token_type),
+    };
+}
+
+// One line per node, children indented below their parent
+fn describe_tree(statement: &Statement, function: &Function, program: &Program) -> Vec<String> {
+    let label = |index: &usize| &function.locals.get(*index).expect("Unreachable").label;
+
+    let (node, children) = match statement {
+        Statement::Declare(index) => (
+            format!(
+                "Declare {}: {}",
+                label(index),
+                function.locals.get(*index).expect("Unreachable").local_type
+            ),
+            vec![],
+        ),
+        Statement::Assign(index, expression) => {
+            (format!("Assign {}", label(index)), vec![expression])
+        }
+        Statement::Store(target, expression) => ("Store".to_owned(), vec![target, expression]),
+        Statement::Destructure(indices, expression) => {
+            let labels: Vec<String> = indices
+                .iter()
+                .map(|index| label(index).to_string())
+                .collect();
+
+            (
+                format!("Destructure {}", labels.join(", ")),
+                vec![expression],
+            )
+        }
+        Statement::Return(expression) => ("Return".to_owned(), vec![expression]),
+        Statement::Call(expression) => ("Call".to_owned(), vec![expression]),
+        Statement::Label(label) => (format!("Label {}", label), vec![]),
+        Statement::Goto(label) => (format!("Goto {}", label), vec![]),
+    };
+
+    let mut lines = vec![node];
+
+    for child in children {
+        describe_node(child, function, program, 1, &mut lines);
+    }
+
+    return lines;
+}
+
+fn describe_node(
+    expression: &Expression,
+    function: &Function,
+    program: &Program,
+    depth: usize,
+    lines: &mut Vec<String>,
+) {
+    let node = match expression {
+        Expression::NumberLiteral(number) => format!("NumberLiteral {}", number),
+        Expression::FloatLiteral(number) => format!("FloatLiteral {:?}", number),
+        Expression::StringLiteral(index) => {
+            format!(
+                "StringLiteral \"{}\"",
+                program.strings[*index].escape_debug()
+            )
+        }
+        Expression::Binary(binary_expression) => {
+            format!("Binary {}", binary_expression.operator.get_symbol())
+        }
+        Expression::Local(index) => format!(
+            "Local {}",
+            function.locals.get(*index).expect("Unreachable").label
+        ),
+        Expression::Call(index, _) => {
+            format!("Call {}", program.functions[*index].get_display_name())
+        }
+        Expression::Intrinsic(intrinsic, _) => format!("Intrinsic {}", intrinsic.get_name()),
+        Expression::Cast(cast_type, _) => format!("Cast {}", cast_type),
+        Expression::Index(..) => "Index".to_owned(),
+        Expression::Field(base, field) => match base.get_type(&function.locals, &program.functions)
+        {
+            Type::Struct(layout) => format!("Field {}", layout.fields[*field].name),
+            _ => panic!("Unreachable"),
+        },
+        Expression::Address(_) => "Address".to_owned(),
+        Expression::Initializer(initializer_type, _) => {
+            format!("Initializer {}", initializer_type)
+        }
+        Expression::Tuple(_) => "Tuple".to_owned(),
+        Expression::Format(..) => "Format".to_owned(),
+    };
+
+    lines.push(format!("{}{}", "  ".repeat(depth), node));
+
+    for child in expression.get_children() {
+        describe_node(child, function, program, depth + 1, lines);
+    }
+}
+
+// Tokens, syntax tree and instructions of every statement side by side
+pub fn write_explanation(program: &Program, explained: &[(String, usize, String)]) -> String {
+    let mut explanation = String::new();
+
+    for (label, index, assembly) in explained.iter() {
+        let function = program
+            .functions
+            .iter()
+            .find(|function| function.label == *label)
+            .expect("Unreachable");
+        let body = function.body.as_ref().expect("Unreachable");
+        let statement = &body.statements[*index];
+        let span = &body.spans[*index];
+
+        let tokens: Vec<String> = program.tokens[span.tokens.clone()]
+            .iter()
+            .map(|token| describe_token(&token.token_type))
+            .collect();
+        let tree = describe_tree(statement, function, program);
+        let instructions: Vec<String> = assembly
+            .lines()
+            .map(|line| line.trim().replace('\t', " "))
+            .filter(|line| !line.is_empty())
+            .collect();
+
+        let header = format!(
+            "{} in {}: {}",
+            span.position,
+            function.get_display_name(),
+            describe_statement(statement, function, program)
+        );
+
+        explanation.push_str(&format!("{}\n{}\n", header, "=".repeat(header.len())));
+
+        let headings = ["tokens", "syntax tree", "instructions"];
+        let columns = [&tokens, &tree, &instructions];
+        let widths: Vec<usize> = columns
+            .iter()
+            .zip(headings.iter())
+            .map(|(column, heading)| {
+                let widest = column.iter().map(|line| line.len()).max().unwrap_or(0);
+                widest.max(heading.len())
+            })
+            .collect();
+        let rows = columns.iter().map(|column| column.len()).max().unwrap_or(0);
+
+        let mut table = vec![
+            headings.map(|heading| heading.to_owned()).to_vec(),
+            widths.iter().map(|width| "-".repeat(*width)).collect(),
+        ];
+
+        for row in 0..rows {
+            table.push(
+                columns
+                    .iter()
+                    .map(|column| column.get(row).cloned().unwrap_or_default())
+                    .collect(),
+            );
+        }
+
+        for (row, cells) in table.iter().enumerate() {
+            let separator = match row {
+                1 => "-+-",
+                _ => " | ",
+            };
+            let line = format!(
+                "{:<tokens$}{}{:<tree$}{}{}",
+                cells[0],
+                separator,
+                cells[1],
+                separator,
+                cells[2],
+                tokens = widths[0],
+                tree = widths[1]
+            );
+
+            explanation.push_str(line.trim_end());
+            explanation.push('\n');
+        }
+
+        explanation.push('\n');
+    }
+
+    return explanation;
+}
@@ -0,0 +1,212 @@
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::{
+    diagnostics::{find_unused_variables, get_offset, Diagnostic, Fix},
+    incremental::{reparse, Edit},
+    lexer::Edition,
+    parser::Program,
+    syntax::SyntaxTree,
+};
+
+// Rounds of parsing and fixing before giving up on fixes that keep producing new ones
+const MAX_ROUNDS: usize = 64;
+
+pub struct FixResult {
+    pub source: String,
+    // Message of every applied fix, in the order they were applied
+    pub applied: Vec<String>,
+    // First compile error without a fix, which stops the fixing
+    pub error: Option<String>,
+}
+
+// Applies the machine applicable fixes of the file in memory, reparsing after every round
+pub fn fix_source(filename: &str, source: String, edition: Edition) -> FixResult {
+    let mut result = FixResult {
+        source,
+        applied: Vec::new(),
+        error: None,
+    };
+
+    // Failed attempts are expected, their diagnostics are reported through the result
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+
+    // Kept between rounds so fixes only parse again the functions they touch
+    let mut parsed: Option<(SyntaxTree, Program)> = None;
+
+    for _ in 0..MAX_ROUNDS {
+        let source = result.source.clone();
+
+        // The tree prints the source back unchanged, fixes are located through it
+        let attempt = match parsed.take() {
+            Some(parsed) => Ok(parsed),
+            None => panic::catch_unwind(|| {
+                let mut tree = SyntaxTree::parse(filename, &source, edition);
+                let program = tree.to_program();
+                tree.link(&program);
+
+                return (tree, program);
+            }),
+        };
+
+        let diagnostics = match attempt {
+            Ok((tree, program)) => {
+                let diagnostics = find_unused_variables(&program, &tree);
+                parsed = Some((tree, program));
+                diagnostics
+            }
+            Err(payload) => match payload.downcast::<Diagnostic>() {
+                Ok(diagnostic) if diagnostic.fix.is_some() => vec![*diagnostic],
+                Ok(diagnostic) => {
+                    result.error = Some(diagnostic.message);
+                    break;
+                }
+                Err(payload) => {
+                    result.error = match (
+                        payload.downcast_ref::<&str>(),
+                        payload.downcast_ref::<String>(),
+                    ) {
+                        (Some(message), _) => Some(message.to_string()),
+                        (_, Some(message)) => Some(message.to_owned()),
+                        _ => None,
+                    };
+                    break;
+                }
+            },
+        };
+
+        // Fixes in included files are left for fixing those files directly
+        let mut fixes: Vec<(usize, Fix, String)> = diagnostics
+            .into_iter()
+            .filter_map(|diagnostic| {
+                let fix = diagnostic.fix?;
+
+                if fix.position.file != filename {
+                    return None;
+                }
+
+                let offset = get_offset(&result.source, &fix.position)?;
+
+                return Some((offset, fix, diagnostic.message));
+            })
+            .collect();
+
+        if fixes.len() == 0 {
+            break;
+        }
+
+        // Applied back to front so earlier offsets stay valid
+        fixes.sort_by_key(|(offset, _, _)| *offset);
+
+        let mut end = result.source.len();
+
+        for (offset, fix, message) in fixes.into_iter().rev() {
+            if offset + fix.length > end {
+                continue;
+            }
+
+            result
+                .source
+                .replace_range(offset..offset + fix.length, &fix.replacement);
+            result.applied.push(message);
+
+            let edit = Edit {
+                range: offset..offset + fix.length,
+                text: fix.replacement,
+            };
+
+            // A failed reparse is reported by the full parse of the next round
+            parsed = parsed.and_then(|(mut tree, mut program)| {
+                panic::catch_unwind(AssertUnwindSafe(|| reparse(&mut tree, &mut program, &edit)))
+                    .ok()?;
+
+                return Some((tree, program));
+            });
+
+            end = offset;
+        }
+    }
+
+    panic::set_hook(hook);
+
+    return result;
+}
+
+// Unified diff of two versions of a file, with three lines of context around changes
+pub fn write_diff(filename: &str, before: &str, after: &str) -> String {
+    let old: Vec<&str> = before.lines().collect();
+    let new: Vec<&str> = after.lines().collect();
+
+    // Longest common subsequence lengths of every pair of suffixes
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = match old[i] == new[j] {
+                true => common[i + 1][j + 1] + 1,
+                false => common[i + 1][j].max(common[i][j + 1]),
+            };
+        }
+    }
+
+    // Every line as kept, removed or added, with its line number in both versions
+    let mut edits: Vec<(char, &str, usize, usize)> = Vec::new();
+    let (mut i, mut j) = (0, 0);
+
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            edits.push((' ', old[i], i, j));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            edits.push(('-', old[i], i, j));
+            i += 1;
+        } else {
+            edits.push(('+', new[j], i, j));
+            j += 1;
+        }
+    }
+
+    let mut diff = format!("--- {}\n+++ {}\n", filename, filename);
+    let mut index = 0;
+
+    while index < edits.len() {
+        if edits[index].0 == ' ' {
+            index += 1;
+            continue;
+        }
+
+        // Grow the hunk while changes are closer than twice the context
+        let start = index.saturating_sub(3);
+        let mut end = index;
+
+        while end < edits.len() {
+            match edits[end..].iter().take(7).position(|edit| edit.0 != ' ') {
+                Some(next) => end += next + 1,
+                None => break,
+            }
+        }
+
+        let end = (end + 3).min(edits.len());
+        let hunk = &edits[start..end];
+
+        let old_count = hunk.iter().filter(|edit| edit.0 != '+').count();
+        let new_count = hunk.iter().filter(|edit| edit.0 != '-').count();
+
+        diff.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            hunk[0].2 + (old_count > 0) as usize,
+            old_count,
+            hunk[0].3 + (new_count > 0) as usize,
+            new_count
+        ));
+
+        for (kind, line, _, _) in hunk.iter() {
+            diff.push_str(&format!("{}{}\n", kind, line));
+        }
+
+        index = end;
+    }
+
+    return diff;
+}
@@ -0,0 +1,282 @@
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum AsmSyntax {
+    /// NASM, assembled with nasm
+    #[default]
+    Nasm,
+    /// GNU as in Intel syntax, assembled with as from binutils
+    Gas,
+}
+
+impl AsmSyntax {
+    pub fn get_comment(&self) -> &str {
+        return match self {
+            AsmSyntax::Nasm => ";",
+            AsmSyntax::Gas => "#",
+        };
+    }
+
+    // Written before the generated code and again after included assembly, which could
+    // have switched to another syntax
+    pub fn get_prologue(&self) -> &str {
+        return match self {
+            AsmSyntax::Nasm => "",
+            AsmSyntax::Gas => ".intel_syntax noprefix",
+        };
+    }
+}
+
+// Rewrites the NASM written by the compiler for GNU as, line by line. Only the subset the
+// compiler and the runtime routines use is understood, instructions keep their Intel operand
+// order under .intel_syntax noprefix so only directives, memory operands and symbols change.
+pub fn translate_to_gas(assembly: &str) -> String {
+    let mut output: Vec<String> = Vec::new();
+
+    for line in assembly.split('\n') {
+        // Data is written as numbers, so a semicolon always starts a comment
+        let (code, comment) = match line.split_once(';') {
+            Some((code, comment)) => (code, Some(comment)),
+            None => (line, None),
+        };
+
+        let indentation = &code[..code.len() - code.trim_start().len()];
+        let mut lines = translate_line(code.trim());
+
+        if let Some(comment) = comment {
+            match lines.last_mut() {
+                Some(last) => last.push_str(&format!("\t#{}", comment)),
+                None => lines.push(format!("#{}", comment)),
+            }
+        }
+
+        if lines.is_empty() {
+            output.push(String::new());
+        }
+
+        for translated in lines {
+            output.push(format!("{}{}", indentation, translated));
+        }
+    }
+
+    return output.join("\n");
+}
+
+fn translate_line(code: &str) -> Vec<String> {
+    if code.is_empty() {
+        return Vec::new();
+    }
+
+    let (first, rest) = match code.split_once(char::is_whitespace) {
+        Some((first, rest)) => (first, rest.trim()),
+        None => (code, ""),
+    };
+
+    match first {
+        "section" => return translate_section(rest),
+        "global" => return vec![format!(".globl {}", rest)],
+        "extern" => return vec![format!(".extern {}", rest)],
+        "bits" => return vec![format!(".code{}", rest)],
+        "align" | "alignb" => return vec![format!(".balign {}", rest)],
+        _ => {}
+    }
+
+    if let Some(data) = get_data_directive(first) {
+        return vec![format!(".{} {}", data, translate_expression(rest))];
+    }
+
+    // Definitions start with their label, like `strltr.0 db 0x41, 0x0`
+    let (directive, operands) = match rest.split_once(char::is_whitespace) {
+        Some((directive, operands)) => (directive, operands.trim()),
+        None => (rest, ""),
+    };
+
+    let label = quote_symbols(first.trim_end_matches(':'));
+
+    match directive {
+        "equ" => {
+            return vec![format!(
+                ".set {}, {}",
+                label,
+                translate_expression(operands)
+            )]
+        }
+        "resb" | "resw" | "resd" | "resq" => {
+            let size = match directive {
+                "resb" => 1,
+                "resw" => 2,
+                "resd" => 4,
+                _ => 8,
+            };
+
+            return vec![
+                format!("{}:", label),
+                format!("\t.zero ({}) * {}", translate_expression(operands), size),
+            ];
+        }
+        _ => {}
+    }
+
+    if let Some(data) = get_data_directive(directive) {
+        return vec![
+            format!("{}:", label),
+            format!("\t.{} {}", data, translate_expression(operands)),
+        ];
+    }
+
+    if code.ends_with(':') {
+        return vec![quote_symbols(code)];
+    }
+
+    return vec![translate_instruction(first, rest)];
+}
+
+fn get_data_directive(directive: &str) -> Option<&str> {
+    return match directive {
+        "db" => Some("byte"),
+        "dw" => Some("word"),
+        "dd" => Some("long"),
+        "dq" => Some("quad"),
+        _ => None,
+    };
+}
+
+// Qualifiers like `exec align=16` become the flags of the section and an alignment
+fn translate_section(operands: &str) -> Vec<String> {
+    let mut words = operands.split_whitespace();
+    let name = words.next().unwrap_or_default();
+    let qualifiers: Vec<&str> = words.collect();
+
+    if qualifiers.is_empty() {
+        return vec![format!(".section {}", name)];
+    }
+
+    let mut flags = String::from("a");
+    let mut alignment: Option<&str> = None;
+
+    for qualifier in qualifiers {
+        match qualifier {
+            "exec" => flags.push('x'),
+            "write" => flags.push('w'),
+            "noalloc" => flags.retain(|flag| flag != 'a'),
+            _ => alignment = qualifier.strip_prefix("align=").or(alignment),
+        }
+    }
+
+    // GNU as checks the type of the sections it knows
+    let section_type = match name {
+        ".init_array" => "@init_array",
+        ".fini_array" => "@fini_array",
+        _ => "@progbits",
+    };
+
+    let mut lines = vec![format!(
+        ".section {}, \"{}\", {}",
+        name, flags, section_type
+    )];
+
+    if let Some(alignment) = alignment {
+        lines.push(format!("\t.balign {}", alignment));
+    }
+
+    return lines;
+}
+
+fn translate_instruction(mnemonic: &str, operands: &str) -> String {
+    if operands.is_empty() {
+        return mnemonic.to_owned();
+    }
+
+    let mut operands: Vec<String> = operands
+        .split(',')
+        .map(|operand| translate_operand(operand.trim()))
+        .collect();
+
+    // A bare symbol is an immediate in NASM but a memory operand in GNU as
+    if operands.len() > 1 || mnemonic == "push" {
+        let last = operands.last_mut().expect("Unreachable");
+
+        if is_symbol(last) {
+            *last = format!("offset {}", last);
+        }
+    }
+
+    return format!("{} {}", mnemonic, operands.join(", "));
+}
+
+fn translate_operand(operand: &str) -> String {
+    let operand = operand.replace(" wrt ..plt", "@PLT");
+    let operand = operand.replace("[rel ", "[rip + ");
+
+    let operand = match operand.split_once(" [") {
+        Some((size, address)) if ["byte", "word", "dword", "qword"].contains(&size) => {
+            format!("{} ptr [{}", size, address)
+        }
+        _ => operand,
+    };
+
+    return quote_symbols(&operand);
+}
+
+// The location counter is $ in NASM and . in GNU as
+fn translate_expression(expression: &str) -> String {
+    return quote_symbols(&expression.replace('$', "."));
+}
+
+// Symbols like ..@ez.loc.main.0 have to be quoted since @ starts a relocation in GNU as
+fn quote_symbols(text: &str) -> String {
+    let mut output = String::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find("..@") {
+        let end = rest[start..]
+            .find(|character: char| {
+                !character.is_ascii_alphanumeric() && !"_.@$".contains(character)
+            })
+            .map_or(rest.len(), |offset| start + offset);
+
+        output.push_str(&rest[..start]);
+        output.push_str(&format!("\"{}\"", &rest[start..end]));
+        rest = &rest[end..];
+    }
+
+    output.push_str(rest);
+
+    return output;
+}
+
+fn is_symbol(operand: &str) -> bool {
+    let starts_like_symbol = operand
+        .chars()
+        .next()
+        .is_some_and(|character| character.is_ascii_alphabetic() || "_.".contains(character));
+
+    let symbol_characters = operand
+        .chars()
+        .all(|character| character.is_ascii_alphanumeric() || "_.".contains(character));
+
+    return starts_like_symbol && symbol_characters && !is_register(operand);
+}
+
+fn is_register(operand: &str) -> bool {
+    const NAMED: [&str; 36] = [
+        "rax", "rbx", "rcx", "rdx", "rsi", "rdi", "rsp", "rbp", "eax", "ebx", "ecx", "edx", "esi",
+        "edi", "esp", "ebp", "ax", "bx", "cx", "dx", "si", "di", "sp", "bp", "al", "bl", "cl",
+        "dl", "ah", "bh", "ch", "dh", "sil", "dil", "spl", "bpl",
+    ];
+
+    const SEGMENTS: [&str; 6] = ["cs", "ds", "es", "fs", "gs", "ss"];
+
+    if NAMED.contains(&operand) || SEGMENTS.contains(&operand) {
+        return true;
+    }
+
+    // r8 to r15 with their d, w and b halves, the vector and the control registers
+    let numbered = operand
+        .strip_prefix('r')
+        .map(|rest| rest.trim_end_matches(['d', 'w', 'b']))
+        .or_else(|| operand.strip_prefix("xmm"))
+        .or_else(|| operand.strip_prefix("cr"));
+
+    return numbered.is_some_and(|number| number.parse::<u8>().is_ok());
+}
@@ -0,0 +1,323 @@
+use crate::parser::{Rule, Symbol};
+
+// Size of a diagram around its main line, which sits `up` pixels below its top
+struct Size {
+    width: usize,
+    up: usize,
+    down: usize,
+}
+
+const GAP: usize = 16;
+const RAIL: usize = 20;
+const CHARACTER_WIDTH: usize = 8;
+
+impl Symbol {
+    fn get_ebnf(&self) -> String {
+        return match self {
+            Symbol::Terminal(text) if text.contains('"') => format!("'{}'", text),
+            Symbol::Terminal(text) => format!("\"{}\"", text),
+            Symbol::NonTerminal(name) => name.to_string(),
+            Symbol::Special(text) => format!("? {} ?", text),
+            Symbol::Sequence(symbols) => symbols
+                .iter()
+                .map(|symbol| match symbol {
+                    Symbol::Choice(_) => format!("( {} )", symbol.get_ebnf()),
+                    _ => symbol.get_ebnf(),
+                })
+                .collect::<Vec<String>>()
+                .join(" , "),
+            Symbol::Choice(symbols) => symbols
+                .iter()
+                .map(|symbol| symbol.get_ebnf())
+                .collect::<Vec<String>>()
+                .join(" | "),
+            Symbol::Optional(symbol) => format!("[ {} ]", symbol.get_ebnf()),
+            Symbol::Repeat(symbol) => format!("{{ {} }}", symbol.get_ebnf()),
+        };
+    }
+
+    fn get_size(&self) -> Size {
+        return match self {
+            Symbol::Terminal(text) | Symbol::NonTerminal(text) | Symbol::Special(text) => Size {
+                width: text.chars().count() * CHARACTER_WIDTH + GAP,
+                up: 12,
+                down: 12,
+            },
+            Symbol::Sequence(symbols) => {
+                let sizes: Vec<Size> = symbols.iter().map(|symbol| symbol.get_size()).collect();
+
+                Size {
+                    width: sizes.iter().map(|size| size.width).sum::<usize>()
+                        + GAP * (sizes.len().max(1) - 1),
+                    up: sizes.iter().map(|size| size.up).max().unwrap_or(0),
+                    down: sizes.iter().map(|size| size.down).max().unwrap_or(0),
+                }
+            }
+            Symbol::Choice(symbols) => get_choice_size(symbols.iter().map(Some).collect()),
+            // Skipping is the main line, the symbol is a branch below it
+            Symbol::Optional(symbol) => get_choice_size(vec![None, Some(*symbol)]),
+            Symbol::Repeat(symbol) => {
+                let size = symbol.get_size();
+
+                get_choice_size_from(vec![
+                    Size {
+                        width: 0,
+                        up: 0,
+                        down: 0,
+                    },
+                    Size {
+                        width: size.width + RAIL * 2,
+                        up: size.up,
+                        down: size.down + 12,
+                    },
+                ])
+            }
+        };
+    }
+
+    // Draws the diagram with its main line entering at (x, y)
+    fn write_svg(&self, x: usize, y: usize, svg: &mut String) {
+        match self {
+            Symbol::Terminal(text) | Symbol::NonTerminal(text) | Symbol::Special(text) => {
+                let width = self.get_size().width;
+                let (class, radius) = match self {
+                    Symbol::Terminal(_) => ("terminal", 12),
+                    Symbol::NonTerminal(_) => ("nonterminal", 0),
+                    _ => ("special", 4),
+                };
+                let escaped = text
+                    .replace('&', "&amp;")
+                    .replace('<', "&lt;")
+                    .replace('>', "&gt;");
+
+                if let Symbol::NonTerminal(name) = self {
+                    svg.push_str(&format!("<a href=\"#{}\">", name));
+                }
+
+                svg.push_str(&format!(
+                    "<rect class=\"{}\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"24\" rx=\"{}\"/>",
+                    class,
+                    x,
+                    y - 12,
+                    width,
+                    radius
+                ));
+                svg.push_str(&format!(
+                    "<text x=\"{}\" y=\"{}\">{}</text>",
+                    x + width / 2,
+                    y + 4,
+                    escaped
+                ));
+
+                if let Symbol::NonTerminal(_) = self {
+                    svg.push_str("</a>");
+                }
+            }
+            Symbol::Sequence(symbols) => {
+                let mut x = x;
+
+                for (index, symbol) in symbols.iter().enumerate() {
+                    if index > 0 {
+                        write_line(x, y, x + GAP, y, svg);
+                        x += GAP;
+                    }
+
+                    symbol.write_svg(x, y, svg);
+                    x += symbol.get_size().width;
+                }
+            }
+            Symbol::Choice(symbols) => {
+                write_choice_svg(symbols.iter().map(Some).collect(), x, y, svg);
+            }
+            Symbol::Optional(symbol) => write_choice_svg(vec![None, Some(*symbol)], x, y, svg),
+            Symbol::Repeat(symbol) => {
+                let size = self.get_size();
+                let inner = symbol.get_size();
+
+                // Same rails as an optional, with a loop back around the symbol
+                let branch = y + 12 + inner.up;
+                let left = x + RAIL;
+                let right = x + size.width - RAIL;
+                let back = branch + inner.down + 12;
+
+                write_line(x, y, x + size.width, y, svg);
+                write_curve(x, y, left, branch, svg);
+                write_curve(x + size.width, y, right, branch, svg);
+
+                write_line(left, branch, left + RAIL, branch, svg);
+                symbol.write_svg(left + RAIL, branch, svg);
+                write_line(left + RAIL + inner.width, branch, right, branch, svg);
+
+                // Rounded loop from the end of the symbol back to its start
+                let (start, end) = (left + RAIL, right - RAIL);
+                let (outer_left, outer_right) = (left + RAIL / 2, right - RAIL / 2);
+
+                svg.push_str(&format!(
+                    "<path d=\"M{end} {branch} Q{outer_right} {branch} {outer_right} {} \
+                     L{outer_right} {} Q{outer_right} {back} {end} {back} L{start} {back} \
+                     Q{outer_left} {back} {outer_left} {} L{outer_left} {} \
+                     Q{outer_left} {branch} {start} {branch}\"/>",
+                    branch + 10,
+                    back - 10,
+                    back - 10,
+                    branch + 10,
+                ));
+            }
+        }
+    }
+}
+
+fn get_choice_size(branches: Vec<Option<&Symbol>>) -> Size {
+    return get_choice_size_from(
+        branches
+            .into_iter()
+            .map(|branch| match branch {
+                Some(symbol) => symbol.get_size(),
+                None => Size {
+                    width: 0,
+                    up: 0,
+                    down: 0,
+                },
+            })
+            .collect(),
+    );
+}
+
+// The first branch is the main line, the others are stacked below it
+fn get_choice_size_from(sizes: Vec<Size>) -> Size {
+    let mut down = sizes[0].down;
+
+    for size in sizes.iter().skip(1) {
+        down += 12 + size.up + size.down;
+    }
+
+    return Size {
+        width: sizes.iter().map(|size| size.width).max().unwrap_or(0) + RAIL * 2,
+        up: sizes[0].up,
+        down,
+    };
+}
+
+fn write_choice_svg(branches: Vec<Option<&Symbol>>, x: usize, y: usize, svg: &mut String) {
+    let size = get_choice_size(branches.clone());
+    let right = x + size.width;
+    let mut branch = y;
+
+    for (index, symbol) in branches.iter().enumerate() {
+        let inner = match symbol {
+            Some(symbol) => symbol.get_size(),
+            None => Size {
+                width: 0,
+                up: 0,
+                down: 0,
+            },
+        };
+
+        if index > 0 {
+            branch += 12 + inner.up;
+            write_curve(x, y, x + RAIL, branch, svg);
+            write_curve(right, y, right - RAIL, branch, svg);
+        } else {
+            write_line(x, y, x + RAIL, y, svg);
+            write_line(right - RAIL, y, right, y, svg);
+        }
+
+        if let Some(symbol) = symbol {
+            symbol.write_svg(x + RAIL, branch, svg);
+        }
+
+        write_line(x + RAIL + inner.width, branch, right - RAIL, branch, svg);
+
+        branch += inner.down;
+    }
+}
+
+fn write_line(x1: usize, y1: usize, x2: usize, y2: usize, svg: &mut String) {
+    svg.push_str(&format!("<path d=\"M{} {} L{} {}\"/>", x1, y1, x2, y2));
+}
+
+// Rail from the main line at (x1, y1) down to a branch starting at (x2, y2)
+fn write_curve(x1: usize, y1: usize, x2: usize, y2: usize, svg: &mut String) {
+    let middle = (x1 + x2) / 2;
+
+    svg.push_str(&format!(
+        "<path d=\"M{} {} Q{} {} {} {} L{} {} Q{} {} {} {}\"/>",
+        x1,
+        y1,
+        middle,
+        y1,
+        middle,
+        y1 + 10,
+        middle,
+        y2 - 10,
+        middle,
+        y2,
+        x2,
+        y2
+    ));
+}
+
+pub fn write_ebnf(grammar: &[Rule]) -> String {
+    let width = grammar
+        .iter()
+        .map(|rule| rule.name.len())
+        .max()
+        .unwrap_or(0);
+
+    return grammar
+        .iter()
+        .map(|rule| {
+            format!(
+                "{:<width$} = {} ;\n",
+                rule.name,
+                rule.definition.get_ebnf(),
+                width = width
+            )
+        })
+        .collect();
+}
+
+// One railroad diagram per rule, nonterminals link to their own rule
+pub fn write_railroad(grammar: &[Rule]) -> String {
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>ezlang grammar</title>\n<style>\n\
+         body { font-family: sans-serif; }\n\
+         svg { display: block; margin-bottom: 24px; }\n\
+         path { fill: none; stroke: #333; stroke-width: 1.5; }\n\
+         rect { stroke: #333; stroke-width: 1.5; }\n\
+         rect.terminal { fill: #fff5c0; }\n\
+         rect.nonterminal { fill: #d8ecff; }\n\
+         rect.special { fill: #eee; }\n\
+         text { font-family: monospace; font-size: 13px; text-anchor: middle; }\n\
+         </style>\n</head>\n<body>\n",
+    );
+
+    for rule in grammar.iter() {
+        let size = rule.definition.get_size();
+        let y = 8 + size.up;
+        let mut svg = String::new();
+
+        write_line(0, y, RAIL, y, &mut svg);
+        rule.definition.write_svg(RAIL, y, &mut svg);
+        write_line(RAIL + size.width, y, RAIL * 2 + size.width, y, &mut svg);
+
+        html.push_str(&format!(
+            "<h2 id=\"{}\">{}</h2>\n<pre>{} = {} ;</pre>\n<svg width=\"{}\" height=\"{}\">{}</svg>\n",
+            rule.name,
+            rule.name,
+            rule.name,
+            rule.definition
+                .get_ebnf()
+                .replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;"),
+            RAIL * 2 + size.width,
+            y + size.down + 8,
+            svg
+        ));
+    }
+
+    html.push_str("</body>\n</html>\n");
+
+    return html;
+}
@@ -0,0 +1,221 @@
+use crate::parser::{Function, Program, Struct, Type};
+
+// Integer and pointer arguments use these registers in the System V ABI, floats use xmm0-7
+pub const INTEGER_REGISTERS: [&str; 6] = ["rdi", "rsi", "rdx", "rcx", "r8", "r9"];
+pub const FLOAT_REGISTERS: usize = 8;
+
+// C name of the wrapper around an exported function
+pub fn get_c_name(function: &Function) -> String {
+    let module: String = function
+        .module
+        .chars()
+        .map(|character| match character.is_ascii_alphanumeric() {
+            true => character,
+            false => '_',
+        })
+        .collect();
+
+    return format!("ez_{}_{}", module, function.name);
+}
+
+pub fn get_c_type(c_type: &Type) -> Option<String> {
+    return match c_type {
+        Type::U8 => Some("uint8_t".to_owned()),
+        Type::U16 => Some("uint16_t".to_owned()),
+        Type::U32 => Some("uint32_t".to_owned()),
+        Type::U64 => Some("uint64_t".to_owned()),
+        Type::I8 => Some("int8_t".to_owned()),
+        Type::I16 => Some("int16_t".to_owned()),
+        Type::I32 => Some("int32_t".to_owned()),
+        Type::I64 => Some("int64_t".to_owned()),
+        Type::F64 => Some("double".to_owned()),
+        Type::Pointer(element) => match &**element {
+            Type::Struct(layout) => Some(format!("struct {} *", layout.name)),
+            Type::Array(..) => Some("void *".to_owned()),
+            element => get_c_type(element).map(|element| format!("{} *", element)),
+        },
+        Type::Array(..) | Type::Struct(_) | Type::Tuple(_) => None,
+    };
+}
+
+// Only signatures that map one to one on the System V ABI can be called from C
+pub fn is_exportable(function: &Function) -> bool {
+    if !function.public || function.body.is_none() {
+        return false;
+    }
+
+    // Every instance of a generic function would get the same C name
+    if !function.type_arguments.is_empty() {
+        return false;
+    }
+
+    return has_c_signature(function);
+}
+
+// Whether the arguments and result have a System V equivalent, so C can call the function and
+// extern functions can be called like C does
+pub fn has_c_signature(function: &Function) -> bool {
+    let types: Vec<&Type> = function
+        .arguments
+        .iter()
+        .map(|index| &function.locals.get(*index).expect("Unreachable").local_type)
+        .collect();
+
+    // Structs are passed by address and copied by the callee, which C does not do
+    if types.iter().any(|arg_type| get_c_type(arg_type).is_none()) {
+        return false;
+    }
+
+    let floats = types
+        .iter()
+        .filter(|arg_type| ***arg_type == Type::F64)
+        .count();
+
+    if floats > FLOAT_REGISTERS || types.len() - floats > INTEGER_REGISTERS.len() {
+        return false;
+    }
+
+    // Two value structs come back in rax and rdx only when both are full integer words
+    return match &function.return_type {
+        Type::Tuple(elements) => elements
+            .iter()
+            .all(|element| element.get_size() == 8 && *element != Type::F64),
+        return_type => get_c_type(return_type).is_some(),
+    };
+}
+
+// Fields are laid out like C does, so structs can be declared as is
+fn get_c_declaration(c_type: &Type, name: &str) -> String {
+    return match c_type {
+        Type::Array(element, length) => {
+            get_c_declaration(element, &format!("{}[{}]", name, length))
+        }
+        Type::Struct(layout) => format!("struct {} {}", layout.name, name),
+        c_type => {
+            let c_type = get_c_type(c_type).expect("Unreachable");
+
+            match c_type.ends_with('*') {
+                true => format!("{}{}", c_type, name),
+                false => format!("{} {}", c_type, name),
+            }
+        }
+    };
+}
+
+// Nested structs come before the structs containing them
+fn collect_structs(c_type: &Type, structs: &mut Vec<Struct>) {
+    match c_type {
+        Type::Pointer(element) | Type::Array(element, _) => collect_structs(element, structs),
+        Type::Struct(layout) => {
+            for field in layout.fields.iter() {
+                collect_structs(&field.field_type, structs);
+            }
+
+            if !structs
+                .iter()
+                .any(|collected| collected.name == layout.name)
+            {
+                structs.push(*layout.clone());
+            }
+        }
+        Type::Tuple(elements) => {
+            for element in elements.iter() {
+                collect_structs(element, structs);
+            }
+        }
+        _ => {}
+    }
+}
+
+pub fn write_header(program: &Program, filename: &str, guard: &str) -> String {
+    let mut header = format!(
+        "/* Generated by ezlang from {}, do not edit. */\n",
+        filename
+    );
+
+    header.push_str(&format!("#ifndef {}\n#define {}\n\n", guard, guard));
+    header.push_str("#include <stdint.h>\n");
+
+    let functions: Vec<&Function> = program
+        .functions
+        .iter()
+        .filter(|function| function.public && function.body.is_some())
+        .collect();
+
+    let mut structs: Vec<Struct> = Vec::new();
+
+    for function in functions.iter().filter(|function| is_exportable(function)) {
+        let types = function
+            .arguments
+            .iter()
+            .map(|index| &function.locals.get(*index).expect("Unreachable").local_type)
+            .chain([&function.return_type]);
+
+        for c_type in types {
+            collect_structs(c_type, &mut structs);
+        }
+    }
+
+    for layout in structs.iter() {
+        header.push_str(&format!("\nstruct {} {{\n", layout.name));
+
+        for field in layout.fields.iter() {
+            header.push_str(&format!(
+                "\t{};\n",
+                get_c_declaration(&field.field_type, &field.name)
+            ));
+        }
+
+        header.push_str("};\n");
+    }
+
+    for function in functions.iter() {
+        header.push('\n');
+
+        if !is_exportable(function) {
+            header.push_str(&format!(
+                "/* {}::{} can not be called from C. */\n",
+                function.module, function.name
+            ));
+            continue;
+        }
+
+        let name = get_c_name(function);
+
+        let return_type = match &function.return_type {
+            Type::Tuple(elements) => {
+                let result = format!("{}_result", name);
+
+                header.push_str(&format!(
+                    "typedef struct {{\n\t{} first;\n\t{} second;\n}} {};\n\n",
+                    get_c_type(&elements[0]).expect("Unreachable"),
+                    get_c_type(&elements[1]).expect("Unreachable"),
+                    result
+                ));
+
+                result
+            }
+            return_type => get_c_type(return_type).expect("Unreachable"),
+        };
+
+        let arguments: Vec<String> = function
+            .arguments
+            .iter()
+            .map(|index| {
+                let argument = function.locals.get(*index).expect("Unreachable");
+                return get_c_declaration(&argument.local_type, &argument.label.get_name());
+            })
+            .collect();
+
+        let arguments = match arguments.len() {
+            0 => "void".to_owned(),
+            _ => arguments.join(", "),
+        };
+
+        header.push_str(&format!("{} {}({});\n", return_type, name, arguments));
+    }
+
+    header.push_str(&format!("\n#endif /* {} */\n", guard));
+
+    return header;
+}
@@ -0,0 +1,240 @@
+use std::ops::Range;
+
+use crate::{
+    parser::{Expression, FunctionId, Parser, Program},
+    span::Position,
+    syntax::{SyntaxElement, SyntaxKind, SyntaxTree},
+};
+
+// Replaces a byte range of the source with new text
+#[derive(Debug, Clone)]
+pub struct Edit {
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Reparse {
+    // Only the function at this index was parsed again
+    Function(usize),
+    // The edit could change other items, the whole file was parsed again
+    File,
+}
+
+// Applies an edit of the root file to its tree and program, parsing again only the function
+// containing it when its signature stays the same. Errors panic like a full parse, leaving
+// both as they were before the edit.
+pub fn reparse(tree: &mut SyntaxTree, program: &mut Program, edit: &Edit) -> Reparse {
+    if let Some(index) = reparse_function(tree, program, edit) {
+        return Reparse::Function(index);
+    }
+
+    let mut source = tree.to_source();
+    source.replace_range(edit.range.clone(), &edit.text);
+
+    let mut reparsed = SyntaxTree::parse(&tree.filename, &source, tree.edition);
+    let reparsed_program = reparsed.to_program();
+    reparsed.link(&reparsed_program);
+
+    *tree = reparsed;
+    *program = reparsed_program;
+
+    return Reparse::File;
+}
+
+fn reparse_function(tree: &mut SyntaxTree, program: &mut Program, edit: &Edit) -> Option<usize> {
+    // Instances of generic functions are parsed from the tokens of other items, so any edit
+    // could need a new one
+    if !program.generics.is_empty() {
+        return None;
+    }
+
+    // The first and last tokens of the item must stay in place for it to remain a single item
+    let item = tree.root.children.iter().position(|item| match item {
+        SyntaxElement::Node(node) if node.kind == SyntaxKind::Function => match node.get_range() {
+            Some(range) => range.start < edit.range.start && edit.range.end < range.end,
+            None => false,
+        },
+        _ => false,
+    })?;
+
+    let node = match &tree.root.children[item] {
+        SyntaxElement::Node(node) => node,
+        SyntaxElement::Token(_) => return None,
+    };
+
+    let tokens = node.get_tokens();
+    let (first, last) = (tokens[0], tokens[tokens.len() - 1]);
+    let start = first.range.start;
+
+    // Columns are only kept for the lines after the item
+    let next_line = match tree.root.children.get(item + 1) {
+        Some(SyntaxElement::Node(next)) => next
+            .get_tokens()
+            .first()
+            .map(|token| token.token.position.line),
+        _ => None,
+    };
+
+    if next_line == Some(last.token.position.line) {
+        return None;
+    }
+
+    let index = program.functions.iter().position(|function| {
+        function.position.file == tree.filename
+            && tokens
+                .iter()
+                .any(|token| is_at(&token.token.position, &function.position))
+    })?;
+
+    let mut text = node.get_text();
+    let replaced = edit.range.start - start..edit.range.end - start;
+    let lines = edit.text.matches('\n').count() as isize
+        - text[replaced.clone()].matches('\n').count() as isize;
+    let bytes = edit.text.len() as isize - replaced.len() as isize;
+
+    text.replace_range(replaced, &edit.text);
+
+    // Padding keeps the lines and columns of the item as in the file
+    let padding =
+        "\n".repeat(first.token.position.line - 1) + &" ".repeat(first.token.position.column - 1);
+    let padded = padding.clone() + &text;
+    let edition = tree.get_file_edition();
+
+    let reparsed = SyntaxTree::parse(&tree.filename, &padded, edition);
+
+    let mut reparsed_item = match reparsed.root.children.as_slice() {
+        [SyntaxElement::Node(node)]
+            if node.kind == SyntaxKind::Function && reparsed.trailing.len() == 0 =>
+        {
+            node.clone()
+        }
+        _ => return None,
+    };
+
+    let mut parser = Parser::from_source(&tree.filename, padded.into_bytes(), edition);
+    parser.generate_tokens();
+
+    let (mut function, strings) = parser.generate_function(program, index);
+
+    let old = &program.functions[index];
+
+    // Callers were checked against the old signature, kept in the table of the program
+    if function.name != old.name
+        || function.public != old.public
+        || function.external != old.external
+        || function.get_signature() != *program.table.get_signature(FunctionId(index))
+    {
+        return None;
+    }
+
+    // Nothing can fail from here on
+    let first_token = program.tokens.iter().position(|token| {
+        token.position.file == tree.filename && is_at(&token.position, &first.token.position)
+    })?;
+    let count = tokens.len();
+    let leading = first.leading.clone();
+
+    let new_tokens: Vec<_> = reparsed_item
+        .get_tokens()
+        .iter()
+        .map(|token| token.token.clone())
+        .collect();
+    let new_count = new_tokens.len();
+
+    program
+        .tokens
+        .splice(first_token..first_token + count, new_tokens);
+
+    for token in program.tokens[first_token + new_count..].iter_mut() {
+        if token.position.file == tree.filename {
+            token.position.line = shift(token.position.line, lines);
+        }
+    }
+
+    if let Some(body) = &mut function.body {
+        for span in body.spans.iter_mut() {
+            span.tokens = span.tokens.start + first_token..span.tokens.end + first_token;
+        }
+    }
+
+    let tokens_delta = new_count as isize - count as isize;
+
+    for later in program.functions[index + 1..].iter_mut() {
+        let root = later.position.file == tree.filename;
+
+        if root {
+            later.position.line = shift(later.position.line, lines);
+
+            for reference in later.references.iter_mut() {
+                reference.position.line = shift(reference.position.line, lines);
+            }
+        }
+
+        if let Some(body) = &mut later.body {
+            for span in body.spans.iter_mut() {
+                span.tokens =
+                    shift(span.tokens.start, tokens_delta)..shift(span.tokens.end, tokens_delta);
+
+                if root {
+                    span.position.line = shift(span.position.line, lines);
+                }
+            }
+
+            if root {
+                for statement in body.statements.iter_mut() {
+                    for expression in statement.get_expressions_mut() {
+                        shift_expression(expression, lines);
+                    }
+                }
+            }
+        }
+    }
+
+    program.functions[index] = function;
+    program.strings = strings;
+
+    // The reparsed item was lexed from the padding, its bytes move back to the file
+    for token in reparsed_item.get_tokens_mut() {
+        token.range =
+            token.range.start - padding.len() + start..token.range.end - padding.len() + start;
+    }
+
+    if let Some(token) = reparsed_item.get_tokens_mut().first_mut() {
+        token.leading = leading;
+    }
+
+    tree.root.children[item] = SyntaxElement::Node(reparsed_item);
+
+    for later in tree.root.children[item + 1..].iter_mut() {
+        if let SyntaxElement::Node(later) = later {
+            for token in later.get_tokens_mut() {
+                token.range = shift(token.range.start, bytes)..shift(token.range.end, bytes);
+                token.token.position.line = shift(token.token.position.line, lines);
+            }
+        }
+    }
+
+    tree.link_function(program, index);
+
+    return Some(index);
+}
+
+fn is_at(a: &Position, b: &Position) -> bool {
+    return a.line == b.line && a.column == b.column;
+}
+
+fn shift(value: usize, delta: isize) -> usize {
+    return (value as isize + delta) as usize;
+}
+
+// Bounds checks report the position of the index expression
+fn shift_expression(expression: &mut Expression, lines: isize) {
+    if let Expression::Index(_, _, position) = expression {
+        position.line = shift(position.line, lines);
+    }
+
+    for child in expression.get_children_mut() {
+        shift_expression(child, lines);
+    }
+}
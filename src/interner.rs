@@ -0,0 +1,78 @@
+use std::{cell::RefCell, collections::HashMap, fmt, rc::Rc};
+
+// Identifier stored once and compared as a number. Names are interned per thread, a program
+// is lexed, parsed and compiled on a single one, so its symbols are never seen elsewhere.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+struct Interner {
+    symbols: HashMap<Rc<str>, Symbol>,
+    names: Vec<Rc<str>>,
+}
+
+thread_local! {
+    static INTERNER: RefCell<Interner> = RefCell::new(Interner {
+        symbols: HashMap::new(),
+        names: Vec::new(),
+    });
+}
+
+impl Symbol {
+    pub fn intern(name: &str) -> Self {
+        return INTERNER.with(|interner| {
+            let mut interner = interner.borrow_mut();
+
+            if let Some(symbol) = interner.symbols.get(name) {
+                return *symbol;
+            }
+
+            let symbol = Symbol(interner.names.len() as u32);
+            let name: Rc<str> = Rc::from(name);
+
+            interner.names.push(name.clone());
+            interner.symbols.insert(name, symbol);
+
+            return symbol;
+        });
+    }
+
+    pub fn get_name(&self) -> Rc<str> {
+        return INTERNER.with(|interner| interner.borrow().names[self.0 as usize].clone());
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        return *self.get_name() == **other;
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}", self.get_name());
+    }
+}
+
+// Written as the name, symbols are only meaningful to the thread that interned them
+#[cfg(feature = "serde")]
+impl serde::Serialize for Symbol {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        return serializer.serialize_str(&self.get_name());
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Symbol {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+
+        return Ok(Symbol::intern(&name));
+    }
+}
+
+// Quoted like a string, the way messages showing tokens print identifiers
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{:?}", self.get_name());
+    }
+}
@@ -0,0 +1,1018 @@
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hasher},
+    io::Write,
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::{
+    interner::Symbol,
+    lexer::BinaryOperator,
+    parser::{Expression, Function, FunctionId, Intrinsic, Local, Program, Statement, Type},
+    trace::{describe_expression, describe_statement},
+};
+
+// Null pointers point into this guard so they never reach a valid value
+const NULL_GUARD: usize = 8;
+
+#[derive(Debug, Clone)]
+pub struct InterpreterOptions {
+    // Statements executed before the program is stopped
+    pub max_steps: u64,
+    // Nested function calls, main included
+    pub max_depth: usize,
+    // Bytes of string literals, static locals and stack frames
+    pub max_memory: usize,
+    // Print every executed statement and the values it changed to stderr
+    pub trace: bool,
+}
+
+// Local variables of the running function, laid out like the compiled stack frame
+struct Frame<'a> {
+    function: &'a Function,
+    // Address right after the frame, where rbp would point to
+    base: usize,
+}
+
+pub struct Interpreter<'a> {
+    program: &'a Program,
+    options: InterpreterOptions,
+    memory: Vec<u8>,
+    strings: Vec<usize>,
+    // Address of each static local by its label, allocated before any frame
+    statics: HashMap<String, usize>,
+    // State of @rand, zero until the first number or a seed
+    random_state: u64,
+    // What @clock_ns counts from, only the difference between two readings means anything
+    started: Instant,
+    steps: u64,
+    depth: usize,
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(program: &'a Program, options: InterpreterOptions) -> Self {
+        Self {
+            program,
+            options,
+            memory: Vec::new(),
+            strings: Vec::new(),
+            statics: HashMap::new(),
+            random_state: 0,
+            started: Instant::now(),
+            steps: 0,
+            depth: 0,
+        }
+    }
+
+    // Runs main and returns the exit code the compiled executable would have
+    pub fn run(&mut self) -> Result<u8, String> {
+        self.load_program()?;
+
+        let main = match self
+            .program
+            .functions
+            .iter()
+            .position(|function| function.label == "main")
+        {
+            Some(main) => FunctionId(main),
+            None => {
+                return Err(
+                    "error[E0014]: No entry point, declare a `fn main: ()` function.".to_owned(),
+                )
+            }
+        };
+
+        for constructor in self.program.get_constructors() {
+            self.call(constructor, Vec::new())?;
+        }
+
+        let (result, _) = self.call(main, Vec::new())?;
+
+        for destructor in self.program.get_destructors() {
+            self.call(destructor, Vec::new())?;
+        }
+
+        let _ = std::io::stdout().flush();
+
+        return Ok(result as u8);
+    }
+
+    // Runs a single function without the constructors, for values computed while compiling
+    pub fn call_function(&mut self, id: FunctionId, arguments: Vec<u64>) -> Result<u64, String> {
+        self.load_program()?;
+
+        let (result, _) = self.call(id, arguments)?;
+
+        return Ok(result);
+    }
+
+    // Places the string literals and static locals in memory, before any frame
+    fn load_program(&mut self) -> Result<(), String> {
+        self.allocate(NULL_GUARD)?;
+
+        for string in self.program.strings.iter() {
+            let address = self.allocate(string.len() + 1)?;
+
+            self.memory[address..address + string.len()].copy_from_slice(string.as_bytes());
+            self.strings.push(address);
+        }
+
+        for function in self.program.functions.iter() {
+            for local in function.locals.locals.iter() {
+                if let Some(label) = &local.static_label {
+                    let address = self.allocate(local.size)?;
+                    self.statics.insert(label.clone(), address);
+                }
+            }
+        }
+
+        return Ok(());
+    }
+
+    fn allocate(&mut self, size: usize) -> Result<usize, String> {
+        let address = self.memory.len();
+
+        if address.saturating_add(size) > self.options.max_memory {
+            return Err(format!(
+                "program exceeded the memory limit of {} bytes",
+                self.options.max_memory
+            ));
+        }
+
+        self.memory.resize(address + size, 0);
+
+        return Ok(address);
+    }
+
+    fn call(&mut self, id: FunctionId, arguments: Vec<u64>) -> Result<(u64, u64), String> {
+        let function = &self.program.functions[id];
+
+        let body = match (&function.body, function.external) {
+            (Some(body), _) => body,
+            (None, true) => {
+                return Err(format!(
+                    "{}: Function {} is extern and can not be interpreted.",
+                    function.position, function.name
+                ))
+            }
+            (None, false) => {
+                return Err(format!(
+                    "{}: Function {} is implemented in assembly and can not be interpreted.",
+                    function.position, function.name
+                ))
+            }
+        };
+
+        if self.depth >= self.options.max_depth {
+            return Err(format!(
+                "{}: program exceeded the call depth limit of {}",
+                function.position, self.options.max_depth
+            ));
+        }
+
+        let size = function.locals.get_size();
+        let start = self.allocate(size)?;
+
+        let frame = Frame {
+            function,
+            base: start + size,
+        };
+
+        for (argument, value) in function.arguments.iter().zip(arguments) {
+            let local = function.locals.get(*argument).expect("Unreachable");
+
+            // Structs are passed by address, the called function gets a copy
+            if local.local_type.is_struct() {
+                self.copy(value as usize, self.get_address(&frame, local), local.size)?;
+                continue;
+            }
+
+            self.store(self.get_address(&frame, local), &local.local_type, value)?;
+        }
+
+        if self.options.trace {
+            let arguments: Vec<String> = function
+                .arguments
+                .iter()
+                .map(|argument| {
+                    let local = function.locals.get(*argument).expect("Unreachable");
+                    let address = self.get_address(&frame, local);
+
+                    return format!(
+                        "{} = {}",
+                        local.label,
+                        self.format_value(address, &local.local_type)
+                    );
+                })
+                .collect();
+
+            self.trace(&format!("call {}({})", function.name, arguments.join(", ")));
+        }
+
+        self.depth += 1;
+
+        let mut result = (0, 0);
+
+        let mut index = 0;
+
+        while let Some(statement) = body.statements.get(index) {
+            let span = &body.spans[index];
+            index += 1;
+
+            self.steps += 1;
+
+            if self.steps > self.options.max_steps {
+                return Err(format!(
+                    "program exceeded the step limit of {}",
+                    self.options.max_steps
+                ));
+            }
+
+            // Aborts like the compiled program, with the position of the statement
+            if let Statement::Call(Expression::Intrinsic(intrinsic, _)) = statement {
+                if let Some(message) = intrinsic.get_abort_message() {
+                    return Err(format!("{}: {}", span.position, message));
+                }
+            }
+
+            if let Some(value) = self.execute_traced(statement, &frame)? {
+                result = value;
+                break;
+            }
+
+            // Loops made of jumps run until the step limit
+            if let Statement::Goto(label) = statement {
+                index = body
+                    .statements
+                    .iter()
+                    .position(|other| matches!(other, Statement::Label(other) if other == label))
+                    .expect("Unreachable");
+            }
+        }
+
+        self.depth -= 1;
+
+        if self.options.trace {
+            let value = match &function.return_type {
+                Type::Tuple(elements) => format!(
+                    "({}, {})",
+                    format_scalar(result.0, &elements[0]),
+                    format_scalar(result.1, &elements[1])
+                ),
+                return_type => format_scalar(result.0, return_type),
+            };
+
+            self.trace(&format!("return {}", value));
+        }
+
+        // Pointers into the frame dangle from here on, like they do in the compiled code
+        self.memory.truncate(start);
+
+        return Ok(result);
+    }
+
+    // Statements are printed before running, changed locals after
+    fn execute_traced(
+        &mut self,
+        statement: &Statement,
+        frame: &Frame,
+    ) -> Result<Option<(u64, u64)>, String> {
+        if !self.options.trace {
+            return self.execute(statement, frame);
+        }
+
+        self.trace(&describe_statement(statement, frame.function, self.program));
+
+        let indices = match statement {
+            Statement::Declare(index) | Statement::Assign(index, _) => vec![*index],
+            Statement::Destructure(indices, _) => indices.clone(),
+            Statement::Store(..)
+            | Statement::Return(_)
+            | Statement::Call(_)
+            | Statement::Label(_)
+            | Statement::Goto(_) => Vec::new(),
+        };
+
+        let locals: Vec<(&Symbol, usize, &Type)> = indices
+            .iter()
+            .map(|index| {
+                let local = frame.function.locals.get(*index).expect("Unreachable");
+                return (
+                    &local.label,
+                    self.get_address(frame, local),
+                    &local.local_type,
+                );
+            })
+            .collect();
+
+        let before: Vec<String> = locals
+            .iter()
+            .map(|(_, address, local_type)| self.format_value(*address, local_type))
+            .collect();
+
+        let result = self.execute(statement, frame)?;
+
+        for ((label, address, local_type), before) in locals.into_iter().zip(before) {
+            let after = self.format_value(address, local_type);
+            self.trace(&format!("  {}: {} -> {}", label, before, after));
+        }
+
+        return Ok(result);
+    }
+
+    // Program output is flushed first so both streams interleave in order
+    fn trace(&self, line: &str) {
+        let _ = std::io::stdout().flush();
+        eprintln!("{}{}", "  ".repeat(self.depth), line);
+    }
+
+    // Aggregates are printed with their parts, dangling memory as ?
+    fn format_value(&self, address: usize, value_type: &Type) -> String {
+        return match value_type {
+            Type::Array(element, length) => {
+                let elements: Vec<String> = (0..*length)
+                    .map(|index| self.format_value(address + index * element.get_size(), element))
+                    .collect();
+
+                format!("[{}]", elements.join(", "))
+            }
+            Type::Struct(layout) => {
+                let fields: Vec<String> = layout
+                    .fields
+                    .iter()
+                    .map(|field| {
+                        format!(
+                            "{}: {}",
+                            field.name,
+                            self.format_value(address + field.offset, &field.field_type)
+                        )
+                    })
+                    .collect();
+
+                format!("{} {{ {} }}", layout.name, fields.join(", "))
+            }
+            value_type => match self.load(address, value_type) {
+                Ok(value) => format_scalar(value, value_type),
+                Err(_) => "?".to_owned(),
+            },
+        };
+    }
+
+    // Returns the value once a return statement is reached
+    fn execute(
+        &mut self,
+        statement: &Statement,
+        frame: &Frame,
+    ) -> Result<Option<(u64, u64)>, String> {
+        let locals = &frame.function.locals;
+
+        match statement {
+            // Static locals are zeroed once, before main
+            Statement::Declare(index) if locals.locals[*index].static_label.is_some() => {}
+            Statement::Declare(index) => {
+                let local = locals.get(*index).expect("Unreachable");
+                let address = self.get_address(frame, local);
+
+                self.memory[address..address + local.size].fill(0);
+            }
+            Statement::Store(target, expression)
+                if target.get_type(locals, self.functions()).is_aggregate() =>
+            {
+                let source = self.evaluate_address(expression, frame)?;
+                let address = self.evaluate_address(target, frame)?;
+                let size = target.get_type(locals, self.functions()).get_size();
+
+                self.copy(source, address, size)?;
+            }
+            Statement::Store(target, expression) => {
+                let value = self.evaluate(expression, frame)?;
+                let address = self.evaluate_address(target, frame)?;
+                let target_type = target.get_type(locals, self.functions());
+
+                // The target is only evaluated once, so stores are traced here
+                let before = match self.options.trace {
+                    true => Some(self.format_value(address, &target_type)),
+                    false => None,
+                };
+
+                self.store(address, &target_type, value)?;
+
+                if let Some(before) = before {
+                    self.trace(&format!(
+                        "  {}: {} -> {}",
+                        describe_expression(target, frame.function, self.program),
+                        before,
+                        self.format_value(address, &target_type)
+                    ));
+                }
+            }
+            Statement::Assign(index, Expression::Initializer(_, elements)) => {
+                let local = locals.get(*index).expect("Unreachable");
+
+                self.initialize(
+                    self.get_address(frame, local),
+                    &local.local_type,
+                    elements,
+                    frame,
+                )?;
+            }
+            Statement::Assign(index, expression)
+                if locals.locals[*index].local_type.is_struct() =>
+            {
+                let local = locals.get(*index).expect("Unreachable");
+                let source = self.evaluate_address(expression, frame)?;
+
+                self.copy(source, self.get_address(frame, local), local.size)?;
+            }
+            Statement::Assign(index, expression) => {
+                let local = locals.get(*index).expect("Unreachable");
+                let value = self.evaluate(expression, frame)?;
+
+                self.store(self.get_address(frame, local), &local.local_type, value)?;
+            }
+            Statement::Destructure(indices, expression) => {
+                let (first, second) = self.evaluate_tuple(expression, frame)?;
+
+                for (index, value) in indices.iter().zip([first, second]) {
+                    let local = locals.get(*index).expect("Unreachable");
+
+                    self.store(self.get_address(frame, local), &local.local_type, value)?;
+                }
+            }
+            Statement::Return(expression) => {
+                let value = match expression.get_type(locals, self.functions()) {
+                    Type::Tuple(_) => self.evaluate_tuple(expression, frame)?,
+                    _ => (self.evaluate(expression, frame)?, 0),
+                };
+
+                return Ok(Some(value));
+            }
+            Statement::Call(expression) => {
+                match expression.get_type(locals, self.functions()) {
+                    Type::Tuple(_) => self.evaluate_tuple(expression, frame)?,
+                    _ => (self.evaluate(expression, frame)?, 0),
+                };
+            }
+            // Jumps are taken by call, which knows where the statements are
+            Statement::Label(_) | Statement::Goto(_) => {}
+        }
+
+        return Ok(None);
+    }
+
+    fn get_address(&self, frame: &Frame, local: &Local) -> usize {
+        return match &local.static_label {
+            Some(label) => self.statics[label],
+            None => frame.base - local.offset - local.size,
+        };
+    }
+
+    fn functions(&self) -> &'a Vec<Function> {
+        return &self.program.functions;
+    }
+
+    fn evaluate(&mut self, expression: &Expression, frame: &Frame) -> Result<u64, String> {
+        let locals = &frame.function.locals;
+
+        return match expression {
+            Expression::NumberLiteral(number) => Ok(*number),
+            Expression::FloatLiteral(number) => Ok(number.to_bits()),
+            Expression::StringLiteral(index) => Ok(self.strings[*index] as u64),
+            Expression::Local(index) => {
+                let local = locals.get(*index).expect("Unreachable");
+                self.load(self.get_address(frame, local), &local.local_type)
+            }
+            Expression::Binary(binary_expression) => {
+                let left = &*binary_expression.left;
+                let right = &*binary_expression.right;
+
+                let mut left_value = self.evaluate(left, frame)?;
+                let mut right_value = self.evaluate(right, frame)?;
+
+                // The integer side of pointer arithmetic counts elements, not bytes
+                match (
+                    left.get_type(locals, self.functions()),
+                    right.get_type(locals, self.functions()),
+                ) {
+                    (Type::Pointer(element), _) => {
+                        right_value = right_value.wrapping_mul(element.get_size() as u64);
+                    }
+                    (_, Type::Pointer(element)) => {
+                        left_value = left_value.wrapping_mul(element.get_size() as u64);
+                    }
+                    _ => {}
+                }
+
+                self.operate(
+                    &binary_expression.operator,
+                    &expression.get_type(locals, self.functions()),
+                    left_value,
+                    right_value,
+                )
+            }
+            Expression::Index(..) | Expression::Field(..) => {
+                let address = self.evaluate_address(expression, frame)?;
+                self.load(address, &expression.get_type(locals, self.functions()))
+            }
+            Expression::Address(expression) => Ok(self.evaluate_address(expression, frame)? as u64),
+            Expression::Cast(cast_type, expression) => {
+                let expression_type = expression.get_type(locals, self.functions());
+                let value = self.evaluate(expression, frame)?;
+
+                Ok(match (&expression_type, cast_type) {
+                    (Type::F64, Type::F64) => value,
+                    (_, Type::F64) => (value as i64 as f64).to_bits(),
+                    (Type::F64, _) => truncate(cast_type, convert_float(value)),
+                    _ => truncate(cast_type, value),
+                })
+            }
+            Expression::Call(id, expressions) => {
+                let arguments = self.evaluate_arguments(expressions, frame)?;
+
+                Ok(self.call(*id, arguments)?.0)
+            }
+            Expression::Format(chunks, expressions) => self.format(chunks, expressions, frame),
+            Expression::Intrinsic(intrinsic, expressions) => {
+                let types: Vec<Type> = expressions
+                    .iter()
+                    .map(|expression| expression.get_type(locals, self.functions()))
+                    .collect();
+
+                let mut arguments: Vec<u64> = Vec::new();
+
+                for expression in expressions.iter() {
+                    arguments.push(self.evaluate(expression, frame)?);
+                }
+
+                Ok(self.intrinsic(intrinsic, &types, &arguments)?.0)
+            }
+            Expression::Initializer(..) | Expression::Tuple(_) => panic!("Unreachable"),
+        };
+    }
+
+    // Both values of a tuple, the compiled code keeps them in rax and rdx
+    fn evaluate_tuple(
+        &mut self,
+        expression: &Expression,
+        frame: &Frame,
+    ) -> Result<(u64, u64), String> {
+        return match expression {
+            Expression::Tuple(elements) => {
+                let first = self.evaluate(&elements[0], frame)?;
+                let second = self.evaluate(&elements[1], frame)?;
+
+                Ok((first, second))
+            }
+            Expression::Call(id, expressions) => {
+                let arguments = self.evaluate_arguments(expressions, frame)?;
+
+                self.call(*id, arguments)
+            }
+            Expression::Intrinsic(intrinsic, expressions) => {
+                let locals = &frame.function.locals;
+
+                let types: Vec<Type> = expressions
+                    .iter()
+                    .map(|expression| expression.get_type(locals, self.functions()))
+                    .collect();
+
+                let mut arguments: Vec<u64> = Vec::new();
+
+                for expression in expressions.iter() {
+                    arguments.push(self.evaluate(expression, frame)?);
+                }
+
+                self.intrinsic(intrinsic, &types, &arguments)
+            }
+            _ => panic!("Unreachable"),
+        };
+    }
+
+    // Structs are passed by their address, like the compiled code does
+    fn evaluate_arguments(
+        &mut self,
+        expressions: &Vec<Expression>,
+        frame: &Frame,
+    ) -> Result<Vec<u64>, String> {
+        let mut arguments: Vec<u64> = Vec::new();
+
+        for expression in expressions.iter() {
+            let argument_type = expression.get_type(&frame.function.locals, self.functions());
+
+            arguments.push(match argument_type.is_struct() {
+                true => self.evaluate_address(expression, frame)? as u64,
+                false => self.evaluate(expression, frame)?,
+            });
+        }
+
+        return Ok(arguments);
+    }
+
+    fn evaluate_address(
+        &mut self,
+        expression: &Expression,
+        frame: &Frame,
+    ) -> Result<usize, String> {
+        let locals = &frame.function.locals;
+
+        return match expression {
+            Expression::Local(index) => {
+                let local = locals.get(*index).expect("Unreachable");
+                Ok(self.get_address(frame, local))
+            }
+            Expression::Index(base, index, position) => {
+                let base_type = base.get_type(locals, self.functions());
+                let element_size = base_type.get_element().expect("Unreachable").get_size();
+
+                let index = self.evaluate(index, frame)?;
+
+                // Indices are always checked, negative ones wrap to huge unsigned values
+                if let Type::Array(_, length) = &base_type {
+                    if index >= *length as u64 {
+                        return Err(format!("{}: Index out of bounds.", position));
+                    }
+                }
+
+                // Arrays live in place while pointers hold the address as value
+                let base = match base_type {
+                    Type::Array(..) => self.evaluate_address(base, frame)? as u64,
+                    _ => self.evaluate(base, frame)?,
+                };
+
+                Ok(base.wrapping_add(index.wrapping_mul(element_size as u64)) as usize)
+            }
+            Expression::Field(base, field) => {
+                let offset = match base.get_type(locals, self.functions()) {
+                    Type::Struct(layout) => layout.fields[*field].offset,
+                    _ => panic!("Unreachable"),
+                };
+
+                Ok(self.evaluate_address(base, frame)? + offset)
+            }
+            _ => panic!("Unreachable"),
+        };
+    }
+
+    fn initialize(
+        &mut self,
+        address: usize,
+        slot_type: &Type,
+        elements: &Vec<Expression>,
+        frame: &Frame,
+    ) -> Result<(), String> {
+        let slots: Vec<(&Type, usize)> = match slot_type {
+            Type::Array(element, _) => (0..elements.len())
+                .map(|index| (&**element, index * element.get_size()))
+                .collect(),
+            Type::Struct(layout) => layout
+                .fields
+                .iter()
+                .map(|field| (&field.field_type, field.offset))
+                .collect(),
+            _ => panic!("Unreachable"),
+        };
+
+        for ((element_type, offset), element) in slots.into_iter().zip(elements.iter()) {
+            if let Expression::Initializer(_, elements) = element {
+                self.initialize(address + offset, element_type, elements, frame)?;
+                continue;
+            }
+
+            if element_type.is_struct() {
+                let source = self.evaluate_address(element, frame)?;
+                self.copy(source, address + offset, element_type.get_size())?;
+                continue;
+            }
+
+            let value = self.evaluate(element, frame)?;
+            self.store(address + offset, element_type, value)?;
+        }
+
+        return Ok(());
+    }
+
+    fn check_range(&self, address: usize, size: usize) -> Result<(), String> {
+        if address < NULL_GUARD || address.saturating_add(size) > self.memory.len() {
+            return Err(format!("Invalid memory access at {:#x}.", address));
+        }
+
+        return Ok(());
+    }
+
+    fn copy(&mut self, source: usize, destination: usize, size: usize) -> Result<(), String> {
+        self.check_range(source, size)?;
+        self.check_range(destination, size)?;
+
+        self.memory.copy_within(source..source + size, destination);
+
+        return Ok(());
+    }
+
+    // Narrow values are extended so they always hold the full value
+    fn load(&self, address: usize, load_type: &Type) -> Result<u64, String> {
+        let size = load_type.get_size();
+        self.check_range(address, size)?;
+
+        let mut bytes = [0u8; 8];
+        bytes[..size].copy_from_slice(&self.memory[address..address + size]);
+
+        return Ok(truncate(load_type, u64::from_le_bytes(bytes)));
+    }
+
+    fn store(&mut self, address: usize, store_type: &Type, value: u64) -> Result<(), String> {
+        let size = store_type.get_size();
+        self.check_range(address, size)?;
+
+        self.memory[address..address + size].copy_from_slice(&value.to_le_bytes()[..size]);
+
+        return Ok(());
+    }
+
+    fn operate(
+        &self,
+        operator: &BinaryOperator,
+        operation_type: &Type,
+        left: u64,
+        right: u64,
+    ) -> Result<u64, String> {
+        if *operation_type == Type::F64 {
+            let (left, right) = (f64::from_bits(left), f64::from_bits(right));
+
+            let result = match operator {
+                BinaryOperator::Add => left + right,
+                BinaryOperator::Sub => left - right,
+                BinaryOperator::Mul => left * right,
+                BinaryOperator::Div => left / right,
+                BinaryOperator::BitwiseOr
+                | BinaryOperator::BitwiseAnd
+                | BinaryOperator::BitwiseXor => panic!("Unreachable"),
+            };
+
+            return Ok(result.to_bits());
+        }
+
+        let result = match operator {
+            BinaryOperator::Add => left.wrapping_add(right),
+            BinaryOperator::Sub => left.wrapping_sub(right),
+            BinaryOperator::Mul => left.wrapping_mul(right),
+            BinaryOperator::Div => divide(operation_type, left, right)?.0,
+            BinaryOperator::BitwiseOr => left | right,
+            BinaryOperator::BitwiseAnd => left & right,
+            BinaryOperator::BitwiseXor => left ^ right,
+        };
+
+        // Wrap around at the width of the type
+        return Ok(truncate(operation_type, result));
+    }
+
+    fn intrinsic(
+        &mut self,
+        intrinsic: &Intrinsic,
+        types: &[Type],
+        arguments: &[u64],
+    ) -> Result<(u64, u64), String> {
+        let result = match intrinsic {
+            Intrinsic::Write => {
+                let (buffer, length) = (arguments[1] as usize, arguments[2] as usize);
+                self.check_range(buffer, length)?;
+
+                self.output(arguments[0], &self.memory[buffer..buffer + length])?;
+                length as u64
+            }
+            Intrinsic::ToStr => {
+                let string = match types[0] {
+                    Type::F64 => format_float(arguments[0]),
+                    _ => (arguments[0] as i64).to_string(),
+                };
+
+                let buffer = arguments[1] as usize;
+                self.check_range(buffer, string.len())?;
+
+                self.memory[buffer..buffer + string.len()].copy_from_slice(string.as_bytes());
+                string.len() as u64
+            }
+            Intrinsic::ParseInt => self.parse_int(arguments[0] as usize)?,
+            Intrinsic::ToF64 => (arguments[0] as i64 as f64).to_bits(),
+            Intrinsic::ToInt => convert_float(arguments[0]),
+            Intrinsic::DivMod => return divide(&types[0], arguments[0], arguments[1]),
+            // Part of the frame, released with it
+            Intrinsic::StackAlloc => self.allocate(arguments[0] as usize)? as u64,
+            Intrinsic::Memset => {
+                let (destination, length) = (arguments[0] as usize, arguments[2] as usize);
+                self.check_range(destination, length)?;
+
+                self.memory[destination..destination + length].fill(arguments[1] as u8);
+                arguments[0]
+            }
+            Intrinsic::Memcpy => {
+                let (destination, source) = (arguments[0] as usize, arguments[1] as usize);
+                let length = arguments[2] as usize;
+                self.check_range(destination, length)?;
+                self.check_range(source, length)?;
+
+                self.memory
+                    .copy_within(source..source + length, destination);
+                arguments[0]
+            }
+            Intrinsic::Memcmp => {
+                let (a, b, length) = (
+                    arguments[0] as usize,
+                    arguments[1] as usize,
+                    arguments[2] as usize,
+                );
+                self.check_range(a, length)?;
+                self.check_range(b, length)?;
+
+                match self.memory[a..a + length].cmp(&self.memory[b..b + length]) {
+                    std::cmp::Ordering::Less => -1i64 as u64,
+                    std::cmp::Ordering::Equal => 0,
+                    std::cmp::Ordering::Greater => 1,
+                }
+            }
+            Intrinsic::PortIn | Intrinsic::PortOut => {
+                return Err(format!(
+                    "{} accesses I/O ports, which can not be interpreted.",
+                    intrinsic.get_name()
+                ))
+            }
+            Intrinsic::Random => self.next_random(),
+            Intrinsic::Seed => {
+                self.random_state = arguments[0];
+                arguments[0]
+            }
+            Intrinsic::Time => SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs()),
+            Intrinsic::ClockNs => self.started.elapsed().as_nanos() as u64,
+            // EINVAL, like nanosleep does for negative durations
+            Intrinsic::SleepMs if (arguments[0] as i64) < 0 => -22i64 as u64,
+            Intrinsic::SleepMs => {
+                thread::sleep(Duration::from_millis(arguments[0]));
+                0
+            }
+            // Statements of their own, stopped at by call
+            Intrinsic::Trap | Intrinsic::Unreachable => panic!("Unreachable"),
+        };
+
+        return Ok((result, 0));
+    }
+
+    // Same xorshift64* as ez_rand, so seeded programs draw the numbers they draw compiled.
+    // Unseeded ones take their seed from the hash keys std picks randomly for each process.
+    fn next_random(&mut self) -> u64 {
+        let mut state = self.random_state;
+
+        if state == 0 {
+            state = RandomState::new().build_hasher().finish() | 1;
+        }
+
+        state ^= state >> 12;
+        state ^= state << 25;
+        state ^= state >> 27;
+        self.random_state = state;
+
+        return state.wrapping_mul(0x2545f4914f6cdd1d) >> 1;
+    }
+
+    // Optionally signed decimal number, stopping at the first non digit character
+    fn parse_int(&self, mut address: usize) -> Result<u64, String> {
+        let mut number: u64 = 0;
+
+        self.check_range(address, 1)?;
+        let negative = self.memory[address] == b'-';
+
+        if negative {
+            address += 1;
+        }
+
+        loop {
+            self.check_range(address, 1)?;
+
+            let digit = self.memory[address].wrapping_sub(b'0');
+
+            if digit > 9 {
+                break;
+            }
+
+            number = number.wrapping_mul(10).wrapping_add(digit as u64);
+            address += 1;
+        }
+
+        return match negative {
+            true => Ok(number.wrapping_neg()),
+            false => Ok(number),
+        };
+    }
+
+    // Writes the chunks and values in order, returning the total written length
+    fn format(
+        &mut self,
+        chunks: &Vec<Option<usize>>,
+        expressions: &Vec<Expression>,
+        frame: &Frame,
+    ) -> Result<u64, String> {
+        let mut written: u64 = 0;
+
+        for (index, chunk) in chunks.iter().enumerate() {
+            if let Some(chunk) = chunk {
+                let string = &self.program.strings[*chunk];
+
+                self.output(1, string.as_bytes())?;
+                written += string.len() as u64;
+            }
+
+            if let Some(expression) = expressions.get(index) {
+                let value = self.evaluate(expression, frame)?;
+
+                let string = match expression.get_type(&frame.function.locals, self.functions()) {
+                    Type::F64 => format_float(value),
+                    _ => (value as i64).to_string(),
+                };
+
+                self.output(1, string.as_bytes())?;
+                written += string.len() as u64;
+            }
+        }
+
+        return Ok(written);
+    }
+
+    fn output(&self, descriptor: u64, bytes: &[u8]) -> Result<(), String> {
+        let result = match descriptor {
+            1 => std::io::stdout().write_all(bytes),
+            2 => std::io::stderr().write_all(bytes),
+            descriptor => {
+                return Err(format!(
+                    "Writing to file descriptor {} is not supported by the interpreter.",
+                    descriptor
+                ))
+            }
+        };
+
+        return result.map_err(|error| error.to_string());
+    }
+}
+
+fn format_scalar(value: u64, value_type: &Type) -> String {
+    return match value_type {
+        Type::F64 => format_float(value),
+        Type::Pointer(_) => format!("{:#x}", value),
+        value_type if value_type.is_signed() => (value as i64).to_string(),
+        _ => value.to_string(),
+    };
+}
+
+// Same results as the truncation after every compiled operation
+fn truncate(integer_type: &Type, value: u64) -> u64 {
+    return match integer_type {
+        Type::U8 => value as u8 as u64,
+        Type::U16 => value as u16 as u64,
+        Type::U32 => value as u32 as u64,
+        Type::I8 => value as i8 as u64,
+        Type::I16 => value as i16 as u64,
+        Type::I32 => value as i32 as u64,
+        _ => value,
+    };
+}
+
+// cvttsd2si gives the lowest i64 for values out of range
+fn convert_float(value: u64) -> u64 {
+    let value = f64::from_bits(value);
+
+    if value.is_nan() || value >= 9223372036854775808.0 || value < -9223372036854775808.0 {
+        return i64::MIN as u64;
+    }
+
+    return value as i64 as u64;
+}
+
+// Quotient and remainder like idiv and div, which trap instead of wrapping
+fn divide(integer_type: &Type, dividend: u64, divisor: u64) -> Result<(u64, u64), String> {
+    if divisor == 0 {
+        return Err("Division by zero.".to_owned());
+    }
+
+    if !integer_type.is_signed() {
+        return Ok((dividend / divisor, dividend % divisor));
+    }
+
+    let (dividend, divisor) = (dividend as i64, divisor as i64);
+
+    return match (dividend.checked_div(divisor), dividend.checked_rem(divisor)) {
+        (Some(quotient), Some(remainder)) => Ok((quotient as u64, remainder as u64)),
+        _ => Err("Division overflow.".to_owned()),
+    };
+}
+
+// Six decimal places with the rounding of ez_ftoa
+fn format_float(value: u64) -> String {
+    let sign = match value >> 63 {
+        1 => "-",
+        _ => "",
+    };
+
+    let scaled = (f64::from_bits(value).abs() * 1000000.0).round_ties_even() as u64;
+
+    return format!("{}{}.{:06}", sign, scaled / 1000000, scaled % 1000000);
+}
@@ -1,25 +1,8 @@
-use std::{fs::File, io::Read};
+use std::{fs::File, io::Read, ops::Range};
 
-#[derive(Debug, Clone)]
-pub struct Position {
-    pub line: usize,
-    pub column: usize,
-}
+use clap::ValueEnum;
 
-impl Position {
-    pub fn start() -> Self {
-        Self { line: 1, column: 1 }
-    }
-
-    pub fn new_line(&mut self) {
-        self.line += 1;
-        self.column = 1;
-    }
-
-    pub fn next_column(&mut self) {
-        self.column += 1;
-    }
-}
+use crate::{diagnostics::set_processing, interner::Symbol, span::Position};
 
 pub struct Lexer {
     pub filename: String,
@@ -28,9 +11,101 @@ pub struct Lexer {
     current_char: u8,
     reached_eof: bool,
     pub file_position: Position,
+    pub edition: Edition,
+}
+
+// Keywords of every edition map to the same tokens, english ones are always accepted
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum Edition {
+    #[default]
+    English,
+    Spanish,
+}
+
+static ENGLISH_KEYWORDS: [(&str, TokenType); 17] = [
+    ("return", TokenType::Return),
+    ("goto", TokenType::Goto),
+    ("if", TokenType::If),
+    ("while", TokenType::While),
+    ("for", TokenType::For),
+    ("true", TokenType::True),
+    ("false", TokenType::False),
+    ("fn", TokenType::Function),
+    ("var", TokenType::Var),
+    ("pub", TokenType::Pub),
+    ("extern", TokenType::Extern),
+    ("struct", TokenType::Struct),
+    ("const", TokenType::Const),
+    ("static_assert", TokenType::StaticAssert),
+    ("as", TokenType::As),
+    ("include", TokenType::Include),
+    ("include_asm", TokenType::IncludeAsm),
+];
+
+static SPANISH_KEYWORDS: [(&str, TokenType); 16] = [
+    ("retorna", TokenType::Return),
+    ("ir_a", TokenType::Goto),
+    ("si", TokenType::If),
+    ("mientras", TokenType::While),
+    ("para", TokenType::For),
+    ("verdadero", TokenType::True),
+    ("falso", TokenType::False),
+    ("funcion", TokenType::Function),
+    ("publica", TokenType::Pub),
+    ("externa", TokenType::Extern),
+    ("estructura", TokenType::Struct),
+    ("constante", TokenType::Const),
+    ("afirma_estatico", TokenType::StaticAssert),
+    ("como", TokenType::As),
+    ("incluye", TokenType::Include),
+    ("incluye_asm", TokenType::IncludeAsm),
+];
+
+impl Edition {
+    pub fn from_name(name: &str) -> Option<Self> {
+        return match name {
+            "english" => Some(Self::English),
+            "spanish" => Some(Self::Spanish),
+            _ => None,
+        };
+    }
+
+    pub fn get_keywords(&self) -> &'static [(&'static str, TokenType)] {
+        return match self {
+            Self::English => &ENGLISH_KEYWORDS,
+            Self::Spanish => &SPANISH_KEYWORDS,
+        };
+    }
+}
+
+// Types an integer literal can be suffixed with
+static INTEGER_SUFFIXES: [&str; 8] = ["u8", "u16", "u32", "u64", "i8", "i16", "i32", "i64"];
+
+// UTF-8 encoding of U+FEFF
+const BYTE_ORDER_MARK: &[u8] = b"\xef\xbb\xbf";
+
+// Digits of an integer literal stop at its suffix, other letters are reported as invalid digits
+fn is_literal_digit(character: u8) -> bool {
+    return character.is_ascii_alphanumeric() && character != b'u' && character != b'i';
+}
+
+// Value of a literal after one more digit, which must still fit in 64 bits
+fn push_digit(result: u64, base: u64, digit: u64, start: &Position) -> u64 {
+    return match result
+        .checked_mul(base)
+        .and_then(|result| result.checked_add(digit))
+    {
+        Some(result) => result,
+        None => panic!(
+            "{}: error[E0012]: Literal out of range, it is larger than {}.",
+            start,
+            u64::MAX
+        ),
+    };
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BinaryOperator {
     Add,
     Sub,
@@ -54,25 +129,47 @@ impl BinaryOperator {
 #[derive(Debug, Clone)]
 pub enum TokenType {
     NumberLiteral(u64),
+    // With a suffix naming its type, like `255u8`
+    TypedNumberLiteral(u64, &'static str),
+    FloatLiteral(f64),
     StringLiteral(String),
     Character(char),
-    Identifier(String),
+    Identifier(Symbol),
     Function,
+    Pub,
+    Extern,
     Var,
+    Struct,
+    Const,
+    StaticAssert,
     Return,
+    Goto,
+    As,
+    Include,
+    IncludeAsm,
     If,
     While,
     For,
     True,
     False,
     Colon,
+    DoubleColon,
     Semicolon,
     LeftPar,
     RightPar,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Equals,
     Comma,
+    Dot,
+    Arrow,
+    // Around the type parameters of a generic function, like `fn max<T: int>`
+    LeftAngle,
+    RightAngle,
+    // Starts an attribute like `#[section(".boot")]`
+    Hash,
     UnaryNot,
     UnaryInc,
     UnaryDec,
@@ -80,6 +177,57 @@ pub enum TokenType {
     Call(usize),
 }
 
+impl TokenType {
+    // Kind and text of the token as `--dump-tokens` prints them. Keywords are spelled in
+    // English whatever the edition, so the same program gives the same dump in both.
+    pub fn get_dump(&self) -> String {
+        let keyword = ENGLISH_KEYWORDS
+            .iter()
+            .find(|(_, keyword)| std::mem::discriminant(keyword) == std::mem::discriminant(self));
+
+        if let Some((keyword, _)) = keyword {
+            return format!("keyword {}", keyword);
+        }
+
+        let punctuation = match self {
+            Self::NumberLiteral(value) => return format!("number {}", value),
+            Self::TypedNumberLiteral(value, suffix) => {
+                return format!("number {}{}", value, suffix)
+            }
+            Self::FloatLiteral(value) => return format!("float {:?}", value),
+            Self::StringLiteral(value) => return format!("string {:?}", value),
+            Self::Character(value) => return format!("character {:?}", value),
+            Self::Identifier(name) => return format!("identifier {}", name),
+            Self::BinaryOperation(operator) => {
+                return format!("operator {}", operator.get_symbol())
+            }
+            Self::UnaryNot => return String::from("operator !"),
+            Self::UnaryInc => return String::from("operator ++"),
+            Self::UnaryDec => return String::from("operator --"),
+            Self::Colon => ":",
+            Self::DoubleColon => "::",
+            Self::Semicolon => ";",
+            Self::LeftPar => "(",
+            Self::RightPar => ")",
+            Self::LeftBrace => "{",
+            Self::RightBrace => "}",
+            Self::LeftBracket => "[",
+            Self::RightBracket => "]",
+            Self::LeftAngle => "<",
+            Self::RightAngle => ">",
+            Self::Equals => "=",
+            Self::Comma => ",",
+            Self::Dot => ".",
+            Self::Arrow => "->",
+            Self::Hash => "#",
+            Self::Call(_) => "@",
+            _ => panic!("Unreachable"),
+        };
+
+        return format!("punctuation {}", punctuation);
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Token {
     pub token_type: TokenType,
@@ -87,21 +235,83 @@ pub struct Token {
 }
 
 impl Lexer {
-    pub fn from_file(filename: &str) -> Self {
-        let mut file: File = File::open(filename).expect("File does not exists");
-
+    pub fn from_file(filename: &str, edition: Edition) -> Self {
         let mut buf: Vec<u8> = Vec::new();
 
-        file.read_to_end(&mut buf).expect("Could not read file");
+        if let Err(error) = File::open(filename).and_then(|mut file| file.read_to_end(&mut buf)) {
+            panic!("{}:1:1: Can not read the file, {}.", filename, error);
+        }
+
+        return Self::from_source(filename, buf, edition);
+    }
 
-        return Self {
+    // Lexes source text that is not on disk yet, like the result of a fix
+    pub fn from_source(filename: &str, buf: Vec<u8>, edition: Edition) -> Self {
+        // An empty source starts at its end, the parser reports it has no functions
+        let mut lexer = Self {
             filename: filename.to_owned(),
-            current_char: buf[0],
+            current_char: buf.first().copied().unwrap_or(b'\n'),
+            reached_eof: buf.is_empty(),
             data: buf,
             position: 0,
-            reached_eof: false,
-            file_position: Position::start(),
+            file_position: Position::start(filename),
+            edition,
+        };
+
+        // Editors may start a file with a byte order mark, it is not part of the text and the
+        // first column comes after it
+        if lexer.data.starts_with(BYTE_ORDER_MARK) {
+            lexer.position = BYTE_ORDER_MARK.len();
+
+            match lexer.data.get(lexer.position) {
+                Some(character) => lexer.current_char = *character,
+                None => lexer.reached_eof = true,
+            }
+        }
+
+        // A first line like `#!/usr/bin/env ezlang` lets scripts run on their own
+        if lexer.data[lexer.position..].starts_with(b"#!") {
+            lexer.skip_line();
+        }
+
+        // A first line like `#edition spanish` overrides the edition for this file only
+        if lexer.data[lexer.position..].starts_with(b"#edition") {
+            lexer.read_pragma();
+        }
+
+        return lexer;
+    }
+
+    // Skips the rest of the line and its line break
+    fn skip_line(&mut self) {
+        while self.current_char != b'\n' && !self.reached_eof {
+            self.next_char();
+        }
+
+        self.next_char();
+    }
+
+    fn read_pragma(&mut self) {
+        let position = self.file_position.clone();
+        let mut buffer: Vec<u8> = Vec::new();
+
+        while self.current_char != b'\n' && !self.reached_eof {
+            buffer.push(self.current_char);
+            self.next_char();
+        }
+
+        let pragma = String::from_utf8_lossy(&buffer).to_string();
+        let name = pragma.trim_start_matches("#edition").trim();
+
+        self.edition = match Edition::from_name(name) {
+            Some(edition) => edition,
+            None => panic!(
+                "{}: Unknown edition {}, expected english or spanish.",
+                position, name
+            ),
         };
+
+        self.next_char();
     }
 
     pub fn next(&mut self) -> Option<Token> {
@@ -111,12 +321,16 @@ impl Lexer {
             return None;
         }
 
+        set_processing(&self.file_position);
+
         return match self.current_char {
             b':' => Some(self.read_colon()),
             b'(' => Some(self.read_l_par()),
             b')' => Some(self.read_r_par()),
             b'{' => Some(self.read_l_brace()),
             b'}' => Some(self.read_r_brace()),
+            b'[' => Some(self.read_l_bracket()),
+            b']' => Some(self.read_r_bracket()),
             b';' => Some(self.read_semicolon()),
             b'+' => Some(self.read_add()),
             b'-' => Some(self.read_sub()),
@@ -124,26 +338,42 @@ impl Lexer {
             b'/' => Some(self.read_div()),
             b'*' => Some(self.read_mul()),
             b',' => Some(self.read_comma()),
+            b'<' => Some(self.read_l_angle()),
+            b'>' => Some(self.read_r_angle()),
+            b'.' => Some(self.read_dot()),
             b'&' => Some(self.read_and()),
             b'|' => Some(self.read_or()),
             b'^' => Some(self.read_xor()),
             b'!' => Some(self.read_not()),
             b'@' => Some(self.read_call()),
+            b'#' => Some(self.read_hash()),
             b'0'..=b'9' => Some(self.read_number_like()),
             b'a'..=b'z' | b'A'..b'Z' | b'_' => Some(self.read_identifier()),
             b'"' => Some(self.read_string()),
             b'\'' => Some(self.read_character()),
             _ => {
-                panic!(
-                    "{}:{}:{}: Unkown token",
-                    self.filename, self.file_position.line, self.file_position.column
-                );
+                panic!("{}: Unkown token", self.file_position);
             }
         };
     }
 
+    // Next token with the byte range of its text, for tools that keep the source around tokens
+    pub fn next_with_range(&mut self) -> Option<(Token, Range<usize>)> {
+        self.skip_whitespaces();
+
+        let start = self.position;
+        let token = self.next()?;
+
+        let end = match self.reached_eof {
+            true => self.data.len(),
+            false => self.position,
+        };
+
+        return Some((token, start..end));
+    }
+
     fn next_char(&mut self) -> u8 {
-        let prev_position = self.file_position.clone();
+        let (prev_line, prev_column) = (self.file_position.line, self.file_position.column);
 
         if self.current_char == b'\n' {
             self.file_position.new_line();
@@ -155,7 +385,8 @@ impl Lexer {
             self.position += 1;
             self.current_char = self.data[self.position];
         } else {
-            self.file_position = prev_position;
+            self.file_position.line = prev_line;
+            self.file_position.column = prev_column;
             self.reached_eof = true;
         }
 
@@ -179,6 +410,15 @@ impl Lexer {
         return token;
     }
 
+    fn read_hash(&mut self) -> Token {
+        let token = Token {
+            token_type: TokenType::Hash,
+            position: self.file_position.clone(),
+        };
+        self.next_char();
+        return token;
+    }
+
     fn read_not(&mut self) -> Token {
         let token = Token {
             token_type: TokenType::UnaryNot,
@@ -233,6 +473,15 @@ impl Lexer {
         return token;
     }
 
+    fn read_dot(&mut self) -> Token {
+        let token = Token {
+            token_type: TokenType::Dot,
+            position: self.file_position.clone(),
+        };
+        self.next_char();
+        return token;
+    }
+
     fn read_comma(&mut self) -> Token {
         let token = Token {
             token_type: TokenType::Comma,
@@ -242,6 +491,24 @@ impl Lexer {
         return token;
     }
 
+    fn read_l_angle(&mut self) -> Token {
+        let token = Token {
+            token_type: TokenType::LeftAngle,
+            position: self.file_position.clone(),
+        };
+        self.next_char();
+        return token;
+    }
+
+    fn read_r_angle(&mut self) -> Token {
+        let token = Token {
+            token_type: TokenType::RightAngle,
+            position: self.file_position.clone(),
+        };
+        self.next_char();
+        return token;
+    }
+
     fn read_equals(&mut self) -> Token {
         let token = Token {
             token_type: TokenType::Equals,
@@ -263,6 +530,13 @@ impl Lexer {
                 token_type: TokenType::UnaryDec,
                 position: current_position,
             }
+        } else if c == b'>' {
+            self.next_char();
+
+            Token {
+                token_type: TokenType::Arrow,
+                position: current_position,
+            }
         } else {
             Token {
                 token_type: TokenType::BinaryOperation(BinaryOperator::Sub),
@@ -309,6 +583,24 @@ impl Lexer {
         return token;
     }
 
+    fn read_r_bracket(&mut self) -> Token {
+        let token = Token {
+            token_type: TokenType::RightBracket,
+            position: self.file_position.clone(),
+        };
+        self.next_char();
+        return token;
+    }
+
+    fn read_l_bracket(&mut self) -> Token {
+        let token = Token {
+            token_type: TokenType::LeftBracket,
+            position: self.file_position.clone(),
+        };
+        self.next_char();
+        return token;
+    }
+
     fn read_r_par(&mut self) -> Token {
         let token = Token {
             token_type: TokenType::RightPar,
@@ -337,42 +629,52 @@ impl Lexer {
     }
 
     fn read_colon(&mut self) -> Token {
-        let token = Token {
-            token_type: TokenType::Colon,
-            position: self.file_position.clone(),
+        let current_position = self.file_position.clone();
+
+        let c = self.next_char();
+
+        return if c == b':' {
+            self.next_char();
+
+            Token {
+                token_type: TokenType::DoubleColon,
+                position: current_position,
+            }
+        } else {
+            Token {
+                token_type: TokenType::Colon,
+                position: current_position,
+            }
         };
-        self.next_char();
-        return token;
     }
 
     fn read_character(&mut self) -> Token {
         let current_position = self.file_position.clone();
 
-        let mut c = self.next_char();
+        let c = self.next_char();
 
-        if c == b'\\' {
-            match self.next_char() {
-                b'\'' => c = b'\'',
-                b'n' => c = b'\n',
-                b't' => c = b'\t',
-                b'r' => c = b'\r',
-                b'0' => c = b'\0',
-                b'\\' => c = b'\\',
-                _ => {}
+        let character = if c == b'\\' {
+            match self.read_escape() {
+                Some(character) => character,
+                None => {
+                    panic!(
+                        "{}: Line continuation is only allowed in strings",
+                        current_position
+                    );
+                }
             }
-        }
+        } else {
+            c as char
+        };
 
         if self.next_char() != b'\'' {
-            panic!(
-                "{}:{}:{}: Expected closing character sign",
-                self.filename, current_position.line, current_position.column
-            );
+            panic!("{}: Expected closing character sign", current_position);
         }
 
         self.next_char();
 
         return Token {
-            token_type: TokenType::Character(c as char),
+            token_type: TokenType::Character(character),
             position: current_position,
         };
     }
@@ -384,41 +686,28 @@ impl Lexer {
 
         let mut c = self.next_char();
 
-        let mut escape = false;
-
-        while ((c == b'"' && escape) || (c != b'"')) && !self.reached_eof {
-            if escape {
-                match c {
-                    b'"' => buffer.push(b'\"'),
-                    b'n' => buffer.push(b'\n'),
-                    b't' => buffer.push(b'\t'),
-                    b'r' => buffer.push(b'\r'),
-                    b'0' => buffer.push(b'\0'),
-                    b'\\' => buffer.push(b'\\'),
-                    _ => {}
+        while c != b'"' && !self.reached_eof {
+            if c == b'\\' {
+                if let Some(character) = self.read_escape() {
+                    buffer.extend(character.encode_utf8(&mut [0; 4]).as_bytes());
                 }
-                escape = false;
             } else {
-                if c == b'\\' {
-                    escape = true;
-                } else {
-                    buffer.push(c);
-                }
+                buffer.push(c);
             }
 
             c = self.next_char();
         }
 
         if c != b'"' {
-            panic!(
-                "{}:{}:{}: Expected closing string sign",
-                self.filename, current_position.line, current_position.column
-            );
+            panic!("{}: Expected closing string sign", current_position);
         }
 
         self.next_char();
 
-        let label = String::from_utf8(buffer).expect("Ut8 error");
+        let label = match String::from_utf8(buffer) {
+            Ok(label) => label,
+            Err(_) => panic!("{}: String literals must be valid UTF-8.", current_position),
+        };
 
         return Token {
             token_type: TokenType::StringLiteral(label),
@@ -426,6 +715,99 @@ impl Lexer {
         };
     }
 
+    fn read_escape(&mut self) -> Option<char> {
+        let position = self.file_position.clone();
+
+        return match self.next_char() {
+            b'"' => Some('"'),
+            b'\'' => Some('\''),
+            b'n' => Some('\n'),
+            b't' => Some('\t'),
+            b'r' => Some('\r'),
+            b'0' => Some('\0'),
+            b'\\' => Some('\\'),
+            b'x' => Some(self.read_hex_escape(&position)),
+            b'u' => Some(self.read_unicode_escape(&position)),
+            b'\n' | b'\r' => {
+                // Line continuation, the indentation of the next line is skipped too
+                while self.position + 1 < self.data.len()
+                    && (self.data[self.position + 1] as char).is_whitespace()
+                {
+                    self.next_char();
+                }
+
+                None
+            }
+            c => {
+                panic!("{}: Unknown escape sequence \\{}", position, c as char);
+            }
+        };
+    }
+
+    fn read_hex_escape(&mut self, position: &Position) -> char {
+        let mut value: u32 = 0;
+
+        for _ in 0..2 {
+            let digit = match (self.next_char() as char).to_digit(16) {
+                Some(digit) => digit,
+                None => {
+                    panic!("{}: Expected two hexadecimal digits after \\x", position);
+                }
+            };
+
+            value = value * 16 + digit;
+        }
+
+        // Bigger values would not be valid utf-8 on their own
+        if value > 0x7f {
+            panic!(
+                "{}: Escape sequence \\x{:02x} out of range, must be at most \\x7f",
+                position, value
+            );
+        }
+
+        return value as u8 as char;
+    }
+
+    fn read_unicode_escape(&mut self, position: &Position) -> char {
+        if self.next_char() != b'{' {
+            panic!("{}: Expected opening brace after \\u", position);
+        }
+
+        let mut value: u32 = 0;
+        let mut digits = 0;
+
+        let mut c = self.next_char();
+
+        while c != b'}' && !self.reached_eof {
+            match (c as char).to_digit(16) {
+                Some(digit) if digits < 6 => {
+                    value = value * 16 + digit;
+                    digits += 1;
+                }
+                _ => {
+                    panic!(
+                        "{}: Expected up to six hexadecimal digits in \\u{{...}}",
+                        position
+                    );
+                }
+            }
+
+            c = self.next_char();
+        }
+
+        if c != b'}' || digits == 0 {
+            panic!("{}: Invalid unicode escape sequence", position);
+        }
+
+        return match char::from_u32(value) {
+            Some(character) => character,
+            None => {
+                panic!("{}: Invalid unicode character \\u{{{:x}}}", position, value);
+            }
+        };
+    }
+
     fn read_identifier(&mut self) -> Token {
         let current_position = self.file_position.clone();
 
@@ -438,43 +820,23 @@ impl Lexer {
             c = self.next_char();
         }
 
-        let label = String::from_utf8(buffer).expect("Ut8 error");
+        let label = match String::from_utf8(buffer) {
+            Ok(label) => label,
+            Err(_) => panic!("{}: Identifiers must be valid UTF-8.", current_position),
+        };
 
-        return match label.as_str() {
-            "return" => Token {
-                token_type: TokenType::Return,
-                position: current_position,
-            },
-            "if" => Token {
-                token_type: TokenType::If,
-                position: current_position,
-            },
-            "while" => Token {
-                token_type: TokenType::While,
-                position: current_position,
-            },
-            "for" => Token {
-                token_type: TokenType::For,
-                position: current_position,
-            },
-            "true" => Token {
-                token_type: TokenType::True,
-                position: current_position,
-            },
-            "false" => Token {
-                token_type: TokenType::False,
-                position: current_position,
-            },
-            "fn" => Token {
-                token_type: TokenType::Function,
-                position: current_position,
-            },
-            "var" => Token {
-                token_type: TokenType::Var,
+        let keyword = ENGLISH_KEYWORDS
+            .iter()
+            .chain(self.edition.get_keywords())
+            .find(|(keyword, _)| *keyword == label);
+
+        return match keyword {
+            Some((_, token_type)) => Token {
+                token_type: token_type.clone(),
                 position: current_position,
             },
-            _ => Token {
-                token_type: TokenType::Identifier(label),
+            None => Token {
+                token_type: TokenType::Identifier(Symbol::intern(&label)),
                 position: current_position,
             },
         };
@@ -483,43 +845,125 @@ impl Lexer {
     fn read_number_like(&mut self) -> Token {
         let current_position = self.file_position.clone();
 
-        let base = self.next_decimal();
+        // Kept as text until it is known whether they are a base, a float or an integer
+        let digits = self.next_decimal_digits();
 
         if self.current_char == b'#' {
             self.next_char();
-            let number = match base {
-                2 => self.next_binary(),
-                8 => self.next_octal(),
-                10 => self.next_decimal(),
-                16 => self.next_hexadecimal(),
-                _ => panic!("Unkown numerical base"),
+            let number = match digits.as_str() {
+                "2" => self.next_binary(&current_position),
+                "8" => self.next_octal(&current_position),
+                "10" => self.next_decimal(&current_position),
+                "16" => self.next_hexadecimal(&current_position),
+                _ => panic!(
+                    "{}: Unknown numerical base {}, expected 2, 8, 10 or 16.",
+                    current_position, digits
+                ),
+            };
+
+            return Token {
+                token_type: self.next_integer_suffix(number, &current_position),
+                position: current_position,
             };
+        } else if self.current_char == b'.' {
+            self.next_char();
+            let fraction = self.next_fraction();
+
+            let number: f64 = format!("{}.{}", digits, fraction)
+                .parse()
+                .expect("Unreachable");
 
             return Token {
-                token_type: TokenType::NumberLiteral(number),
+                token_type: TokenType::FloatLiteral(number),
                 position: current_position,
             };
         } else {
+            let number = digits.bytes().fold(0, |result, digit| {
+                return push_digit(result, 10, (digit - b'0') as u64, &current_position);
+            });
+
             return Token {
-                token_type: TokenType::NumberLiteral(base),
+                token_type: self.next_integer_suffix(number, &current_position),
                 position: current_position,
             };
         }
     }
 
-    fn next_binary(&mut self) -> u64 {
-        let mut result: u64 = 0;
+    // Suffixes give the literal a type, which must hold its value
+    fn next_integer_suffix(&mut self, value: u64, position: &Position) -> TokenType {
+        if self.current_char != b'u' && self.current_char != b'i' {
+            return TokenType::NumberLiteral(value);
+        }
+
+        let mut suffix = String::new();
+        let mut c = self.current_char;
+
+        while (c as char).is_alphanumeric() && !self.reached_eof {
+            suffix.push(c as char);
+            c = self.next_char();
+        }
+
+        let suffix = match INTEGER_SUFFIXES.iter().find(|other| **other == suffix) {
+            Some(suffix) => *suffix,
+            None => panic!(
+                "{}: Invalid literal suffix {}, expected one of {}.",
+                position,
+                suffix,
+                INTEGER_SUFFIXES.join(", ")
+            ),
+        };
+
+        let bits: u32 = suffix[1..].parse().expect("Unreachable");
+
+        let limit: u128 = match suffix.starts_with('i') {
+            true => 1 << (bits - 1),
+            false => 1 << bits,
+        };
+
+        if value as u128 >= limit {
+            panic!(
+                "{}: error[E0012]: Literal {} out of range for {}.",
+                position, value, suffix
+            );
+        }
+
+        return TokenType::TypedNumberLiteral(value, suffix);
+    }
+
+    fn next_fraction(&mut self) -> String {
+        let mut digits = String::new();
 
         let mut c = self.current_char;
 
         while (c as char).is_alphanumeric() && !self.reached_eof {
+            if (c as char).is_numeric() {
+                digits.push(c as char);
+            } else {
+                panic!("{}: Invalid floating point number", self.file_position);
+            }
+            c = self.next_char();
+        }
+
+        if digits.len() == 0 {
+            panic!(
+                "{}: Expected digits after the decimal point",
+                self.file_position
+            );
+        }
+
+        return digits;
+    }
+
+    fn next_binary(&mut self, start: &Position) -> u64 {
+        let mut result: u64 = 0;
+
+        let mut c = self.current_char;
+
+        while is_literal_digit(c) && !self.reached_eof {
             if c == b'0' || c == b'1' {
-                result = result * 2 + (c - b'0') as u64;
+                result = push_digit(result, 2, (c - b'0') as u64, start);
             } else {
-                panic!(
-                    "{}:{}:{}: Invalid binary number",
-                    self.filename, self.file_position.line, self.file_position.column
-                );
+                panic!("{}: Invalid binary number", self.file_position);
             }
             c = self.next_char();
         }
@@ -527,19 +971,16 @@ impl Lexer {
         return result;
     }
 
-    fn next_octal(&mut self) -> u64 {
+    fn next_octal(&mut self, start: &Position) -> u64 {
         let mut result: u64 = 0;
 
         let mut c = self.current_char;
 
-        while (c as char).is_alphanumeric() && !self.reached_eof {
+        while is_literal_digit(c) && !self.reached_eof {
             if c >= b'0' && c <= b'7' {
-                result = result * 8 + (c - b'0') as u64;
+                result = push_digit(result, 8, (c - b'0') as u64, start);
             } else {
-                panic!(
-                    "{}:{}:{}: Invalid octal number",
-                    self.filename, self.file_position.line, self.file_position.column
-                );
+                panic!("{}: Invalid octal number", self.file_position);
             }
             c = self.next_char();
         }
@@ -547,48 +988,48 @@ impl Lexer {
         return result;
     }
 
-    fn next_hexadecimal(&mut self) -> u64 {
+    fn next_hexadecimal(&mut self, start: &Position) -> u64 {
         let mut result: u64 = 0;
 
         let mut c = self.current_char;
 
-        while (c as char).is_alphanumeric() && !self.reached_eof {
+        while is_literal_digit(c) && !self.reached_eof {
             let value = match c {
                 b'0'..=b'9' => c - b'0',
                 b'A'..=b'F' => 10 + c - b'A',
                 b'a'..=b'f' => 10 + c - b'a',
                 _ => {
-                    panic!(
-                        "{}:{}:{}: Invalid hexadecimal number",
-                        self.filename, self.file_position.line, self.file_position.column
-                    );
+                    panic!("{}: Invalid hexadecimal number", self.file_position);
                 }
             };
 
-            result = result * 16 + value as u64;
+            result = push_digit(result, 16, value as u64, start);
             c = self.next_char();
         }
 
         return result;
     }
 
-    fn next_decimal(&mut self) -> u64 {
-        let mut result: u64 = 0;
+    fn next_decimal(&mut self, start: &Position) -> u64 {
+        return self.next_decimal_digits().bytes().fold(0, |result, digit| {
+            return push_digit(result, 10, (digit - b'0') as u64, start);
+        });
+    }
+
+    fn next_decimal_digits(&mut self) -> String {
+        let mut digits = String::new();
 
         let mut c = self.current_char;
 
-        while (c as char).is_alphanumeric() && !self.reached_eof {
+        while is_literal_digit(c) && !self.reached_eof {
             if (c as char).is_numeric() {
-                result = result * 10 + (c - b'0') as u64;
+                digits.push(c as char);
             } else {
-                panic!(
-                    "{}:{}:{}: Invalid decimal number",
-                    self.filename, self.file_position.line, self.file_position.column
-                );
+                panic!("{}: Invalid decimal number", self.file_position);
             }
             c = self.next_char();
         }
 
-        return result;
+        return digits;
     }
 }
@@ -0,0 +1,77 @@
+// The ez compiler as a library, the command line tool in main.rs is built on it.
+// Without the native feature nothing shells out to nasm or ld, so it also builds for
+// wasm32-unknown-unknown where the wasm feature exposes compile_to_asm to JavaScript.
+pub mod analysis;
+pub mod cfg;
+pub mod compiler;
+pub mod constants;
+pub mod coverage;
+pub mod ctfe;
+pub mod dataflow;
+pub mod diagnostics;
+pub mod errors;
+pub mod explain;
+pub mod fix;
+pub mod gas;
+pub mod grammar;
+pub mod header;
+pub mod incremental;
+pub mod interner;
+pub mod interpreter;
+pub mod lexer;
+pub mod lint;
+pub mod logging;
+pub mod manifest;
+pub mod metrics;
+pub mod parser;
+pub mod pass;
+pub mod peephole;
+pub mod profile;
+pub mod runtime;
+pub mod span;
+pub mod syntax;
+pub mod target;
+pub mod toml;
+pub mod trace;
+pub mod validator;
+pub mod visit;
+
+#[cfg(feature = "wasm")]
+mod wasm;
+
+use std::panic;
+
+use compiler::{Compiler, CompilerOptions};
+use diagnostics::{get_panic_message, Diagnostic};
+
+// Name of the program in diagnostics and in the header of the assembly
+const SOURCE_NAME: &str = "input.ez";
+
+// NASM assembly of a program without includes, or the diagnostic that stopped it. Errors are
+// caught by unwinding, so on wasm32-unknown-unknown they abort before returning, see wasm.rs.
+pub fn compile_to_asm(source: &str) -> Result<String, Vec<Diagnostic>> {
+    let options = CompilerOptions {
+        limits: parser::Limits {
+            includes: false,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let source = source.as_bytes().to_vec();
+
+    let result = panic::catch_unwind(move || {
+        let mut compiler = Compiler::from_source(SOURCE_NAME, source, options);
+        compiler.generate();
+
+        return compiler.get_assembly();
+    });
+
+    return result.map_err(|payload| match payload.downcast::<Diagnostic>() {
+        Ok(diagnostic) => vec![*diagnostic],
+        Err(payload) => match get_panic_message(&*payload) {
+            Some(message) => vec![Diagnostic { message, fix: None }],
+            None => Vec::new(),
+        },
+    });
+}
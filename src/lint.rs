@@ -0,0 +1,402 @@
+use clap::ValueEnum;
+
+use crate::{
+    lexer::TokenType,
+    parser::{Expression, Function, Program, Reference},
+    span::Position,
+    toml::{Document, Value},
+    visit::{walk_expression, Visitor},
+};
+
+// Callers of functions taking more arguments are hard to read, a struct names each value
+const MAX_ARGUMENTS: usize = 6;
+
+// Operations, calls and accesses nested inside a single expression
+const MAX_NESTING: usize = 8;
+
+// Checks of style that do not change what the program does, each one can be allowed, left
+// as a warning or denied to fail the build
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Lint {
+    /// Functions and variables in snake_case, structs in PascalCase, constants in UPPER_CASE
+    Naming,
+    /// Integer literals other than 0 and 1 in function bodies
+    MagicNumbers,
+    /// Functions taking more than 6 arguments
+    TooManyArguments,
+    /// Expressions nesting more than 8 levels of operations and calls
+    DeepNesting,
+}
+
+const LINTS: [Lint; 4] = [
+    Lint::Naming,
+    Lint::MagicNumbers,
+    Lint::TooManyArguments,
+    Lint::DeepNesting,
+];
+
+impl Lint {
+    pub fn from_name(name: &str) -> Option<Self> {
+        return LINTS.into_iter().find(|lint| lint.get_name() == name);
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        return match self {
+            Self::Naming => "naming",
+            Self::MagicNumbers => "magic-numbers",
+            Self::TooManyArguments => "too-many-arguments",
+            Self::DeepNesting => "deep-nesting",
+        };
+    }
+
+    // Sizes and offsets are written as literals all the time, so those are only checked
+    // when asked for
+    fn get_default_level(&self) -> LintLevel {
+        return match self {
+            Self::MagicNumbers => LintLevel::Allow,
+            _ => LintLevel::Warn,
+        };
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LintLevel {
+    Allow,
+    Warn,
+    Deny,
+}
+
+impl LintLevel {
+    pub fn from_name(name: &str) -> Option<Self> {
+        return match name {
+            "allow" => Some(Self::Allow),
+            "warn" => Some(Self::Warn),
+            "deny" => Some(Self::Deny),
+            _ => None,
+        };
+    }
+
+    fn get_severity(&self) -> &'static str {
+        return match self {
+            Self::Deny => "error",
+            _ => "warning",
+        };
+    }
+}
+
+// Levels set by ez.toml and the command line, the other lints keep their default
+#[derive(Debug, Clone, Default)]
+pub struct LintConfig {
+    levels: Vec<(Lint, LintLevel)>,
+}
+
+impl LintConfig {
+    // The last level set wins, so flags set after reading ez.toml override it
+    pub fn set_level(&mut self, lint: Lint, level: LintLevel) {
+        self.levels.retain(|(other, _)| *other != lint);
+        self.levels.push((lint, level));
+    }
+
+    pub fn get_level(&self, lint: Lint) -> LintLevel {
+        return self
+            .levels
+            .iter()
+            .find(|(other, _)| *other == lint)
+            .map_or(lint.get_default_level(), |(_, level)| *level);
+    }
+
+    // The [lints] table, like `magic-numbers = "deny"`
+    pub fn from_document(filename: &str, document: &Document) -> Result<Self, String> {
+        let mut config = Self::default();
+
+        let table = match document.get_table("lints") {
+            Some(table) => table,
+            None => return Ok(config),
+        };
+
+        for ((name, value), line) in table.entries.iter().zip(table.lines.iter()) {
+            let lint = match Lint::from_name(name) {
+                Some(lint) => lint,
+                None => {
+                    return Err(format!(
+                        "{}:{}: Unknown lint {}, the lints are {}.",
+                        filename,
+                        line,
+                        name,
+                        LINTS.map(|lint| lint.get_name()).join(", ")
+                    ))
+                }
+            };
+
+            let level = match value {
+                Value::String(level) => LintLevel::from_name(level),
+                _ => None,
+            };
+
+            match level {
+                Some(level) => config.set_level(lint, level),
+                None => {
+                    return Err(format!(
+                        "{}:{}: Lint {} must be set to \"allow\", \"warn\" or \"deny\".",
+                        filename, line, name
+                    ))
+                }
+            }
+        }
+
+        return Ok(config);
+    }
+}
+
+// Messages of every lint that is not allowed, with the level it was found at. Instances of a
+// generic function share its tokens, so each message is only given once.
+pub fn run_lints(program: &Program, config: &LintConfig) -> Vec<(LintLevel, String)> {
+    let mut linter = Linter {
+        config,
+        messages: Vec::new(),
+    };
+
+    linter.check_declarations(program);
+
+    for function in program.functions.iter() {
+        linter.check_function(program, function);
+    }
+
+    return linter.messages;
+}
+
+struct Linter<'a> {
+    config: &'a LintConfig,
+    messages: Vec<(LintLevel, String)>,
+}
+
+impl Linter<'_> {
+    fn report(&mut self, lint: Lint, position: &Position, message: String) {
+        let level = self.config.get_level(lint);
+
+        if level == LintLevel::Allow {
+            return;
+        }
+
+        let message = format!(
+            "{}: {}[{}]: {}",
+            position,
+            level.get_severity(),
+            lint.get_name(),
+            message
+        );
+
+        if !self.messages.iter().any(|(_, other)| *other == message) {
+            self.messages.push((level, message));
+        }
+    }
+
+    fn check_declarations(&mut self, program: &Program) {
+        for (_, constant) in program.constants.iter() {
+            if constant.name.chars().any(char::is_lowercase) {
+                self.report(
+                    Lint::Naming,
+                    &constant.position,
+                    format!(
+                        "Constant {} is not in UPPER_CASE, rename it to {}.",
+                        constant.name,
+                        to_snake_case(&constant.name).to_uppercase()
+                    ),
+                );
+            }
+        }
+
+        for (_, generic) in program.generics.iter() {
+            self.check_snake_case(&generic.position, "Function", &generic.name);
+        }
+
+        // Structs only keep their name, the position is the one of their declaration
+        for pair in program.tokens.windows(2) {
+            let name = match (&pair[0].token_type, &pair[1].token_type) {
+                (TokenType::Struct, TokenType::Identifier(name)) => name.get_name(),
+                _ => continue,
+            };
+
+            let pascal_case =
+                name.chars().next().map_or(true, char::is_uppercase) && !name.contains('_');
+
+            if !pascal_case {
+                self.report(
+                    Lint::Naming,
+                    &pair[1].position,
+                    format!(
+                        "Struct {} is not in PascalCase, rename it to {}.",
+                        name,
+                        to_pascal_case(&name)
+                    ),
+                );
+            }
+        }
+    }
+
+    fn check_function(&mut self, program: &Program, function: &Function) {
+        // Extern functions are named and shaped by the code implementing them
+        if function.external {
+            return;
+        }
+
+        self.check_snake_case(&function.position, "Function", &function.name);
+
+        if function.arguments.len() > MAX_ARGUMENTS {
+            self.report(
+                Lint::TooManyArguments,
+                &function.position,
+                format!(
+                    "Function {} takes {} arguments, more than {}. Pass them in a struct.",
+                    function.name,
+                    function.arguments.len(),
+                    MAX_ARGUMENTS
+                ),
+            );
+        }
+
+        // Locals are declared where they are first written
+        for (index, local) in function.locals.locals.iter().enumerate() {
+            let declaration = function
+                .references
+                .iter()
+                .find(|reference| reference.value == Reference::Local(index));
+
+            if let Some(declaration) = declaration {
+                self.check_snake_case(&declaration.position, "Variable", &local.label.get_name());
+            }
+        }
+
+        let body = match &function.body {
+            Some(body) => body,
+            None => return,
+        };
+
+        for (statement, span) in body.statements.iter().zip(body.spans.iter()) {
+            let mut nesting = NestingVisitor {
+                depth: 0,
+                deepest: 0,
+            };
+            nesting.visit_statement(statement);
+
+            if nesting.deepest > MAX_NESTING {
+                self.report(
+                    Lint::DeepNesting,
+                    &span.position,
+                    format!(
+                        "Expression nests {} levels, more than {}. Keep parts of it in variables.",
+                        nesting.deepest, MAX_NESTING
+                    ),
+                );
+            }
+
+            // Literals become indistinguishable from constants once parsed, so the tokens
+            // are checked. Array lengths in types like `[u8; 16]` follow a semicolon.
+            let tokens = match program.tokens.get(span.tokens.clone()) {
+                Some(tokens) => tokens,
+                None => continue,
+            };
+
+            for (index, token) in tokens.iter().enumerate() {
+                let value = match token.token_type {
+                    TokenType::NumberLiteral(value) | TokenType::TypedNumberLiteral(value, _)
+                        if value > 1 =>
+                    {
+                        value
+                    }
+                    _ => continue,
+                };
+
+                if index > 0 {
+                    if let TokenType::Semicolon = tokens[index - 1].token_type {
+                        continue;
+                    }
+                }
+
+                self.report(
+                    Lint::MagicNumbers,
+                    &token.position,
+                    format!("Magic number {}, name it with a constant.", value),
+                );
+            }
+        }
+    }
+
+    fn check_snake_case(&mut self, position: &Position, kind: &str, name: &str) {
+        if name.chars().any(char::is_uppercase) {
+            self.report(
+                Lint::Naming,
+                position,
+                format!(
+                    "{} {} is not in snake_case, rename it to {}.",
+                    kind,
+                    name,
+                    to_snake_case(name)
+                ),
+            );
+        }
+    }
+}
+
+// Deepest chain of expressions below the statement
+struct NestingVisitor {
+    depth: usize,
+    deepest: usize,
+}
+
+impl Visitor for NestingVisitor {
+    fn visit_expression(&mut self, expression: &Expression) {
+        self.depth += 1;
+        self.deepest = self.deepest.max(self.depth);
+
+        match expression {
+            // The left operand continues a chain like `a + b + c` instead of nesting in it
+            Expression::Binary(binary_expression) => {
+                self.depth -= 1;
+                self.visit_expression(&binary_expression.left);
+                self.depth += 1;
+
+                self.visit_expression(&binary_expression.right);
+            }
+            expression => walk_expression(self, expression),
+        }
+
+        self.depth -= 1;
+    }
+}
+
+// Words start at capitals following a lowercase letter or a digit, like `parseHTTPHeader`
+// becoming parse_httpheader
+fn to_snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    let mut previous: Option<char> = None;
+
+    for character in name.chars() {
+        if character.is_uppercase()
+            && previous.map_or(false, |previous| {
+                previous.is_lowercase() || previous.is_ascii_digit()
+            })
+        {
+            snake.push('_');
+        }
+
+        snake.extend(character.to_lowercase());
+        previous = Some(character);
+    }
+
+    return snake;
+}
+
+fn to_pascal_case(name: &str) -> String {
+    return name
+        .split('_')
+        .map(|word| {
+            let mut characters = word.chars();
+
+            return match characters.next() {
+                Some(first) => first.to_uppercase().chain(characters).collect(),
+                None => String::new(),
+            };
+        })
+        .collect();
+}
@@ -0,0 +1,87 @@
+use std::{
+    cell::Cell,
+    fmt,
+    sync::atomic::{AtomicU8, Ordering},
+    time::{Duration, Instant},
+};
+
+// Messages about what the compiler itself is doing, for debugging it rather than the
+// programs it compiles. Written to stderr, nothing is shown unless -v or -vv is given.
+static VERBOSITY: AtomicU8 = AtomicU8::new(0);
+
+thread_local! {
+    // Spans entered on this thread, messages are indented under them
+    static DEPTH: Cell<usize> = Cell::new(0);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum Level {
+    // Phases and the tools run, shown with -v
+    Info = 1,
+    // Decisions inside a phase, shown with -vv
+    Debug = 2,
+}
+
+impl Level {
+    fn get_name(&self) -> &'static str {
+        return match self {
+            Level::Info => "info",
+            Level::Debug => "debug",
+        };
+    }
+}
+
+pub fn set_verbosity(verbosity: u8) {
+    VERBOSITY.store(verbosity, Ordering::Relaxed);
+}
+
+pub fn is_enabled(level: Level) -> bool {
+    return VERBOSITY.load(Ordering::Relaxed) >= level as u8;
+}
+
+pub fn log(level: Level, message: fmt::Arguments) {
+    if !is_enabled(level) {
+        return;
+    }
+
+    let indentation = "  ".repeat(DEPTH.with(|depth| depth.get()));
+
+    eprintln!("[{}] {}{}", level.get_name(), indentation, message);
+}
+
+// Phase of the compilation, logged when entered and again with its duration when dropped
+pub struct Span {
+    name: &'static str,
+    start: Instant,
+}
+
+impl Span {
+    pub fn enter(name: &'static str) -> Self {
+        log(Level::Info, format_args!("{}", name));
+        DEPTH.with(|depth| depth.set(depth.get() + 1));
+
+        return Self {
+            name,
+            start: Instant::now(),
+        };
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        return self.start.elapsed();
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        DEPTH.with(|depth| depth.set(depth.get().saturating_sub(1)));
+
+        log(
+            Level::Info,
+            format_args!(
+                "{} done in {:.3} ms",
+                self.name,
+                self.elapsed().as_secs_f64() * 1000.0
+            ),
+        );
+    }
+}
@@ -1,11 +1,1436 @@
-mod compiler;
-mod lexer;
-mod parser;
+mod debugger;
+mod server;
 
-use compiler::Compiler;
+use std::{
+    any::Any,
+    collections::hash_map::RandomState,
+    env::{self, current_dir},
+    fs::{create_dir_all, read_to_string, remove_dir_all, write, DirBuilder},
+    hash::{BuildHasher, Hasher},
+    io::{self, ErrorKind},
+    os::unix::{fs::DirBuilderExt, process::ExitStatusExt},
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    process::{self, ExitCode},
+    time::Duration,
+};
 
-fn main() {
-    let filename = "examples/square.ez";
-    let mut program = Compiler::from_file(filename);
-    program.compile();
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use debugger::Debugger;
+use ezlang::{
+    analysis,
+    cfg::CfgFormat,
+    compiler::{Compiler, CompilerOptions},
+    coverage,
+    diagnostics::{
+        get_offset, get_panic_message, is_internal_error, write_internal_error, Diagnostic, Fix,
+    },
+    errors::{find_error_code, get_error_code, ERROR_CODES},
+    fix,
+    gas::AsmSyntax,
+    grammar,
+    interpreter::{Interpreter, InterpreterOptions},
+    lexer::{Edition, Lexer},
+    lint::{Lint, LintConfig, LintLevel},
+    logging,
+    manifest::{self, Binary, Manifest, Package, BUILD_DIRECTORY, MANIFEST_NAME},
+    parser::{Limits, Parser as SourceParser, GRAMMAR},
+    profile,
+    span::Position,
+    syntax::SyntaxTree,
+    target::Target,
+    validator::validate_program,
+};
+use server::ServerOptions;
+
+// Compile errors, assembler or linker failures, and invalid command lines
+const EXIT_COMPILE_ERROR: u8 = 1;
+const EXIT_TOOL_ERROR: u8 = 2;
+const EXIT_USAGE_ERROR: u8 = 64;
+// Bugs of the compiler, like states the earlier phases rule out
+const EXIT_INTERNAL_ERROR: u8 = 70;
+// Interpreted programs failing at runtime, like the compiled bounds checks
+const EXIT_RUNTIME_ERROR: u8 = 101;
+
+const LONG_VERSION: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    "\ntargets: x86_64-linux (nasm elf64, ld)"
+);
+
+/// Compiler for the ez language, producing x86-64 Linux executables
+#[derive(Parser)]
+#[command(
+    version,
+    long_version = LONG_VERSION,
+    about,
+    args_conflicts_with_subcommands = true,
+    after_help = "Exit codes: 0 success, 1 compile error, 2 assembler or linker failure, 64 usage error, 70 internal compiler error, 101 runtime error in `run`."
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Log what the compiler does to stderr, -vv also logs the decisions inside each phase
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    verbose: u8,
+
+    #[command(flatten)]
+    build: BuildArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Compile, assemble and link a source file, the default without a subcommand. A source
+    /// file alone is compiled to a temporary directory and run like a script.
+    Build(BuildArgs),
+    /// Interpret a source file without assembling it, exiting with the value main returns
+    Run(RunArgs),
+    /// Create a project directory with an ez.toml and a main printing a greeting
+    New(NewArgs),
+    /// Compile a source file and step through the executable by source line
+    Debug(DebugArgs),
+    /// Work with the counts written by programs built with --coverage
+    #[command(subcommand)]
+    Cov(CovCommand),
+    /// Work with the counts written by programs built with --profile
+    #[command(subcommand)]
+    Prof(ProfCommand),
+    /// Print the grammar of the language as accepted by the parser
+    Grammar(GrammarArgs),
+    /// Describe an error code with an example and how to fix it, or list all codes
+    Explain(ExplainArgs),
+    /// Apply the fixes suggested by diagnostics to a source file
+    Fix(FixArgs),
+    /// Describe the symbol at a position of a source file
+    Hover(HoverArgs),
+    /// Print the class of every highlighted token of a source file
+    Tokens(TokensArgs),
+    /// Rename the variable or function at a position everywhere it is used
+    Rename(RenameArgs),
+    /// Compile programs sent over HTTP, answering with their diagnostics and assembly
+    Serve(ServeArgs),
+}
+
+#[derive(Subcommand)]
+enum ProfCommand {
+    /// Print the functions ranked by the cycles spent in them
+    Report(ReportArgs),
+}
+
+#[derive(Subcommand)]
+enum CovCommand {
+    /// Print the source annotated with how often each line ran
+    Report(ReportArgs),
+}
+
+#[derive(Args)]
+struct BuildArgs {
+    /// Source file to compile, the entry of the project in the current directory without one
+    filename: Option<String>,
+
+    /// Check array indices against the array length at runtime
+    #[arg(long)]
+    checked_bounds: bool,
+
+    /// Keywords accepted in files without an `#edition` pragma
+    #[arg(long, value_enum, default_value_t)]
+    edition: Edition,
+
+    /// Embed the compiler version, source hash and timestamp in the output
+    #[arg(long)]
+    metadata: bool,
+
+    /// Produce byte-identical output for identical input, leaving out the timestamp
+    #[arg(long)]
+    reproducible: bool,
+
+    /// Print the ez call stack when the program aborts or crashes
+    #[arg(long)]
+    backtraces: bool,
+
+    /// Count statement executions and write them to <stem>.cov when main returns
+    #[arg(long, conflicts_with = "shared")]
+    coverage: bool,
+
+    /// Count calls and cycles per function and write them to <stem>.prof when main returns
+    #[arg(long, conflicts_with = "shared")]
+    profile: bool,
+
+    /// Link a position independent shared library instead of an executable
+    #[arg(long)]
+    shared: bool,
+
+    /// Archive a static library with a C header instead of linking an executable
+    #[arg(long, conflicts_with_all = ["shared", "coverage", "profile", "linker_script"])]
+    static_lib: bool,
+
+    /// Object files or libraries providing extern functions, passed to the linker
+    #[arg(long)]
+    link: Vec<String>,
+
+    /// Linker script placing the sections, like the ones of #[section(".boot")] functions
+    #[arg(long, value_name = "FILE")]
+    linker_script: Option<String>,
+
+    /// Extra outputs to write next to the executable
+    #[arg(long, value_enum)]
+    emit: Vec<Emit>,
+
+    /// Only assemble the output, reporting code the assembler rejects as a compiler bug
+    #[arg(long, conflicts_with = "static_lib")]
+    check_asm: bool,
+
+    /// Print the tokens, syntax tree and instructions of every statement side by side
+    #[arg(long, conflicts_with = "json")]
+    explain: bool,
+
+    /// Print the instructions, estimated cycles, stack usage and calls of every function
+    #[arg(long, conflicts_with = "json")]
+    analyze: bool,
+
+    /// Print the worst case stack of every function through its calls, recursion and runtime
+    /// sized allocations make it unbounded
+    #[arg(long, conflicts_with = "json")]
+    stack_analysis: bool,
+
+    /// Print every token of the file as the lexer reads it and stop, includes are not followed
+    #[arg(long, conflicts_with_all = ["json", "explain"])]
+    dump_tokens: bool,
+
+    /// Print the basic blocks of every function and the edges between them
+    #[arg(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "text",
+        conflicts_with = "json"
+    )]
+    dump_cfg: Option<CfgFormat>,
+
+    /// Optimization level, 1 also leaves out the frame of leaf functions without locals.
+    /// Defaults to the opt-level of the project, or 0
+    #[arg(short = 'O', long, value_parser = clap::value_parser!(u8).range(0..=1))]
+    opt_level: Option<u8>,
+
+    /// Assembler to write the output for and to assemble it with
+    #[arg(long, value_enum, default_value_t)]
+    asm_syntax: AsmSyntax,
+
+    /// System the program runs on, baremetal-x86_64 also writes <stem>.bin to boot.
+    /// Defaults to the target of the project, or linux-x86_64
+    #[arg(long, value_enum)]
+    target: Option<Target>,
+
+    #[command(flatten)]
+    limits: LimitArgs,
+
+    /// Binary of the project to build, all of them without one
+    #[arg(long, value_name = "NAME", conflicts_with = "filename")]
+    bin: Option<String>,
+
+    /// Lint to leave unchecked, overriding the [lints] table of ez.toml
+    #[arg(long, value_enum, value_name = "LINT")]
+    allow: Vec<Lint>,
+
+    /// Lint failing the build, overriding the [lints] table of ez.toml
+    #[arg(long, value_enum, value_name = "LINT")]
+    deny: Vec<Lint>,
+
+    /// Print a JSON summary with the status, artifacts and phase timings to stdout
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Args)]
+struct LimitArgs {
+    /// Levels of parentheses and calls a single expression may nest
+    #[arg(long, value_name = "LEVELS", default_value_t = Limits::default().expression_depth)]
+    max_expression_depth: usize,
+
+    /// Operations a single expression may chain, counting the ones inside parentheses and calls
+    #[arg(long, value_name = "COUNT", default_value_t = Limits::default().expression_length)]
+    max_expression_length: usize,
+
+    /// Pointers and arrays a single type may nest
+    #[arg(long, value_name = "LEVELS", default_value_t = Limits::default().type_depth)]
+    max_type_depth: usize,
+
+    /// Tokens the program may have, counting every included file
+    #[arg(long, value_name = "COUNT", default_value_t = Limits::default().tokens)]
+    max_tokens: usize,
+
+    /// Functions the program may declare
+    #[arg(long, value_name = "COUNT", default_value_t = Limits::default().functions)]
+    max_functions: usize,
+
+    /// Variables a single function may declare, arguments included
+    #[arg(long, value_name = "COUNT", default_value_t = Limits::default().locals)]
+    max_locals: usize,
+
+    /// Bytes all string literals of the program may take together
+    #[arg(long, value_name = "BYTES", default_value_t = Limits::default().string_bytes)]
+    max_string_bytes: usize,
+
+    /// Statements each call in a constant may run at compile time
+    #[arg(long, value_name = "COUNT", default_value_t = Limits::default().const_steps)]
+    max_const_steps: u64,
+}
+
+#[derive(Args)]
+struct ServeArgs {
+    /// Address to listen on for HTTP requests
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    address: String,
+
+    /// Check array indices against the array length at runtime
+    #[arg(long)]
+    checked_bounds: bool,
+
+    /// Keywords accepted in programs without an `#edition` pragma
+    #[arg(long, value_enum, default_value_t)]
+    edition: Edition,
+
+    /// Optimization level, 1 also leaves out the frame of leaf functions without locals
+    #[arg(short = 'O', long, default_value_t = 0, value_parser = clap::value_parser!(u8).range(0..=1))]
+    opt_level: u8,
+
+    /// Milliseconds to read a whole request, and then to compile it, before answering with a timeout
+    #[arg(long, value_name = "MILLISECONDS", default_value_t = 5000)]
+    timeout: u64,
+
+    /// Bytes of source a single request may send
+    #[arg(long, value_name = "BYTES", default_value_t = 1 << 20)]
+    max_request_bytes: usize,
+
+    /// Connections handled at the same time, more are answered with 503 right away
+    #[arg(long, value_name = "COUNT", default_value_t = 64, value_parser = clap::value_parser!(u64).range(1..))]
+    max_connections: u64,
+
+    /// Compilations running at the same time, including the ones past their timeout
+    #[arg(long, value_name = "COUNT", default_value_t = 8, value_parser = clap::value_parser!(u64).range(1..))]
+    max_compilations: u64,
+
+    /// Let programs include files of the machine running the server
+    #[arg(long)]
+    allow_includes: bool,
+
+    #[command(flatten)]
+    limits: LimitArgs,
+}
+
+#[derive(Args)]
+struct NewArgs {
+    /// Name of the project and of its directory
+    name: String,
+}
+
+#[derive(Args)]
+struct RunArgs {
+    /// Source file to interpret, the entry of the project in the current directory without one
+    filename: Option<String>,
+
+    /// Binary of the project to interpret, needed when it has several
+    #[arg(long, value_name = "NAME", conflicts_with = "filename")]
+    bin: Option<String>,
+
+    /// Keywords accepted in files without an `#edition` pragma
+    #[arg(long, value_enum, default_value_t)]
+    edition: Edition,
+
+    /// Statements executed before the program is stopped
+    #[arg(long, default_value_t = 10_000_000)]
+    max_steps: u64,
+
+    /// Nested function calls before the program is stopped
+    #[arg(long, default_value_t = 1024)]
+    max_depth: usize,
+
+    /// Bytes of string literals and stack frames before the program is stopped
+    #[arg(long, default_value_t = 64 * 1024 * 1024)]
+    max_memory: usize,
+
+    /// Print every executed statement with the values it changed to stderr
+    #[arg(long)]
+    trace: bool,
+}
+
+#[derive(Args)]
+struct DebugArgs {
+    /// Source file to compile and debug
+    filename: String,
+
+    /// Check array indices against the array length at runtime
+    #[arg(long)]
+    checked_bounds: bool,
+
+    /// Keywords accepted in files without an `#edition` pragma
+    #[arg(long, value_enum, default_value_t)]
+    edition: Edition,
+
+    /// Assembler to write the output for and to assemble it with
+    #[arg(long, value_enum, default_value_t)]
+    asm_syntax: AsmSyntax,
+}
+
+#[derive(Args)]
+struct ReportArgs {
+    /// Source file the program was built from
+    filename: String,
+}
+
+#[derive(Args)]
+struct GrammarArgs {
+    /// Notation to print the grammar in
+    #[arg(long, value_enum, default_value_t = GrammarFormat::Ebnf)]
+    format: GrammarFormat,
+}
+
+#[derive(Args)]
+struct FixArgs {
+    /// Source file to fix in place
+    filename: String,
+
+    /// Print the fixes as a unified diff instead of writing them
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Keywords accepted in files without an `#edition` pragma
+    #[arg(long, value_enum, default_value_t)]
+    edition: Edition,
+}
+
+#[derive(Args)]
+struct HoverArgs {
+    /// Position as FILE:LINE:COLUMN, like in diagnostics
+    position: String,
+
+    /// Keywords accepted in files without an `#edition` pragma
+    #[arg(long, value_enum, default_value_t)]
+    edition: Edition,
+}
+
+#[derive(Args)]
+struct TokensArgs {
+    /// Source file to classify
+    filename: String,
+
+    /// Keywords accepted in files without an `#edition` pragma
+    #[arg(long, value_enum, default_value_t)]
+    edition: Edition,
+}
+
+#[derive(Args)]
+struct RenameArgs {
+    /// Position as FILE:LINE:COLUMN of the declaration or of any use
+    position: String,
+
+    /// Name replacing the current one
+    new_name: String,
+
+    /// Print the edits as a unified diff instead of writing them
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Keywords accepted in files without an `#edition` pragma
+    #[arg(long, value_enum, default_value_t)]
+    edition: Edition,
+}
+
+#[derive(Args)]
+struct ExplainArgs {
+    /// Error code like E0001, as shown in the diagnostic
+    code: Option<String>,
+}
+
+#[derive(Clone, PartialEq, ValueEnum)]
+enum GrammarFormat {
+    /// ISO 14977 extended Backus-Naur form
+    Ebnf,
+    /// HTML page with a railroad diagram per rule
+    Html,
+}
+
+#[derive(Clone, PartialEq, ValueEnum)]
+enum Emit {
+    /// C header declaring the public functions, which also get C callable wrappers
+    Header,
+    /// Map from statement labels in the executable back to source positions
+    SourceMap,
+    /// Parsed program as JSON, needs ezlang built with the serde feature
+    AstJson,
+    /// Assembly listing with the source line of each statement and the bytes it encodes to
+    Listing,
+}
+
+fn main() -> ExitCode {
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(error) => {
+            let _ = error.print();
+
+            // Help and version requests are not errors
+            return match error.use_stderr() {
+                true => ExitCode::from(EXIT_USAGE_ERROR),
+                false => ExitCode::SUCCESS,
+            };
+        }
+    };
+
+    logging::set_verbosity(cli.verbose);
+
+    // Diagnostics are raised as panics, only their message is shown
+    panic::set_hook(Box::new(|info| match get_panic_message(info.payload()) {
+        Some(message) if is_internal_error(&message) => {
+            eprintln!("{}", write_internal_error(&message));
+        }
+        Some(message) => {
+            eprintln!("{}", message);
+
+            if let Some(Diagnostic { fix: Some(fix), .. }) =
+                info.payload().downcast_ref::<Diagnostic>()
+            {
+                eprintln!(
+                    "help: run `ezlang fix {}` to apply the suggested fix.",
+                    fix.position.file
+                );
+            }
+
+            // Lints name themselves in place of a code, they have no explanation
+            if let Some(code) =
+                get_error_code(&message).filter(|code| find_error_code(code).is_some())
+            {
+                eprintln!(
+                    "For more information about this error, try `ezlang explain {}`.",
+                    code
+                );
+            }
+        }
+        None => eprintln!("{}", write_internal_error(&info.to_string())),
+    }));
+
+    return match cli.command {
+        Some(Command::Build(args)) => build(args),
+        Some(Command::Run(args)) => run(args),
+        Some(Command::Debug(args)) => debug(args),
+        Some(Command::Cov(CovCommand::Report(args))) => {
+            print_report(coverage::write_report(&args.filename))
+        }
+        Some(Command::Prof(ProfCommand::Report(args))) => {
+            print_report(profile::write_report(&args.filename))
+        }
+        Some(Command::Grammar(args)) => {
+            match args.format {
+                GrammarFormat::Ebnf => print!("{}", grammar::write_ebnf(GRAMMAR)),
+                GrammarFormat::Html => print!("{}", grammar::write_railroad(GRAMMAR)),
+            }
+
+            ExitCode::SUCCESS
+        }
+        Some(Command::Explain(args)) => explain(args),
+        Some(Command::Fix(args)) => fix(args),
+        Some(Command::Hover(args)) => hover(args),
+        Some(Command::Tokens(args)) => tokens(args),
+        Some(Command::Rename(args)) => rename(args),
+        Some(Command::Serve(args)) => serve(args),
+        Some(Command::New(args)) => new(args),
+        // Only a source file runs it like a script, anything else builds
+        None => match (&cli.build.filename, env::args_os().count()) {
+            (Some(filename), 2) => script(filename),
+            _ => build(cli.build),
+        },
+    };
+}
+
+fn new(args: NewArgs) -> ExitCode {
+    if let Err(error) = manifest::create_project(&args.name) {
+        eprintln!("error: {}", error);
+        return ExitCode::from(EXIT_TOOL_ERROR);
+    }
+
+    eprintln!(
+        "Created project {}, build it with `cd {} && ezlang build`.",
+        args.name, args.name
+    );
+
+    return ExitCode::SUCCESS;
+}
+
+fn build(args: BuildArgs) -> ExitCode {
+    let project = match get_project(args.filename.as_deref(), args.bin.as_deref()) {
+        Ok(project) => project,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            return ExitCode::from(EXIT_USAGE_ERROR);
+        }
+    };
+
+    // Flags win over the package, which wins over the defaults
+    let package = project.package.as_ref();
+    let target = args
+        .target
+        .or(package.map(|package| package.target))
+        .unwrap_or_default();
+    let opt_level = args
+        .opt_level
+        .or(package.map(|package| package.opt_level))
+        .unwrap_or(0);
+
+    // Their runtime support writes and exits with system calls
+    let hosted = [
+        (args.shared, "--shared"),
+        (args.checked_bounds, "--checked-bounds"),
+        (args.backtraces, "--backtraces"),
+        (args.coverage, "--coverage"),
+        (args.profile, "--profile"),
+    ];
+
+    if let (false, Some((_, flag))) = (target.has_os(), hosted.iter().find(|(enabled, _)| *enabled))
+    {
+        eprintln!(
+            "error: {} needs an operating system, it can not be used with --target {}.",
+            flag,
+            target.get_name()
+        );
+        return ExitCode::from(EXIT_USAGE_ERROR);
+    }
+
+    if args.dump_tokens {
+        for (filename, _) in project.sources.iter() {
+            let code = dump_tokens(filename, args.edition);
+
+            if code != ExitCode::SUCCESS {
+                return code;
+            }
+        }
+
+        return ExitCode::SUCCESS;
+    }
+
+    if cfg!(not(feature = "serde")) && args.emit.contains(&Emit::AstJson) {
+        eprintln!("error: --emit ast-json needs ezlang built with the serde feature.");
+        return ExitCode::from(EXIT_USAGE_ERROR);
+    }
+
+    let mut lints = project.lints;
+
+    for lint in args.allow.iter() {
+        lints.set_level(*lint, LintLevel::Allow);
+    }
+
+    for lint in args.deny.iter() {
+        lints.set_level(*lint, LintLevel::Deny);
+    }
+
+    let mut options = CompilerOptions {
+        checked_bounds: args.checked_bounds,
+        metadata: args.metadata,
+        reproducible: args.reproducible,
+        header: args.static_lib || args.emit.contains(&Emit::Header),
+        shared: args.shared,
+        library: args.static_lib,
+        archive: args.static_lib,
+        link: args.link,
+        linker_script: args.linker_script,
+        source_map: args.emit.contains(&Emit::SourceMap),
+        ast_json: args.emit.contains(&Emit::AstJson),
+        listing: args.emit.contains(&Emit::Listing),
+        check_assembly: args.check_asm,
+        backtraces: args.backtraces,
+        coverage: args.coverage,
+        profile: args.profile,
+        explain: args.explain,
+        analyze: args.analyze,
+        stack_analysis: args.stack_analysis,
+        dump_cfg: args.dump_cfg,
+        opt_level,
+        edition: args.edition,
+        limits: get_limits(&args.limits),
+        lints,
+        asm_syntax: args.asm_syntax,
+        target,
+        output: None,
+    };
+
+    // Dependencies come before the libraries depending on them, each one is an object the
+    // binaries link
+    for (filename, output) in project.libraries {
+        let directory = Path::new(&output).parent().unwrap_or(Path::new("."));
+
+        if let Err(error) = create_dir_all(directory) {
+            eprintln!("error: Can not create {}: {}", directory.display(), error);
+            return ExitCode::from(EXIT_TOOL_ERROR);
+        }
+
+        let object = format!("{}.o", output);
+
+        let library = CompilerOptions {
+            library: true,
+            output: Some(output),
+            ..options.clone()
+        };
+
+        let code = compile(filename, library, args.json);
+
+        if code != 0 {
+            return ExitCode::from(code);
+        }
+
+        options.link.push(object);
+    }
+
+    // Binaries are built in the order the manifest declares them, up to the first failure
+    for (filename, output) in project.sources {
+        if let Some(output) = &output {
+            let directory = Path::new(output).parent().unwrap_or(Path::new("."));
+
+            if let Err(error) = create_dir_all(directory) {
+                eprintln!("error: Can not create {}: {}", directory.display(), error);
+                return ExitCode::from(EXIT_TOOL_ERROR);
+            }
+        }
+
+        let options = CompilerOptions {
+            output,
+            ..options.clone()
+        };
+
+        let code = compile(filename, options, args.json);
+
+        if code != 0 {
+            return ExitCode::from(code);
+        }
+    }
+
+    return ExitCode::SUCCESS;
+}
+
+// Compile errors and bugs of the compiler both unwind, only the message tells them apart
+fn get_panic_code(payload: &(dyn Any + Send)) -> u8 {
+    return match get_panic_message(payload) {
+        Some(message) if !is_internal_error(&message) => EXIT_COMPILE_ERROR,
+        _ => EXIT_INTERNAL_ERROR,
+    };
+}
+
+// Writes the summary of the build with --json, returns the exit code
+fn compile(filename: String, options: CompilerOptions, json: bool) -> u8 {
+    let result = panic::catch_unwind(move || {
+        let mut compiler = Compiler::from_file(&filename, options);
+        let result = compiler.compile();
+
+        let warnings = compiler.warnings.len();
+
+        return (result, compiler.artifacts, compiler.timings, warnings);
+    });
+
+    let (code, status, error, artifacts, timings, warnings) = match result {
+        Ok((Ok(()), artifacts, timings, warnings)) => {
+            (0, "success", None, artifacts, timings, warnings)
+        }
+        Ok((Err(error), artifacts, timings, warnings)) => {
+            eprintln!("error: {}", error);
+            (
+                EXIT_TOOL_ERROR,
+                "tool_error",
+                Some(error),
+                artifacts,
+                timings,
+                warnings,
+            )
+        }
+        Err(payload) => {
+            let code = get_panic_code(&*payload);
+
+            let status = match code {
+                EXIT_COMPILE_ERROR => "compile_error",
+                _ => "internal_error",
+            };
+
+            (
+                code,
+                status,
+                get_panic_message(&*payload),
+                Vec::new(),
+                Vec::new(),
+                0,
+            )
+        }
+    };
+
+    if json {
+        println!(
+            "{}",
+            write_summary(status, &error, &artifacts, warnings, &timings)
+        );
+    }
+
+    return code;
+}
+
+fn serve(args: ServeArgs) -> ExitCode {
+    let options = ServerOptions {
+        compiler: CompilerOptions {
+            checked_bounds: args.checked_bounds,
+            opt_level: args.opt_level,
+            edition: args.edition,
+            limits: Limits {
+                includes: args.allow_includes,
+                ..get_limits(&args.limits)
+            },
+            ..Default::default()
+        },
+        timeout: Duration::from_millis(args.timeout),
+        max_request_bytes: args.max_request_bytes,
+        max_connections: args.max_connections as usize,
+        max_compilations: args.max_compilations as usize,
+    };
+
+    return match server::serve(&args.address, options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            ExitCode::from(EXIT_TOOL_ERROR)
+        }
+    };
+}
+
+// What a command works on: the source file given, or the binaries of the package in the
+// current directory, with the ez.toml closest to them
+struct Project {
+    // Source files with the stem of their outputs. Binaries of a package are named after
+    // themselves in its build directory, other files after their stem.
+    sources: Vec<(String, Option<String>)>,
+    // Libraries the binaries link, built first in the same way into the lib directory of the
+    // build directory
+    libraries: Vec<(String, String)>,
+    package: Option<Package>,
+    lints: LintConfig,
+}
+
+fn get_project(filename: Option<&str>, binary: Option<&str>) -> Result<Project, String> {
+    let current = match current_dir() {
+        Ok(current) => current,
+        Err(error) => return Err(format!("Can not read the current directory: {}", error)),
+    };
+
+    // A file that does not exist fails to compile with its own error
+    let directory = match filename {
+        Some(filename) => Path::new(filename)
+            .canonicalize()
+            .ok()
+            .and_then(|path| path.parent().map(Path::to_path_buf)),
+        None => Some(current.clone()),
+    };
+
+    let manifest = match directory.and_then(|directory| Manifest::find(&directory)) {
+        Some(path) => Some(Manifest::load(&path)?),
+        None => None,
+    };
+
+    if let Some(filename) = filename {
+        return Ok(Project {
+            sources: vec![(filename.to_owned(), None)],
+            libraries: Vec::new(),
+            package: None,
+            lints: manifest.map_or(LintConfig::default(), |manifest| manifest.lints),
+        });
+    }
+
+    let manifest =
+        match manifest {
+            Some(manifest) => manifest,
+            None => return Err(String::from(
+                "No source file given and no ez.toml found, create a project with `ezlang new`.",
+            )),
+        };
+
+    let libraries = manifest.get_libraries()?;
+
+    let package = match manifest.package {
+        Some(package) => package,
+        None => {
+            return Err(format!(
+                "{} has no [package] table, give a source file to compile.",
+                manifest.root.join(MANIFEST_NAME).display()
+            ))
+        }
+    };
+
+    // Paths inside the current directory stay relative, like the ones given on the command line
+    let root = manifest
+        .root
+        .strip_prefix(&current)
+        .unwrap_or(&manifest.root);
+
+    let binaries: Vec<&Binary> = match binary {
+        Some(name) => match package.find_binary(name) {
+            Some(binary) => vec![binary],
+            None => {
+                let names: Vec<&str> = package
+                    .binaries
+                    .iter()
+                    .map(|binary| binary.name.as_str())
+                    .collect();
+
+                return Err(format!(
+                    "Package {} has no binary {}, its binaries are {}.",
+                    package.name,
+                    name,
+                    names.join(", ")
+                ));
+            }
+        },
+        None => package.binaries.iter().collect(),
+    };
+
+    let build = root.join(BUILD_DIRECTORY);
+
+    let libraries = libraries
+        .into_iter()
+        .map(|library| {
+            let filename = library
+                .entry
+                .strip_prefix(&current)
+                .unwrap_or(&library.entry);
+            let output = build.join("lib").join(&library.name);
+
+            return (
+                filename.to_string_lossy().to_string(),
+                output.to_string_lossy().to_string(),
+            );
+        })
+        .collect();
+
+    let sources = binaries
+        .iter()
+        .map(|binary| {
+            let filename = root.join(&binary.entry);
+            let output = build.join(&binary.name);
+
+            return (
+                filename.to_string_lossy().to_string(),
+                Some(output.to_string_lossy().to_string()),
+            );
+        })
+        .collect();
+
+    return Ok(Project {
+        sources,
+        libraries,
+        package: Some(package),
+        lints: manifest.lints,
+    });
+}
+
+fn get_limits(args: &LimitArgs) -> Limits {
+    return Limits {
+        expression_depth: args.max_expression_depth,
+        expression_length: args.max_expression_length,
+        type_depth: args.max_type_depth,
+        tokens: args.max_tokens,
+        functions: args.max_functions,
+        locals: args.max_locals,
+        string_bytes: args.max_string_bytes,
+        includes: true,
+        const_steps: args.max_const_steps,
+    };
+}
+
+fn run(args: RunArgs) -> ExitCode {
+    let options = InterpreterOptions {
+        max_steps: args.max_steps,
+        max_depth: args.max_depth,
+        max_memory: args.max_memory,
+        trace: args.trace,
+    };
+
+    let project = match get_project(args.filename.as_deref(), args.bin.as_deref()) {
+        Ok(project) => project,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            return ExitCode::from(EXIT_USAGE_ERROR);
+        }
+    };
+
+    let filename = match project.sources.as_slice() {
+        [(filename, _)] => filename.to_owned(),
+        [] => {
+            eprintln!("error: The package has no binary to run, only a library.");
+            return ExitCode::from(EXIT_USAGE_ERROR);
+        }
+        sources => {
+            let names: Vec<&str> = sources
+                .iter()
+                .filter_map(|(_, output)| output.as_deref())
+                .filter_map(|output| Path::new(output).file_name()?.to_str())
+                .collect();
+
+            eprintln!(
+                "error: The package has several binaries, pick the one to run with --bin: {}.",
+                names.join(", ")
+            );
+            return ExitCode::from(EXIT_USAGE_ERROR);
+        }
+    };
+
+    let program = match panic::catch_unwind(move || {
+        let mut parser = SourceParser::from_file(&filename, args.edition);
+        parser.generate_tokens();
+
+        let program = parser.generate_program();
+        validate_program(&program, &filename, false);
+
+        return program;
+    }) {
+        Ok(program) => program,
+        Err(payload) => return ExitCode::from(get_panic_code(&*payload)),
+    };
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        return Interpreter::new(&program, options).run();
+    }));
+
+    return match result {
+        Ok(Ok(code)) => ExitCode::from(code),
+        Ok(Err(error)) => {
+            eprintln!("error: {}", error);
+            ExitCode::from(EXIT_RUNTIME_ERROR)
+        }
+        Err(_) => ExitCode::from(EXIT_INTERNAL_ERROR),
+    };
+}
+
+// Compiles into a directory of its own under the temporary one and runs the executable with
+// the standard streams of ezlang, exiting with its code
+// A new directory only the user can enter, so nobody else can swap the executable before it
+// runs. The name is random and an existing directory is never reused.
+fn create_temp_directory() -> io::Result<PathBuf> {
+    loop {
+        let suffix = RandomState::new().build_hasher().finish();
+        let directory = env::temp_dir().join(format!("ezlang-{}-{:016x}", process::id(), suffix));
+
+        match DirBuilder::new().mode(0o700).create(&directory) {
+            Ok(()) => return Ok(directory),
+            Err(error) if error.kind() == ErrorKind::AlreadyExists => continue,
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+fn script(filename: &str) -> ExitCode {
+    let directory = match create_temp_directory() {
+        Ok(directory) => directory,
+        Err(error) => {
+            eprintln!(
+                "error: Can not create a directory in {}: {}",
+                env::temp_dir().display(),
+                error
+            );
+            return ExitCode::from(EXIT_TOOL_ERROR);
+        }
+    };
+
+    let stem = Path::new(filename).file_stem().unwrap_or_default();
+    let executable = directory.join(stem);
+
+    let options = CompilerOptions {
+        output: Some(executable.to_string_lossy().to_string()),
+        ..Default::default()
+    };
+
+    let code = match compile(filename.to_owned(), options, false) {
+        0 => match process::Command::new(&executable).status() {
+            // Killed by a signal, exit like a shell does
+            Ok(status) => status
+                .code()
+                .or(status.signal().map(|signal| 128 + signal))
+                .unwrap_or(1) as u8,
+            Err(error) => {
+                eprintln!("error: Can not run {}: {}", executable.display(), error);
+                EXIT_TOOL_ERROR
+            }
+        },
+        code => code,
+    };
+
+    let _ = remove_dir_all(&directory);
+
+    return ExitCode::from(code);
+}
+
+fn debug(args: DebugArgs) -> ExitCode {
+    let options = CompilerOptions {
+        checked_bounds: args.checked_bounds,
+        source_map: true,
+        edition: args.edition,
+        asm_syntax: args.asm_syntax,
+        ..Default::default()
+    };
+
+    let result = panic::catch_unwind(move || {
+        let mut compiler = Compiler::from_file(&args.filename, options);
+        return compiler.compile().map(|_| compiler.artifacts);
+    });
+
+    let artifacts = match result {
+        Ok(Ok(artifacts)) => artifacts,
+        Ok(Err(error)) => {
+            eprintln!("error: {}", error);
+            return ExitCode::from(EXIT_TOOL_ERROR);
+        }
+        Err(payload) => return ExitCode::from(get_panic_code(&*payload)),
+    };
+
+    // The executable is linked last, next to its source map
+    let executable = format!("./{}", artifacts.last().expect("Unreachable"));
+    let source_map = artifacts
+        .iter()
+        .find(|artifact| artifact.ends_with(".map"))
+        .expect("Unreachable");
+
+    let result = Debugger::launch(&executable, source_map).and_then(|mut debugger| debugger.run());
+
+    return match result {
+        Ok(code) => ExitCode::from(code),
+        Err(error) => {
+            eprintln!("error: {}", error);
+            ExitCode::from(EXIT_TOOL_ERROR)
+        }
+    };
+}
+
+fn fix(args: FixArgs) -> ExitCode {
+    let source = match read_to_string(&args.filename) {
+        Ok(source) => source,
+        Err(error) => {
+            eprintln!("error: Can not read {}: {}", args.filename, error);
+            return ExitCode::from(EXIT_TOOL_ERROR);
+        }
+    };
+
+    let result = fix::fix_source(&args.filename, source.clone(), args.edition);
+
+    for message in result.applied.iter() {
+        eprintln!("fixed: {}", message);
+    }
+
+    if args.dry_run {
+        if result.source != source {
+            print!(
+                "{}",
+                fix::write_diff(&args.filename, &source, &result.source)
+            );
+        }
+    } else if result.source != source {
+        if let Err(error) = write(&args.filename, &result.source) {
+            eprintln!("error: Can not write {}: {}", args.filename, error);
+            return ExitCode::from(EXIT_TOOL_ERROR);
+        }
+    }
+
+    // Errors without a fix are left for the author
+    return match result.error {
+        Some(error) => {
+            eprintln!("{}", error);
+            ExitCode::from(EXIT_COMPILE_ERROR)
+        }
+        None => ExitCode::SUCCESS,
+    };
+}
+
+fn hover(args: HoverArgs) -> ExitCode {
+    let position = match parse_position(&args.position) {
+        Some(position) => position,
+        None => {
+            eprintln!(
+                "error: Invalid position {}, expected FILE:LINE:COLUMN.",
+                args.position
+            );
+            return ExitCode::from(EXIT_USAGE_ERROR);
+        }
+    };
+
+    let filename = position.file.clone();
+
+    let program = match panic::catch_unwind(move || {
+        let mut parser = SourceParser::from_file(&filename, args.edition);
+        parser.generate_tokens();
+
+        return parser.generate_program();
+    }) {
+        Ok(program) => program,
+        Err(payload) => return ExitCode::from(get_panic_code(&*payload)),
+    };
+
+    if let Some(hover) = analysis::hover(&program, &position) {
+        println!(
+            "{}-{}",
+            hover.position,
+            hover.position.column + hover.length
+        );
+        println!("{}", hover.contents);
+    }
+
+    return ExitCode::SUCCESS;
+}
+
+fn tokens(args: TokensArgs) -> ExitCode {
+    let source = match read_to_string(&args.filename) {
+        Ok(source) => source,
+        Err(error) => {
+            eprintln!("error: Can not read {}: {}", args.filename, error);
+            return ExitCode::from(EXIT_TOOL_ERROR);
+        }
+    };
+
+    let tree = SyntaxTree::parse(&args.filename, &source, args.edition);
+
+    let program = match panic::catch_unwind(|| tree.to_program()) {
+        Ok(program) => program,
+        Err(payload) => return ExitCode::from(get_panic_code(&*payload)),
+    };
+
+    for token in analysis::semantic_tokens(&program, &tree) {
+        println!(
+            "{}:{} {} {}",
+            token.position.line,
+            token.position.column,
+            token.length,
+            token.kind.get_name()
+        );
+    }
+
+    return ExitCode::SUCCESS;
+}
+
+// One token per line as LINE:COLUMN KIND TEXT, stable enough to compare against golden files
+fn dump_tokens(filename: &str, edition: Edition) -> ExitCode {
+    let source = match read_to_string(filename) {
+        Ok(source) => source,
+        Err(error) => {
+            eprintln!("error: Can not read {}: {}", filename, error);
+            return ExitCode::from(EXIT_TOOL_ERROR);
+        }
+    };
+
+    // The lexer needs at least one character
+    if source.is_empty() {
+        return ExitCode::SUCCESS;
+    }
+
+    let result = panic::catch_unwind(|| {
+        let mut lexer = Lexer::from_source(filename, source.into_bytes(), edition);
+        let mut lines: Vec<String> = Vec::new();
+
+        while let Some(token) = lexer.next() {
+            lines.push(format!(
+                "{}:{} {}",
+                token.position.line,
+                token.position.column,
+                token.token_type.get_dump()
+            ));
+        }
+
+        return lines;
+    });
+
+    return match result {
+        Ok(lines) => {
+            for line in lines {
+                println!("{}", line);
+            }
+
+            ExitCode::SUCCESS
+        }
+        Err(payload) => ExitCode::from(get_panic_code(&*payload)),
+    };
+}
+
+fn rename(args: RenameArgs) -> ExitCode {
+    let position = match parse_position(&args.position) {
+        Some(position) => position,
+        None => {
+            eprintln!(
+                "error: Invalid position {}, expected FILE:LINE:COLUMN.",
+                args.position
+            );
+            return ExitCode::from(EXIT_USAGE_ERROR);
+        }
+    };
+
+    let filename = position.file.clone();
+
+    let program = match panic::catch_unwind(move || {
+        let mut parser = SourceParser::from_file(&filename, args.edition);
+        parser.generate_tokens();
+
+        return parser.generate_program();
+    }) {
+        Ok(program) => program,
+        Err(payload) => return ExitCode::from(get_panic_code(&*payload)),
+    };
+
+    let edits = match analysis::rename(&program, &position, &args.new_name) {
+        Ok(edits) => edits,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            return ExitCode::from(EXIT_USAGE_ERROR);
+        }
+    };
+
+    // Edits come sorted by file, the functions of an included file may be renamed too
+    let mut files: Vec<(String, Vec<Fix>)> = Vec::new();
+
+    for edit in edits {
+        match files.last_mut() {
+            Some((file, fixes)) if *file == edit.position.file => fixes.push(edit),
+            _ => files.push((edit.position.file.to_owned(), vec![edit])),
+        }
+    }
+
+    for (file, fixes) in files {
+        let source = match read_to_string(&file) {
+            Ok(source) => source,
+            Err(error) => {
+                eprintln!("error: Can not read {}: {}", file, error);
+                return ExitCode::from(EXIT_TOOL_ERROR);
+            }
+        };
+
+        let mut renamed = source.clone();
+
+        // Back to front so earlier offsets stay valid
+        for fix in fixes.iter().rev() {
+            if let Some(offset) = get_offset(&source, &fix.position) {
+                renamed.replace_range(offset..offset + fix.length, &fix.replacement);
+            }
+        }
+
+        if args.dry_run {
+            print!("{}", fix::write_diff(&file, &source, &renamed));
+        } else if let Err(error) = write(&file, &renamed) {
+            eprintln!("error: Can not write {}: {}", file, error);
+            return ExitCode::from(EXIT_TOOL_ERROR);
+        }
+    }
+
+    return ExitCode::SUCCESS;
+}
+
+fn explain(args: ExplainArgs) -> ExitCode {
+    let code = match args.code {
+        Some(code) => code,
+        None => {
+            for error_code in ERROR_CODES.iter() {
+                println!("{}: {}", error_code.code, error_code.title);
+            }
+
+            return ExitCode::SUCCESS;
+        }
+    };
+
+    return match find_error_code(&code) {
+        Some(error_code) => {
+            println!("{}: {}\n", error_code.code, error_code.title);
+            print!("{}", error_code.explanation);
+            ExitCode::SUCCESS
+        }
+        None => {
+            eprintln!(
+                "error: Unknown error code {}, run `ezlang explain` to list them.",
+                code
+            );
+            ExitCode::from(EXIT_USAGE_ERROR)
+        }
+    };
+}
+
+// The filename may contain colons, the line and column are the last two fields
+fn parse_position(text: &str) -> Option<Position> {
+    let mut fields = text.rsplitn(3, ':');
+
+    let column = fields.next()?.parse().ok()?;
+    let line = fields.next()?.parse().ok()?;
+    let file = fields.next()?;
+
+    return Some(Position {
+        file: file.to_owned(),
+        line,
+        column,
+    });
+}
+
+fn print_report(report: Result<String, String>) -> ExitCode {
+    return match report {
+        Ok(report) => {
+            print!("{}", report);
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            eprintln!("error: {}", error);
+            ExitCode::from(EXIT_TOOL_ERROR)
+        }
+    };
+}
+
+// Single line JSON for build systems, written by hand to avoid extra dependencies
+fn write_summary(
+    status: &str,
+    error: &Option<String>,
+    artifacts: &Vec<String>,
+    warnings: usize,
+    timings: &Vec<(&str, Duration)>,
+) -> String {
+    let error = match error {
+        Some(error) => escape_json(error),
+        None => "null".to_owned(),
+    };
+
+    let artifacts: Vec<String> = artifacts
+        .iter()
+        .map(|artifact| escape_json(artifact))
+        .collect();
+
+    let timings: Vec<String> = timings
+        .iter()
+        .map(|(phase, duration)| {
+            format!(
+                "{}: {:.3}",
+                escape_json(phase),
+                duration.as_secs_f64() * 1000.0
+            )
+        })
+        .collect();
+
+    return format!(
+        "{{\"status\": {}, \"error\": {}, \"artifacts\": [{}], \"warnings\": {}, \"timings_ms\": {{{}}}}}",
+        escape_json(status),
+        error,
+        artifacts.join(", "),
+        warnings,
+        timings.join(", ")
+    );
+}
+
+fn escape_json(string: &str) -> String {
+    let mut escaped = String::from("\"");
+
+    for character in string.chars() {
+        match character {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            character if character.is_control() => {
+                escaped.push_str(&format!("\\u{:04x}", character as u32))
+            }
+            character => escaped.push(character),
+        }
+    }
+
+    escaped.push('"');
+
+    return escaped;
 }
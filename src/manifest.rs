@@ -0,0 +1,567 @@
+use std::{
+    fs::{create_dir, create_dir_all, read_to_string, write},
+    path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+
+use crate::{
+    lint::LintConfig,
+    target::Target,
+    toml::{Document, Table, Value},
+};
+
+pub const MANIFEST_NAME: &str = "ez.toml";
+
+// Outputs of a project are written here, under the directory of its manifest
+pub const BUILD_DIRECTORY: &str = "build";
+
+const DEFAULT_ENTRY: &str = "src/main.ez";
+
+const DEFAULT_LIBRARY: &str = "src/lib.ez";
+
+const MAIN_TEMPLATE: &str = "fn main: () {
+    @printf(\"Hello, world!\\n\");
+    return 0;
+}
+";
+
+// Settings of ez.toml. A file with only a [lints] table configures the files next to it,
+// building without a source file needs the [package] table.
+#[derive(Debug, Clone)]
+pub struct Manifest {
+    // Directory of ez.toml, the paths in it are relative to it
+    pub root: PathBuf,
+    pub package: Option<Package>,
+    pub lints: LintConfig,
+}
+
+#[derive(Debug, Clone)]
+pub struct Package {
+    pub name: String,
+    pub target: Target,
+    pub opt_level: u8,
+    // Executables of the package. The entry of [package] is the first one and named after
+    // the package, each [[bin]] table adds another.
+    pub binaries: Vec<Binary>,
+    // Entry of the code other packages link, src/lib.ez when it exists or the one of [lib]
+    pub library: Option<String>,
+    pub dependencies: Vec<Dependency>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Binary {
+    pub name: String,
+    pub entry: String,
+}
+
+// Package in a directory relative to the manifest depending on it, like
+// `util = { path = "../util" }`
+#[derive(Debug, Clone)]
+pub struct Dependency {
+    pub name: String,
+    pub path: String,
+}
+
+// Library compiled to an object before the binaries linking it. Only its public functions are
+// global symbols, the binaries call them through extern declarations.
+#[derive(Debug, Clone)]
+pub struct Library {
+    pub name: String,
+    pub entry: PathBuf,
+}
+
+impl Package {
+    pub fn find_binary(&self, name: &str) -> Option<&Binary> {
+        return self.binaries.iter().find(|binary| binary.name == name);
+    }
+}
+
+impl Manifest {
+    // ez.toml in the directory or the closest one above it
+    pub fn find(directory: &Path) -> Option<PathBuf> {
+        return directory
+            .ancestors()
+            .map(|ancestor| ancestor.join(MANIFEST_NAME))
+            .find(|path| path.is_file());
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let filename = path.to_string_lossy();
+
+        let source = match read_to_string(path) {
+            Ok(source) => source,
+            Err(error) => return Err(format!("Can not read {}: {}", filename, error)),
+        };
+
+        let document = Document::parse(&filename, &source)?;
+
+        let root = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+
+        let mut package = match document.get_table("package") {
+            Some(table) => Some(Package::from_table(&filename, table)?),
+            None => None,
+        };
+
+        // Tables describing the package are meaningless in a file only configuring lints
+        let headers = [
+            ("bin", "[[bin]]"),
+            ("lib", "[lib]"),
+            ("dependencies", "[dependencies]"),
+        ];
+
+        for (name, header) in headers {
+            if let (Some(table), None) = (document.get_table(name), &package) {
+                return Err(format!(
+                    "{}:{}: {} needs a [package] table.",
+                    filename, table.line, header
+                ));
+            }
+        }
+
+        for table in document.get_tables("bin") {
+            let binary = Binary::from_table(&filename, table)?;
+            let package = package.as_mut().expect("Unreachable");
+
+            if package.find_binary(&binary.name).is_some() {
+                return Err(format!(
+                    "{}:{}: Binary {} is declared twice.",
+                    filename,
+                    table.get_line("name"),
+                    binary.name
+                ));
+            }
+
+            package.binaries.push(binary);
+        }
+
+        if let Some(package) = &mut package {
+            package.library = match document.get_table("lib") {
+                Some(table) => Some(read_library(&filename, table)?),
+                None if root.join(DEFAULT_LIBRARY).is_file() => Some(DEFAULT_LIBRARY.to_owned()),
+                None => None,
+            };
+
+            if let Some(table) = document.get_table("dependencies") {
+                package.dependencies = read_dependencies(&filename, table)?;
+            }
+        }
+
+        // Without an entry, the package builds src/main.ez when it exists or when neither a
+        // [[bin]] table nor a library give it something else to build
+        if let Some(package) = &mut package {
+            let has_entry = package.find_binary(&package.name).is_some();
+            let has_targets = package.binaries.len() > 0 || package.library.is_some();
+
+            if !has_entry && (!has_targets || root.join(DEFAULT_ENTRY).is_file()) {
+                package.binaries.insert(
+                    0,
+                    Binary {
+                        name: package.name.clone(),
+                        entry: DEFAULT_ENTRY.to_owned(),
+                    },
+                );
+            }
+        }
+
+        return Ok(Self {
+            root,
+            package,
+            lints: LintConfig::from_document(&filename, &document)?,
+        });
+    }
+
+    // Libraries of the dependencies, each once and after the ones it depends on, followed by
+    // the library of the package itself
+    pub fn get_libraries(&self) -> Result<Vec<Library>, String> {
+        let mut libraries: Vec<Library> = Vec::new();
+        let mut stack: Vec<(String, PathBuf)> = Vec::new();
+
+        self.collect_libraries(&mut libraries, &mut stack)?;
+
+        if let Some(package) = &self.package {
+            if let Some(entry) = &package.library {
+                add_library(
+                    &mut libraries,
+                    Library {
+                        name: package.name.clone(),
+                        entry: self.root.join(entry),
+                    },
+                )?;
+            }
+        }
+
+        return Ok(libraries);
+    }
+
+    // Packages are told apart by their canonical directory, a dependency on one of the
+    // packages being resolved is a cycle
+    fn collect_libraries(
+        &self,
+        libraries: &mut Vec<Library>,
+        stack: &mut Vec<(String, PathBuf)>,
+    ) -> Result<(), String> {
+        let package = match &self.package {
+            Some(package) => package,
+            None => return Ok(()),
+        };
+
+        let filename = self.root.join(MANIFEST_NAME);
+        let root = self.root.canonicalize().unwrap_or(self.root.clone());
+
+        stack.push((package.name.clone(), root.clone()));
+
+        for dependency in package.dependencies.iter() {
+            let directory = match root.join(&dependency.path).canonicalize() {
+                Ok(directory) => directory,
+                Err(error) => {
+                    return Err(format!(
+                        "{}: Can not find dependency {} at {}: {}",
+                        filename.display(),
+                        dependency.name,
+                        dependency.path,
+                        error
+                    ))
+                }
+            };
+
+            if let Some(index) = stack.iter().position(|(_, other)| *other == directory) {
+                let cycle: Vec<&str> = stack[index..]
+                    .iter()
+                    .map(|(name, _)| name.as_str())
+                    .chain([dependency.name.as_str()])
+                    .collect();
+
+                return Err(format!(
+                    "{}: Dependency cycle {}.",
+                    filename.display(),
+                    cycle.join(" -> ")
+                ));
+            }
+
+            let manifest = Manifest::load(&directory.join(MANIFEST_NAME))?;
+
+            let entry = match &manifest.package {
+                Some(other) if other.name != dependency.name => {
+                    return Err(format!(
+                        "{}: Dependency {} at {} is the package {}.",
+                        filename.display(),
+                        dependency.name,
+                        dependency.path,
+                        other.name
+                    ))
+                }
+                Some(other) => match &other.library {
+                    Some(entry) => directory.join(entry),
+                    None => {
+                        return Err(format!(
+                            "{}: Dependency {} has no library, add {} to it.",
+                            filename.display(),
+                            dependency.name,
+                            DEFAULT_LIBRARY
+                        ))
+                    }
+                },
+                None => {
+                    return Err(format!(
+                        "{}: Dependency {} at {} has no [package] table.",
+                        filename.display(),
+                        dependency.name,
+                        dependency.path
+                    ))
+                }
+            };
+
+            // Shared by several packages, it is only built once
+            if libraries.iter().any(|library| library.entry == entry) {
+                continue;
+            }
+
+            manifest.collect_libraries(libraries, stack)?;
+
+            add_library(
+                libraries,
+                Library {
+                    name: dependency.name.clone(),
+                    entry,
+                },
+            )?;
+        }
+
+        stack.pop();
+
+        return Ok(());
+    }
+}
+
+// Libraries are written to the build directory by name, so two packages can not share one
+fn add_library(libraries: &mut Vec<Library>, library: Library) -> Result<(), String> {
+    if let Some(other) = libraries.iter().find(|other| other.name == library.name) {
+        return Err(format!(
+            "Two packages named {} are linked, with the libraries {} and {}.",
+            library.name,
+            other.entry.display(),
+            library.entry.display()
+        ));
+    }
+
+    libraries.push(library);
+
+    return Ok(());
+}
+
+// The [lib] table, only to move the library from src/lib.ez
+fn read_library(filename: &str, table: &Table) -> Result<String, String> {
+    let mut entry = DEFAULT_LIBRARY.to_owned();
+
+    for ((key, value), line) in table.entries.iter().zip(table.lines.iter()) {
+        let error = |message: String| format!("{}:{}: {}", filename, line, message);
+
+        match (key.as_str(), value) {
+            ("entry", Value::String(value)) => entry = value.to_owned(),
+            ("entry", value) => {
+                return Err(error(format!(
+                    "entry must be a string, found {}.",
+                    value.get_kind()
+                )))
+            }
+            _ => return Err(error(format!("Unknown key {} in [lib].", key))),
+        }
+    }
+
+    return Ok(entry);
+}
+
+fn read_dependencies(filename: &str, table: &Table) -> Result<Vec<Dependency>, String> {
+    let mut dependencies: Vec<Dependency> = Vec::new();
+
+    for ((name, value), line) in table.entries.iter().zip(table.lines.iter()) {
+        // Inline tables are on a single line, so their keys share the line of the dependency
+        let error = |message: String| format!("{}:{}: {}", filename, line, message);
+
+        check_name(name).map_err(error)?;
+
+        let entries = match value {
+            Value::Table(entries) => entries,
+            _ => {
+                return Err(error(format!(
+                    "Dependency {} must be a table like {{ path = \"../{}\" }}, found {}.",
+                    name,
+                    name,
+                    value.get_kind()
+                )))
+            }
+        };
+
+        let mut path: Option<String> = None;
+
+        for (key, value) in entries.iter() {
+            match (key.as_str(), value) {
+                ("path", Value::String(value)) => path = Some(value.to_owned()),
+                ("path", value) => {
+                    return Err(error(format!(
+                        "path of dependency {} must be a string, found {}.",
+                        name,
+                        value.get_kind()
+                    )))
+                }
+                _ => {
+                    return Err(error(format!(
+                        "Unknown key {} in dependency {}, only path dependencies are supported.",
+                        key, name
+                    )))
+                }
+            }
+        }
+
+        match path {
+            Some(path) => dependencies.push(Dependency {
+                name: name.to_owned(),
+                path,
+            }),
+            None => return Err(error(format!("Dependency {} needs a path.", name))),
+        }
+    }
+
+    return Ok(dependencies);
+}
+
+impl Package {
+    fn from_table(filename: &str, table: &Table) -> Result<Self, String> {
+        let error = |message: String| format!("{}:{}: {}", filename, table.line, message);
+
+        let mut name: Option<String> = None;
+        let mut entry: Option<String> = None;
+        let mut package = Self {
+            name: String::new(),
+            target: Target::default(),
+            opt_level: 0,
+            binaries: Vec::new(),
+            library: None,
+            dependencies: Vec::new(),
+        };
+
+        for ((key, value), line) in table.entries.iter().zip(table.lines.iter()) {
+            let error = |message: String| format!("{}:{}: {}", filename, line, message);
+
+            match (key.as_str(), value) {
+                ("name", Value::String(value)) => {
+                    check_name(value).map_err(error)?;
+                    name = Some(value.to_owned());
+                }
+                ("entry", Value::String(value)) => entry = Some(value.to_owned()),
+                ("target", Value::String(value)) => {
+                    package.target = match Target::from_str(value, false) {
+                        Ok(target) => target,
+                        Err(_) => {
+                            let targets: Vec<String> = Target::value_variants()
+                                .iter()
+                                .map(|target| target.get_name().to_owned())
+                                .collect();
+
+                            return Err(error(format!(
+                                "Unknown target {}, the targets are {}.",
+                                value,
+                                targets.join(", ")
+                            )));
+                        }
+                    }
+                }
+                ("opt-level", Value::Integer(value)) => {
+                    package.opt_level = match *value {
+                        0 | 1 => *value as u8,
+                        _ => return Err(error(String::from("opt-level must be 0 or 1."))),
+                    }
+                }
+                ("name" | "entry" | "target", value) => {
+                    return Err(error(format!(
+                        "{} must be a string, found {}.",
+                        key,
+                        value.get_kind()
+                    )))
+                }
+                ("opt-level", value) => {
+                    return Err(error(format!(
+                        "opt-level must be an integer, found {}.",
+                        value.get_kind()
+                    )))
+                }
+                _ => return Err(error(format!("Unknown key {} in [package].", key))),
+            }
+        }
+
+        package.name = match name {
+            Some(name) => name,
+            None => return Err(error(String::from("[package] needs a name."))),
+        };
+
+        if let Some(entry) = entry {
+            package.binaries.push(Binary {
+                name: package.name.clone(),
+                entry,
+            });
+        }
+
+        return Ok(package);
+    }
+}
+
+impl Binary {
+    // The entry defaults to src/bin/<name>.ez
+    fn from_table(filename: &str, table: &Table) -> Result<Self, String> {
+        let error = |message: String| format!("{}:{}: {}", filename, table.line, message);
+
+        let mut name: Option<String> = None;
+        let mut entry: Option<String> = None;
+
+        for ((key, value), line) in table.entries.iter().zip(table.lines.iter()) {
+            let error = |message: String| format!("{}:{}: {}", filename, line, message);
+
+            match (key.as_str(), value) {
+                ("name", Value::String(value)) => {
+                    check_name(value).map_err(error)?;
+                    name = Some(value.to_owned());
+                }
+                ("entry", Value::String(value)) => entry = Some(value.to_owned()),
+                ("name" | "entry", value) => {
+                    return Err(error(format!(
+                        "{} must be a string, found {}.",
+                        key,
+                        value.get_kind()
+                    )))
+                }
+                _ => return Err(error(format!("Unknown key {} in [[bin]].", key))),
+            }
+        }
+
+        let name = match name {
+            Some(name) => name,
+            None => return Err(error(String::from("[[bin]] needs a name."))),
+        };
+
+        return Ok(Self {
+            entry: entry.unwrap_or(format!("src/bin/{}.ez", name)),
+            name,
+        });
+    }
+}
+
+// Names become file names of executables, so they keep to characters safe in paths
+fn check_name(name: &str) -> Result<(), String> {
+    let valid = !name.is_empty()
+        && !name.starts_with('-')
+        && name
+            .chars()
+            .all(|character| character.is_ascii_alphanumeric() || "_-".contains(character));
+
+    return match valid {
+        true => Ok(()),
+        false => Err(format!(
+            "Invalid package name {:?}, use letters, digits, `_` and `-`.",
+            name
+        )),
+    };
+}
+
+// Directory named after the package with a manifest and a main that prints a greeting
+pub fn create_project(name: &str) -> Result<(), String> {
+    check_name(name)?;
+
+    let root = Path::new(name);
+
+    if let Err(error) = create_dir(root) {
+        return Err(format!("Can not create {}: {}", name, error));
+    }
+
+    let manifest = format!(
+        "[package]\nname = \"{}\"\nentry = \"{}\"\ntarget = \"{}\"\nopt-level = 0\n",
+        name,
+        DEFAULT_ENTRY,
+        Target::default().get_name()
+    );
+
+    let files = [
+        (PathBuf::from(MANIFEST_NAME), manifest),
+        (PathBuf::from(DEFAULT_ENTRY), MAIN_TEMPLATE.to_owned()),
+        (
+            PathBuf::from(".gitignore"),
+            format!("/{}\n", BUILD_DIRECTORY),
+        ),
+    ];
+
+    for (path, contents) in files.iter() {
+        let path = root.join(path);
+
+        if let Some(parent) = path.parent() {
+            if let Err(error) = create_dir_all(parent) {
+                return Err(format!("Can not create {}: {}", parent.display(), error));
+            }
+        }
+
+        if let Err(error) = write(&path, contents) {
+            return Err(format!("Can not write {}: {}", path.display(), error));
+        }
+    }
+
+    return Ok(());
+}
@@ -0,0 +1,340 @@
+use crate::parser::{Function, Program};
+
+// Rough latencies in cycles on a recent x86-64 core, every other instruction takes one.
+// Branches, caches and instructions running in parallel are not modeled, so the estimate is
+// only good to compare functions and optimization levels.
+const COSTS: [(&str, usize); 12] = [
+    ("div", 26),
+    ("idiv", 26),
+    ("mul", 3),
+    ("imul", 3),
+    ("call", 3),
+    ("ret", 2),
+    ("syscall", 100),
+    ("rdtsc", 25),
+    ("addsd", 4),
+    ("subsd", 4),
+    ("mulsd", 4),
+    ("divsd", 14),
+];
+
+// Reading or writing memory adds a load or a store to the instruction
+const MEMORY_COST: usize = 4;
+
+// Static metrics of a function as it was written, for --analyze and --stack-analysis
+#[derive(Debug, Clone)]
+pub struct FunctionMetrics {
+    pub label: String,
+    pub instructions: usize,
+    // A single run through every instruction, the body of a loop is counted once
+    pub cycles: usize,
+    // Deepest the function takes the stack itself: the return address, the saved frame
+    // pointer, the locals and the values it pushes. Arguments are counted by the caller.
+    pub frame: usize,
+    // Label called by each call instruction, runtime routines included, with the stack
+    // taken right before it
+    pub calls: Vec<(String, usize)>,
+    // Allocates on the stack a size only known at runtime
+    pub dynamic: bool,
+}
+
+// Worst case of the stack below a function through the calls it makes
+#[derive(Debug, Clone, PartialEq)]
+pub enum StackDepth {
+    Bytes(usize),
+    // A chain of calls can come back to a function already in it
+    Recursive,
+    // The function or one it calls allocates a size only known at runtime
+    Dynamic(String),
+}
+
+// Instructions are the indented lines, labels start at the beginning of theirs. The stack is
+// followed through the instructions in the order they are written, which the compiler keeps
+// balanced around every jump.
+pub fn measure_function(label: &str, assembly: &str) -> FunctionMetrics {
+    let mut metrics = FunctionMetrics {
+        label: label.to_owned(),
+        instructions: 0,
+        cycles: 0,
+        frame: 8,
+        calls: Vec::new(),
+        dynamic: false,
+    };
+
+    let mut depth: usize = 8;
+    let mut base: usize = 8;
+
+    for line in assembly.lines() {
+        if !line.starts_with(char::is_whitespace) {
+            continue;
+        }
+
+        let code = line.split(';').next().unwrap_or_default().trim();
+
+        let (mnemonic, operands) = match code.split_once(char::is_whitespace) {
+            Some((mnemonic, operands)) => (mnemonic, operands.trim()),
+            None => (code, ""),
+        };
+
+        if mnemonic.is_empty() {
+            continue;
+        }
+
+        let cost = COSTS
+            .iter()
+            .find(|(name, _)| *name == mnemonic)
+            .map_or(1, |(_, cost)| *cost);
+
+        // lea only computes the address
+        let memory = match code.contains('[') && mnemonic != "lea" {
+            true => MEMORY_COST,
+            false => 0,
+        };
+
+        metrics.instructions += 1;
+        metrics.cycles += cost + memory;
+
+        let adjustment = operands
+            .strip_prefix("rsp, ")
+            .map(|amount| parse_number(amount.trim()));
+
+        match (mnemonic, operands, adjustment) {
+            ("push", _, _) => depth += 8,
+            ("pop", _, _) => depth = depth.saturating_sub(8),
+            ("sub", _, Some(Some(amount))) => depth += amount,
+            ("sub", _, Some(None)) => metrics.dynamic = true,
+            ("add", _, Some(Some(amount))) => depth = depth.saturating_sub(amount),
+            ("mov", "rbp, rsp", _) => base = depth,
+            ("mov", "rsp, rbp", _) => depth = base,
+            ("call", target, _) => {
+                let target = target.split_whitespace().next().unwrap_or_default();
+                metrics.calls.push((target.to_owned(), depth));
+            }
+            _ => {}
+        }
+
+        metrics.frame = metrics.frame.max(depth);
+    }
+
+    return metrics;
+}
+
+fn parse_number(text: &str) -> Option<usize> {
+    return match text.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    };
+}
+
+// One row per function in the order they were written, with the deepest the stack gets below
+// it through the functions it calls
+pub fn write_metrics(program: &Program, metrics: &[FunctionMetrics]) -> String {
+    let mut depths: Vec<Option<StackDepth>> = vec![None; metrics.len()];
+
+    let mut rows: Vec<Vec<String>> = vec![[
+        "function",
+        "instructions",
+        "cycles",
+        "frame",
+        "stack",
+        "calls",
+        "callees",
+    ]
+    .map(|heading| heading.to_owned())
+    .to_vec()];
+
+    for (index, function_metrics) in metrics.iter().enumerate() {
+        let function = match find_function(program, &function_metrics.label) {
+            Some(function) => function,
+            None => continue,
+        };
+
+        let stack = match get_depth(metrics, index, &mut depths, &mut Vec::new()) {
+            StackDepth::Bytes(bytes) => bytes.to_string(),
+            _ => String::from("unbounded"),
+        };
+
+        let mut callees: Vec<String> = Vec::new();
+
+        for id in function.get_calls() {
+            let name = program.functions[id].get_display_name();
+
+            if !callees.contains(&name) {
+                callees.push(name);
+            }
+        }
+
+        rows.push(vec![
+            function.get_display_name(),
+            function_metrics.instructions.to_string(),
+            function_metrics.cycles.to_string(),
+            function_metrics.frame.to_string(),
+            stack,
+            function_metrics.calls.len().to_string(),
+            callees.join(", "),
+        ]);
+    }
+
+    return write_table(&rows, &[0, 6]);
+}
+
+// Worst case stack of every function with the chain of calls reaching it, for targets
+// without an operating system growing the stack on demand
+pub fn write_stack_analysis(program: &Program, metrics: &[FunctionMetrics]) -> String {
+    let mut depths: Vec<Option<StackDepth>> = vec![None; metrics.len()];
+
+    let mut rows: Vec<Vec<String>> = vec![["function", "stack", "deepest"]
+        .map(|heading| heading.to_owned())
+        .to_vec()];
+
+    for (index, function_metrics) in metrics.iter().enumerate() {
+        let function = match find_function(program, &function_metrics.label) {
+            Some(function) => function,
+            None => continue,
+        };
+
+        let row = match get_depth(metrics, index, &mut depths, &mut Vec::new()) {
+            StackDepth::Bytes(bytes) => {
+                let chain = get_deepest_chain(program, metrics, index, &depths);
+                vec![bytes.to_string(), chain.join(" -> ")]
+            }
+            StackDepth::Recursive => {
+                vec![String::from("unbounded"), String::from("recursive calls")]
+            }
+            StackDepth::Dynamic(label) => vec![
+                String::from("unbounded"),
+                format!("runtime sized allocation in {}", get_name(program, &label)),
+            ],
+        };
+
+        rows.push([vec![function.get_display_name()], row].concat());
+    }
+
+    return write_table(&rows, &[0, 2]);
+}
+
+fn find_function<'a>(program: &'a Program, label: &str) -> Option<&'a Function> {
+    return program
+        .functions
+        .iter()
+        .find(|function| function.label == label);
+}
+
+// Functions by the name they are written with, runtime routines by their label
+fn get_name(program: &Program, label: &str) -> String {
+    return find_function(program, label)
+        .map_or(label.to_owned(), |function| function.get_display_name());
+}
+
+// Columns are aligned to the right unless listed
+fn write_table(rows: &[Vec<String>], left: &[usize]) -> String {
+    let widths: Vec<usize> = (0..rows[0].len())
+        .map(|column| rows.iter().map(|row| row[column].len()).max().unwrap_or(0))
+        .collect();
+
+    let mut table = String::new();
+
+    for row in rows.iter() {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(widths.iter())
+            .enumerate()
+            .map(|(column, (cell, width))| match left.contains(&column) {
+                true => format!("{:<width$}", cell, width = width),
+                false => format!("{:>width$}", cell, width = width),
+            })
+            .collect();
+
+        table.push_str(cells.join("  ").trim_end());
+        table.push('\n');
+    }
+
+    return table;
+}
+
+// Deepest of the frame of the function and the stack taken at each call plus the worst case
+// of the callee. Labels that were not measured, like extern functions, take nothing.
+fn get_depth(
+    metrics: &[FunctionMetrics],
+    index: usize,
+    depths: &mut Vec<Option<StackDepth>>,
+    chain: &mut Vec<usize>,
+) -> StackDepth {
+    if let Some(depth) = &depths[index] {
+        return depth.clone();
+    }
+
+    // Every function in the chain reaches this one, so they all recurse
+    if chain.contains(&index) {
+        return StackDepth::Recursive;
+    }
+
+    let function_metrics = &metrics[index];
+
+    let mut depth = match function_metrics.dynamic {
+        true => StackDepth::Dynamic(function_metrics.label.clone()),
+        false => StackDepth::Bytes(function_metrics.frame),
+    };
+
+    chain.push(index);
+
+    for (label, taken) in function_metrics.calls.iter() {
+        let callee = match metrics.iter().position(|other| other.label == *label) {
+            Some(callee) => get_depth(metrics, callee, depths, chain),
+            None => continue,
+        };
+
+        depth = match (depth, callee) {
+            (StackDepth::Recursive, _) | (_, StackDepth::Recursive) => StackDepth::Recursive,
+            (StackDepth::Dynamic(label), _) | (_, StackDepth::Dynamic(label)) => {
+                StackDepth::Dynamic(label)
+            }
+            (StackDepth::Bytes(deepest), StackDepth::Bytes(bytes)) => {
+                StackDepth::Bytes(deepest.max(taken + bytes))
+            }
+        };
+    }
+
+    chain.pop();
+
+    depths[index] = Some(depth.clone());
+
+    return depth;
+}
+
+// Functions along the calls making the worst case of a bounded function
+fn get_deepest_chain(
+    program: &Program,
+    metrics: &[FunctionMetrics],
+    index: usize,
+    depths: &[Option<StackDepth>],
+) -> Vec<String> {
+    let mut chain = vec![get_name(program, &metrics[index].label)];
+    let mut current = index;
+
+    loop {
+        let expected = match &depths[current] {
+            Some(StackDepth::Bytes(bytes)) => *bytes,
+            _ => break,
+        };
+
+        let deepest = metrics[current].calls.iter().find_map(|(label, taken)| {
+            let callee = metrics.iter().position(|other| other.label == *label)?;
+
+            return match &depths[callee] {
+                Some(StackDepth::Bytes(bytes)) if taken + bytes == expected => Some(callee),
+                _ => None,
+            };
+        });
+
+        match deepest {
+            Some(callee) if expected > metrics[current].frame => {
+                chain.push(get_name(program, &metrics[callee].label));
+                current = callee;
+            }
+            _ => break,
+        }
+    }
+
+    return chain;
+}
@@ -1,79 +1,594 @@
-use crate::lexer::{BinaryOperator, Lexer, Token, TokenType};
+use core::fmt;
+use std::{
+    collections::HashMap,
+    fs::read_to_string,
+    ops::Index,
+    panic::panic_any,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use crate::{
+    constants::{evaluate, get_literal, wrap},
+    ctfe::evaluate_call,
+    diagnostics::{get_suggestion, set_processing, Diagnostic, Fix},
+    header::{has_c_signature, FLOAT_REGISTERS, INTEGER_REGISTERS},
+    interner,
+    lexer::{BinaryOperator, Edition, Lexer, Token, TokenType},
+    logging::{log, Level},
+    span::{Position, Span, Spanned},
+    visit::{rebuild_expression, walk_expression, Folder, Visitor},
+};
+
+// Names resolved by Type::from_name, for suggestions
+const BUILTIN_TYPES: [&str; 9] = ["u8", "u16", "u32", "u64", "i8", "i16", "i32", "i64", "f64"];
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Type {
+    U8,
+    U16,
+    U32,
+    U64,
+    I8,
+    I16,
+    I32,
+    I64,
+    F64,
+    Pointer(Box<Type>),
+    Array(Box<Type>, usize),
+    Struct(Box<Struct>),
+    Tuple(Vec<Type>),
+}
+
+// Largest type in bytes. Frames and the parts of values are reached through signed 32 bit
+// displacements, half of their range leaves room for the rest of the frame.
+pub const MAX_TYPE_SIZE: usize = 1 << 30;
+
+impl Type {
+    pub fn from_name(name: &str) -> Option<Self> {
+        return match name {
+            "u8" => Some(Self::U8),
+            "u16" => Some(Self::U16),
+            "u32" => Some(Self::U32),
+            "u64" => Some(Self::U64),
+            "i8" => Some(Self::I8),
+            "i16" => Some(Self::I16),
+            "i32" => Some(Self::I32),
+            "i64" => Some(Self::I64),
+            "f64" => Some(Self::F64),
+            _ => None,
+        };
+    }
+
+    pub fn get_size(&self) -> usize {
+        return match self {
+            Self::U8 | Self::I8 => 1,
+            Self::U16 | Self::I16 => 2,
+            Self::U32 | Self::I32 => 4,
+            Self::U64 | Self::I64 | Self::F64 | Self::Pointer(_) => 8,
+            Self::Array(element, length) => element.get_size() * length,
+            Self::Struct(layout) => layout.size,
+            Self::Tuple(elements) => elements.iter().map(|element| element.get_size()).sum(),
+        };
+    }
+
+    // Scalars are aligned to their size, aggregates to their strictest member
+    pub fn get_alignment(&self) -> usize {
+        return match self {
+            Self::Array(element, _) => element.get_alignment(),
+            Self::Struct(layout) => layout.alignment,
+            Self::Tuple(_) => 8,
+            _ => self.get_size(),
+        };
+    }
+
+    pub fn is_integer(&self) -> bool {
+        return match self {
+            Self::U8 | Self::U16 | Self::U32 | Self::U64 => true,
+            Self::I8 | Self::I16 | Self::I32 | Self::I64 => true,
+            Self::F64 | Self::Pointer(_) | Self::Array(..) | Self::Struct(_) => false,
+            Self::Tuple(_) => false,
+        };
+    }
+
+    // Aggregates only live in memory and are accessed by their parts
+    pub fn is_aggregate(&self) -> bool {
+        return match self {
+            Self::Array(..) | Self::Struct(_) => true,
+            _ => false,
+        };
+    }
+
+    pub fn is_struct(&self) -> bool {
+        return match self {
+            Self::Struct(_) => true,
+            _ => false,
+        };
+    }
+
+    // Tuples only exist as return values and are destructured right away
+    pub fn is_tuple(&self) -> bool {
+        return match self {
+            Self::Tuple(_) => true,
+            _ => false,
+        };
+    }
+
+    pub fn is_pointer(&self) -> bool {
+        return match self {
+            Self::Pointer(_) => true,
+            _ => false,
+        };
+    }
+
+    pub fn is_signed(&self) -> bool {
+        return match self {
+            Self::I8 | Self::I16 | Self::I32 | Self::I64 | Self::F64 => true,
+            Self::U8 | Self::U16 | Self::U32 | Self::U64 => false,
+            Self::Pointer(_) | Self::Array(..) | Self::Struct(_) | Self::Tuple(_) => false,
+        };
+    }
+
+    // Type of the values reached by indexing
+    pub fn get_element(&self) -> Option<&Type> {
+        return match self {
+            Self::Pointer(element) | Self::Array(element, _) => Some(element),
+            _ => None,
+        };
+    }
+
+    pub fn can_cast_to(&self, target: &Type) -> bool {
+        if self.is_aggregate() || target.is_aggregate() || self.is_tuple() || target.is_tuple() {
+            return false;
+        }
+
+        return match (self, target) {
+            (Self::F64, Self::Pointer(_)) | (Self::Pointer(_), Self::F64) => false,
+            _ => true,
+        };
+    }
+
+    // Every value of self is representable in target without any conversion
+    pub fn widens_to(&self, target: &Type) -> bool {
+        if !self.is_integer() || !target.is_integer() {
+            return false;
+        }
+
+        return match (self.is_signed(), target.is_signed()) {
+            (false, false) | (true, true) => self.get_size() <= target.get_size(),
+            (false, true) => self.get_size() < target.get_size(),
+            (true, false) => false,
+        };
+    }
+
+    pub fn fits(&self, value: u64) -> bool {
+        let bits = self.get_size() * 8;
+
+        if !self.is_integer() || bits == 64 {
+            return true;
+        }
+
+        return match self.is_signed() {
+            true => value < (1 << (bits - 1)),
+            false => value < (1 << bits),
+        };
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::U8 => write!(f, "u8"),
+            Type::U16 => write!(f, "u16"),
+            Type::U32 => write!(f, "u32"),
+            Type::U64 => write!(f, "u64"),
+            Type::I8 => write!(f, "i8"),
+            Type::I16 => write!(f, "i16"),
+            Type::I32 => write!(f, "i32"),
+            Type::I64 => write!(f, "i64"),
+            Type::F64 => write!(f, "f64"),
+            Type::Pointer(element) => write!(f, "*{}", element),
+            Type::Array(element, length) => write!(f, "[{}; {}]", element, length),
+            Type::Struct(layout) => write!(f, "{}", layout.name),
+            Type::Tuple(elements) => {
+                let elements: Vec<String> =
+                    elements.iter().map(|element| element.to_string()).collect();
+                write!(f, "({})", elements.join(", "))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Field {
+    pub name: String,
+    pub field_type: Type,
+    pub offset: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Struct {
+    pub name: String,
+    pub fields: Vec<Field>,
+    pub size: usize,
+    pub alignment: usize,
+}
+
+impl Struct {
+    pub fn find(&self, name: &str) -> Option<usize> {
+        return self.fields.iter().position(|field| field.name == name);
+    }
+
+    fn suggest(&self, name: &str) -> String {
+        return get_suggestion(name, self.fields.iter().map(|field| field.name.as_str()));
+    }
+}
+
+// Declared with `const NAME = expression;`, the value is computed while parsing and every
+// use is replaced by it
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Constant {
+    pub name: String,
+    pub constant_type: Type,
+    pub value: u64,
+    pub position: Position,
+}
+
+// Declared with type parameters like `fn max<T: int>: (a: T, b: T) -> T`, only the signature
+// is read where it is declared. Each call parses the tokens again with the types it infers,
+// adding an instance of the function for every list of types used.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Generic {
+    pub name: String,
+    pub module: String,
+    pub public: bool,
+    pub position: Position,
+    // Index of the first token of the declaration, attributes included
+    pub start: usize,
+    pub parameters: Vec<String>,
+    // Type parameter each argument is declared with, the values passed infer it
+    pub inferred: Vec<Option<usize>>,
+}
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Local {
     pub size: usize,
     pub offset: usize,
-    pub label: String,
+    pub label: interner::Symbol,
+    pub local_type: Type,
+    // Label of the zeroed storage in .bss of static locals, which live outside the frame
+    // and keep their value between calls
+    pub static_label: Option<String>,
 }
 
+// Serialized as its locals, the indices are built again when reading them back
 #[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "Vec<Local>", into = "Vec<Local>")
+)]
 pub struct LocalStack {
     pub locals: Vec<Local>,
+    // Index of the local with each name, so finding one does not depend on how many there are
+    indices: HashMap<interner::Symbol, usize>,
 }
 
 impl LocalStack {
     fn new() -> Self {
-        Self { locals: Vec::new() }
+        Self {
+            locals: Vec::new(),
+            indices: HashMap::new(),
+        }
+    }
+
+    // Locals in the given order, like after laying out the frame again
+    pub fn from_locals(locals: Vec<Local>) -> Self {
+        let mut indices: HashMap<interner::Symbol, usize> = HashMap::new();
+
+        for (index, local) in locals.iter().enumerate() {
+            indices.entry(local.label).or_insert(index);
+        }
+
+        return Self { locals, indices };
     }
 
-    fn insert(&mut self, label: String, size: usize) -> usize {
-        return match self.find(&label) {
+    fn insert(&mut self, label: interner::Symbol, local_type: Type) -> usize {
+        return match self.find(label) {
             Some(index) => index,
             None => {
-                let offset = match self.locals.last() {
-                    Some(local) => local.offset + local.size,
-                    None => 0,
-                };
+                let end = self.get_size();
+
+                // rbp is 16 bytes aligned so the slot start only depends on its distance to rbp
+                let size = local_type.get_size();
+                let offset = (end + size).next_multiple_of(local_type.get_alignment()) - size;
 
                 self.locals.push(Local {
                     size,
                     offset,
                     label,
+                    local_type,
+                    static_label: None,
                 });
+                self.indices.insert(label, self.locals.len() - 1);
 
                 self.locals.len() - 1
             }
         };
     }
 
-    fn find(&self, label: &str) -> Option<usize> {
-        return self.locals.iter().position(|local| local.label == label);
+    // Labelled once the label of the function is known
+    fn insert_static(&mut self, label: interner::Symbol, local_type: Type) -> usize {
+        self.locals.push(Local {
+            size: local_type.get_size(),
+            offset: 0,
+            label,
+            local_type,
+            static_label: Some(label.to_string()),
+        });
+        self.indices.entry(label).or_insert(self.locals.len() - 1);
+
+        return self.locals.len() - 1;
+    }
+
+    fn find(&self, label: interner::Symbol) -> Option<usize> {
+        return self.indices.get(&label).copied();
+    }
+
+    fn suggest(&self, label: interner::Symbol) -> String {
+        let names: Vec<Rc<str>> = self
+            .locals
+            .iter()
+            .map(|local| local.label.get_name())
+            .collect();
+
+        return get_suggestion(&label.get_name(), names.iter().map(|name| &**name));
     }
 
     pub fn get(&self, index: usize) -> Option<&Local> {
         return self.locals.get(index);
     }
 
+    // Locals laid out again after parsing may end before the last one, static locals
+    // take no space in the frame
     pub fn get_size(&self) -> usize {
-        return match self.locals.last() {
-            Some(local) => local.offset + local.size,
-            None => 0,
-        };
+        return self
+            .locals
+            .iter()
+            .filter(|local| local.static_label.is_none())
+            .map(|local| local.offset + local.size)
+            .max()
+            .unwrap_or(0);
+    }
+}
+
+impl From<Vec<Local>> for LocalStack {
+    fn from(locals: Vec<Local>) -> Self {
+        return Self::from_locals(locals);
+    }
+}
+
+impl From<LocalStack> for Vec<Local> {
+    fn from(stack: LocalStack) -> Self {
+        return stack.locals;
+    }
+}
+
+// What an identifier refers to, recorded while parsing for editor tooling
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Reference {
+    // Index in the locals of the function
+    Local(usize),
+    Function(FunctionId),
+    Module(String),
+    Struct(String),
+    // Name of the struct and index of the field
+    Field(String, usize),
+    Constant(String),
+}
+
+// Written as `#[name(argument)]` before a function
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Attributes {
+    // Section of the object file the code is placed in instead of .text
+    pub section: Option<String>,
+    // Called before main, in the order they are declared
+    pub constructor: bool,
+    // Called once main returns, in the reverse order they are declared
+    pub destructor: bool,
+}
+
+// Position of a function in the functions of the program, which never move once parsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FunctionId(pub usize);
+
+impl Index<FunctionId> for [Function] {
+    type Output = Function;
+
+    fn index(&self, id: FunctionId) -> &Function {
+        return &self[id.0];
+    }
+}
+
+impl Index<FunctionId> for Vec<Function> {
+    type Output = Function;
+
+    fn index(&self, id: FunctionId) -> &Function {
+        return &self[id.0];
+    }
+}
+
+// What calls are checked against, without the body or locals of the function
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Signature {
+    pub arguments: Vec<Type>,
+    pub return_type: Type,
+}
+
+// Functions by name with their signatures. A name declared by several modules maps to the
+// function of each one, in declaration order.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FunctionTable {
+    ids: HashMap<String, Vec<FunctionId>>,
+    signatures: Vec<Signature>,
+}
+
+impl FunctionTable {
+    pub fn from_functions(functions: &[Function]) -> Self {
+        let mut table = Self::default();
+
+        for function in functions {
+            table.insert(function);
+        }
+
+        return table;
+    }
+
+    // Functions are inserted in the order they are pushed to the program, so ids match indices
+    pub fn insert(&mut self, function: &Function) -> FunctionId {
+        let id = FunctionId(self.signatures.len());
+
+        self.ids.entry(function.name.clone()).or_default().push(id);
+        self.signatures.push(function.get_signature());
+
+        return id;
+    }
+
+    pub fn find(&self, name: &str) -> &[FunctionId] {
+        return self.ids.get(name).map_or(&[], |ids| ids.as_slice());
+    }
+
+    pub fn get_signature(&self, id: FunctionId) -> &Signature {
+        return &self.signatures[id.0];
+    }
+
+    pub fn len(&self) -> usize {
+        return self.signatures.len();
     }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Function {
     pub name: String,
+    pub module: String,
+    pub label: String,
+    pub public: bool,
+    // Provided by another object file at link time
+    pub external: bool,
+    pub position: Position,
     pub locals: LocalStack,
     pub arguments: Vec<usize>,
-    pub body: Scope,
+    pub return_type: Type,
+    pub body: Option<Scope>,
+    // Identifiers of the signature and body with what they refer to
+    pub references: Vec<Spanned<Reference>>,
+    pub attributes: Attributes,
+    // Types an instance of a generic function was parsed with, empty for the others
+    pub type_arguments: Vec<Type>,
+}
+
+impl Function {
+    // Functions of the root file keep their plain name in diagnostics and reports
+    pub fn get_display_name(&self) -> String {
+        let suffix: String = self
+            .type_arguments
+            .iter()
+            .map(|type_argument| format!(".{}", type_argument))
+            .collect();
+
+        let name = match self.label == format!("{}{}", self.name, suffix) {
+            true => self.name.clone(),
+            false => format!("{}::{}", self.module, self.name),
+        };
+
+        if self.type_arguments.is_empty() {
+            return name;
+        }
+
+        let types: Vec<String> = self
+            .type_arguments
+            .iter()
+            .map(|type_argument| type_argument.to_string())
+            .collect();
+
+        return format!("{}<{}>", name, types.join(", "));
+    }
+
+    pub fn get_signature(&self) -> Signature {
+        let arguments = self
+            .arguments
+            .iter()
+            .map(|argument| {
+                let local = self.locals.get(*argument).expect("Unreachable");
+                return local.local_type.clone();
+            })
+            .collect();
+
+        return Signature {
+            arguments,
+            return_type: self.return_type.clone(),
+        };
+    }
+
+    // Every function called from the body
+    pub fn get_calls(&self) -> Vec<FunctionId> {
+        let mut collector = CallCollector { calls: Vec::new() };
+        collector.visit_function(self);
+
+        return collector.calls;
+    }
+}
+
+struct CallCollector {
+    calls: Vec<FunctionId>,
+}
+
+impl Visitor for CallCollector {
+    fn visit_expression(&mut self, expression: &Expression) {
+        if let Expression::Call(index, _) = expression {
+            self.calls.push(*index);
+        }
+
+        walk_expression(self, expression);
+    }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Scope {
     pub statements: Vec<Statement>,
+    // Where each statement came from, for source maps and explanations
+    pub spans: Vec<Span>,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Statement {
+    Declare(usize),
     Assign(usize, Expression),
+    Store(Expression, Expression),
+    Destructure(Vec<usize>, Expression),
     Return(Expression),
     Call(Expression),
+    // Targets of goto, only jumped to from the same function
+    Label(interner::Symbol),
+    Goto(interner::Symbol),
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BinaryExpression {
     pub operator: BinaryOperator,
     pub left: Box<Expression>,
@@ -81,389 +596,3664 @@ pub struct BinaryExpression {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Intrinsic {
+    Write,
+    ToStr,
+    ParseInt,
+    ToF64,
+    ToInt,
+    DivMod,
+    // Port I/O of the processor, only allowed without an operating system
+    PortIn,
+    PortOut,
+    // Bytes in the frame of the calling function, freed when it returns
+    StackAlloc,
+    // Byte by byte, with the string instructions of the processor
+    Memset,
+    Memcpy,
+    Memcmp,
+    // Abort with the position of the statement, only allowed as a statement of their own
+    Trap,
+    Unreachable,
+    // Pseudorandom numbers of a xorshift generator, seeded from the time stamp counter unless
+    // a seed is set
+    Random,
+    Seed,
+    // Seconds since the epoch, nanoseconds of a clock that only goes forward, and sleeping for
+    // milliseconds, with system calls
+    Time,
+    ClockNs,
+    SleepMs,
+}
+
+impl Intrinsic {
+    pub fn from_name(name: &str) -> Option<Self> {
+        return match name {
+            "write" => Some(Self::Write),
+            "to_str" => Some(Self::ToStr),
+            "parse_int" => Some(Self::ParseInt),
+            "to_f64" => Some(Self::ToF64),
+            "to_int" => Some(Self::ToInt),
+            "divmod" => Some(Self::DivMod),
+            "port_in" => Some(Self::PortIn),
+            "port_out" => Some(Self::PortOut),
+            "stack_alloc" => Some(Self::StackAlloc),
+            "memset" => Some(Self::Memset),
+            "memcpy" => Some(Self::Memcpy),
+            "memcmp" => Some(Self::Memcmp),
+            "trap" => Some(Self::Trap),
+            "unreachable" => Some(Self::Unreachable),
+            "rand" => Some(Self::Random),
+            "seed" => Some(Self::Seed),
+            "time" => Some(Self::Time),
+            "clock_ns" => Some(Self::ClockNs),
+            "sleep_ms" => Some(Self::SleepMs),
+            _ => None,
+        };
+    }
+
+    pub fn get_arguments(&self) -> usize {
+        return match self {
+            Self::Write => 3,
+            Self::ToStr => 2,
+            Self::ParseInt => 1,
+            Self::ToF64 => 1,
+            Self::ToInt => 1,
+            Self::DivMod => 2,
+            Self::PortIn => 1,
+            Self::PortOut => 2,
+            Self::StackAlloc => 1,
+            Self::Memset | Self::Memcpy | Self::Memcmp => 3,
+            Self::Trap | Self::Unreachable | Self::Random | Self::Time | Self::ClockNs => 0,
+            Self::Seed | Self::SleepMs => 1,
+        };
+    }
+
+    // Message written after the position when the program aborts, for the intrinsics that
+    // never come back
+    pub fn get_abort_message(&self) -> Option<&str> {
+        return match self {
+            Self::Trap => Some("Trap."),
+            Self::Unreachable => Some("Entered unreachable code."),
+            _ => None,
+        };
+    }
+
+    // None when the argument types are not accepted
+    pub fn get_type(&self, arguments: &[Type]) -> Option<Type> {
+        return match (self, arguments) {
+            (Self::Write, [fd, buffer, length])
+                if fd.is_integer()
+                    && (buffer.is_integer() || buffer.is_pointer())
+                    && length.is_integer() =>
+            {
+                Some(Type::I64)
+            }
+            (Self::ToStr, [number, buffer])
+                if (number.is_integer() || number.is_pointer() || *number == Type::F64)
+                    && (buffer.is_integer() || buffer.is_pointer()) =>
+            {
+                Some(Type::I64)
+            }
+            (Self::ParseInt, [buffer]) if buffer.is_integer() || buffer.is_pointer() => {
+                Some(Type::I64)
+            }
+            (Self::ToF64, [number]) if number.is_integer() => Some(Type::F64),
+            (Self::ToInt, [Type::F64]) => Some(Type::I64),
+            // Quotient and remainder take the type of the dividend
+            (Self::DivMod, [dividend, divisor])
+                if dividend.is_integer() && divisor.is_integer() =>
+            {
+                Some(Type::Tuple(vec![dividend.clone(), dividend.clone()]))
+            }
+            (Self::PortIn, [port]) if port.is_integer() => Some(Type::U8),
+            (Self::PortOut, [port, value]) if port.is_integer() && value.is_integer() => {
+                Some(Type::I64)
+            }
+            (Self::StackAlloc, [size]) if size.is_integer() => {
+                Some(Type::Pointer(Box::new(Type::U8)))
+            }
+            // The destination is returned like in C
+            (Self::Memset, [destination, value, length])
+                if (destination.is_integer() || destination.is_pointer())
+                    && value.is_integer()
+                    && length.is_integer() =>
+            {
+                Some(destination.clone())
+            }
+            (Self::Memcpy, [destination, source, length])
+                if (destination.is_integer() || destination.is_pointer())
+                    && (source.is_integer() || source.is_pointer())
+                    && length.is_integer() =>
+            {
+                Some(destination.clone())
+            }
+            // Negative, zero or positive as the first different byte of a is below, equal
+            // or above the one of b
+            (Self::Memcmp, [a, b, length])
+                if (a.is_integer() || a.is_pointer())
+                    && (b.is_integer() || b.is_pointer())
+                    && length.is_integer() =>
+            {
+                Some(Type::I64)
+            }
+            (Self::Trap | Self::Unreachable, []) => Some(Type::I64),
+            // Never negative, so the remainder of @divmod picks a number in a range
+            (Self::Random, []) => Some(Type::I64),
+            // A seed of 0 seeds the next number from the time stamp counter again
+            (Self::Seed, [seed]) if seed.is_integer() => Some(Type::I64),
+            (Self::Time | Self::ClockNs, []) => Some(Type::I64),
+            // 0, or the negated error number like the system call
+            (Self::SleepMs, [milliseconds]) if milliseconds.is_integer() => Some(Type::I64),
+            _ => None,
+        };
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Expression {
     NumberLiteral(u64),
+    FloatLiteral(f64),
+    StringLiteral(usize),
     Binary(BinaryExpression),
     Local(usize),
-    Call(usize, Vec<Expression>),
+    Call(FunctionId, Vec<Expression>),
+    Intrinsic(Intrinsic, Vec<Expression>),
+    Cast(Type, Box<Expression>),
+    Index(Box<Expression>, Box<Expression>, Position),
+    Field(Box<Expression>, usize),
+    Address(Box<Expression>),
+    Initializer(Type, Vec<Expression>),
+    Tuple(Vec<Expression>),
+    Format(Vec<Option<usize>>, Vec<Expression>),
 }
 
-#[derive(Debug)]
-pub struct Program {
-    pub functions: Vec<Function>,
-}
+impl Statement {
+    pub fn get_expressions(&self) -> Vec<&Expression> {
+        return match self {
+            Self::Declare(_) => Vec::new(),
+            Self::Store(target, expression) => vec![target, expression],
+            Self::Assign(_, expression)
+            | Self::Destructure(_, expression)
+            | Self::Return(expression)
+            | Self::Call(expression) => vec![expression],
+            Self::Label(_) | Self::Goto(_) => Vec::new(),
+        };
+    }
 
-impl Program {
-    fn new() -> Self {
-        Self {
-            functions: Vec::new(),
-        }
+    pub fn get_expressions_mut(&mut self) -> Vec<&mut Expression> {
+        return match self {
+            Self::Declare(_) => Vec::new(),
+            Self::Store(target, expression) => vec![target, expression],
+            Self::Assign(_, expression)
+            | Self::Destructure(_, expression)
+            | Self::Return(expression)
+            | Self::Call(expression) => vec![expression],
+            Self::Label(_) | Self::Goto(_) => Vec::new(),
+        };
     }
-}
 
-pub struct Parser {
-    lexer: Lexer,
-    tokens: Vec<Token>,
-    position: usize,
-    current_token: Option<Token>,
-    lookahead_token: Option<Token>,
-    functions: Vec<Function>,
+    // Statements control never comes back from, like `@unreachable();`
+    pub fn is_diverging(&self) -> bool {
+        return match self {
+            Self::Call(Expression::Intrinsic(intrinsic, _)) => {
+                intrinsic.get_abort_message().is_some()
+            }
+            _ => false,
+        };
+    }
 }
 
-impl Parser {
-    pub fn from_file(filename: &str) -> Self {
-        return Self {
-            lexer: Lexer::from_file(filename),
-            tokens: Vec::new(),
-            position: 0,
-            current_token: None,
-            lookahead_token: None,
-            functions: Vec::new(),
+impl Expression {
+    // Direct subexpressions
+    pub fn get_children(&self) -> Vec<&Expression> {
+        return match self {
+            Self::NumberLiteral(_)
+            | Self::FloatLiteral(_)
+            | Self::StringLiteral(_)
+            | Self::Local(_) => Vec::new(),
+            Self::Binary(binary_expression) => {
+                vec![&binary_expression.left, &binary_expression.right]
+            }
+            Self::Cast(_, expression) | Self::Field(expression, _) | Self::Address(expression) => {
+                vec![expression]
+            }
+            Self::Index(base, index, _) => vec![base, index],
+            Self::Call(_, expressions)
+            | Self::Intrinsic(_, expressions)
+            | Self::Initializer(_, expressions)
+            | Self::Tuple(expressions)
+            | Self::Format(_, expressions) => expressions.iter().collect(),
         };
     }
 
-    pub fn generate_tokens(&mut self) {
-        while let Some(token) = self.lexer.next() {
-            self.tokens.push(token);
-        }
+    pub fn get_children_mut(&mut self) -> Vec<&mut Expression> {
+        return match self {
+            Self::NumberLiteral(_)
+            | Self::FloatLiteral(_)
+            | Self::StringLiteral(_)
+            | Self::Local(_) => Vec::new(),
+            Self::Binary(binary_expression) => {
+                vec![&mut binary_expression.left, &mut binary_expression.right]
+            }
+            Self::Cast(_, expression) | Self::Field(expression, _) | Self::Address(expression) => {
+                vec![expression]
+            }
+            Self::Index(base, index, _) => vec![base, index],
+            Self::Call(_, expressions)
+            | Self::Intrinsic(_, expressions)
+            | Self::Initializer(_, expressions)
+            | Self::Tuple(expressions)
+            | Self::Format(_, expressions) => expressions.iter_mut().collect(),
+        };
+    }
 
-        if self.tokens.len() == 0 {
-            panic!(
-                "{}:{}:{}: Empty source file. Try writting a main function first.",
-                self.lexer.filename, 1, 1
+    // Levels of expressions down to the deepest leaf, without recursing so it is safe on
+    // expressions that are too deep
+    pub fn get_depth(&self) -> usize {
+        let mut pending: Vec<(&Expression, usize)> = vec![(self, 1)];
+        let mut depth = 0;
+
+        while let Some((expression, level)) = pending.pop() {
+            depth = depth.max(level);
+            pending.extend(
+                expression
+                    .get_children()
+                    .into_iter()
+                    .map(|child| (child, level + 1)),
             );
         }
 
-        self.lookahead_token = Some(self.tokens.get(0).expect("Unreachable").clone());
+        return depth;
     }
 
-    pub fn generate_program(&mut self) -> Program {
-        return self.next_program();
+    // Expressions with a memory location that can be addressed
+    pub fn is_place(&self) -> bool {
+        return match self {
+            Self::Local(_) | Self::Index(..) | Self::Field(..) => true,
+            _ => false,
+        };
     }
 
-    fn next_token(&mut self) -> Option<Token> {
-        if self.position + 1 <= self.tokens.len() {
-            if let Some(token) = self.tokens.get(self.position) {
-                self.current_token = Some(token.to_owned());
-            } else {
-                self.current_token = None;
+    // Integer literals take the type of the other operand, i64 by default
+    pub fn is_untyped(&self) -> bool {
+        return match self {
+            Self::NumberLiteral(_) => true,
+            // Chains of operators lean left, the right operand ends the check soonest
+            Self::Binary(binary_expression) => {
+                binary_expression.right.is_untyped() && binary_expression.left.is_untyped()
             }
+            _ => false,
+        };
+    }
 
-            if let Some(token) = self.tokens.get(self.position + 1) {
-                self.lookahead_token = Some(token.to_owned());
-            } else {
-                self.lookahead_token = None;
+    pub fn get_type(&self, locals: &LocalStack, functions: &Vec<Function>) -> Type {
+        return match self {
+            Self::NumberLiteral(_) | Self::StringLiteral(_) | Self::Format(..) => Type::I64,
+            Self::FloatLiteral(_) => Type::F64,
+            Self::Binary(binary_expression) => {
+                let right_type = binary_expression.right.get_type(locals, functions);
+
+                // Pointer arithmetic always results in the pointer type
+                if binary_expression.left.is_untyped() || right_type.is_pointer() {
+                    right_type
+                } else {
+                    binary_expression.left.get_type(locals, functions)
+                }
             }
+            Self::Cast(cast_type, _) => cast_type.clone(),
+            Self::Index(base, ..) => base
+                .get_type(locals, functions)
+                .get_element()
+                .expect("Unreachable")
+                .clone(),
+            Self::Field(base, field) => match base.get_type(locals, functions) {
+                Type::Struct(layout) => layout.fields[*field].field_type.clone(),
+                _ => panic!("Unreachable"),
+            },
+            Self::Initializer(initializer_type, _) => initializer_type.clone(),
+            Self::Tuple(elements) => Type::Tuple(
+                elements
+                    .iter()
+                    .map(|element| element.get_type(locals, functions))
+                    .collect(),
+            ),
+            Self::Address(expression) => match expression.get_type(locals, functions) {
+                Type::Array(element, _) => Type::Pointer(element),
+                expression_type => Type::Pointer(Box::new(expression_type)),
+            },
+            Self::Local(index) => locals.get(*index).expect("Unreachable").local_type.clone(),
+            Self::Call(id, _) => functions
+                .get(id.0)
+                .expect("Unreachable")
+                .return_type
+                .clone(),
+            Self::Intrinsic(intrinsic, expressions) => {
+                let types: Vec<Type> = expressions
+                    .iter()
+                    .map(|expression| expression.get_type(locals, functions))
+                    .collect();
 
-            self.position += 1;
-            return self.current_token.clone();
-        } else {
-            return None;
-        }
+                intrinsic.get_type(&types).expect("Unreachable")
+            }
+        };
     }
+}
 
-    fn next_program(&mut self) -> Program {
-        let mut program = Program::new();
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Assembly {
+    pub filename: String,
+    pub source: String,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Program {
+    pub functions: Vec<Function>,
+    pub strings: Vec<String>,
+    pub assembly: Vec<Assembly>,
+    pub sources: Vec<String>,
+    // Every token of the program, in include order. Left out when serialized, `ezlang tokens`
+    // prints them for tools that need them.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub tokens: Vec<Token>,
+    // Declared structs with the number of functions declared before them
+    pub structs: Vec<(usize, Struct)>,
+    // Declared constants with the number of functions declared before them
+    pub constants: Vec<(usize, Constant)>,
+    // Declared generic functions with the number of functions declared before them
+    pub generics: Vec<(usize, Generic)>,
+    pub table: FunctionTable,
+}
+
+impl Program {
+    fn new() -> Self {
+        Self {
+            functions: Vec::new(),
+            strings: Vec::new(),
+            assembly: Vec::new(),
+            sources: Vec::new(),
+            tokens: Vec::new(),
+            structs: Vec::new(),
+            constants: Vec::new(),
+            generics: Vec::new(),
+            table: FunctionTable::default(),
+        }
+    }
+
+    // Functions run before main, in the order they are called
+    pub fn get_constructors(&self) -> Vec<FunctionId> {
+        return (0..self.functions.len())
+            .filter(|index| self.functions[*index].attributes.constructor)
+            .map(FunctionId)
+            .collect();
+    }
+
+    // Functions run after main, in the order they are called
+    pub fn get_destructors(&self) -> Vec<FunctionId> {
+        return (0..self.functions.len())
+            .rev()
+            .filter(|index| self.functions[*index].attributes.destructor)
+            .map(FunctionId)
+            .collect();
+    }
+
+    // Whether each string is only ever read: format chunks, literals read by write,
+    // parse_int, memcpy and memcmp and literals used as pointers, which are never written
+    // through. Any other literal can end up written like a buffer.
+    pub fn get_read_only_strings(&self) -> Vec<bool> {
+        let mut read_only = vec![true; self.strings.len()];
+
+        let mut expressions: Vec<&Expression> = self
+            .functions
+            .iter()
+            .filter_map(|function| function.body.as_ref())
+            .flat_map(|body| body.statements.iter())
+            .flat_map(|statement| statement.get_expressions())
+            .collect();
+
+        while let Some(expression) = expressions.pop() {
+            let read: Vec<&Expression> = match expression {
+                Expression::StringLiteral(index) => {
+                    read_only[*index] = false;
+                    Vec::new()
+                }
+                Expression::Intrinsic(Intrinsic::Write | Intrinsic::Memcpy, arguments) => {
+                    vec![&arguments[1]]
+                }
+                Expression::Intrinsic(Intrinsic::ParseInt, arguments) => vec![&arguments[0]],
+                Expression::Intrinsic(Intrinsic::Memcmp, arguments) => {
+                    vec![&arguments[0], &arguments[1]]
+                }
+                Expression::Cast(Type::Pointer(_), operand) => vec![operand],
+                _ => Vec::new(),
+            };
+
+            // Literals in a read position are not visited, so they stay read-only
+            expressions.extend(expression.get_children().into_iter().filter(|child| {
+                return !matches!(child, Expression::StringLiteral(_))
+                    || !read.iter().any(|read| std::ptr::eq(*read, *child));
+            }));
+        }
+
+        return read_only;
+    }
+}
+
+// Grammar accepted by the parser, update it together with the next_* functions
+// so the exported documentation never drifts from what is implemented
+#[derive(Debug)]
+pub enum Symbol {
+    Terminal(&'static str),
+    NonTerminal(&'static str),
+    // Described in prose, for lexical rules
+    Special(&'static str),
+    Sequence(&'static [Symbol]),
+    Choice(&'static [Symbol]),
+    Optional(&'static Symbol),
+    Repeat(&'static Symbol),
+}
+
+#[derive(Debug)]
+pub struct Rule {
+    pub name: &'static str,
+    pub definition: Symbol,
+}
+
+pub static GRAMMAR: &[Rule] = &[
+    Rule {
+        name: "program",
+        definition: Symbol::Repeat(&Symbol::NonTerminal("item")),
+    },
+    Rule {
+        name: "item",
+        definition: Symbol::Choice(&[
+            Symbol::NonTerminal("include"),
+            Symbol::NonTerminal("include_asm"),
+            Symbol::NonTerminal("struct"),
+            Symbol::NonTerminal("constant"),
+            Symbol::NonTerminal("static_assert"),
+            Symbol::NonTerminal("function"),
+        ]),
+    },
+    Rule {
+        name: "include",
+        definition: Symbol::Sequence(&[
+            Symbol::Terminal("include"),
+            Symbol::NonTerminal("string"),
+            Symbol::Terminal(";"),
+        ]),
+    },
+    Rule {
+        name: "include_asm",
+        definition: Symbol::Sequence(&[
+            Symbol::Terminal("include_asm"),
+            Symbol::NonTerminal("string"),
+            Symbol::Terminal(";"),
+        ]),
+    },
+    Rule {
+        name: "struct",
+        definition: Symbol::Sequence(&[
+            Symbol::Terminal("struct"),
+            Symbol::NonTerminal("identifier"),
+            Symbol::Terminal("{"),
+            Symbol::NonTerminal("field"),
+            Symbol::Repeat(&Symbol::NonTerminal("field")),
+            Symbol::Terminal("}"),
+        ]),
+    },
+    Rule {
+        name: "constant",
+        definition: Symbol::Sequence(&[
+            Symbol::Terminal("const"),
+            Symbol::NonTerminal("identifier"),
+            Symbol::Optional(&Symbol::Sequence(&[
+                Symbol::Terminal(":"),
+                Symbol::NonTerminal("type"),
+            ])),
+            Symbol::Terminal("="),
+            Symbol::NonTerminal("expression"),
+            Symbol::Terminal(";"),
+        ]),
+    },
+    Rule {
+        name: "static_assert",
+        definition: Symbol::Sequence(&[
+            Symbol::Terminal("static_assert"),
+            Symbol::Terminal("("),
+            Symbol::NonTerminal("expression"),
+            Symbol::Terminal(","),
+            Symbol::NonTerminal("string"),
+            Symbol::Terminal(")"),
+            Symbol::Terminal(";"),
+        ]),
+    },
+    Rule {
+        name: "field",
+        definition: Symbol::Sequence(&[
+            Symbol::NonTerminal("identifier"),
+            Symbol::Terminal(":"),
+            Symbol::NonTerminal("type"),
+            Symbol::Optional(&Symbol::Terminal(",")),
+        ]),
+    },
+    Rule {
+        name: "function",
+        definition: Symbol::Sequence(&[
+            Symbol::Repeat(&Symbol::NonTerminal("attribute")),
+            Symbol::Optional(&Symbol::Terminal("pub")),
+            Symbol::Optional(&Symbol::Terminal("extern")),
+            Symbol::Terminal("fn"),
+            Symbol::NonTerminal("identifier"),
+            Symbol::Optional(&Symbol::NonTerminal("type_parameters")),
+            Symbol::Terminal(":"),
+            Symbol::Terminal("("),
+            Symbol::Optional(&Symbol::Sequence(&[
+                Symbol::NonTerminal("argument"),
+                Symbol::Repeat(&Symbol::Sequence(&[
+                    Symbol::Terminal(","),
+                    Symbol::NonTerminal("argument"),
+                ])),
+            ])),
+            Symbol::Terminal(")"),
+            Symbol::Optional(&Symbol::Sequence(&[
+                Symbol::Terminal("->"),
+                Symbol::NonTerminal("return_type"),
+            ])),
+            Symbol::Choice(&[Symbol::Terminal(";"), Symbol::NonTerminal("block")]),
+        ]),
+    },
+    Rule {
+        name: "attribute",
+        definition: Symbol::Sequence(&[
+            Symbol::Terminal("#"),
+            Symbol::Terminal("["),
+            Symbol::Choice(&[
+                Symbol::Sequence(&[
+                    Symbol::Terminal("section"),
+                    Symbol::Terminal("("),
+                    Symbol::NonTerminal("string"),
+                    Symbol::Terminal(")"),
+                ]),
+                Symbol::Terminal("constructor"),
+                Symbol::Terminal("destructor"),
+            ]),
+            Symbol::Terminal("]"),
+        ]),
+    },
+    Rule {
+        name: "type_parameters",
+        definition: Symbol::Sequence(&[
+            Symbol::Terminal("<"),
+            Symbol::NonTerminal("type_parameter"),
+            Symbol::Repeat(&Symbol::Sequence(&[
+                Symbol::Terminal(","),
+                Symbol::NonTerminal("type_parameter"),
+            ])),
+            Symbol::Terminal(">"),
+        ]),
+    },
+    Rule {
+        name: "type_parameter",
+        definition: Symbol::Sequence(&[
+            Symbol::NonTerminal("identifier"),
+            Symbol::Terminal(":"),
+            Symbol::Terminal("int"),
+        ]),
+    },
+    Rule {
+        name: "argument",
+        definition: Symbol::Sequence(&[
+            Symbol::NonTerminal("identifier"),
+            Symbol::Optional(&Symbol::Sequence(&[
+                Symbol::Terminal(":"),
+                Symbol::NonTerminal("type"),
+            ])),
+        ]),
+    },
+    Rule {
+        name: "return_type",
+        definition: Symbol::Choice(&[
+            Symbol::NonTerminal("type"),
+            Symbol::Sequence(&[
+                Symbol::Terminal("("),
+                Symbol::NonTerminal("type"),
+                Symbol::Terminal(","),
+                Symbol::NonTerminal("type"),
+                Symbol::Terminal(")"),
+            ]),
+        ]),
+    },
+    Rule {
+        name: "type",
+        definition: Symbol::Choice(&[
+            Symbol::Sequence(&[Symbol::Terminal("*"), Symbol::NonTerminal("type")]),
+            Symbol::Sequence(&[
+                Symbol::Terminal("["),
+                Symbol::NonTerminal("type"),
+                Symbol::Terminal(";"),
+                Symbol::NonTerminal("expression"),
+                Symbol::Terminal("]"),
+            ]),
+            Symbol::NonTerminal("identifier"),
+        ]),
+    },
+    Rule {
+        name: "block",
+        definition: Symbol::Sequence(&[
+            Symbol::Terminal("{"),
+            Symbol::Repeat(&Symbol::NonTerminal("statement")),
+            Symbol::Terminal("}"),
+        ]),
+    },
+    Rule {
+        name: "statement",
+        definition: Symbol::Choice(&[
+            Symbol::NonTerminal("return"),
+            Symbol::NonTerminal("declaration"),
+            Symbol::NonTerminal("destructure"),
+            Symbol::NonTerminal("assignment"),
+            Symbol::NonTerminal("store"),
+            Symbol::NonTerminal("static_assert"),
+            Symbol::NonTerminal("goto"),
+            Symbol::NonTerminal("label"),
+            Symbol::Sequence(&[Symbol::NonTerminal("call"), Symbol::Terminal(";")]),
+        ]),
+    },
+    Rule {
+        name: "goto",
+        definition: Symbol::Sequence(&[
+            Symbol::Terminal("goto"),
+            Symbol::NonTerminal("identifier"),
+            Symbol::Terminal(";"),
+        ]),
+    },
+    Rule {
+        name: "label",
+        definition: Symbol::Sequence(&[Symbol::NonTerminal("identifier"), Symbol::Terminal(":")]),
+    },
+    Rule {
+        name: "return",
+        definition: Symbol::Sequence(&[
+            Symbol::Terminal("return"),
+            Symbol::Choice(&[
+                Symbol::NonTerminal("expression"),
+                Symbol::NonTerminal("arguments"),
+            ]),
+            Symbol::Terminal(";"),
+        ]),
+    },
+    Rule {
+        name: "declaration",
+        definition: Symbol::Sequence(&[
+            Symbol::Terminal("var"),
+            Symbol::NonTerminal("identifier"),
+            Symbol::Choice(&[
+                Symbol::Sequence(&[
+                    Symbol::Terminal(":"),
+                    Symbol::NonTerminal("type"),
+                    Symbol::Optional(&Symbol::Choice(&[
+                        Symbol::Sequence(&[
+                            Symbol::Terminal("="),
+                            Symbol::NonTerminal("expression"),
+                        ]),
+                        Symbol::Sequence(&[Symbol::Terminal("@"), Symbol::Terminal("static")]),
+                    ])),
+                ]),
+                Symbol::Sequence(&[Symbol::Terminal("="), Symbol::NonTerminal("expression")]),
+            ]),
+            Symbol::Terminal(";"),
+        ]),
+    },
+    Rule {
+        name: "destructure",
+        definition: Symbol::Sequence(&[
+            Symbol::Terminal("var"),
+            Symbol::Terminal("("),
+            Symbol::NonTerminal("identifier"),
+            Symbol::Repeat(&Symbol::Sequence(&[
+                Symbol::Terminal(","),
+                Symbol::NonTerminal("identifier"),
+            ])),
+            Symbol::Terminal(")"),
+            Symbol::Terminal("="),
+            Symbol::NonTerminal("expression"),
+            Symbol::Terminal(";"),
+        ]),
+    },
+    Rule {
+        name: "assignment",
+        definition: Symbol::Sequence(&[
+            Symbol::NonTerminal("identifier"),
+            Symbol::Terminal("="),
+            Symbol::NonTerminal("expression"),
+            Symbol::Terminal(";"),
+        ]),
+    },
+    Rule {
+        name: "store",
+        definition: Symbol::Sequence(&[
+            Symbol::NonTerminal("identifier"),
+            Symbol::NonTerminal("accessor"),
+            Symbol::Repeat(&Symbol::NonTerminal("accessor")),
+            Symbol::Terminal("="),
+            Symbol::NonTerminal("expression"),
+            Symbol::Terminal(";"),
+        ]),
+    },
+    Rule {
+        name: "accessor",
+        definition: Symbol::Choice(&[
+            Symbol::Sequence(&[
+                Symbol::Terminal("["),
+                Symbol::NonTerminal("expression"),
+                Symbol::Terminal("]"),
+            ]),
+            Symbol::Sequence(&[Symbol::Terminal("."), Symbol::NonTerminal("identifier")]),
+        ]),
+    },
+    Rule {
+        name: "expression",
+        definition: Symbol::Sequence(&[
+            Symbol::NonTerminal("operand"),
+            Symbol::Repeat(&Symbol::Sequence(&[
+                Symbol::NonTerminal("binary_operator"),
+                Symbol::NonTerminal("operand"),
+            ])),
+        ]),
+    },
+    Rule {
+        name: "operand",
+        definition: Symbol::Sequence(&[
+            Symbol::NonTerminal("primary"),
+            Symbol::Repeat(&Symbol::Choice(&[
+                Symbol::NonTerminal("accessor"),
+                Symbol::Sequence(&[Symbol::Terminal("as"), Symbol::NonTerminal("type")]),
+            ])),
+        ]),
+    },
+    Rule {
+        name: "primary",
+        definition: Symbol::Choice(&[
+            Symbol::NonTerminal("number"),
+            Symbol::NonTerminal("float"),
+            Symbol::NonTerminal("string"),
+            Symbol::NonTerminal("identifier"),
+            Symbol::NonTerminal("call"),
+            Symbol::NonTerminal("array"),
+            Symbol::NonTerminal("initializer"),
+            Symbol::Sequence(&[
+                Symbol::Terminal("("),
+                Symbol::NonTerminal("expression"),
+                Symbol::Terminal(")"),
+            ]),
+        ]),
+    },
+    Rule {
+        name: "call",
+        definition: Symbol::Sequence(&[
+            Symbol::Terminal("@"),
+            Symbol::Choice(&[
+                Symbol::Sequence(&[
+                    Symbol::Terminal("cast"),
+                    Symbol::Terminal("("),
+                    Symbol::NonTerminal("type"),
+                    Symbol::Terminal(","),
+                    Symbol::NonTerminal("expression"),
+                    Symbol::Terminal(")"),
+                ]),
+                Symbol::Sequence(&[
+                    Symbol::Terminal("len"),
+                    Symbol::Terminal("("),
+                    Symbol::NonTerminal("string"),
+                    Symbol::Terminal(")"),
+                ]),
+                Symbol::Sequence(&[
+                    Symbol::Terminal("sizeof"),
+                    Symbol::Terminal("("),
+                    Symbol::NonTerminal("type"),
+                    Symbol::Terminal(")"),
+                ]),
+                Symbol::Sequence(&[
+                    Symbol::NonTerminal("identifier"),
+                    Symbol::Optional(&Symbol::Sequence(&[
+                        Symbol::Terminal("::"),
+                        Symbol::NonTerminal("identifier"),
+                    ])),
+                    Symbol::NonTerminal("arguments"),
+                ]),
+            ]),
+        ]),
+    },
+    Rule {
+        name: "arguments",
+        definition: Symbol::Sequence(&[
+            Symbol::Terminal("("),
+            Symbol::Optional(&Symbol::Sequence(&[
+                Symbol::NonTerminal("expression"),
+                Symbol::Repeat(&Symbol::Sequence(&[
+                    Symbol::Terminal(","),
+                    Symbol::NonTerminal("expression"),
+                ])),
+            ])),
+            Symbol::Terminal(")"),
+        ]),
+    },
+    Rule {
+        name: "array",
+        definition: Symbol::Sequence(&[
+            Symbol::Terminal("["),
+            Symbol::Optional(&Symbol::Sequence(&[
+                Symbol::NonTerminal("expression"),
+                Symbol::Repeat(&Symbol::Sequence(&[
+                    Symbol::Terminal(","),
+                    Symbol::NonTerminal("expression"),
+                ])),
+            ])),
+            Symbol::Terminal("]"),
+        ]),
+    },
+    Rule {
+        name: "initializer",
+        definition: Symbol::Sequence(&[
+            Symbol::NonTerminal("identifier"),
+            Symbol::Terminal("{"),
+            Symbol::Repeat(&Symbol::Sequence(&[
+                Symbol::NonTerminal("identifier"),
+                Symbol::Terminal(":"),
+                Symbol::NonTerminal("expression"),
+                Symbol::Optional(&Symbol::Terminal(",")),
+            ])),
+            Symbol::Terminal("}"),
+        ]),
+    },
+    Rule {
+        name: "binary_operator",
+        definition: Symbol::Choice(&[
+            Symbol::Terminal("+"),
+            Symbol::Terminal("-"),
+            Symbol::Terminal("*"),
+            Symbol::Terminal("/"),
+            Symbol::Terminal("&"),
+            Symbol::Terminal("|"),
+            Symbol::Terminal("^"),
+        ]),
+    },
+    Rule {
+        name: "identifier",
+        definition: Symbol::Special("a letter or _ followed by letters, digits or _"),
+    },
+    Rule {
+        name: "number",
+        definition: Symbol::Special(
+            "decimal digits, or a base, # and digits in that base like 16#ff, then optionally \
+             the integer type of the literal like 255u8, one of u8, u16, u32, u64, i8, i16, i32 \
+             and i64",
+        ),
+    },
+    Rule {
+        name: "float",
+        definition: Symbol::Special("decimal digits, . and decimal digits"),
+    },
+    Rule {
+        name: "string",
+        definition: Symbol::Special("characters between double quotes, with \\ escapes"),
+    },
+];
+
+// Bounds on the input, so programs that are too large fail with a diagnostic
+// instead of exhausting the compiler
+#[derive(Debug, Clone)]
+pub struct Limits {
+    // Parentheses and calls nested inside a single expression
+    pub expression_depth: usize,
+    // Operations, casts, accesses and calls one inside the other in a single expression,
+    // flat chains like `1 + 1 + 1` included
+    pub expression_length: usize,
+    // Pointers and arrays nested inside a single type
+    pub type_depth: usize,
+    // Tokens of the root file and every included file together
+    pub tokens: usize,
+    pub functions: usize,
+    // Variables of a single function, arguments included
+    pub locals: usize,
+    // Bytes of every string literal and format chunk together
+    pub string_bytes: usize,
+    // Whether include and include_asm may read other files, off for programs from untrusted users
+    pub includes: bool,
+    // Statements run by each call evaluated at compile time
+    pub const_steps: u64,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        return Self {
+            expression_depth: 256,
+            expression_length: 1024,
+            type_depth: 64,
+            tokens: 1 << 20,
+            functions: 1 << 12,
+            locals: 1 << 12,
+            string_bytes: 1 << 20,
+            includes: true,
+            const_steps: 1 << 20,
+        };
+    }
+}
+
+pub struct Parser {
+    lexer: Lexer,
+    tokens: Vec<Token>,
+    position: usize,
+    current_token: Option<Token>,
+    lookahead_token: Option<Token>,
+    functions: Vec<Function>,
+    // Ids and signatures of the functions, by name
+    table: FunctionTable,
+    structs: Vec<Struct>,
+    constants: Vec<Constant>,
+    generics: Vec<Generic>,
+    // Index of the function added for each generic function and list of types
+    instances: Vec<(usize, Vec<Type>, FunctionId)>,
+    // Types of the instance being parsed, by the name of their parameter
+    type_arguments: Vec<(String, Type)>,
+    // Instances being parsed, a generic function can not need itself
+    instantiating: Vec<(usize, Vec<Type>)>,
+    strings: Vec<String>,
+    return_type: Type,
+    // Keywords of files without an edition pragma
+    edition: Edition,
+    references: Vec<Spanned<Reference>>,
+    limits: Limits,
+    // Expressions being parsed, each call argument starts one inside the enclosing one
+    nesting: usize,
+    string_bytes: usize,
+    // Format chunks are never written, each distinct one is stored once
+    interned: HashMap<String, usize>,
+}
+
+impl Parser {
+    pub fn from_file(filename: &str, edition: Edition) -> Self {
+        return Self::from_lexer(Lexer::from_file(filename, edition), edition);
+    }
+
+    pub fn from_source(filename: &str, source: Vec<u8>, edition: Edition) -> Self {
+        return Self::from_lexer(Lexer::from_source(filename, source, edition), edition);
+    }
+
+    fn from_lexer(lexer: Lexer, edition: Edition) -> Self {
+        return Self {
+            lexer,
+            tokens: Vec::new(),
+            position: 0,
+            current_token: None,
+            lookahead_token: None,
+            functions: Vec::new(),
+            table: FunctionTable::default(),
+            structs: Vec::new(),
+            constants: Vec::new(),
+            generics: Vec::new(),
+            instances: Vec::new(),
+            type_arguments: Vec::new(),
+            instantiating: Vec::new(),
+            strings: Vec::new(),
+            return_type: Type::I64,
+            edition,
+            references: Vec::new(),
+            limits: Limits::default(),
+            nesting: 0,
+            string_bytes: 0,
+            interned: HashMap::new(),
+        };
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    pub fn generate_tokens(&mut self) {
+        let mut includes: Vec<PathBuf> = Vec::new();
+
+        if let Ok(path) = Path::new(&self.lexer.filename).canonicalize() {
+            includes.push(path);
+        }
+
+        let mut tokens: Vec<Token> = Vec::new();
+
+        Self::lex_tokens(
+            &mut self.lexer,
+            &mut tokens,
+            &mut includes,
+            self.edition,
+            &self.limits,
+        );
+
+        self.tokens = tokens;
+
+        if self.tokens.len() == 0 {
+            panic!(
+                "{}: error[E0014]: Empty source file, expected at least a `fn main: ()` function.",
+                Position::start(&self.lexer.filename)
+            );
+        }
+
+        self.lookahead_token = Some(self.tokens.get(0).expect("Unreachable").clone());
+    }
+
+    pub fn generate_program(&mut self) -> Program {
+        return self.next_program();
+    }
+
+    // Parses the tokens as a single function of the program in place of the one at the index,
+    // seeing only what was declared before it. Returns the function and the strings of the
+    // program followed by the new ones.
+    pub fn generate_function(
+        &mut self,
+        program: &Program,
+        index: usize,
+    ) -> (Function, Vec<String>) {
+        self.functions = program.functions[..index].to_vec();
+        self.table = FunctionTable::from_functions(&self.functions);
+        self.structs = program
+            .structs
+            .iter()
+            .filter(|(declared, _)| *declared <= index)
+            .map(|(_, layout)| layout.clone())
+            .collect();
+        self.constants = program
+            .constants
+            .iter()
+            .filter(|(declared, _)| *declared <= index)
+            .map(|(_, constant)| constant.clone())
+            .collect();
+        self.strings = program.strings.clone();
+        self.string_bytes = self.strings.iter().map(|string| string.len()).sum();
+        // Chunks of the other functions are not known, the compiler still shares their storage
+        self.interned.clear();
+
+        let function = self.next_function();
+
+        if let Some(token) = &self.lookahead_token {
+            panic!("{}: Unexpected token.", token.position);
+        }
+
+        return (function, self.strings.clone());
+    }
+
+    // Included files start with the edition given on the command line, not the one of their includer
+    // Appends the tokens of the file and the ones it includes, stopping at the first token over
+    // the limit so a huge program is not lexed to the end first
+    fn lex_tokens(
+        lexer: &mut Lexer,
+        tokens: &mut Vec<Token>,
+        includes: &mut Vec<PathBuf>,
+        edition: Edition,
+        limits: &Limits,
+    ) {
+        while let Some(token) = lexer.next() {
+            if let TokenType::Include = token.token_type {
+                if !limits.includes {
+                    panic!(
+                        "{}: Includes are not allowed in this program.",
+                        token.position
+                    );
+                }
+
+                let path = Self::lex_include_path(lexer, &token);
+
+                let canonical = match path.canonicalize() {
+                    Ok(canonical) => canonical,
+                    Err(_) => panic!(
+                        "{}: Included file {} does not exist.",
+                        token.position,
+                        path.display()
+                    ),
+                };
+
+                if includes.contains(&canonical) {
+                    panic!(
+                        "{}: Recursive include of {}.",
+                        token.position,
+                        path.display()
+                    );
+                }
+
+                log(
+                    Level::Debug,
+                    format_args!("including {}", canonical.display()),
+                );
+
+                includes.push(canonical);
+
+                let mut included = Lexer::from_file(&path.to_string_lossy(), edition);
+                Self::lex_tokens(&mut included, tokens, includes, edition, limits);
+
+                includes.pop();
+            } else if tokens.len() == limits.tokens {
+                panic!(
+                    "{}: error[E0017]: Program too large, it has more than {} tokens.",
+                    token.position, limits.tokens
+                );
+            } else {
+                tokens.push(token);
+            }
+        }
+    }
+
+    fn lex_include_path(lexer: &mut Lexer, include: &Token) -> PathBuf {
+        let path = match lexer.next() {
+            Some(Token {
+                token_type: TokenType::StringLiteral(path),
+                ..
+            }) => path,
+            _ => panic!("{}: Expected a file path after include.", include.position),
+        };
+
+        match lexer.next() {
+            Some(Token {
+                token_type: TokenType::Semicolon,
+                ..
+            }) => {}
+            _ => panic!("{}: Expected a semicolon after include.", include.position),
+        }
+
+        return Self::resolve_path(&include.position.file, &path);
+    }
+
+    fn resolve_path(from: &str, path: &str) -> PathBuf {
+        return match Path::new(from).parent() {
+            Some(directory) => directory.join(path),
+            None => PathBuf::from(path),
+        };
+    }
+
+    fn next_token(&mut self) -> Option<Token> {
+        if self.position + 1 <= self.tokens.len() {
+            if let Some(token) = self.tokens.get(self.position) {
+                set_processing(&token.position);
+                self.current_token = Some(token.to_owned());
+            } else {
+                self.current_token = None;
+            }
+
+            if let Some(token) = self.tokens.get(self.position + 1) {
+                self.lookahead_token = Some(token.to_owned());
+            } else {
+                self.lookahead_token = None;
+            }
+
+            self.position += 1;
+            return self.current_token.clone();
+        } else {
+            return None;
+        }
+    }
+
+    fn next_program(&mut self) -> Program {
+        let mut program = Program::new();
 
         while let Some(token) = &self.lookahead_token {
             match token.token_type {
-                TokenType::Function => {
-                    // TODO: Think about another way of storing functions
+                TokenType::Function | TokenType::Pub | TokenType::Extern | TokenType::Hash => {
+                    if self.functions.len() == self.limits.functions {
+                        panic!(
+                            "{}: error[E0017]: Program too large, it declares more than {} functions.",
+                            token.position,
+                            self.limits.functions
+                        );
+                    }
+
+                    if self.is_generic() {
+                        let generic = self.next_generic();
+                        program
+                            .generics
+                            .push((self.functions.len(), generic.clone()));
+                        self.generics.push(generic);
+                        continue;
+                    }
+
                     let function = self.next_function();
-                    self.functions.push(function);
+                    self.add_function(function);
+                }
+                TokenType::IncludeAsm => {
+                    self.next_token();
+                    let assembly = self.next_include_asm();
+                    program.assembly.push(assembly);
+                }
+                TokenType::Struct => {
+                    self.next_token();
+                    let layout = self.next_struct();
+                    program.structs.push((self.functions.len(), layout.clone()));
+                    self.structs.push(layout);
+                }
+                TokenType::Const => {
+                    self.next_token();
+                    let constant = self.next_constant();
+                    program
+                        .constants
+                        .push((self.functions.len(), constant.clone()));
+                    self.constants.push(constant);
+                }
+                TokenType::StaticAssert => {
+                    let token = token.clone();
+                    self.next_token();
+                    self.next_static_assert(&token, &LocalStack::new());
                 }
                 _ => {
+                    panic!("{}: Unexpected token.", token.position);
+                }
+            }
+        }
+
+        program.functions = self.functions.clone();
+        program.table = self.table.clone();
+        program.strings = self.strings.clone();
+
+        // Every source file that contributed tokens, in include order
+        for token in self.tokens.iter() {
+            if !program.sources.contains(&token.position.file) {
+                program.sources.push(token.position.file.to_owned());
+            }
+        }
+
+        program.tokens = self.tokens.clone();
+
+        return program;
+    }
+
+    fn next_function(&mut self) -> Function {
+        let attributes = self.next_attributes();
+
+        let public = match self.lookahead_token {
+            Some(Token {
+                token_type: TokenType::Pub,
+                ..
+            }) => {
+                self.next_token();
+                true
+            }
+            _ => false,
+        };
+
+        let external = match self.lookahead_token {
+            Some(Token {
+                token_type: TokenType::Extern,
+                ..
+            }) => {
+                self.next_token();
+                true
+            }
+            _ => false,
+        };
+
+        self.next_fn();
+
+        // Types of struct declarations before the function are not part of it
+        self.references = Vec::new();
+
+        if let Some(token) = self.next_token() {
+            if let TokenType::Identifier(function_name) = token.token_type {
+                let function_name = function_name.to_string();
+                let module = Self::get_module(&token.position);
+
+                // Instances share the name of their generic function
+                if let Some(Token {
+                    token_type: TokenType::LeftAngle,
+                    ..
+                }) = self.lookahead_token
+                {
+                    self.next_type_parameters();
+                }
+
+                let duplicated =
+                    self.table
+                        .find(&function_name)
+                        .iter()
+                        .any(|id| self.functions[*id].module == module)
+                        || self.generics.iter().any(|generic| {
+                            generic.name == function_name && generic.module == module
+                        });
+
+                if duplicated && self.type_arguments.is_empty() {
+                    panic!(
+                        "{}: error[E0013]: Duplicated function {}::{}.",
+                        token.position, module, function_name
+                    );
+                }
+
+                self.next_colon();
+
+                let mut locals = LocalStack::new();
+                let arguments = self.next_args(&mut locals);
+                let return_type = self.next_return_type();
+
+                self.return_type = return_type.clone();
+
+                // A declaration without body is implemented by included assembly
+                let body = match &self.lookahead_token {
+                    Some(Token {
+                        token_type: TokenType::Semicolon,
+                        ..
+                    }) => {
+                        self.next_semicolon();
+                        None
+                    }
+                    _ if external => {
+                        panic!("{}: Extern functions can not have a body.", token.position)
+                    }
+                    _ => Some(self.next_scope(&mut locals)),
+                };
+
+                if attributes.section.is_some() && body.is_none() {
+                    panic!(
+                        "{}: Only functions with a body can be placed in a section.",
+                        token.position
+                    );
+                }
+
+                // Hooks are called with nothing to pass them
+                if (attributes.constructor || attributes.destructor)
+                    && (body.is_none() || arguments.len() > 0)
+                {
+                    panic!(
+                        "{}: Constructors and destructors must have a body and no arguments.",
+                        token.position
+                    );
+                }
+
+                if locals.locals.len() > self.limits.locals {
+                    panic!(
+                        "{}: error[E0017]: Function {} too large, it declares more than {} variables.",
+                        token.position,
+                        function_name,
+                        self.limits.locals
+                    );
+                }
+
+                // The frame is addressed from rbp like the parts of a value
+                if locals.get_size() > MAX_TYPE_SIZE {
+                    panic!(
+                        "{}: error[E0017]: Function {} too large, its variables take more than {} bytes.",
+                        token.position, function_name, MAX_TYPE_SIZE
+                    );
+                }
+
+                // Functions from included files are mangled to avoid clashes,
+                // declarations keep the label of their assembly implementation
+                let label = match (&body, token.position.file == self.lexer.filename) {
+                    (Some(_), false) => format!("{}.{}", module, function_name),
+                    _ => function_name.to_owned(),
+                };
+
+                // Each instance of a generic function gets its own label, like max.u8
+                let label = self
+                    .type_arguments
+                    .iter()
+                    .fold(label, |label, (_, type_argument)| {
+                        format!("{}.{}", label, type_argument)
+                    });
+
+                for local in locals.locals.iter_mut() {
+                    if local.static_label.is_some() {
+                        local.static_label = Some(format!("ezstatic.{}.{}", label, local.label));
+                    }
+                }
+
+                // Instances of generic functions called from the body were added before this
+                // one, so its index is only known now
+                self.references.insert(
+                    0,
+                    Spanned::new(
+                        token.position.clone(),
+                        Reference::Function(FunctionId(self.functions.len())),
+                    ),
+                );
+
+                let function = Function {
+                    name: function_name,
+                    module,
+                    label,
+                    public,
+                    external,
+                    position: token.position.clone(),
+                    locals,
+                    arguments,
+                    return_type,
+                    body,
+                    references: std::mem::take(&mut self.references),
+                    attributes,
+                    type_arguments: self
+                        .type_arguments
+                        .iter()
+                        .map(|(_, type_argument)| type_argument.clone())
+                        .collect(),
+                };
+
+                // Extern functions are called like C, with arguments in registers
+                if function.external && !has_c_signature(&function) {
+                    panic!(
+                        "{}: Extern function {} can not be called like C, it takes at most {} \
+                         integers or pointers and {} floats and returns one of them or a pair \
+                         of integers.",
+                        function.position,
+                        function.name,
+                        INTEGER_REGISTERS.len(),
+                        FLOAT_REGISTERS
+                    );
+                }
+
+                return function;
+            } else {
+                panic!("{}: Expected function name", token.position);
+            }
+        } else {
+            panic!(
+                "{}: Expected function name but reached end of file.",
+                self.lexer.file_position
+            );
+        }
+    }
+
+    fn next_attributes(&mut self) -> Attributes {
+        let mut attributes = Attributes::default();
+
+        while let Some(Token {
+            token_type: TokenType::Hash,
+            ..
+        }) = self.lookahead_token
+        {
+            self.next_token();
+            self.next_l_bracket();
+
+            let token = match self.next_token() {
+                Some(token) => token,
+                None => panic!(
+                    "{}: Expected an attribute but reached end of file.",
+                    self.lexer.file_position
+                ),
+            };
+
+            match &token.token_type {
+                TokenType::Identifier(name) if *name == "section" => {
+                    self.next_l_par();
+                    let section = self.next_section_name();
+                    self.next_r_par();
+
+                    if attributes.section.replace(section).is_some() {
+                        panic!("{}: Duplicated attribute section.", token.position);
+                    }
+                }
+                TokenType::Identifier(name) if *name == "constructor" || *name == "destructor" => {
+                    let hook = match &*name.get_name() {
+                        "constructor" => &mut attributes.constructor,
+                        _ => &mut attributes.destructor,
+                    };
+
+                    if *hook {
+                        panic!("{}: Duplicated attribute {}.", token.position, name);
+                    }
+
+                    *hook = true;
+                }
+                _ => panic!(
+                    "{}: Unknown attribute, expected section, constructor or destructor.",
+                    token.position
+                ),
+            }
+
+            self.next_r_bracket();
+        }
+
+        return attributes;
+    }
+
+    // The name is written into the assembly as is, so only characters of symbols are allowed
+    fn next_section_name(&mut self) -> String {
+        let token = match self.next_token() {
+            Some(token) => token,
+            None => panic!(
+                "{}: Expected a section name but reached end of file.",
+                self.lexer.file_position
+            ),
+        };
+
+        let name = match token.token_type {
+            TokenType::StringLiteral(name) => name,
+            _ => panic!(
+                "{}: Expected a section name like \".boot\".",
+                token.position
+            ),
+        };
+
+        let valid = name
+            .chars()
+            .all(|character| character.is_ascii_alphanumeric() || "._".contains(character));
+
+        if name.is_empty() || !valid {
+            panic!(
+                "{}: Invalid section name {:?}, use letters, digits, dots and underscores.",
+                token.position, name
+            );
+        }
+
+        return name;
+    }
+
+    // Modules are named after the file stem of their source file
+    fn get_module(position: &Position) -> String {
+        return match Path::new(&position.file).file_stem() {
+            Some(stem) => stem.to_string_lossy().to_string(),
+            None => position.file.to_owned(),
+        };
+    }
+
+    // Unqualified names prefer the calling module and must be unique otherwise,
+    // private functions are only visible inside their own module
+    fn find_function(&self, token: &Token, module: Option<&str>, name: &str) -> FunctionId {
+        let current = Self::get_module(&token.position);
+
+        let (candidates, private): (Vec<FunctionId>, Vec<FunctionId>) = self
+            .table
+            .find(name)
+            .iter()
+            .filter(|id| module.map_or(true, |module| self.functions[**id].module == module))
+            .partition(|id| {
+                let function = &self.functions[**id];
+                function.public || function.module == current
+            });
+
+        if let ([], [id, ..]) = (candidates.as_slice(), private.as_slice()) {
+            let function = &self.functions[*id];
+
+            panic!(
+                "{}: error[E0007]: Function {}::{} is private, declare it with `pub fn` to use it from other modules.",
+                token.position, function.module, name
+            );
+        }
+
+        if let Some(id) = candidates
+            .iter()
+            .find(|id| self.functions[**id].module == current)
+        {
+            return *id;
+        }
+
+        // Suggestions only name functions the call could reach
+        let visible = self
+            .functions
+            .iter()
+            .filter(|function| {
+                (function.public || function.module == current)
+                    && module.map_or(true, |module| function.module == module)
+            })
+            .map(|function| function.name.as_str());
+
+        return match candidates.as_slice() {
+            [id] => *id,
+            [] => match module {
+                Some(module) => panic!(
+                    "{}: error[E0003]: Call to undefined function {}::{}.{}",
+                    token.position,
+                    module,
+                    name,
+                    get_suggestion(name, visible)
+                ),
+                None => panic!(
+                    "{}: error[E0003]: Call to undefined function {}.{}",
+                    token.position,
+                    name,
+                    get_suggestion(name, visible)
+                ),
+            },
+            _ => panic!(
+                "{}: error[E0008]: Ambiguous call to {}, qualify it with its module like {}::{}.",
+                token.position, name, self.functions[candidates[0]].module, name
+            ),
+        };
+    }
+
+    // Private generic functions are only found inside their own module, calls of the others
+    // prefer the calling module
+    fn find_generic(&self, token: &Token, module: Option<&str>, name: &str) -> Option<usize> {
+        let current = Self::get_module(&token.position);
+
+        let candidates: Vec<usize> = self
+            .generics
+            .iter()
+            .enumerate()
+            .filter(|(_, generic)| {
+                generic.name == name
+                    && module.map_or(true, |module| generic.module == module)
+                    && (generic.public || generic.module == current)
+            })
+            .map(|(index, _)| index)
+            .collect();
+
+        return candidates
+            .iter()
+            .find(|index| self.generics[**index].module == current)
+            .or(candidates.first())
+            .copied();
+    }
+
+    // Whether the function starting at the lookahead token has type parameters
+    fn is_generic(&self) -> bool {
+        let function = self.tokens[self.position..]
+            .iter()
+            .position(|token| matches!(token.token_type, TokenType::Function));
+
+        return match function.map(|function| &self.tokens[self.position + function..]) {
+            Some(
+                [_, Token {
+                    token_type: TokenType::Identifier(_),
+                    ..
+                }, Token {
+                    token_type: TokenType::LeftAngle,
+                    ..
+                }, ..],
+            ) => true,
+            _ => false,
+        };
+    }
+
+    fn next_generic(&mut self) -> Generic {
+        let start = self.position;
+        let attributes = self.next_attributes();
+
+        let public = match self.lookahead_token {
+            Some(Token {
+                token_type: TokenType::Pub,
+                ..
+            }) => {
+                self.next_token();
+                true
+            }
+            _ => false,
+        };
+
+        if let Some(Token {
+            token_type: TokenType::Extern,
+            position,
+        }) = &self.lookahead_token
+        {
+            panic!("{}: Extern functions can not be generic.", position);
+        }
+
+        self.next_fn();
+
+        let token = self.next_token().expect("Unreachable");
+
+        let name = match &token.token_type {
+            TokenType::Identifier(name) => name.to_string(),
+            _ => panic!("Unreachable"),
+        };
+
+        let module = Self::get_module(&token.position);
+
+        if self
+            .functions
+            .iter()
+            .any(|function| function.name == name && function.module == module)
+            || self
+                .generics
+                .iter()
+                .any(|generic| generic.name == name && generic.module == module)
+        {
+            panic!(
+                "{}: error[E0013]: Duplicated function {}::{}.",
+                token.position, module, name
+            );
+        }
+
+        // Hooks are called with no arguments to infer the types from
+        if attributes.constructor || attributes.destructor {
+            panic!(
+                "{}: Constructors and destructors can not be generic.",
+                token.position
+            );
+        }
+
+        let parameters = self.next_type_parameters();
+
+        self.next_colon();
+        self.next_l_par();
+
+        // Tokens of each argument, only the ones declared with a bare type parameter like
+        // `a: T` tell the type of the parameter
+        let mut arguments: Vec<Vec<Token>> = vec![Vec::new()];
+        let mut depth = 0;
+
+        loop {
+            let argument = match self.next_token() {
+                Some(argument) => argument,
+                None => panic!(
+                    "{}: Expected the arguments of {} but reached end of file.",
+                    token.position, name
+                ),
+            };
+
+            match argument.token_type {
+                TokenType::RightPar if depth == 0 => break,
+                TokenType::Comma if depth == 0 => {
+                    arguments.push(Vec::new());
+                    continue;
+                }
+                TokenType::LeftPar | TokenType::LeftBracket => depth += 1,
+                TokenType::RightPar | TokenType::RightBracket => depth -= 1,
+                _ => {}
+            }
+
+            arguments.last_mut().expect("Unreachable").push(argument);
+        }
+
+        let inferred: Vec<Option<usize>> = arguments
+            .iter()
+            .filter(|argument| !argument.is_empty())
+            .map(|argument| match argument.as_slice() {
+                [_, Token {
+                    token_type: TokenType::Colon,
+                    ..
+                }, Token {
+                    token_type: TokenType::Identifier(type_name),
+                    ..
+                }] => parameters
+                    .iter()
+                    .position(|parameter| *type_name == parameter.as_str()),
+                _ => None,
+            })
+            .collect();
+
+        for (index, parameter) in parameters.iter().enumerate() {
+            if !inferred.contains(&Some(index)) {
+                panic!(
+                    "{}: Type parameter {} of {} is not the type of any argument, calls could not infer it.",
+                    token.position, parameter, name
+                );
+            }
+        }
+
+        // The return type is checked with the body of each instance
+        loop {
+            match &self.lookahead_token {
+                Some(Token {
+                    token_type: TokenType::LeftBrace,
+                    ..
+                }) => break,
+                Some(Token {
+                    token_type: TokenType::Semicolon,
+                    ..
+                })
+                | None => panic!("{}: Generic functions must have a body.", token.position),
+                _ => {
+                    self.next_token();
+                }
+            }
+        }
+
+        let mut depth = 0;
+
+        while let Some(body) = self.next_token() {
+            match body.token_type {
+                TokenType::LeftBrace => depth += 1,
+                TokenType::RightBrace if depth == 1 => break,
+                TokenType::RightBrace => depth -= 1,
+                _ => {}
+            }
+        }
+
+        return Generic {
+            name,
+            module,
+            public,
+            position: token.position,
+            start,
+            parameters,
+            inferred,
+        };
+    }
+
+    // Type parameters like `<T: int, U: int>`, int is the only bound and allows any integer type
+    fn next_type_parameters(&mut self) -> Vec<String> {
+        self.next_token();
+
+        let mut parameters: Vec<String> = Vec::new();
+
+        loop {
+            let token = match self.next_token() {
+                Some(token) => token,
+                None => panic!(
+                    "{}: Expected a type parameter but reached end of file.",
+                    self.lexer.file_position
+                ),
+            };
+
+            let name = match &token.token_type {
+                TokenType::Identifier(name) => name.to_string(),
+                _ => panic!("{}: Expected a type parameter.", token.position),
+            };
+
+            if parameters.contains(&name) {
+                panic!("{}: Duplicated type parameter {}.", token.position, name);
+            }
+
+            if Type::from_name(&name).is_some()
+                || self.structs.iter().any(|layout| layout.name == name)
+            {
+                panic!(
+                    "{}: Type parameter {} hides the type with the same name.",
+                    token.position, name
+                );
+            }
+
+            match (self.next_token(), self.next_token()) {
+                (
+                    Some(Token {
+                        token_type: TokenType::Colon,
+                        ..
+                    }),
+                    Some(Token {
+                        token_type: TokenType::Identifier(bound),
+                        ..
+                    }),
+                ) if bound == "int" => {}
+                _ => panic!(
+                    "{}: Expected the bound of {} like `{}: int`, only integer types are allowed.",
+                    token.position, name, name
+                ),
+            }
+
+            let last = match self.next_token() {
+                Some(Token {
+                    token_type: TokenType::Comma,
+                    ..
+                }) => false,
+                Some(Token {
+                    token_type: TokenType::RightAngle,
+                    ..
+                }) => true,
+                _ => panic!(
+                    "{}: Expected a comma or > after the type parameter {}.",
+                    token.position, name
+                ),
+            };
+
+            parameters.push(name);
+
+            if last {
+                break;
+            }
+        }
+
+        return parameters;
+    }
+
+    fn next_struct(&mut self) -> Struct {
+        let token = match self.next_token() {
+            Some(token) => token,
+            None => panic!(
+                "{}: Expected struct name but reached end of file.",
+                self.lexer.file_position
+            ),
+        };
+
+        let name = match &token.token_type {
+            TokenType::Identifier(name) => name.to_string(),
+            _ => panic!("{}: Expected struct name.", token.position),
+        };
+
+        if Type::from_name(&name).is_some() || self.structs.iter().any(|s| s.name == name) {
+            panic!("{}: Duplicated type {}.", token.position, name);
+        }
+
+        self.next_l_brace();
+
+        let mut layout = Struct {
+            name,
+            fields: Vec::new(),
+            size: 0,
+            alignment: 1,
+        };
+
+        while let Some(field_token) = self.lookahead_token.clone() {
+            match field_token.token_type {
+                TokenType::RightBrace => break,
+                TokenType::Identifier(field_name) => {
+                    self.next_token();
+
+                    let field_type = match self.next_type_annotation() {
+                        Some(field_type) => field_type,
+                        None => panic!(
+                            "{}: Expected a type for field {}.",
+                            field_token.position, field_name
+                        ),
+                    };
+
+                    let field_name = field_name.to_string();
+
+                    if layout.find(&field_name).is_some() {
+                        panic!("{}: Duplicated field {}.", field_token.position, field_name);
+                    }
+
+                    let size = field_type.get_size();
+                    let alignment = field_type.get_alignment();
+                    let offset = layout.size.next_multiple_of(alignment);
+
+                    self.check_type_size(&field_token, offset.checked_add(size));
+
+                    layout.fields.push(Field {
+                        name: field_name,
+                        field_type,
+                        offset,
+                    });
+
+                    layout.size = offset + size;
+                    layout.alignment = layout.alignment.max(alignment);
+
+                    if let Some(Token {
+                        token_type: TokenType::Comma,
+                        ..
+                    }) = self.lookahead_token
+                    {
+                        self.next_comma();
+                    }
+                }
+                _ => {
+                    panic!("{}: Expected a field name.", field_token.position);
+                }
+            }
+        }
+
+        self.next_r_brace();
+
+        if layout.fields.len() == 0 {
+            panic!("{}: Struct {} has no fields.", token.position, layout.name);
+        }
+
+        // Padding at the end keeps every element of an array aligned
+        layout.size = layout.size.next_multiple_of(layout.alignment);
+
+        self.check_type_size(&token, Some(layout.size));
+
+        return layout;
+    }
+
+    fn next_constant(&mut self) -> Constant {
+        let token = match self.next_token() {
+            Some(token) => token,
+            None => panic!(
+                "{}: Expected constant name but reached end of file.",
+                self.lexer.file_position
+            ),
+        };
+
+        let name = match &token.token_type {
+            TokenType::Identifier(name) => name.to_string(),
+            _ => panic!("{}: Expected constant name.", token.position),
+        };
+
+        if self.constants.iter().any(|constant| constant.name == name) {
+            panic!("{}: Duplicated constant {}.", token.position, name);
+        }
+
+        let annotation = self.next_type_annotation();
+
+        self.next_equals();
+
+        // Constants are declared outside of functions, no variable is visible
+        let locals = LocalStack::new();
+        let expression = self.next_expression(&locals, false);
+
+        self.next_semicolon();
+
+        let constant_type = match annotation {
+            Some(annotation) => {
+                self.expect_assignable(&token, &annotation, &expression, &locals);
+                annotation
+            }
+            None => expression.get_type(&locals, &self.functions),
+        };
+
+        if !constant_type.is_integer() {
+            panic!(
+                "{}: Constants must be integers, found {}.",
+                token.position, constant_type
+            );
+        }
+
+        let value = self.evaluate_constant(&token, &expression, &locals);
+
+        return Constant {
+            name,
+            value: wrap(value, &constant_type),
+            constant_type,
+            position: token.position,
+        };
+    }
+
+    // Value of an expression the parser needs right away, like the length of an array
+    fn evaluate_constant(
+        &self,
+        token: &Token,
+        expression: &Expression,
+        locals: &LocalStack,
+    ) -> u64 {
+        let mut evaluator = CallEvaluator {
+            parser: self,
+            token,
+            locals,
+        };
+        let expression = evaluator.fold_expression(expression.clone());
+
+        return match evaluate(&expression, locals, &self.functions) {
+            Ok(value) => value,
+            Err(reason) => panic!(
+                "{}: error[E0019]: Expected a constant expression, {}.",
+                token.position, reason
+            ),
+        };
+    }
+
+    // Fails the compilation with the message when the condition, a constant expression, is zero
+    fn next_static_assert(&mut self, token: &Token, locals: &LocalStack) {
+        self.next_l_par();
+
+        let condition = self.next_expression(locals, true);
+
+        self.next_comma();
+
+        let message = match self.next_token() {
+            Some(Token {
+                token_type: TokenType::StringLiteral(message),
+                ..
+            }) => message,
+            _ => panic!(
+                "{}: Expected the message of the assertion as a string literal.",
+                token.position
+            ),
+        };
+
+        self.next_r_par();
+        self.next_semicolon();
+
+        if self.evaluate_constant(token, &condition, locals) == 0 {
+            panic!(
+                "{}: error[E0021]: Static assertion failed: {}",
+                token.position, message
+            );
+        }
+    }
+
+    fn next_include_asm(&mut self) -> Assembly {
+        if let Some(token) = self.next_token() {
+            if !self.limits.includes {
+                panic!(
+                    "{}: Includes are not allowed in this program.",
+                    token.position
+                );
+            }
+
+            if let TokenType::StringLiteral(path) = token.token_type {
+                let filename = Self::resolve_path(&token.position.file, &path);
+
+                let source = match read_to_string(&filename) {
+                    Ok(source) => source,
+                    Err(_) => panic!(
+                        "{}: Could not read included assembly file {}.",
+                        token.position,
+                        filename.display()
+                    ),
+                };
+
+                self.next_semicolon();
+
+                return Assembly {
+                    filename: filename.to_string_lossy().to_string(),
+                    source,
+                };
+            } else {
+                panic!(
+                    "{}: Expected a file path after include_asm.",
+                    token.position
+                );
+            }
+        } else {
+            panic!(
+                "{}: Expected a file path but reached end of file.",
+                self.lexer.file_position
+            );
+        }
+    }
+
+    fn next_args(&mut self, locals: &mut LocalStack) -> Vec<usize> {
+        self.next_l_par();
+
+        let mut args: Vec<usize> = Vec::new();
+
+        while let Some((label, arg_type, position)) = self.next_arg() {
+            let index = locals.insert(label, arg_type);
+            self.add_reference(&position, Reference::Local(index));
+            args.push(index);
+        }
+
+        self.next_r_par();
+
+        return args;
+    }
+
+    fn next_return_type(&mut self) -> Type {
+        if let Some(Token {
+            token_type: TokenType::Arrow,
+            ..
+        }) = self.lookahead_token
+        {
+            let token = self.next_token().expect("Unreachable");
+
+            if let Some(Token {
+                token_type: TokenType::LeftPar,
+                ..
+            }) = self.lookahead_token
+            {
+                return self.next_tuple_type();
+            }
+
+            let return_type = self.next_type();
+
+            if return_type.is_aggregate() {
+                panic!(
+                    "{}: {} can not be returned by value, return a pointer instead.",
+                    token.position, return_type
+                );
+            }
+
+            return return_type;
+        }
+
+        return Type::I64;
+    }
+
+    // Tuples are returned in rax and rdx, so they hold exactly two scalars
+    fn next_tuple_type(&mut self) -> Type {
+        let token = self.next_token().expect("Unreachable");
+
+        let first = self.next_type();
+        self.next_comma();
+        let second = self.next_type();
+
+        if let Some(Token {
+            token_type: TokenType::Comma,
+            ..
+        }) = self.lookahead_token
+        {
+            panic!("{}: Tuples can only hold two values.", token.position);
+        }
+
+        self.next_r_par();
+
+        for element in [&first, &second] {
+            if element.is_aggregate() {
+                panic!(
+                    "{}: {} can not be returned by value, return a pointer instead.",
+                    token.position, element
+                );
+            }
+        }
+
+        return Type::Tuple(vec![first, second]);
+    }
+
+    fn next_type_annotation(&mut self) -> Option<Type> {
+        if let Some(Token {
+            token_type: TokenType::Colon,
+            ..
+        }) = self.lookahead_token
+        {
+            self.next_token();
+            return Some(self.next_type());
+        }
+
+        return None;
+    }
+
+    fn next_type(&mut self) -> Type {
+        // Pointers and arrays wrap the type after them, they wait here until it is parsed so
+        // deeply nested types do not recurse
+        let mut wrappers: Vec<Token> = Vec::new();
+
+        let mut next_type = loop {
+            let token = match self.next_token() {
+                Some(token) => token,
+                None => panic!(
+                    "{}: Expected a type but reached end of file.",
+                    self.lexer.file_position
+                ),
+            };
+
+            match &token.token_type {
+                TokenType::BinaryOperation(BinaryOperator::Mul) | TokenType::LeftBracket => {
+                    if wrappers.len() == self.limits.type_depth {
+                        panic!(
+                            "{}: Type too complex, it nests more than {} pointers and arrays.",
+                            token.position, self.limits.type_depth
+                        );
+                    }
+
+                    wrappers.push(token);
+                }
+                TokenType::Identifier(name) => {
+                    break self.next_named_type(&token, &name.get_name());
+                }
+                _ => panic!("{}: Expected a type.", token.position),
+            }
+        };
+
+        while let Some(token) = wrappers.pop() {
+            if let TokenType::BinaryOperation(BinaryOperator::Mul) = token.token_type {
+                next_type = Type::Pointer(Box::new(next_type));
+                continue;
+            }
+
+            self.next_semicolon();
+
+            let length = self.next_array_length(&token);
+
+            self.next_r_bracket();
+
+            self.check_type_size(&token, next_type.get_size().checked_mul(length));
+
+            next_type = Type::Array(Box::new(next_type), length);
+        }
+
+        return next_type;
+    }
+
+    // Sizes are computed with checked arithmetic, None when they overflow
+    fn check_type_size(&self, token: &Token, size: Option<usize>) {
+        if size.map_or(true, |size| size > MAX_TYPE_SIZE) {
+            panic!(
+                "{}: Type too large, it takes more than {} bytes.",
+                token.position, MAX_TYPE_SIZE
+            );
+        }
+    }
+
+    // Any constant expression, so lengths can be computed from constants and @sizeof
+    fn next_array_length(&mut self, token: &Token) -> usize {
+        let locals = LocalStack::new();
+        let expression = self.next_expression(&locals, true);
+
+        let length = self.evaluate_constant(token, &expression, &locals);
+
+        // Literals are never negative, 18446744073709551615 is only -1 once it is computed on
+        let is_literal = matches!(expression, Expression::NumberLiteral(_));
+
+        if expression.get_type(&locals, &self.functions).is_signed()
+            && (length as i64) < 0
+            && !is_literal
+        {
+            panic!("{}: Array lengths can not be negative.", token.position);
+        }
+
+        // Anything longer is past the range of signed lengths, larger types are checked by size
+        if length > i64::MAX as u64 {
+            panic!("{}: Array length is too large.", token.position);
+        }
+
+        return length as usize;
+    }
+
+    fn next_named_type(&mut self, token: &Token, name: &str) -> Type {
+        if let Some((_, type_argument)) = self
+            .type_arguments
+            .iter()
+            .find(|(parameter, _)| parameter == name)
+        {
+            return type_argument.clone();
+        }
+
+        if let Some(layout) = self.structs.iter().find(|layout| layout.name == name) {
+            let layout = layout.clone();
+            self.add_reference(&token.position, Reference::Struct(name.to_owned()));
+
+            return Type::Struct(Box::new(layout));
+        }
+
+        return match Type::from_name(name) {
+            Some(found_type) => found_type,
+            None => panic!(
+                "{}: error[E0009]: Unknown type {}.{}",
+                token.position,
+                name,
+                get_suggestion(
+                    name,
+                    BUILTIN_TYPES
+                        .into_iter()
+                        .chain(self.structs.iter().map(|layout| layout.name.as_str()))
+                )
+            ),
+        };
+    }
+
+    fn next_arg(&mut self) -> Option<(interner::Symbol, Type, Position)> {
+        if let Some(token) = self.lookahead_token.clone() {
+            match token.token_type {
+                TokenType::Identifier(arg_name) => {
+                    self.next_token();
+
+                    let arg_type = self.next_type_annotation().unwrap_or(Type::I64);
+
+                    // Structs are copied by the called function
+                    if arg_type.is_aggregate() && !arg_type.is_struct() {
+                        panic!(
+                            "{}: {} can not be passed by value, pass a pointer instead.",
+                            token.position, arg_type
+                        );
+                    }
+
+                    if let Some(token) = self.lookahead_token.clone() {
+                        match token.token_type {
+                            TokenType::Comma => {
+                                self.next_comma();
+                            }
+                            TokenType::RightPar => {}
+                            TokenType::Identifier(_) => {
+                                let message = format!("{}: Unexpected token. Maybe you forgot to put a comma between the two arguments.", token.position);
+
+                                // The comma goes right after the previous argument
+                                let end = match &self.current_token {
+                                    Some(Token {
+                                        token_type: TokenType::Identifier(name),
+                                        position,
+                                    }) => Some((position, name.get_name().len())),
+                                    Some(Token {
+                                        token_type: TokenType::RightBracket,
+                                        position,
+                                    }) => Some((position, 1)),
+                                    _ => None,
+                                };
+
+                                let fix = end.map(|(position, length)| Fix {
+                                    position: Position {
+                                        column: position.column + length,
+                                        ..position.clone()
+                                    },
+                                    length: 0,
+                                    replacement: ",".to_owned(),
+                                });
+
+                                panic_any(Diagnostic { message, fix });
+                            }
+                            _ => {
+                                panic!("{}: Unexpected token.", token.position);
+                            }
+                        }
+                    } else {
+                        panic!(
+                            "{}: Expected comma or right parentheses but reached end of file.",
+                            self.lexer.file_position
+                        );
+                    }
+
+                    return Some((arg_name, arg_type, token.position));
+                }
+                TokenType::RightPar => {
+                    if let Some(token) = self.current_token.clone() {
+                        match token.token_type {
+                            TokenType::Identifier(_) | TokenType::LeftPar => {
+                                return None;
+                            }
+                            _ => {
+                                panic!("{}: Unexpected token", token.position);
+                            }
+                        }
+                    } else {
+                        panic!("Unreachable");
+                    }
+                }
+                _ => {
+                    panic!("{}: Expected right parentheses", token.position);
+                }
+            }
+        } else {
+            panic!("{}: Reached end of file", self.lexer.file_position);
+        }
+    }
+
+    fn next_scope(&mut self, locals: &mut LocalStack) -> Scope {
+        self.next_l_brace();
+
+        let mut statements: Vec<Statement> = Vec::new();
+        let mut spans: Vec<Span> = Vec::new();
+
+        loop {
+            let position = match &self.lookahead_token {
+                Some(token) => token.position.clone(),
+                None => self.lexer.file_position.clone(),
+            };
+            let start = self.position;
+
+            // Checked while parsing, nothing is left for the body
+            if let Some(
+                token @ Token {
+                    token_type: TokenType::StaticAssert,
+                    ..
+                },
+            ) = self.lookahead_token.clone()
+            {
+                self.next_token();
+                self.next_static_assert(&token, locals);
+                continue;
+            }
+
+            match self.next_statement(locals) {
+                Some(statement) => {
+                    statements.push(statement);
+                    spans.push(Span {
+                        position,
+                        tokens: start..self.position,
+                    });
+                }
+                None => break,
+            }
+        }
+
+        self.next_r_brace();
+
+        return Scope { statements, spans };
+    }
+
+    fn next_statement(&mut self, locals: &mut LocalStack) -> Option<Statement> {
+        if let Some(token) = self.lookahead_token.clone() {
+            match token.token_type {
+                TokenType::Return => {
+                    self.next_token();
+                    return Some(self.next_return(locals));
+                }
+                TokenType::Var => {
+                    return Some(self.next_var_declaration(locals));
+                }
+                TokenType::Goto => {
+                    self.next_token();
+                    return Some(self.next_goto());
+                }
+                // Labels like `again:`, a colon never follows the target of an assignment
+                TokenType::Identifier(name)
+                    if matches!(
+                        self.tokens.get(self.position + 1),
+                        Some(Token {
+                            token_type: TokenType::Colon,
+                            ..
+                        })
+                    ) =>
+                {
+                    self.next_token();
+                    self.next_token();
+                    return Some(Statement::Label(name));
+                }
+                TokenType::Identifier(_) => {
+                    if let Some(Token {
+                        token_type: TokenType::LeftBracket | TokenType::Dot,
+                        ..
+                    }) = self.tokens.get(self.position + 1)
+                    {
+                        return Some(self.next_store(locals));
+                    }
+
+                    return Some(self.next_assign(locals));
+                }
+                TokenType::Call(_) => {
+                    let call = self.next_call(locals);
+                    self.next_semicolon();
+                    return Some(Statement::Call(call));
+                }
+                TokenType::RightBrace => {
+                    return None;
+                }
+                _ => {
+                    panic!("{}: Unexpected token.", token.position);
+                }
+            }
+        } else {
+            panic!(
+                "{}: Expected statement but found end of file.",
+                self.lexer.file_position
+            );
+        }
+    }
+
+    // Labels are resolved once the whole function is parsed, jumps forward are allowed
+    fn next_goto(&mut self) -> Statement {
+        match self.next_token() {
+            Some(Token {
+                token_type: TokenType::Identifier(label),
+                ..
+            }) => {
+                self.next_semicolon();
+                return Statement::Goto(label);
+            }
+            Some(token) => panic!("{}: Expected a label after goto.", token.position),
+            None => panic!(
+                "{}: Expected a label after goto but reached end of file.",
+                self.lexer.file_position
+            ),
+        }
+    }
+
+    fn next_var_declaration(&mut self, locals: &mut LocalStack) -> Statement {
+        self.next_var();
+
+        if let Some(Token {
+            token_type: TokenType::LeftPar,
+            ..
+        }) = self.lookahead_token
+        {
+            return self.next_destructure(locals);
+        }
+
+        if let Some(token) = self.next_token() {
+            if let TokenType::Identifier(name) = &token.token_type {
+                let var_type = self.next_type_annotation();
+
+                if let Some(_) = locals.find(*name) {
+                    panic!(
+                        "{}: error[E0004]: Duplicated variable declaration.",
+                        token.position
+                    );
+                }
+
+                // Static variables are zeroed once, when the program is loaded
+                if let (
+                    Some(var_type),
+                    Some(Token {
+                        token_type: TokenType::Call(_),
+                        ..
+                    }),
+                ) = (&var_type, &self.lookahead_token)
+                {
+                    self.next_static_attribute();
+
+                    let index = locals.insert_static(name.to_owned(), var_type.clone());
+                    self.add_reference(&token.position, Reference::Local(index));
+
+                    self.next_semicolon();
+
+                    return Statement::Declare(index);
+                }
+
+                // Annotated variables without initializer start zeroed
+                if let (
+                    Some(var_type),
+                    Some(Token {
+                        token_type: TokenType::Semicolon,
+                        ..
+                    }),
+                ) = (&var_type, &self.lookahead_token)
+                {
+                    let index = locals.insert(name.to_owned(), var_type.clone());
+                    self.add_reference(&token.position, Reference::Local(index));
+
+                    self.next_semicolon();
+
+                    return Statement::Declare(index);
+                }
+
+                if let (Some(_), Some(lookahead)) = (&var_type, &self.lookahead_token) {
+                    self.check_missing_semicolon(lookahead);
+                }
+
+                self.next_equals();
+
+                let expression = self.next_expression(locals, false);
+
+                // Without annotation the type is inferred from the expression
+                let var_type = match var_type {
+                    Some(var_type) => {
+                        self.expect_assignable(&token, &var_type, &expression, locals);
+                        var_type
+                    }
+                    None => {
+                        let var_type = expression.get_type(locals, &self.functions);
+
+                        if var_type.is_tuple() {
+                            panic!(
+                                "{}: Tuples must be destructured, use `var (a, b) = ...`.",
+                                token.position
+                            );
+                        }
+
+                        self.expect_assignable(&token, &var_type, &expression, locals);
+                        var_type
+                    }
+                };
+
+                let index = locals.insert(name.to_owned(), var_type);
+                self.add_reference(&token.position, Reference::Local(index));
+
+                self.next_semicolon();
+
+                return Statement::Assign(index, expression);
+            } else {
+                panic!("{}: Expected identifier.", token.position);
+            }
+        } else {
+            panic!(
+                "{}: Expected identifier but reached end of file.",
+                self.lexer.file_position
+            );
+        }
+    }
+
+    fn next_static_attribute(&mut self) {
+        let token = self.next_token().expect("Unreachable");
+
+        match self.next_token() {
+            Some(Token {
+                token_type: TokenType::Identifier(name),
+                ..
+            }) if name == "static" => {}
+            _ => panic!(
+                "{}: Unknown variable attribute, expected @static.",
+                token.position
+            ),
+        }
+
+        if let Some(Token {
+            token_type: TokenType::Equals,
+            position,
+        }) = &self.lookahead_token
+        {
+            panic!(
+                "{}: Static variables start zeroed and can not have an initializer.",
+                position
+            );
+        }
+    }
+
+    fn next_destructure(&mut self, locals: &mut LocalStack) -> Statement {
+        let token = self.next_token().expect("Unreachable");
+
+        let mut names: Vec<interner::Symbol> = Vec::new();
+        let mut positions: Vec<Position> = Vec::new();
+
+        loop {
+            match self.next_token() {
+                Some(Token {
+                    token_type: TokenType::Identifier(name),
+                    position,
+                }) => {
+                    if locals.find(name).is_some() || names.contains(&name) {
+                        panic!(
+                            "{}: error[E0004]: Duplicated variable declaration.",
+                            position
+                        );
+                    }
+
+                    names.push(name);
+                    positions.push(position);
+                }
+                _ => panic!("{}: Expected identifier.", token.position),
+            }
+
+            match self.next_token() {
+                Some(Token {
+                    token_type: TokenType::Comma,
+                    ..
+                }) => continue,
+                Some(Token {
+                    token_type: TokenType::RightPar,
+                    ..
+                }) => break,
+                _ => panic!(
+                    "{}: Expected a comma or a right parenthesis.",
+                    token.position
+                ),
+            }
+        }
+
+        self.next_equals();
+
+        let expression = self.next_expression(locals, false);
+
+        let element_types = match expression.get_type(locals, &self.functions) {
+            Type::Tuple(element_types) => element_types,
+            found => panic!(
+                "{}: Only tuples can be destructured but found {}.",
+                token.position, found
+            ),
+        };
+
+        if element_types.len() != names.len() {
+            panic!(
+                "{}: Expected {} names but found {}.",
+                token.position,
+                element_types.len(),
+                names.len()
+            );
+        }
+
+        let indices: Vec<usize> = names
+            .into_iter()
+            .zip(element_types)
+            .map(|(name, element_type)| locals.insert(name, element_type))
+            .collect();
+
+        for (index, position) in indices.iter().zip(positions.iter()) {
+            self.add_reference(position, Reference::Local(*index));
+        }
+
+        self.next_semicolon();
+
+        return Statement::Destructure(indices, expression);
+    }
+
+    fn next_assign(&mut self, locals: &mut LocalStack) -> Statement {
+        if let Some(token) = self.next_token() {
+            if let TokenType::Identifier(name) = &token.token_type {
+                self.next_equals();
+
+                match locals.find(*name) {
+                    Some(index) => {
+                        self.add_reference(&token.position, Reference::Local(index));
+
+                        let expression = self.next_expression(locals, false);
+
+                        let local_type = &locals.get(index).expect("Unreachable").local_type;
+                        self.expect_assignable(&token, local_type, &expression, locals);
+
+                        self.next_semicolon();
+
+                        return Statement::Assign(index, expression);
+                    }
+                    None => {
+                        panic!(
+                            "{}: error[E0001]: Undeclared variable.{}",
+                            token.position,
+                            locals.suggest(*name)
+                        );
+                    }
+                }
+            } else {
+                panic!("{}: Expected identifier.", token.position);
+            }
+        } else {
+            panic!(
+                "{}: Expected identifier but reached end of file.",
+                self.lexer.file_position
+            );
+        }
+    }
+
+    fn next_store(&mut self, locals: &LocalStack) -> Statement {
+        let token = self.next_token().expect("Unreachable");
+
+        let mut target = match &token.token_type {
+            TokenType::Identifier(name) => match locals.find(*name) {
+                Some(index) => {
+                    self.add_reference(&token.position, Reference::Local(index));
+                    Expression::Local(index)
+                }
+                None => panic!(
+                    "{}: error[E0001]: Undeclared variable.{}",
+                    token.position,
+                    locals.suggest(*name)
+                ),
+            },
+            _ => panic!("Unreachable"),
+        };
+
+        while let Some(lookahead) = self.lookahead_token.clone() {
+            match lookahead.token_type {
+                TokenType::LeftBracket => {
+                    let index = self.next_index(locals);
+                    target = self.check_index(&token, target, index, locals);
+                }
+                TokenType::Dot => {
+                    self.next_token();
+                    let (field, position) = self.next_field_name();
+                    target = self.check_field(&token, target, &field, &position, locals);
+                }
+                _ => break,
+            }
+        }
+
+        self.next_equals();
+
+        let expression = self.next_expression(locals, false);
+
+        let target_type = target.get_type(locals, &self.functions);
+
+        // Initializers are only supported when declaring or assigning variables, structs
+        // are copied from another place
+        if target_type.is_aggregate() && (!target_type.is_struct() || !expression.is_place()) {
+            panic!(
+                "{}: Values of type {} can not be assigned as a whole.",
+                token.position, target_type
+            );
+        }
+
+        self.expect_assignable(&token, &target_type, &expression, locals);
+
+        self.next_semicolon();
+
+        return Statement::Store(target, expression);
+    }
+
+    fn next_index(&mut self, locals: &LocalStack) -> Expression {
+        self.next_l_bracket();
+
+        let index = self.next_expression(locals, true);
+
+        self.next_r_bracket();
+
+        return index;
+    }
+
+    fn check_index(
+        &self,
+        token: &Token,
+        base: Expression,
+        index: Expression,
+        locals: &LocalStack,
+    ) -> Expression {
+        let base_type = base.get_type(locals, &self.functions);
+
+        if let None = base_type.get_element() {
+            panic!(
+                "{}: Can not index into a value of type {}.",
+                token.position, base_type
+            );
+        }
+
+        if let (Type::Array(..), false) = (&base_type, base.is_place()) {
+            panic!("{}: Can not index a temporary array.", token.position);
+        }
+
+        let index_type = index.get_type(locals, &self.functions);
+
+        if !index_type.is_integer() {
+            panic!(
+                "{}: Index must be an integer but found {}.",
+                token.position, index_type
+            );
+        }
+
+        return Expression::Index(Box::new(base), Box::new(index), token.position.clone());
+    }
+
+    fn next_field_name(&mut self) -> (String, Position) {
+        if let Some(token) = self.next_token() {
+            if let TokenType::Identifier(name) = token.token_type {
+                return (name.to_string(), token.position);
+            } else {
+                panic!("{}: Expected a field name.", token.position);
+            }
+        } else {
+            panic!(
+                "{}: Expected a field name but reached end of file.",
+                self.lexer.file_position
+            );
+        }
+    }
+
+    fn check_field(
+        &mut self,
+        token: &Token,
+        base: Expression,
+        name: &str,
+        position: &Position,
+        locals: &LocalStack,
+    ) -> Expression {
+        let base_type = base.get_type(locals, &self.functions);
+
+        let layout = match &base_type {
+            Type::Struct(layout) => layout,
+            _ => panic!(
+                "{}: Can not access field {} of type {}.",
+                token.position, name, base_type
+            ),
+        };
+
+        let field = match layout.find(name) {
+            Some(field) => field,
+            None => panic!(
+                "{}: error[E0010]: No field {} in {}.{}",
+                token.position,
+                name,
+                base_type,
+                layout.suggest(name)
+            ),
+        };
+
+        if !base.is_place() {
+            panic!(
+                "{}: Can not access a field of a temporary struct.",
+                token.position
+            );
+        }
+
+        let reference = Reference::Field(layout.name.to_owned(), field);
+        self.add_reference(position, reference);
+
+        return Expression::Field(Box::new(base), field);
+    }
+
+    fn next_array_initializer(&mut self, locals: &LocalStack) -> Expression {
+        let token = self.next_token().expect("Unreachable");
+
+        let mut elements: Vec<Expression> = Vec::new();
+
+        while let Some(lookahead) = self.lookahead_token.clone() {
+            if let TokenType::RightBracket = lookahead.token_type {
+                break;
+            }
+
+            elements.push(self.next_expression(locals, true));
+
+            if let Some(Token {
+                token_type: TokenType::Comma,
+                ..
+            }) = self.lookahead_token
+            {
+                self.next_comma();
+            } else {
+                break;
+            }
+        }
+
+        self.next_r_bracket();
+
+        if elements.len() == 0 {
+            panic!("{}: Empty array literal.", token.position);
+        }
+
+        // Untyped literals follow the first typed element
+        let element_type = match elements.iter().find(|element| !element.is_untyped()) {
+            Some(element) => element.get_type(locals, &self.functions),
+            None => Type::I64,
+        };
+
+        self.check_type_size(&token, element_type.get_size().checked_mul(elements.len()));
+
+        return Expression::Initializer(
+            Type::Array(Box::new(element_type), elements.len()),
+            elements,
+        );
+    }
+
+    fn next_struct_initializer(&mut self, locals: &LocalStack, layout: Struct) -> Expression {
+        let token = self.next_token().expect("Unreachable");
+
+        self.add_reference(&token.position, Reference::Struct(layout.name.to_owned()));
+
+        self.next_l_brace();
+
+        let mut elements: Vec<Option<Expression>> = vec![None; layout.fields.len()];
+
+        while let Some(field_token) = self.lookahead_token.clone() {
+            let field_name = match &field_token.token_type {
+                TokenType::RightBrace => break,
+                TokenType::Identifier(field_name) => field_name.to_string(),
+                _ => panic!("{}: Expected a field name.", field_token.position),
+            };
+
+            self.next_token();
+
+            let field = match layout.find(&field_name) {
+                Some(field) => field,
+                None => panic!(
+                    "{}: error[E0010]: No field {} in {}.{}",
+                    field_token.position,
+                    field_name,
+                    layout.name,
+                    layout.suggest(&field_name)
+                ),
+            };
+
+            self.add_reference(
+                &field_token.position,
+                Reference::Field(layout.name.to_owned(), field),
+            );
+
+            if elements[field].is_some() {
+                panic!(
+                    "{}: Field {} initialized twice.",
+                    field_token.position, field_name
+                );
+            }
+
+            match self.next_token() {
+                Some(Token {
+                    token_type: TokenType::Colon,
+                    ..
+                }) => {}
+                _ => panic!(
+                    "{}: Expected a colon after field name.",
+                    field_token.position
+                ),
+            }
+
+            let expression = self.next_expression(locals, true);
+
+            let field_type = &layout.fields[field].field_type;
+            self.expect_assignable(&field_token, field_type, &expression, locals);
+
+            elements[field] = Some(expression);
+
+            if let Some(Token {
+                token_type: TokenType::Comma,
+                ..
+            }) = self.lookahead_token
+            {
+                self.next_comma();
+            }
+        }
+
+        self.next_r_brace();
+
+        for (field, element) in layout.fields.iter().zip(elements.iter()) {
+            if element.is_none() {
+                panic!(
+                    "{}: Missing field {} in {} initializer.",
+                    token.position, field.name, layout.name
+                );
+            }
+        }
+
+        return Expression::Initializer(
+            Type::Struct(Box::new(layout)),
+            elements.into_iter().flatten().collect(),
+        );
+    }
+
+    fn check_cast(
+        &self,
+        token: &Token,
+        cast_type: &Type,
+        expression: &Expression,
+        locals: &LocalStack,
+    ) {
+        let expression_type = expression.get_type(locals, &self.functions);
+
+        if !expression_type.can_cast_to(cast_type) {
+            panic!(
+                "{}: error[E0011]: Can not cast {} to {}.",
+                token.position, expression_type, cast_type
+            );
+        }
+    }
+
+    fn next_return(&mut self, locals: &LocalStack) -> Statement {
+        let token = self.current_token.clone().expect("Unreachable");
+
+        if let (
+            Type::Tuple(element_types),
+            Some(Token {
+                token_type: TokenType::LeftPar,
+                ..
+            }),
+        ) = (self.return_type.clone(), &self.lookahead_token)
+        {
+            let elements = self.next_call_args(locals);
+
+            if elements.len() != element_types.len() {
+                panic!(
+                    "{}: Expected {} values but found {}.",
+                    token.position,
+                    element_types.len(),
+                    elements.len()
+                );
+            }
+
+            for (element_type, element) in element_types.iter().zip(&elements) {
+                self.expect_assignable(&token, element_type, element, locals);
+            }
+
+            self.next_semicolon();
+
+            return Statement::Return(Expression::Tuple(elements));
+        }
+
+        let expression = self.next_expression(locals, false);
+
+        self.expect_assignable(&token, &self.return_type, &expression, locals);
+
+        self.next_semicolon();
+
+        return Statement::Return(expression);
+    }
+
+    fn expect_assignable(
+        &self,
+        token: &Token,
+        expected: &Type,
+        expression: &Expression,
+        locals: &LocalStack,
+    ) {
+        if expected.is_aggregate() {
+            if let Expression::Initializer(initializer_type, elements) = expression {
+                self.check_initializer(token, expected, initializer_type, elements, locals);
+                return;
+            }
+
+            // Copied byte by byte, so only the same struct fits
+            if expected.is_struct() && expression.is_place() {
+                let found = expression.get_type(locals, &self.functions);
+
+                if found.is_aggregate() && found.get_size() != expected.get_size() {
+                    panic!(
+                        "{}: error[E0005]: Mismatched types, expected {} of {} bytes but found {} of {} bytes.",
+                        token.position,
+                        expected,
+                        expected.get_size(),
+                        found,
+                        found.get_size()
+                    );
+                }
+
+                self.expect_type(token, expected, &found);
+                return;
+            }
+
+            panic!(
+                "{}: Values of type {} can not be assigned as a whole.",
+                token.position, expected
+            );
+        }
+
+        if expression.is_untyped() && expected.is_integer() {
+            if let Expression::NumberLiteral(number) = expression {
+                if !expected.fits(*number) {
+                    panic!(
+                        "{}: error[E0012]: Literal {} out of range for {}.",
+                        token.position, number, expected
+                    );
+                }
+            }
+
+            return;
+        }
+
+        let found = expression.get_type(locals, &self.functions);
+
+        if found == *expected || found.widens_to(expected) {
+            return;
+        }
+
+        if found.is_integer() && expected.is_integer() {
+            panic!(
+                "{}: error[E0006]: Implicit conversion from {} to {} may lose information, use `as {}`.",
+                token.position,
+                found,
+                expected,
+                expected
+            );
+        }
+
+        self.expect_type(token, expected, &found);
+    }
+
+    fn check_initializer(
+        &self,
+        token: &Token,
+        expected: &Type,
+        initializer_type: &Type,
+        elements: &Vec<Expression>,
+        locals: &LocalStack,
+    ) {
+        match (expected, initializer_type) {
+            (Type::Array(element, length), Type::Array(..)) => {
+                if elements.len() != *length {
                     panic!(
-                        "{}:{}:{}: Unexpected token.",
-                        self.lexer.filename, token.position.line, token.position.column
+                        "{}: Expected {} elements but found {}.",
+                        token.position,
+                        length,
+                        elements.len()
                     );
                 }
+
+                for expression in elements.iter() {
+                    self.expect_assignable(token, element, expression, locals);
+                }
+            }
+            (Type::Struct(layout), Type::Struct(found)) if layout.name == found.name => {
+                for (field, expression) in layout.fields.iter().zip(elements.iter()) {
+                    self.expect_assignable(token, &field.field_type, expression, locals);
+                }
             }
+            _ => self.expect_type(token, expected, initializer_type),
         }
+    }
 
-        program.functions = self.functions.clone();
-
-        return program;
+    fn expect_type(&self, token: &Token, expected: &Type, found: &Type) {
+        if expected != found {
+            panic!(
+                "{}: error[E0005]: Mismatched types, expected {} but found {}.",
+                token.position, expected, found
+            );
+        }
     }
 
-    fn next_function(&mut self) -> Function {
-        self.next_fn();
+    fn next_call(&mut self, locals: &LocalStack) -> Expression {
+        self.next_at();
 
         if let Some(token) = self.next_token() {
-            if let TokenType::Identifier(function_name) = token.token_type {
-                self.next_colon();
+            if let TokenType::Identifier(function_name) = &token.token_type {
+                let function_name = &*function_name.get_name();
 
-                let mut locals = LocalStack::new();
-                let arguments = self.next_args(&mut locals);
-                let body = self.next_scope(&mut locals);
+                if let Some(Token {
+                    token_type: TokenType::DoubleColon,
+                    ..
+                }) = self.lookahead_token
+                {
+                    self.next_token();
 
-                let function = Function {
-                    name: function_name,
-                    locals,
-                    arguments,
-                    body,
-                };
+                    let (name, position) = match self.next_token() {
+                        Some(Token {
+                            token_type: TokenType::Identifier(name),
+                            position,
+                        }) => (name.to_string(), position),
+                        _ => panic!(
+                            "{}: Expected function name after {}::.",
+                            token.position, function_name
+                        ),
+                    };
 
-                return function;
-            } else {
-                panic!(
-                    "{}:{}:{}: Expected function name",
-                    self.lexer.filename, token.position.line, token.position.column
-                );
-            }
-        } else {
-            panic!(
-                "{}:{}:{}: Expected function name but reached end of file.",
-                self.lexer.filename, self.lexer.file_position.line, self.lexer.file_position.column
-            );
-        }
-    }
+                    if let Some(generic) = self.find_generic(&token, Some(function_name), &name) {
+                        self.add_reference(
+                            &token.position,
+                            Reference::Module(function_name.to_owned()),
+                        );
 
-    fn next_args(&mut self, locals: &mut LocalStack) -> Vec<usize> {
-        self.next_l_par();
+                        return self.next_generic_call(&token, &position, generic, locals);
+                    }
 
-        let mut args: Vec<usize> = Vec::new();
+                    let id = self.find_function(&token, Some(function_name), &name);
 
-        while let Some((label, size)) = self.next_arg() {
-            let index = locals.insert(label, size);
-            args.push(index);
-        }
+                    self.add_reference(
+                        &token.position,
+                        Reference::Module(function_name.to_owned()),
+                    );
+                    self.add_reference(&position, Reference::Function(id));
 
-        self.next_r_par();
+                    return self.next_function_call(&token, id, locals);
+                }
 
-        return args;
-    }
+                // Resolved at compile time, the string is not stored in the program
+                if function_name == "len" {
+                    return self.next_len();
+                }
 
-    fn next_arg(&mut self) -> Option<(String, usize)> {
-        if let Some(token) = self.lookahead_token.clone() {
-            match token.token_type {
-                TokenType::Identifier(arg_name) => {
-                    self.next_token();
+                if function_name == "cast" {
+                    return self.next_cast(&token, locals);
+                }
 
-                    if let Some(token) = self.lookahead_token.clone() {
-                        match token.token_type {
-                            TokenType::Comma => {
-                                self.next_comma();
-                            }
-                            TokenType::RightPar => {}
-                            TokenType::Identifier(_) => {
-                                panic!("{}:{}:{}: Unexpected token. Maybe you forgot to put a comma between the two arguments.", self.lexer.filename, token.position.line, token.position.column);
-                            }
-                            _ => {
-                                panic!(
-                                    "{}:{}:{}: Unexpected token.",
-                                    self.lexer.filename, token.position.line, token.position.column
-                                );
-                            }
-                        }
-                    } else {
+                if function_name == "addr" {
+                    return self.next_addr(&token, locals);
+                }
+
+                if function_name == "sizeof" {
+                    return self.next_sizeof();
+                }
+
+                if function_name == "printf" {
+                    return self.next_printf(&token, locals);
+                }
+
+                if let Some(intrinsic) = Intrinsic::from_name(&function_name) {
+                    let args = self.next_call_args(locals);
+
+                    if args.len() != intrinsic.get_arguments() {
                         panic!(
-                            "{}:{}:{}: Expected comma or right parentheses but reached end of file.",
-                            self.lexer.filename,
-                            self.lexer.file_position.line,
-                            self.lexer.file_position.column
+                            "{}: error[E0002]: Unmatched number of arguments.",
+                            token.position
                         );
                     }
 
-                    // FIXME: Don't hardcode local size
-                    return Some((arg_name, 8));
-                }
-                TokenType::RightPar => {
-                    if let Some(token) = self.current_token.clone() {
-                        match token.token_type {
-                            TokenType::Identifier(_) | TokenType::LeftPar => {
-                                return None;
-                            }
-                            _ => {
-                                panic!(
-                                    "{}:{}:{}: Unexpected token",
-                                    self.lexer.filename, token.position.line, token.position.column
-                                );
-                            }
-                        }
-                    } else {
-                        panic!("Unreachable");
+                    let types: Vec<Type> = args
+                        .iter()
+                        .map(|arg| arg.get_type(locals, &self.functions))
+                        .collect();
+
+                    if let None = intrinsic.get_type(&types) {
+                        panic!(
+                            "{}: Invalid argument types for @{}.",
+                            token.position, function_name
+                        );
                     }
+
+                    return Expression::Intrinsic(intrinsic, args);
                 }
-                _ => {
-                    panic!(
-                        "{}:{}:{}: Expected right parentheses",
-                        self.lexer.filename, token.position.line, token.position.column
-                    );
+
+                if let Some(generic) = self.find_generic(&token, None, function_name) {
+                    return self.next_generic_call(&token, &token.position, generic, locals);
                 }
+
+                let id = self.find_function(&token, None, function_name);
+
+                self.add_reference(&token.position, Reference::Function(id));
+
+                return self.next_function_call(&token, id, locals);
+            } else {
+                panic!("{}: Expected fuction name.", token.position);
             }
         } else {
             panic!(
-                "{}:{}:{}: Reached end of file",
-                self.lexer.filename, self.lexer.file_position.line, self.lexer.file_position.column
+                "{}: Expected function name but reached end of file.",
+                self.lexer.file_position
             );
         }
     }
 
-    fn next_scope(&mut self, locals: &mut LocalStack) -> Scope {
-        self.next_l_brace();
-
-        let mut statements: Vec<Statement> = Vec::new();
+    fn next_function_call(
+        &mut self,
+        token: &Token,
+        id: FunctionId,
+        locals: &LocalStack,
+    ) -> Expression {
+        let args = self.next_call_args(locals);
 
-        while let Some(statement) = self.next_statement(locals) {
-            statements.push(statement);
+        if args.len() != self.table.get_signature(id).arguments.len() {
+            panic!(
+                "{}: error[E0002]: Unmatched number of arguments.",
+                token.position
+            );
         }
 
-        self.next_r_brace();
+        self.check_call_args(token, id, &args, locals);
 
-        return Scope { statements };
+        return Expression::Call(id, args);
     }
 
-    fn next_statement(&mut self, locals: &mut LocalStack) -> Option<Statement> {
-        if let Some(token) = self.lookahead_token.clone() {
-            match token.token_type {
-                TokenType::Return => {
-                    self.next_token();
-                    return Some(self.next_return(locals));
-                }
-                TokenType::Var => {
-                    return Some(self.next_var_declaration(locals));
-                }
-                TokenType::Identifier(_) => {
-                    return Some(self.next_assign(locals));
-                }
-                TokenType::Call(_) => {
-                    let call = self.next_call(locals);
-                    self.next_semicolon();
-                    return Some(Statement::Call(call));
-                }
-                TokenType::RightBrace => {
-                    return None;
-                }
-                _ => {
-                    panic!(
-                        "{}:{}:{}: Unexpected token.",
-                        self.lexer.filename, token.position.line, token.position.column
-                    );
-                }
-            }
-        } else {
-            panic!(
-                "{}:{}:{}: Expected statement but found end of file.",
-                self.lexer.filename, self.lexer.file_position.line, self.lexer.file_position.column
-            );
+    fn check_call_args(
+        &self,
+        token: &Token,
+        id: FunctionId,
+        args: &Vec<Expression>,
+        locals: &LocalStack,
+    ) {
+        let signature = self.table.get_signature(id);
+
+        for (arg, argument_type) in args.iter().zip(signature.arguments.iter()) {
+            self.expect_assignable(token, argument_type, arg, locals);
         }
     }
 
-    fn next_var_declaration(&mut self, locals: &mut LocalStack) -> Statement {
-        self.next_var();
-
-        if let Some(token) = self.next_token() {
-            if let TokenType::Identifier(name) = token.token_type {
-                self.next_equals();
+    // Each type parameter is the type of the first value passed for it that is not a literal,
+    // literals alone make it an i64 like they are everywhere else
+    fn next_generic_call(
+        &mut self,
+        token: &Token,
+        position: &Position,
+        generic: usize,
+        locals: &LocalStack,
+    ) -> Expression {
+        let args = self.next_call_args(locals);
+        let inferred = self.generics[generic].inferred.clone();
 
-                if let Some(_) = locals.find(&name) {
-                    panic!(
-                        "{}:{}:{}: Duplicated variable declaration.",
-                        self.lexer.filename, token.position.line, token.position.column
-                    );
-                }
+        if args.len() != inferred.len() {
+            panic!(
+                "{}: error[E0002]: Unmatched number of arguments.",
+                token.position
+            );
+        }
 
-                // FIXME: Don't hardcode size
-                let index = locals.insert(name, 8);
+        let mut types: Vec<Option<Type>> = vec![None; self.generics[generic].parameters.len()];
 
-                let statement = Statement::Assign(index, self.next_expression(locals, false));
+        for (arg, parameter) in args.iter().zip(inferred) {
+            let parameter = match parameter {
+                Some(parameter) if types[parameter].is_none() && !arg.is_untyped() => parameter,
+                _ => continue,
+            };
 
-                self.next_semicolon();
+            let arg_type = arg.get_type(locals, &self.functions);
 
-                return statement;
-            } else {
+            if !arg_type.is_integer() {
                 panic!(
-                    "{}:{}:{}: Expected identifier.",
-                    self.lexer.filename, token.position.line, token.position.column
+                    "{}: error[E0005]: Mismatched types, expected an integer for {} but found {}.",
+                    token.position, self.generics[generic].parameters[parameter], arg_type
                 );
             }
-        } else {
+
+            types[parameter] = Some(arg_type);
+        }
+
+        let types = types
+            .into_iter()
+            .map(|found| found.unwrap_or(Type::I64))
+            .collect();
+
+        let id = self.instantiate(token, generic, types);
+
+        self.add_reference(position, Reference::Function(id));
+        self.check_call_args(token, id, &args, locals);
+
+        return Expression::Call(id, args);
+    }
+
+    // Instance of a generic function for a list of types, parsed from the tokens of the
+    // declaration the first time a call needs it. The function being parsed resumes after.
+    fn instantiate(&mut self, token: &Token, generic: usize, types: Vec<Type>) -> FunctionId {
+        if let Some((_, _, id)) = self
+            .instances
+            .iter()
+            .find(|(instanced, instance_types, _)| {
+                *instanced == generic && *instance_types == types
+            })
+        {
+            return *id;
+        }
+
+        let key = (generic, types.clone());
+
+        if self.instantiating.contains(&key) {
             panic!(
-                "{}:{}:{}: Expected identifier but reached end of file.",
-                self.lexer.filename, self.lexer.file_position.line, self.lexer.file_position.column
+                "{}: Generic function {} can not call itself.",
+                token.position, self.generics[generic].name
             );
         }
+
+        if self.functions.len() == self.limits.functions {
+            panic!(
+                "{}: error[E0017]: Program too large, it declares more than {} functions.",
+                token.position, self.limits.functions
+            );
+        }
+
+        let type_arguments = self.generics[generic]
+            .parameters
+            .iter()
+            .cloned()
+            .zip(types.iter().cloned())
+            .collect();
+
+        let position = self.position;
+        let current_token = self.current_token.take();
+        let lookahead_token = self.lookahead_token.take();
+        let references = std::mem::take(&mut self.references);
+        let return_type = self.return_type.clone();
+        let nesting = self.nesting;
+        let type_arguments = std::mem::replace(&mut self.type_arguments, type_arguments);
+
+        self.position = self.generics[generic].start;
+        self.lookahead_token = self.tokens.get(self.position).cloned();
+        self.nesting = 0;
+        self.instantiating.push(key);
+
+        let function = self.next_function();
+
+        self.instantiating.pop();
+        self.position = position;
+        self.current_token = current_token;
+        self.lookahead_token = lookahead_token;
+        self.references = references;
+        self.return_type = return_type;
+        self.nesting = nesting;
+        self.type_arguments = type_arguments;
+
+        log(
+            Level::Debug,
+            format_args!("instantiated {}", function.get_display_name()),
+        );
+
+        let id = self.add_function(function);
+        self.instances.push((generic, types, id));
+
+        return id;
     }
 
-    fn next_assign(&mut self, locals: &mut LocalStack) -> Statement {
-        if let Some(token) = self.next_token() {
-            if let TokenType::Identifier(name) = token.token_type {
-                self.next_equals();
+    fn add_function(&mut self, function: Function) -> FunctionId {
+        let id = self.table.insert(&function);
+        self.functions.push(function);
 
-                match locals.find(&name) {
-                    Some(index) => {
-                        let statement =
-                            Statement::Assign(index, self.next_expression(locals, false));
+        return id;
+    }
 
-                        self.next_semicolon();
+    fn next_len(&mut self) -> Expression {
+        self.next_l_par();
 
-                        return statement;
-                    }
-                    None => {
-                        panic!(
-                            "{}:{}:{}: Undeclared variable.",
-                            self.lexer.filename, token.position.line, token.position.column
-                        );
-                    }
-                }
+        if let Some(token) = self.next_token() {
+            if let TokenType::StringLiteral(string) = token.token_type {
+                self.next_r_par();
+
+                return Expression::NumberLiteral(string.len() as u64);
             } else {
-                panic!(
-                    "{}:{}:{}: Expected identifier.",
-                    self.lexer.filename, token.position.line, token.position.column
-                );
+                panic!("{}: Expected a string literal.", token.position);
             }
         } else {
             panic!(
-                "{}:{}:{}: Expected identifier but reached end of file.",
-                self.lexer.filename, self.lexer.file_position.line, self.lexer.file_position.column
+                "{}: Expected a string literal but reached end of file.",
+                self.lexer.file_position
             );
         }
     }
 
-    fn next_return(&mut self, locals: &LocalStack) -> Statement {
-        let statement = Statement::Return(self.next_expression(locals, false));
+    // Size in bytes of a type, padding included, as a plain literal
+    fn next_sizeof(&mut self) -> Expression {
+        self.next_l_par();
 
-        self.next_semicolon();
+        let sized_type = self.next_type();
+
+        self.next_r_par();
 
-        return statement;
+        return Expression::NumberLiteral(sized_type.get_size() as u64);
     }
 
-    fn next_call(&mut self, locals: &LocalStack) -> Expression {
-        self.next_at();
+    fn next_cast(&mut self, token: &Token, locals: &LocalStack) -> Expression {
+        self.next_l_par();
 
-        if let Some(token) = self.next_token() {
-            if let TokenType::Identifier(function_name) = token.token_type {
-                let index = match self.functions.iter().position(|f| f.name == function_name) {
-                    Some(index) => index,
-                    None => panic!(
-                        "{}:{}:{}: Call to undefined function.",
-                        self.lexer.filename, token.position.line, token.position.column
-                    ),
-                };
+        let cast_type = self.next_type();
 
-                let args = self.next_call_args(locals);
+        self.next_comma();
 
-                if args.len() != self.functions.get(index).unwrap().arguments.len() {
-                    panic!(
-                        "{}:{}:{}: Unmatched number of arguments.",
-                        self.lexer.filename, token.position.line, token.position.column
-                    );
-                }
+        let expression = self.next_expression(locals, true);
 
-                return Expression::Call(index, args);
-            } else {
+        self.next_r_par();
+
+        self.check_cast(token, &cast_type, &expression, locals);
+
+        return Expression::Cast(cast_type, Box::new(expression));
+    }
+
+    // The format is split at compile time so only writes are left at runtime
+    fn next_printf(&mut self, token: &Token, locals: &LocalStack) -> Expression {
+        self.next_l_par();
+
+        let format = match self.next_token() {
+            Some(Token {
+                token_type: TokenType::StringLiteral(format),
+                ..
+            }) => format,
+            _ => panic!("{}: Expected a format string literal.", token.position),
+        };
+
+        let mut args: Vec<Expression> = Vec::new();
+
+        while let Some(arg) = self.next_call_arg(locals) {
+            args.push(arg);
+        }
+
+        self.next_r_par();
+
+        let chunks = self.split_format(token, &format);
+
+        if chunks.len() - 1 != args.len() {
+            panic!(
+                "{}: Expected {} values for the format but found {}.",
+                token.position,
+                chunks.len() - 1,
+                args.len()
+            );
+        }
+
+        for arg in args.iter() {
+            let arg_type = arg.get_type(locals, &self.functions);
+
+            if !arg_type.is_integer() && !arg_type.is_pointer() && arg_type != Type::F64 {
                 panic!(
-                    "{}:{}:{}: Expected fuction name.",
-                    self.lexer.filename, token.position.line, token.position.column
+                    "{}: Values of type {} can not be formatted.",
+                    token.position, arg_type
                 );
             }
-        } else {
-            panic!(
-                "{}:{}:{}: Expected function name but reached end of file.",
-                self.lexer.filename, self.lexer.file_position.line, self.lexer.file_position.column
-            );
         }
+
+        let chunks = chunks
+            .into_iter()
+            .map(|chunk| {
+                if chunk.is_empty() {
+                    return None;
+                }
+
+                return Some(self.intern_string(token, chunk));
+            })
+            .collect();
+
+        return Expression::Format(chunks, args);
+    }
+
+    // Literal chunks around each {} placeholder, {{ and }} escape the braces
+    fn split_format(&self, token: &Token, format: &str) -> Vec<String> {
+        let mut chunks: Vec<String> = vec![String::new()];
+        let mut characters = format.chars().peekable();
+
+        while let Some(character) = characters.next() {
+            match (character, characters.peek()) {
+                ('{', Some('}')) => {
+                    characters.next();
+                    chunks.push(String::new());
+                }
+                ('{', Some('{')) | ('}', Some('}')) => {
+                    characters.next();
+                    chunks.last_mut().unwrap().push(character);
+                }
+                ('{', _) | ('}', _) => panic!(
+                    "{}: Unmatched brace in format string, use {{{{ or }}}} to escape it.",
+                    token.position
+                ),
+                _ => chunks.last_mut().unwrap().push(character),
+            }
+        }
+
+        return chunks;
+    }
+
+    fn next_addr(&mut self, token: &Token, locals: &LocalStack) -> Expression {
+        self.next_l_par();
+
+        let expression = self.next_expression(locals, true);
+
+        self.next_r_par();
+
+        if expression.is_place() {
+            return Expression::Address(Box::new(expression));
+        }
+
+        panic!(
+            "{}: Can only take the address of a variable or an element.",
+            token.position
+        );
     }
 
     fn next_call_args(&mut self, locals: &LocalStack) -> Vec<Expression> {
@@ -489,10 +4279,7 @@ impl Parser {
                 TokenType::Comma => {
                     if let Some(token_prev) = &self.current_token {
                         if let TokenType::LeftPar = token_prev.token_type {
-                            panic!(
-                                "{}:{}:{}: Expected a expression.",
-                                self.lexer.filename, token.position.line, token.position.column
-                            );
+                            panic!("{}: Expected a expression.", token.position);
                         }
                     }
 
@@ -505,19 +4292,35 @@ impl Parser {
             }
         } else {
             panic!(
-                "{}:{}:{}: Expected call arguments but reached end of file.",
-                self.lexer.filename, self.lexer.file_position.line, self.lexer.file_position.column
+                "{}: Expected call arguments but reached end of file.",
+                self.lexer.file_position
             );
         }
     }
 
     fn next_expression(&mut self, locals: &LocalStack, call_arg: bool) -> Expression {
+        self.nesting += 1;
+
+        if let Some(token) = &self.lookahead_token {
+            self.check_depth(token, self.nesting);
+        }
+
         let mut queue: Vec<Token> = Vec::new();
 
         let mut stack: Vec<Token> = Vec::new();
 
         let mut calls: Vec<Expression> = Vec::new();
 
+        let mut casts: Vec<Type> = Vec::new();
+
+        // Open parentheses, which nest like the arguments of calls do. Chains of operators
+        // are not limited, `1 + 1 + ... + 1` only nests to the left.
+        let mut parentheses: usize = 0;
+
+        let mut indices: Vec<Expression> = Vec::new();
+
+        let mut fields: Vec<(String, Position)> = Vec::new();
+
         let mut last_token: Option<Token> = None;
 
         let mut end = false;
@@ -525,8 +4328,14 @@ impl Parser {
         while let Some(token) = self.lookahead_token.clone() {
             last_token = Some(token.clone());
 
+            if !call_arg {
+                self.check_missing_semicolon(&token);
+            }
+
             match &token.token_type {
                 TokenType::Call(_) => {
+                    self.check_missing_comma(&token, call_arg);
+
                     let call = self.next_call(locals);
                     calls.push(call);
                     queue.push(Token {
@@ -535,39 +4344,97 @@ impl Parser {
                     });
                     continue;
                 }
-                TokenType::Identifier(_) => {
-                    if let Some(current_token) = &self.current_token {
-                        if let TokenType::Identifier(_) = current_token.token_type {
-                            panic!(
-                                "{}:{}:{}: Invalid expression.",
-                                self.lexer.filename, token.position.line, token.position.column
-                            );
-                        }
-                    } else {
-                        panic!("Unreachable");
+                TokenType::As => {
+                    // Casts bind tighter than any binary operator
+                    self.next_token();
+                    casts.push(self.next_type());
+                    queue.push(token);
+                    continue;
+                }
+                TokenType::LeftBracket => {
+                    // After an operand it is indexing, otherwise an array literal
+                    if let Some(Token {
+                        token_type:
+                            TokenType::Identifier(_)
+                            | TokenType::NumberLiteral(_)
+                            | TokenType::TypedNumberLiteral(..)
+                            | TokenType::FloatLiteral(_)
+                            | TokenType::StringLiteral(_)
+                            | TokenType::RightPar
+                            | TokenType::RightBracket
+                            | TokenType::RightBrace,
+                        ..
+                    }) = self.current_token
+                    {
+                        // Same as casts, indexing applies to the last operand
+                        indices.push(self.next_index(locals));
+                        queue.push(token);
+                        continue;
                     }
+
+                    self.check_missing_comma(&token, call_arg);
+
+                    let initializer = self.next_array_initializer(locals);
+                    calls.push(initializer);
+                    queue.push(Token {
+                        token_type: TokenType::Call(calls.len() - 1),
+                        position: token.position,
+                    });
+                    continue;
+                }
+                TokenType::Dot => {
+                    self.next_token();
+                    fields.push(self.next_field_name());
                     queue.push(token);
+                    continue;
                 }
-                TokenType::NumberLiteral(_) => {
-                    if let Some(current_token) = &self.current_token {
-                        if let TokenType::NumberLiteral(_) = current_token.token_type {
-                            panic!(
-                                "{}:{}:{}: Invalid expression.",
-                                self.lexer.filename, token.position.line, token.position.column
-                            );
-                        }
-                    } else {
-                        panic!("Unreachable");
+                TokenType::RightBracket | TokenType::RightBrace => {
+                    if !call_arg {
+                        panic!("{}: Unexpected token.", token.position);
+                    }
+                    end = true;
+                    break;
+                }
+                TokenType::Identifier(name) => {
+                    self.check_missing_comma(&token, call_arg);
+
+                    let layout = self
+                        .structs
+                        .iter()
+                        .find(|layout| *name == layout.name.as_str())
+                        .cloned();
+
+                    let is_initializer = matches!(
+                        self.tokens.get(self.position + 1),
+                        Some(Token {
+                            token_type: TokenType::LeftBrace,
+                            ..
+                        })
+                    );
+
+                    if let (Some(layout), true) = (layout, is_initializer) {
+                        let initializer = self.next_struct_initializer(locals, layout);
+                        calls.push(initializer);
+                        queue.push(Token {
+                            token_type: TokenType::Call(calls.len() - 1),
+                            position: token.position,
+                        });
+                        continue;
                     }
+
+                    queue.push(token);
+                }
+                TokenType::NumberLiteral(_)
+                | TokenType::TypedNumberLiteral(..)
+                | TokenType::FloatLiteral(_)
+                | TokenType::StringLiteral(_) => {
+                    self.check_missing_comma(&token, call_arg);
                     queue.push(token);
                 }
                 TokenType::BinaryOperation(operator) => {
                     if let Some(current_token) = &self.current_token {
                         if let TokenType::BinaryOperation(_) = current_token.token_type {
-                            panic!(
-                                "{}:{}:{}: Invalid expression.",
-                                self.lexer.filename, token.position.line, token.position.column
-                            );
+                            panic!("{}: Invalid expression.", token.position);
                         }
                     } else {
                         panic!("Unreachable");
@@ -580,7 +4447,8 @@ impl Parser {
                             TokenType::BinaryOperation(operator) => {
                                 let top_precedence = operator.get_precedence();
 
-                                if top_precedence > current_precedence {
+                                // Every operator is left associative, `a - b - c` is `(a - b) - c`
+                                if top_precedence >= current_precedence {
                                     queue.push(stack.pop().unwrap());
                                 } else {
                                     break;
@@ -598,6 +4466,11 @@ impl Parser {
                     stack.push(token);
                 }
                 TokenType::LeftPar => {
+                    self.check_missing_comma(&token, call_arg);
+
+                    parentheses += 1;
+                    self.check_depth(&token, self.nesting + parentheses);
+
                     stack.push(token);
                 }
                 TokenType::RightPar => {
@@ -611,6 +4484,7 @@ impl Parser {
                     while let Some(token) = stack.pop() {
                         match &token.token_type {
                             TokenType::LeftPar => {
+                                parentheses -= 1;
                                 reached_left_par = true;
                                 break;
                             }
@@ -623,41 +4497,28 @@ impl Parser {
 
                     if !reached_left_par {
                         if call_arg {
-                            println!("tonoto 2");
                             end = true;
                             break;
                         }
-                        panic!(
-                            "{}:{}:{}: Unmatched parenthesis.",
-                            self.lexer.filename, token.position.line, token.position.column
-                        );
+                        panic!("{}: Unmatched parenthesis.", token.position);
                     }
                 }
                 TokenType::Semicolon => {
                     if call_arg {
-                        panic!(
-                            "{}:{}:{}: Unexpected token.",
-                            self.lexer.filename, token.position.line, token.position.column
-                        );
+                        panic!("{}: Unexpected token.", token.position);
                     }
                     end = true;
                     break;
                 }
                 TokenType::Comma => {
                     if !call_arg {
-                        panic!(
-                            "{}:{}:{}: Unexpected token.",
-                            self.lexer.filename, token.position.line, token.position.column
-                        );
+                        panic!("{}: Unexpected token.", token.position);
                     }
                     end = true;
                     break;
                 }
                 _ => {
-                    panic!(
-                        "{}:{}:{}: Unexpected token.",
-                        self.lexer.filename, token.position.line, token.position.column
-                    );
+                    panic!("{}: Unexpected token.", token.position);
                 }
             };
 
@@ -667,16 +4528,23 @@ impl Parser {
         if end {
             while let Some(token) = stack.pop() {
                 if let TokenType::LeftPar | TokenType::RightPar = token.token_type {
-                    panic!(
-                        "{}:{}:{}: Unmatched parentheses.",
-                        self.lexer.filename, token.position.line, token.position.column
-                    );
+                    panic!("{}: Unmatched parentheses.", token.position);
                 }
                 queue.push(token);
             }
 
             let mut expressions: Vec<Expression> = Vec::new();
 
+            let mut casts = casts.into_iter();
+
+            let mut indices = indices.into_iter();
+
+            let mut fields = fields.into_iter();
+
+            // Levels of the tree below each expression on the stack, which the recursive passes
+            // over it go down one at a time
+            let mut depths: Vec<usize> = Vec::new();
+
             for token in queue.iter() {
                 match &token.token_type {
                     TokenType::Call(func) => {
@@ -689,56 +4557,294 @@ impl Parser {
                     TokenType::NumberLiteral(number) => {
                         expressions.push(Expression::NumberLiteral(*number));
                     }
+                    // Typed like a cast, the lexer already checked the value fits
+                    TokenType::TypedNumberLiteral(number, suffix) => {
+                        expressions.push(Expression::Cast(
+                            Type::from_name(suffix).expect("Unreachable"),
+                            Box::new(Expression::NumberLiteral(*number)),
+                        ));
+                    }
+                    TokenType::FloatLiteral(number) => {
+                        expressions.push(Expression::FloatLiteral(*number));
+                    }
+                    TokenType::StringLiteral(string) => {
+                        let index = self.add_string(token, string.to_owned());
+                        expressions.push(Expression::StringLiteral(index));
+                    }
                     TokenType::Identifier(name) => {
-                        let index = match locals.find(name) {
-                            Some(index) => index,
-                            None => {
+                        if let Some(index) = locals.find(*name) {
+                            self.add_reference(&token.position, Reference::Local(index));
+                            expressions.push(Expression::Local(index));
+                        } else if let Some(constant) = self
+                            .constants
+                            .iter()
+                            .find(|constant| *name == constant.name.as_str())
+                        {
+                            // Variables shadow constants
+                            let literal = get_literal(constant.value, &constant.constant_type);
+                            self.add_reference(
+                                &token.position,
+                                Reference::Constant(name.to_string()),
+                            );
+                            expressions.push(literal);
+                        } else {
+                            panic!(
+                                "{}: error[E0001]: Undeclared variable.{}",
+                                token.position,
+                                locals.suggest(*name)
+                            );
+                        }
+                    }
+                    TokenType::BinaryOperation(operator) => {
+                        if let (Some(mut right), Some(mut left)) =
+                            (expressions.pop(), expressions.pop())
+                        {
+                            let left_type = left.get_type(locals, &self.functions);
+                            let right_type = right.get_type(locals, &self.functions);
+
+                            let operation_type = if left_type.is_aggregate()
+                                || right_type.is_aggregate()
+                            {
+                                panic!(
+                                    "{}: Aggregates can not be used as values, access their parts or take their @addr.",
+                                    token.position
+                                );
+                            } else if left_type.is_tuple() || right_type.is_tuple() {
+                                panic!(
+                                    "{}: Tuples can not be used as values, destructure them first.",
+                                    token.position
+                                );
+                            } else if left_type.is_pointer() || right_type.is_pointer() {
+                                self.check_pointer_arithmetic(
+                                    token,
+                                    operator,
+                                    &left_type,
+                                    &right_type,
+                                )
+                            } else if left.is_untyped() && right_type.is_integer() {
+                                right_type
+                            } else if right.is_untyped() && left_type.is_integer() {
+                                left_type
+                            } else if left_type == right_type {
+                                left_type
+                            } else if right_type.widens_to(&left_type) {
+                                // The narrower operand is widened to the type of the other one
+                                right = Expression::Cast(left_type.clone(), Box::new(right));
+                                left_type
+                            } else if left_type.widens_to(&right_type) {
+                                left = Expression::Cast(right_type.clone(), Box::new(left));
+                                right_type
+                            } else if left_type.is_integer() && right_type.is_integer() {
+                                panic!(
+                                    "{}: error[E0022]: Operands of types {} and {} have no common type, convert one of them with `as`.",
+                                    token.position, left_type, right_type
+                                );
+                            } else {
+                                self.expect_type(token, &left_type, &right_type);
+                                left_type
+                            };
+
+                            if let (
+                                Type::F64,
+                                BinaryOperator::BitwiseAnd
+                                | BinaryOperator::BitwiseOr
+                                | BinaryOperator::BitwiseXor,
+                            ) = (&operation_type, operator)
+                            {
                                 panic!(
-                                    "{}:{}:{}: Undeclared local.",
-                                    self.lexer.filename, token.position.line, token.position.column
+                                    "{}: Bitwise operations are not allowed on {}.",
+                                    token.position, operation_type
                                 );
                             }
-                        };
-                        expressions.push(Expression::Local(index));
-                    }
-                    TokenType::BinaryOperation(operator) => {
-                        if let (Some(right), Some(left)) = (expressions.pop(), expressions.pop()) {
+
                             expressions.push(Expression::Binary(BinaryExpression {
                                 operator: operator.clone(),
                                 left: Box::new(left),
                                 right: Box::new(right),
                             }));
                         } else {
-                            panic!(
-                                "{}:{}:{}: Missing operator.",
-                                self.lexer.filename, token.position.line, token.position.column
-                            );
+                            panic!("{}: Missing operator.", token.position);
+                        }
+                    }
+                    TokenType::As => {
+                        let cast_type = casts.next().expect("Unreachable");
+
+                        if let Some(expression) = expressions.pop() {
+                            self.check_cast(token, &cast_type, &expression, locals);
+                            expressions.push(Expression::Cast(cast_type, Box::new(expression)));
+                        } else {
+                            panic!("{}: Missing expression to cast.", token.position);
+                        }
+                    }
+                    TokenType::LeftBracket => {
+                        let index = indices.next().expect("Unreachable");
+
+                        if let Some(base) = expressions.pop() {
+                            let expression = self.check_index(token, base, index, locals);
+                            expressions.push(expression);
+                        } else {
+                            panic!("{}: Missing expression to index.", token.position);
+                        }
+                    }
+                    TokenType::Dot => {
+                        let (field, position) = fields.next().expect("Unreachable");
+
+                        if let Some(base) = expressions.pop() {
+                            let expression =
+                                self.check_field(token, base, &field, &position, locals);
+                            expressions.push(expression);
+                        } else {
+                            panic!("{}: Missing expression to access.", token.position);
                         }
                     }
                     _ => {}
                 }
+
+                let operands = match &token.token_type {
+                    TokenType::BinaryOperation(_) => 2,
+                    TokenType::As | TokenType::Dot => 1,
+                    // Calls and indices bring expressions parsed on their own
+                    TokenType::LeftBracket => {
+                        depths.pop();
+                        0
+                    }
+                    TokenType::Call(_)
+                    | TokenType::NumberLiteral(_)
+                    | TokenType::TypedNumberLiteral(..)
+                    | TokenType::FloatLiteral(_)
+                    | TokenType::StringLiteral(_)
+                    | TokenType::Identifier(_) => 0,
+                    _ => continue,
+                };
+
+                let depth = match operands {
+                    0 => expressions
+                        .last()
+                        .map_or(1, |expression| expression.get_depth()),
+                    _ => {
+                        let below = depths.split_off(depths.len().saturating_sub(operands));
+                        1 + below.into_iter().max().unwrap_or(0)
+                    }
+                };
+
+                self.check_length(token, depth);
+                depths.push(depth);
             }
 
             if let Some(token) = last_token {
                 if expressions.len() == 0 {
-                    panic!(
-                        "{}:{}:{}: Expected a expression.",
-                        self.lexer.filename, token.position.line, token.position.column
-                    );
+                    panic!("{}: Expected a expression.", token.position);
+                }
+
+                // Operands next to each other are reported while reading them
+                if expressions.len() > 1 {
+                    panic!("{}: Missing operator.", token.position);
                 }
             } else {
                 panic!("Unreachable");
             }
 
-            assert!(expressions.len() == 1);
+            self.nesting -= 1;
 
             return expressions.last().unwrap().to_owned();
         } else {
             panic!(
-                "{}:{}:{}: Expected expression but found end of file.",
-                self.lexer.filename, self.lexer.file_position.line, self.lexer.file_position.column
+                "{}: Expected expression but found end of file.",
+                self.lexer.file_position
+            );
+        }
+    }
+
+    fn add_string(&mut self, token: &Token, string: String) -> usize {
+        self.string_bytes += string.len();
+
+        if self.string_bytes > self.limits.string_bytes {
+            panic!(
+                "{}: error[E0017]: Program too large, its strings take more than {} bytes.",
+                token.position, self.limits.string_bytes
+            );
+        }
+
+        self.strings.push(string);
+
+        return self.strings.len() - 1;
+    }
+
+    // Only for strings that are never written, a literal can be used as a buffer
+    fn intern_string(&mut self, token: &Token, string: String) -> usize {
+        if let Some(index) = self.interned.get(&string) {
+            return *index;
+        }
+
+        let index = self.add_string(token, string.clone());
+        self.interned.insert(string, index);
+
+        return index;
+    }
+
+    fn check_depth(&self, token: &Token, depth: usize) {
+        if depth > self.limits.expression_depth {
+            panic!(
+                "{}: error[E0016]: Expression too complex, it nests more than {} levels of parentheses and calls.",
+                token.position,
+                self.limits.expression_depth
+            );
+        }
+    }
+
+    // Longer chains would overflow the stack of the recursive passes over the expression
+    fn check_length(&self, token: &Token, depth: usize) {
+        if depth > self.limits.expression_length {
+            panic!(
+                "{}: error[E0016]: Expression too long, it chains more than {} operations. Keep parts of it in variables.",
+                token.position,
+                self.limits.expression_length
+            );
+        }
+    }
+
+    fn check_pointer_arithmetic(
+        &self,
+        token: &Token,
+        operator: &BinaryOperator,
+        left_type: &Type,
+        right_type: &Type,
+    ) -> Type {
+        if left_type.is_pointer() && right_type.is_pointer() {
+            if let BinaryOperator::Add = operator {
+                panic!("{}: Can not add two pointers.", token.position);
+            }
+
+            panic!(
+                "{}: Invalid operation between two pointers.",
+                token.position
+            );
+        }
+
+        let (pointer_type, offset_type) = match left_type.is_pointer() {
+            true => (left_type, right_type),
+            false => (right_type, left_type),
+        };
+
+        if !offset_type.is_integer() {
+            panic!(
+                "{}: Pointer offset must be an integer but found {}.",
+                token.position, offset_type
             );
         }
+
+        // Only pointer + integer, integer + pointer and pointer - integer
+        match (operator, left_type.is_pointer()) {
+            (BinaryOperator::Add, _) | (BinaryOperator::Sub, true) => {}
+            _ => {
+                panic!(
+                    "{}: Only adding or subtracting an integer is allowed on {}.",
+                    token.position, pointer_type
+                );
+            }
+        }
+
+        return pointer_type.clone();
     }
 
     fn next_at(&mut self) {
@@ -746,15 +4852,12 @@ impl Parser {
             if let TokenType::Call(_) = token.token_type {
                 return;
             } else {
-                panic!(
-                    "{}:{}:{}: Expected a call token.",
-                    self.lexer.filename, token.position.line, token.position.column
-                );
+                panic!("{}: Expected a call token.", token.position);
             }
         } else {
             panic!(
-                "{}:{}:{}: Expected a call token but reached end of file.",
-                self.lexer.filename, self.lexer.file_position.line, self.lexer.file_position.column
+                "{}: Expected a call token but reached end of file.",
+                self.lexer.file_position
             );
         }
     }
@@ -764,33 +4867,178 @@ impl Parser {
             if let TokenType::Equals = token.token_type {
                 return;
             } else {
-                panic!(
-                    "{}:{}:{}: Expected an equals token.",
-                    self.lexer.filename, token.position.line, token.position.column
-                );
+                panic!("{}: Expected an equals token.", token.position);
             }
         } else {
             panic!(
-                "{}:{}:{}: Expected an equals token but reached end of file.",
-                self.lexer.filename, self.lexer.file_position.line, self.lexer.file_position.column
+                "{}: Expected an equals token but reached end of file.",
+                self.lexer.file_position
             );
         }
     }
 
+    fn add_reference(&mut self, position: &Position, reference: Reference) {
+        self.references
+            .push(Spanned::new(position.clone(), reference));
+    }
+
+    // An operand right after another one, like `@add(x 2)`, is most likely a forgotten comma
+    fn check_missing_comma(&self, token: &Token, call_arg: bool) {
+        let previous = match &self.current_token {
+            Some(previous) => previous,
+            None => return,
+        };
+
+        let ends_operand = matches!(
+            previous.token_type,
+            TokenType::Identifier(_)
+                | TokenType::NumberLiteral(_)
+                | TokenType::TypedNumberLiteral(..)
+                | TokenType::FloatLiteral(_)
+                | TokenType::StringLiteral(_)
+                | TokenType::RightPar
+                | TokenType::RightBracket
+                | TokenType::RightBrace
+        );
+
+        if !ends_operand {
+            return;
+        }
+
+        if !call_arg {
+            panic!("{}: Invalid expression.", token.position);
+        }
+
+        // The comma goes right after the previous argument, before any space separating them
+        let end = match token.position.line == previous.position.line {
+            true => self.get_line(&token.position).and_then(|line| {
+                let before = line.get(..token.position.column - 1)?;
+                return Some((token.position.line, before.trim_end().len()));
+            }),
+            false => self
+                .get_line(&previous.position)
+                .map(|line| (previous.position.line, line.trim_end().len())),
+        };
+
+        let fix = match token.position.file == previous.position.file {
+            true => end.map(|(line, end)| Fix {
+                position: Position {
+                    file: previous.position.file.to_owned(),
+                    line,
+                    column: end + 1,
+                },
+                length: 0,
+                replacement: ",".to_owned(),
+            }),
+            false => None,
+        };
+
+        panic_any(Diagnostic {
+            message: format!("{}: Expected `,` between arguments.", token.position),
+            fix,
+        });
+    }
+
+    // Line of the source a position is in, without its line break
+    fn get_line(&self, position: &Position) -> Option<String> {
+        // The root file may not be on disk in this form, like while fixing it
+        let source = match *position.file == *self.lexer.filename {
+            true => String::from_utf8_lossy(&self.lexer.data).to_string(),
+            false => read_to_string(&*position.file).ok()?,
+        };
+
+        // Columns start after the byte order mark like in the lexer
+        let source = source.strip_prefix('\u{feff}').unwrap_or(&source);
+
+        return source.lines().nth(position.line - 1).map(str::to_owned);
+    }
+
+    // A statement starting on a later line almost always means the `;` before it was forgotten,
+    // so the diagnostic points at the end of the previous line instead of the next statement
+    fn check_missing_semicolon(&self, token: &Token) {
+        let previous = match &self.current_token {
+            Some(previous) => previous,
+            None => return,
+        };
+
+        let ends_statement = matches!(
+            previous.token_type,
+            TokenType::Identifier(_)
+                | TokenType::NumberLiteral(_)
+                | TokenType::TypedNumberLiteral(..)
+                | TokenType::FloatLiteral(_)
+                | TokenType::StringLiteral(_)
+                | TokenType::RightPar
+                | TokenType::RightBracket
+                | TokenType::RightBrace
+        );
+        let starts_statement = matches!(
+            token.token_type,
+            TokenType::Var
+                | TokenType::Return
+                | TokenType::Goto
+                | TokenType::StaticAssert
+                | TokenType::Identifier(_)
+                | TokenType::Call(_)
+                | TokenType::RightBrace
+        );
+
+        if !ends_statement
+            || !starts_statement
+            || token.position.file != previous.position.file
+            || token.position.line <= previous.position.line
+        {
+            return;
+        }
+
+        let end = match self.get_line(&previous.position) {
+            Some(line) => line.trim_end().len(),
+            None => return,
+        };
+
+        let position = Position {
+            file: previous.position.file.to_owned(),
+            line: previous.position.line,
+            column: end + 1,
+        };
+
+        panic_any(Diagnostic {
+            message: format!(
+                "{}: Missing semicolon at the end of the statement, insert `;` here.",
+                position
+            ),
+            fix: Some(Fix {
+                position,
+                length: 0,
+                replacement: ";".to_owned(),
+            }),
+        });
+    }
+
     fn next_semicolon(&mut self) {
+        if let Some(Token {
+            token_type: TokenType::Semicolon,
+            ..
+        }) = &self.lookahead_token
+        {
+            self.next_token();
+            return;
+        }
+
+        if let Some(token) = &self.lookahead_token {
+            self.check_missing_semicolon(token);
+        }
+
         if let Some(token) = self.next_token() {
             if let TokenType::Semicolon = token.token_type {
                 return;
             } else {
-                panic!(
-                    "{}:{}:{}: Expected a semicolon.",
-                    self.lexer.filename, token.position.line, token.position.column
-                );
+                panic!("{}: Expected a semicolon.", token.position);
             }
         } else {
             panic!(
-                "{}:{}:{}: Expected a semicolon but reached end of file.",
-                self.lexer.filename, self.lexer.file_position.line, self.lexer.file_position.column
+                "{}: Expected a semicolon but reached end of file.",
+                self.lexer.file_position
             );
         }
     }
@@ -800,10 +5048,13 @@ impl Parser {
             if let TokenType::Comma = token.token_type {
                 return;
             } else {
-                panic!("Expected comma token.");
+                panic!("{}: Expected a comma.", token.position);
             }
         } else {
-            panic!("No token");
+            panic!(
+                "{}: Expected a comma but reached end of file.",
+                self.lexer.file_position
+            );
         }
     }
 
@@ -812,15 +5063,12 @@ impl Parser {
             if let TokenType::Colon = token.token_type {
                 return;
             } else {
-                panic!(
-                    "{}:{}:{}: Expected a colon after function name.",
-                    self.lexer.filename, token.position.line, token.position.column
-                );
+                panic!("{}: Expected a colon after function name.", token.position);
             }
         } else {
             panic!(
-                "{}:{}:{}: Expected a colon after function name but reached end of file.",
-                self.lexer.filename, self.lexer.file_position.line, self.lexer.file_position.column
+                "{}: Expected a colon after function name but reached end of file.",
+                self.lexer.file_position
             );
         }
     }
@@ -830,15 +5078,12 @@ impl Parser {
             if let TokenType::RightBrace = token.token_type {
                 return;
             } else {
-                panic!(
-                    "{}:{}:{}: Expected right brace token.",
-                    self.lexer.filename, token.position.line, token.position.column
-                );
+                panic!("{}: Expected right brace token.", token.position);
             }
         } else {
             panic!(
-                "{}:{}:{}: Expected right brace token but reached end of file",
-                self.lexer.filename, self.lexer.file_position.line, self.lexer.file_position.column
+                "{}: Expected right brace token but reached end of file",
+                self.lexer.file_position
             );
         }
     }
@@ -848,15 +5093,42 @@ impl Parser {
             if let TokenType::LeftBrace = token.token_type {
                 return;
             } else {
-                panic!(
-                    "{}:{}:{}: Expected left brace token.",
-                    self.lexer.filename, token.position.line, token.position.column
-                );
+                panic!("{}: Expected left brace token.", token.position);
+            }
+        } else {
+            panic!(
+                "{}: Expected left brace token but reached end of file.",
+                self.lexer.file_position
+            );
+        }
+    }
+
+    fn next_r_bracket(&mut self) {
+        if let Some(token) = self.next_token() {
+            if let TokenType::RightBracket = token.token_type {
+                return;
+            } else {
+                panic!("{}: Expected right bracket token.", token.position);
+            }
+        } else {
+            panic!(
+                "{}: Expected right bracket token but reached end of file",
+                self.lexer.file_position
+            );
+        }
+    }
+
+    fn next_l_bracket(&mut self) {
+        if let Some(token) = self.next_token() {
+            if let TokenType::LeftBracket = token.token_type {
+                return;
+            } else {
+                panic!("{}: Expected left bracket token.", token.position);
             }
         } else {
             panic!(
-                "{}:{}:{}: Expected left brace token but reached end of file.",
-                self.lexer.filename, self.lexer.file_position.line, self.lexer.file_position.column
+                "{}: Expected left bracket token but reached end of file.",
+                self.lexer.file_position
             );
         }
     }
@@ -866,15 +5138,12 @@ impl Parser {
             if let TokenType::RightPar = token.token_type {
                 return;
             } else {
-                panic!(
-                    "{}:{}:{}: Expected right parentheses token.",
-                    self.lexer.filename, token.position.line, token.position.column
-                );
+                panic!("{}: Expected right parentheses token.", token.position);
             }
         } else {
             panic!(
-                "{}:{}:{}: Expected right parentheses token but reached end of file",
-                self.lexer.filename, self.lexer.file_position.line, self.lexer.file_position.column
+                "{}: Expected right parentheses token but reached end of file",
+                self.lexer.file_position
             );
         }
     }
@@ -884,15 +5153,12 @@ impl Parser {
             if let TokenType::LeftPar = token.token_type {
                 return;
             } else {
-                panic!(
-                    "{}:{}:{}: Expected left parentheses token.",
-                    self.lexer.filename, token.position.line, token.position.column
-                );
+                panic!("{}: Expected left parentheses token.", token.position);
             }
         } else {
             panic!(
-                "{}:{}:{}: Expected left parentheses token but reached end of file.",
-                self.lexer.filename, self.lexer.file_position.line, self.lexer.file_position.column
+                "{}: Expected left parentheses token but reached end of file.",
+                self.lexer.file_position
             );
         }
     }
@@ -902,15 +5168,12 @@ impl Parser {
             if let TokenType::Function = token.token_type {
                 return;
             } else {
-                panic!(
-                    "{}:{}:{}: Expected function declaration (fn).",
-                    self.lexer.filename, token.position.line, token.position.column
-                );
+                panic!("{}: Expected function declaration (fn).", token.position);
             }
         } else {
             panic!(
-                "{}:{}:{}: Expected function declaration (fn) token but reached end of file.",
-                self.lexer.filename, self.lexer.file_position.line, self.lexer.file_position.column
+                "{}: Expected function declaration (fn) token but reached end of file.",
+                self.lexer.file_position
             );
         }
     }
@@ -920,16 +5183,59 @@ impl Parser {
             if let TokenType::Var = token.token_type {
                 return;
             } else {
-                panic!(
-                    "{}:{}:{}: Expected var token.",
-                    self.lexer.filename, token.position.line, token.position.column
-                );
+                panic!("{}: Expected var token.", token.position);
             }
         } else {
             panic!(
-                "{}:{}:{}: Expected var token but reached end of file.",
-                self.lexer.filename, self.lexer.file_position.line, self.lexer.file_position.column
+                "{}: Expected var token but reached end of file.",
+                self.lexer.file_position
             );
         }
     }
 }
+
+// Calls of a constant expression are run by the interpreter and replaced by their result
+struct CallEvaluator<'a> {
+    parser: &'a Parser,
+    token: &'a Token,
+    locals: &'a LocalStack,
+}
+
+impl Folder for CallEvaluator<'_> {
+    fn fold_expression(&mut self, expression: Expression) -> Expression {
+        let expression = rebuild_expression(self, expression);
+
+        let (id, arguments) = match &expression {
+            Expression::Call(id, arguments) => (*id, arguments),
+            _ => return expression,
+        };
+
+        let parser = self.parser;
+        let function = &parser.functions[id];
+
+        // Left to fail like any other value that is not an integer
+        if !function.return_type.is_integer() {
+            return expression;
+        }
+
+        let arguments: Vec<u64> = arguments
+            .iter()
+            .map(|argument| parser.evaluate_constant(self.token, argument, self.locals))
+            .collect();
+
+        let mut program = Program::new();
+        program.functions = parser.functions.clone();
+        program.table = parser.table.clone();
+        program.strings = parser.strings.clone();
+
+        return match evaluate_call(&program, id, arguments, parser.limits.const_steps) {
+            Ok(value) => get_literal(wrap(value, &function.return_type), &function.return_type),
+            Err(reason) => panic!(
+                "{}: error[E0020]: Can not evaluate @{} at compile time: {}.",
+                self.token.position,
+                function.get_display_name(),
+                reason.trim_end_matches('.')
+            ),
+        };
+    }
+}
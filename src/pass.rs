@@ -0,0 +1,11 @@
+use crate::{diagnostics::Diagnostic, parser::Program};
+
+// Step of a crate built on the compiler, run on the parsed program before any code is
+// written. A pass can check the program like a lint or rewrite it, the program it leaves is
+// validated like a parsed one. Its diagnostic stops the compilation like a compile error.
+pub trait Pass {
+    // Names the pass in the log
+    fn get_name(&self) -> &str;
+
+    fn run(&mut self, program: &mut Program) -> Result<(), Diagnostic>;
+}
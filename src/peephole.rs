@@ -0,0 +1,78 @@
+// Cleanups on the assembly of a function once it is written, line by line
+
+// Label defined by a line like `.return_main:`
+fn get_label(line: &str) -> Option<&str> {
+    if line.starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    return line.trim_end().strip_suffix(':');
+}
+
+// Target of an unconditional jump, without the comment after it
+fn get_jump(line: &str) -> Option<&str> {
+    let instruction = line.split(';').next()?.trim();
+
+    return Some(instruction.strip_prefix("jmp ")?.trim());
+}
+
+fn is_blank(line: &str) -> bool {
+    return line
+        .split(';')
+        .next()
+        .map_or(true, |code| code.trim().is_empty());
+}
+
+// Jumps to a label that only jumps again go straight to the final target, and jumps to a
+// label right after them are dropped since execution falls through to it anyway
+pub fn remove_redundant_jumps(assembly: &str) -> String {
+    let lines: Vec<&str> = assembly.split('\n').collect();
+
+    // First instruction after each label, skipping other labels and blank lines
+    let get_code_after = |start: usize| {
+        return lines[start..]
+            .iter()
+            .position(|line| get_label(line).is_none() && !is_blank(line))
+            .map(|offset| start + offset);
+    };
+
+    let get_trampoline = |label: &str| {
+        let index = lines
+            .iter()
+            .position(|line| get_label(line) == Some(label))?;
+        return get_jump(lines[get_code_after(index + 1)?]);
+    };
+
+    let mut output: Vec<String> = Vec::new();
+
+    for (index, line) in lines.iter().enumerate() {
+        let target = match get_jump(line) {
+            Some(target) => target,
+            None => {
+                output.push(line.to_string());
+                continue;
+            }
+        };
+
+        // Bounded so jumps chained in a loop keep their first target
+        let mut threaded = target;
+
+        for _ in 0..lines.len() {
+            match get_trampoline(threaded) {
+                Some(next) if next != target => threaded = next,
+                _ => break,
+            }
+        }
+
+        let next_code = get_code_after(index + 1).unwrap_or(lines.len());
+        let falls_through = lines[index + 1..next_code]
+            .iter()
+            .any(|line| get_label(line) == Some(threaded));
+
+        if !falls_through {
+            output.push(line.replacen(target, threaded, 1));
+        }
+    }
+
+    return output.join("\n");
+}
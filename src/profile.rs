@@ -0,0 +1,72 @@
+use std::{fs::read_to_string, path::Path};
+
+use crate::coverage::read_counters;
+
+// Functions ranked by the cycles spent in them, callees included
+pub fn write_report(filename: &str) -> Result<String, String> {
+    let stem = match Path::new(filename).file_stem() {
+        Some(stem) => stem.to_string_lossy().to_string(),
+        None => return Err(format!("Invalid source file {}.", filename)),
+    };
+
+    let map = match read_to_string(format!("{}.profmap", stem)) {
+        Ok(map) => map,
+        Err(error) => {
+            return Err(format!(
+                "Can not read {}.profmap, build with --profile first: {}",
+                stem, error
+            ))
+        }
+    };
+
+    let counters = read_counters(&format!("{}.prof", stem))?;
+
+    let functions: Vec<(&str, &str)> = map
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .map(|line| line.split_once('\t').unwrap_or((line, "")))
+        .collect();
+
+    if functions.len() * 2 != counters.len() {
+        return Err(format!(
+            "{}.prof does not match {}.profmap, rebuild and run the program again.",
+            stem, stem
+        ));
+    }
+
+    let mut rows: Vec<(&str, &str, u64, u64)> = functions
+        .iter()
+        .zip(counters.chunks_exact(2))
+        .map(|((name, position), counters)| (*name, *position, counters[0], counters[1]))
+        .collect();
+
+    rows.sort_by(|a, b| b.3.cmp(&a.3).then(b.2.cmp(&a.2)));
+
+    // The entry point includes every other function
+    let total = rows.iter().map(|row| row.3).max().unwrap_or(0).max(1);
+
+    let mut report = format!(
+        "{:>4}  {:<24} {:>10} {:>14} {:>12} {:>7}  {}\n",
+        "rank", "function", "calls", "cycles", "cycles/call", "time", "defined at"
+    );
+
+    for (rank, (name, position, calls, cycles)) in rows.iter().enumerate() {
+        let per_call = match calls {
+            0 => 0,
+            calls => cycles / calls,
+        };
+
+        report.push_str(&format!(
+            "{:>4}  {:<24} {:>10} {:>14} {:>12} {:>6.1}%  {}\n",
+            rank + 1,
+            name,
+            calls,
+            cycles,
+            per_call,
+            *cycles as f64 * 100.0 / total as f64,
+            position
+        ));
+    }
+
+    return Ok(report);
+}
@@ -0,0 +1,330 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Routine {
+    Itoa,
+    Atoi,
+    Ftoa,
+    Trap,
+    TrapTrace,
+    Backtrace,
+    CrashHandler,
+    DumpFile,
+    Random,
+}
+
+impl Routine {
+    pub fn get_label(&self) -> &str {
+        return match self {
+            Self::Itoa => "ez_itoa",
+            Self::Atoi => "ez_atoi",
+            Self::Ftoa => "ez_ftoa",
+            Self::Trap => "ez_trap",
+            Self::TrapTrace => "ez_trap_trace",
+            Self::Backtrace => "ez_backtrace",
+            Self::CrashHandler => "ez_crash_install",
+            Self::DumpFile => "ez_dump_file",
+            Self::Random => "ez_rand",
+        };
+    }
+
+    pub fn get_dependencies(&self) -> Vec<Routine> {
+        return match self {
+            Self::Itoa | Self::Atoi | Self::Trap | Self::Backtrace => Vec::new(),
+            Self::DumpFile | Self::Random => Vec::new(),
+            Self::Ftoa => vec![Self::Itoa],
+            Self::TrapTrace | Self::CrashHandler => vec![Self::Backtrace],
+        };
+    }
+
+    pub fn get_source(&self) -> &str {
+        return match self {
+            Self::Itoa => ITOA,
+            Self::Atoi => ATOI,
+            Self::Ftoa => FTOA,
+            Self::Trap => TRAP,
+            Self::TrapTrace => TRAP_TRACE,
+            Self::Backtrace => BACKTRACE,
+            Self::CrashHandler => CRASH_HANDLER,
+            Self::DumpFile => DUMP_FILE,
+            Self::Random => RANDOM,
+        };
+    }
+}
+
+// Writes the signed decimal representation of rdi into the buffer at rsi.
+// Returns the written length in rax, the buffer should fit 20 bytes.
+const ITOA: &str = "
+ez_itoa:
+	mov rax, rdi
+	mov r8, rsi
+	test rax, rax
+	jns .itoa_digits
+	mov byte [rsi], 0x2d
+	inc rsi
+	neg rax
+.itoa_digits:
+	mov rdi, rsi
+	mov rcx, 0xa
+.itoa_divide:
+	xor rdx, rdx
+	div rcx
+	add dl, 0x30
+	mov byte [rsi], dl
+	inc rsi
+	test rax, rax
+	jnz .itoa_divide
+	mov rax, rsi
+	sub rax, r8
+	dec rsi
+.itoa_reverse:
+	cmp rdi, rsi
+	jae .itoa_done
+	mov cl, byte [rdi]
+	mov dl, byte [rsi]
+	mov byte [rdi], dl
+	mov byte [rsi], cl
+	inc rdi
+	dec rsi
+	jmp .itoa_reverse
+.itoa_done:
+	ret";
+
+// Parses an optionally signed decimal number from the buffer at rdi,
+// stopping at the first non digit character. Returns the number in rax.
+const ATOI: &str = "
+ez_atoi:
+	xor rax, rax
+	xor r8, r8
+	cmp byte [rdi], 0x2d
+	jne .atoi_digits
+	mov r8, 0x1
+	inc rdi
+.atoi_digits:
+	movzx rcx, byte [rdi]
+	sub rcx, 0x30
+	cmp rcx, 0x9
+	ja .atoi_sign
+	imul rax, rax, 0xa
+	add rax, rcx
+	inc rdi
+	jmp .atoi_digits
+.atoi_sign:
+	test r8, r8
+	jz .atoi_done
+	neg rax
+.atoi_done:
+	ret";
+
+// Writes the f64 in rdi into the buffer at rsi with six decimal places.
+// Returns the written length in rax, the buffer should fit 24 bytes.
+// Only values with an absolute value below 2^63 / 10^6 are printed correctly.
+const FTOA: &str = "
+ez_ftoa:
+	push rsi
+	mov rax, rdi
+	btr rax, 0x3f
+	movq xmm0, rax
+	mov rax, 0xf4240
+	cvtsi2sd xmm1, rax
+	mulsd xmm0, xmm1
+	cvtsd2si rax, xmm0
+	test rdi, rdi
+	jns .ftoa_integer
+	mov byte [rsi], 0x2d
+	inc rsi
+.ftoa_integer:
+	xor rdx, rdx
+	mov rcx, 0xf4240
+	div rcx
+	push rdx
+	push rsi
+	mov rdi, rax
+	call ez_itoa
+	pop rsi
+	add rsi, rax
+	pop rax
+	mov byte [rsi], 0x2e
+	add rsi, 0x6
+	mov rcx, 0xa
+	mov r8, 0x6
+.ftoa_fraction:
+	xor rdx, rdx
+	div rcx
+	add dl, 0x30
+	mov byte [rsi], dl
+	dec rsi
+	dec r8
+	jnz .ftoa_fraction
+	add rsi, 0x7
+	pop rax
+	sub rsi, rax
+	mov rax, rsi
+	ret";
+
+// Writes the message at rdi with length rsi to stderr and stops at an invalid instruction,
+// the kernel kills the program with SIGILL.
+const TRAP: &str = "
+ez_trap:
+	mov rdx, rsi
+	mov rsi, rdi
+	mov rdi, 0x2
+	mov rax, 0x1
+	syscall
+	ud2";
+
+// Like ez_trap, with the backtrace written after the message.
+const TRAP_TRACE: &str = "
+ez_trap_trace:
+	mov rdx, rsi
+	mov rsi, rdi
+	mov rdi, 0x2
+	mov rax, 0x1
+	syscall
+	call ez_backtrace
+	ud2";
+
+// Writes the shadow call stack to stderr, innermost function first.
+// Entries point to null terminated lines, calls past the capacity are not recorded.
+const BACKTRACE: &str = "
+ez_backtrace:
+	push rbx
+	lea rsi, [rel ez_backtrace_header]
+	mov rdx, ez_backtrace_header.len
+	mov rdi, 0x2
+	mov rax, 0x1
+	syscall
+	mov rbx, [rel ez_shadow_depth]
+	cmp rbx, ez_shadow_capacity
+	jbe .backtrace_next
+	mov rbx, ez_shadow_capacity
+.backtrace_next:
+	test rbx, rbx
+	jz .backtrace_done
+	dec rbx
+	lea rax, [rel ez_shadow_stack]
+	mov rsi, [rax + rbx * 8]
+	xor rdx, rdx
+.backtrace_length:
+	cmp byte [rsi + rdx], 0x0
+	je .backtrace_write
+	inc rdx
+	jmp .backtrace_length
+.backtrace_write:
+	mov rdi, 0x2
+	mov rax, 0x1
+	syscall
+	jmp .backtrace_next
+.backtrace_done:
+	pop rbx
+	ret";
+
+// Installs ez_crash for SIGBUS, SIGFPE and SIGSEGV. The handler resets itself and
+// is not blocked, so raising the signal again after the backtrace terminates the
+// program the way the crash would have. x86-64 requires a restorer to be set.
+const CRASH_HANDLER: &str = "
+ez_crash_install:
+	sub rsp, 0x20
+	lea rax, [rel ez_crash]
+	mov [rsp], rax
+	mov rax, 0xc4000000
+	mov [rsp + 0x8], rax
+	lea rax, [rel ez_crash_restore]
+	mov [rsp + 0x10], rax
+	mov qword [rsp + 0x18], 0x0
+	mov rdi, 0x7
+	mov rsi, rsp
+	xor rdx, rdx
+	mov r10, 0x8
+	mov rax, 0xd
+	syscall
+	mov rdi, 0x8
+	mov rsi, rsp
+	xor rdx, rdx
+	mov r10, 0x8
+	mov rax, 0xd
+	syscall
+	mov rdi, 0xb
+	mov rsi, rsp
+	xor rdx, rdx
+	mov r10, 0x8
+	mov rax, 0xd
+	syscall
+	add rsp, 0x20
+	ret
+ez_crash:
+	mov rbx, rdi
+	lea rsi, [rel ez_crash_segv]
+	mov rdx, ez_crash_segv.len
+	cmp rbx, 0xb
+	je .crash_message
+	lea rsi, [rel ez_crash_fpe]
+	mov rdx, ez_crash_fpe.len
+	cmp rbx, 0x8
+	je .crash_message
+	lea rsi, [rel ez_crash_bus]
+	mov rdx, ez_crash_bus.len
+.crash_message:
+	mov rdi, 0x2
+	mov rax, 0x1
+	syscall
+	call ez_backtrace
+	mov rax, 0x27
+	syscall
+	mov rdi, rax
+	mov rsi, rbx
+	mov rax, 0x3e
+	syscall
+ez_crash_restore:
+	mov rax, 0xf
+	syscall";
+
+// Writes rdx bytes from rsi to the file named by the null terminated path at rdi,
+// replacing earlier contents. Failures are ignored so the exit code stays the one of main.
+const DUMP_FILE: &str = "
+ez_dump_file:
+	push rsi
+	push rdx
+	mov rsi, 0x241
+	mov rdx, 0x1a4
+	mov rax, 0x2
+	syscall
+	pop rdx
+	pop rsi
+	test rax, rax
+	js .dump_done
+	push rax
+	mov rdi, rax
+	mov rax, 0x1
+	syscall
+	pop rdi
+	mov rax, 0x3
+	syscall
+.dump_done:
+	ret";
+
+// Steps the xorshift64* generator in ez_rand_state and returns the next number in rax, with
+// the sign bit cleared. A zero state is seeded from the time stamp counter first, which needs
+// no operating system. Clobbers rdx.
+const RANDOM: &str = "
+ez_rand:
+	mov rax, [rel ez_rand_state]
+	test rax, rax
+	jnz .rand_next
+	rdtsc
+	shl rdx, 0x20
+	or rax, rdx
+	or rax, 0x1
+.rand_next:
+	mov rdx, rax
+	shr rdx, 0xc
+	xor rax, rdx
+	mov rdx, rax
+	shl rdx, 0x19
+	xor rax, rdx
+	mov rdx, rax
+	shr rdx, 0x1b
+	xor rax, rdx
+	mov [rel ez_rand_state], rax
+	mov rdx, 0x2545f4914f6cdd1d
+	imul rax, rdx
+	shr rax, 0x1
+	ret";
@@ -0,0 +1,372 @@
+use std::{
+    io::{self, BufRead, BufReader, ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream},
+    panic,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use ezlang::{
+    compiler::{Compiler, CompilerOptions},
+    diagnostics::get_panic_message,
+};
+
+use crate::escape_json;
+
+// Name of the program of every request in its diagnostics
+const SOURCE_NAME: &str = "input.ez";
+
+// Request line and headers, the body is bounded separately
+const MAX_HEADER_BYTES: u64 = 16 * 1024;
+
+// Same as the main thread, the passes over expressions recurse up to the depth limit
+const COMPILE_STACK_SIZE: usize = 8 * 1024 * 1024;
+
+pub struct ServerOptions {
+    pub compiler: CompilerOptions,
+    // Time to read a whole request and to compile it, each
+    pub timeout: Duration,
+    pub max_request_bytes: usize,
+    // Connections handled at the same time, others are answered right away with an error
+    pub max_connections: usize,
+    // Compilations running at the same time, counting the ones that outlived their timeout
+    pub max_compilations: usize,
+}
+
+struct Response {
+    status: &'static str,
+    body: String,
+}
+
+// Number of threads doing one kind of work, each one holds a slot until it finishes
+struct Slots {
+    used: AtomicUsize,
+    max: usize,
+}
+
+struct Slot(Arc<Slots>);
+
+impl Slots {
+    fn new(max: usize) -> Arc<Slots> {
+        return Arc::new(Slots {
+            used: AtomicUsize::new(0),
+            max,
+        });
+    }
+
+    fn acquire(self: &Arc<Self>) -> Option<Slot> {
+        let acquired = self
+            .used
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| {
+                return (used < self.max).then_some(used + 1);
+            });
+
+        return acquired.ok().map(|_| Slot(Arc::clone(self)));
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.used.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+// Reads of a request share one deadline, so sending a byte at a time does not extend it
+struct DeadlineReader<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineReader<'_> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let left = self.deadline.saturating_duration_since(Instant::now());
+
+        if left.is_zero() {
+            return Err(io::Error::new(
+                ErrorKind::TimedOut,
+                "The request took longer than the timeout.",
+            ));
+        }
+
+        self.stream.set_read_timeout(Some(left))?;
+
+        return self.stream.read(buffer);
+    }
+}
+
+// Answers `POST /compile` with the source as body until the process is stopped, every
+// connection on a thread of its own
+pub fn serve(address: &str, options: ServerOptions) -> Result<(), String> {
+    let listener = match TcpListener::bind(address) {
+        Ok(listener) => listener,
+        Err(error) => return Err(format!("Can not listen on {}: {}", address, error)),
+    };
+
+    // Diagnostics go to the client in the response, not to the log of the server
+    panic::set_hook(Box::new(|_| {}));
+
+    eprintln!("Listening on http://{}/compile", address);
+
+    let options = Arc::new(options);
+    let connections = Slots::new(options.max_connections);
+    let compilations = Slots::new(options.max_compilations);
+
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(error) => {
+                eprintln!("error: {}", error);
+                continue;
+            }
+        };
+
+        // A client that does not read its answer can not hold the server either
+        if let Err(error) = stream.set_write_timeout(Some(options.timeout)) {
+            eprintln!("error: {}", error);
+            continue;
+        }
+
+        let slot = match connections.acquire() {
+            Some(slot) => slot,
+            None => {
+                let response = write_error(
+                    "503 Service Unavailable",
+                    "Too many connections, try again later.",
+                );
+
+                if let Err(error) = write_response(&mut stream, &response) {
+                    eprintln!("error: {}", error);
+                }
+
+                continue;
+            }
+        };
+
+        let options = Arc::clone(&options);
+        let compilations = Arc::clone(&compilations);
+
+        let spawned = thread::Builder::new().spawn(move || {
+            let _slot = slot;
+
+            if let Err(error) = handle_connection(stream, &options, &compilations) {
+                eprintln!("error: {}", error);
+            }
+        });
+
+        if let Err(error) = spawned {
+            eprintln!("error: {}", error);
+        }
+    }
+
+    return Ok(());
+}
+
+fn handle_connection(
+    mut stream: TcpStream,
+    options: &ServerOptions,
+    compilations: &Arc<Slots>,
+) -> io::Result<()> {
+    let deadline = Instant::now() + options.timeout;
+
+    let response = match read_request(&stream, deadline, options) {
+        Ok(source) => compile(source, options, compilations),
+        Err(response) => response,
+    };
+
+    return write_response(&mut stream, &response);
+}
+
+fn write_response(stream: &mut TcpStream, response: &Response) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        response.body.len(),
+        response.body
+    )?;
+
+    return stream.flush();
+}
+
+// Body of a valid request, or the response rejecting it
+fn read_request(
+    stream: &TcpStream,
+    deadline: Instant,
+    options: &ServerOptions,
+) -> Result<Vec<u8>, Response> {
+    let limit = MAX_HEADER_BYTES + options.max_request_bytes as u64;
+    let mut reader = BufReader::new(DeadlineReader { stream, deadline }.take(limit));
+
+    let mut request_line = String::new();
+    let mut length: Option<usize> = None;
+
+    if reader.read_line(&mut request_line).is_err() {
+        return Err(write_error("400 Bad Request", "Can not read the request."));
+    }
+
+    loop {
+        let mut header = String::new();
+
+        match reader.read_line(&mut header) {
+            Ok(0) | Err(_) => {
+                return Err(write_error("400 Bad Request", "Can not read the headers."))
+            }
+            Ok(_) => {}
+        }
+
+        let header = header.trim_end();
+
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().ok();
+            }
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+
+    match (parts.next(), parts.next()) {
+        (Some("POST"), Some("/compile")) => {}
+        (Some(_), Some("/compile")) => {
+            return Err(write_error(
+                "405 Method Not Allowed",
+                "Send the program with POST.",
+            ))
+        }
+        _ => return Err(write_error("404 Not Found", "Only /compile is served.")),
+    }
+
+    let length = match length {
+        Some(length) => length,
+        None => {
+            return Err(write_error(
+                "411 Length Required",
+                "Send the length of the program in Content-Length.",
+            ))
+        }
+    };
+
+    if length > options.max_request_bytes {
+        return Err(write_error(
+            "413 Content Too Large",
+            &format!(
+                "Programs can have at most {} bytes.",
+                options.max_request_bytes
+            ),
+        ));
+    }
+
+    let mut source = vec![0; length];
+
+    if reader.read_exact(&mut source).is_err() {
+        return Err(write_error("400 Bad Request", "Can not read the program."));
+    }
+
+    return Ok(source);
+}
+
+// Compiles on a thread of its own so a request taking too long can be answered. The thread
+// can not be stopped and keeps running in the background, the limits bound how long it takes
+// and its slot how many of them there are.
+fn compile(source: Vec<u8>, options: &ServerOptions, compilations: &Arc<Slots>) -> Response {
+    let slot = match compilations.acquire() {
+        Some(slot) => slot,
+        None => {
+            return write_error(
+                "503 Service Unavailable",
+                "Too many compilations are running, try again later.",
+            )
+        }
+    };
+
+    let (sender, receiver) = mpsc::channel();
+    let compiler_options = options.compiler.clone();
+
+    let spawned = thread::Builder::new()
+        .stack_size(COMPILE_STACK_SIZE)
+        .spawn(move || {
+            let _slot = slot;
+
+            let result = panic::catch_unwind(move || {
+                let mut compiler = Compiler::from_source(SOURCE_NAME, source, compiler_options);
+                compiler.generate();
+
+                return (compiler.get_assembly(), compiler.warnings);
+            });
+
+            let _ = sender.send(result.map_err(|payload| get_panic_message(&*payload)));
+        });
+
+    if spawned.is_err() {
+        return write_error("503 Service Unavailable", "Can not start the compilation.");
+    }
+
+    return match receiver.recv_timeout(options.timeout) {
+        Ok(Ok((assembly, warnings))) => Response {
+            status: "200 OK",
+            body: write_result("success", &[], &warnings, Some(&assembly)),
+        },
+        Ok(Err(message)) => {
+            let diagnostics = match message {
+                Some(message) => vec![message],
+                None => Vec::new(),
+            };
+
+            Response {
+                status: "200 OK",
+                body: write_result("compile_error", &diagnostics, &[], None),
+            }
+        }
+        Err(_) => write_error(
+            "503 Service Unavailable",
+            "Compilation took longer than the timeout.",
+        ),
+    };
+}
+
+fn write_error(status: &'static str, error: &str) -> Response {
+    return Response {
+        status,
+        body: format!(
+            "{{\"status\": \"request_error\", \"error\": {}}}",
+            escape_json(error)
+        ),
+    };
+}
+
+fn write_result(
+    status: &str,
+    diagnostics: &[String],
+    warnings: &[String],
+    assembly: Option<&str>,
+) -> String {
+    let write_list = |messages: &[String]| {
+        let messages: Vec<String> = messages
+            .iter()
+            .map(|message| escape_json(message))
+            .collect();
+
+        return messages.join(", ");
+    };
+
+    let assembly = match assembly {
+        Some(assembly) => escape_json(assembly),
+        None => "null".to_owned(),
+    };
+
+    return format!(
+        "{{\"status\": {}, \"diagnostics\": [{}], \"warnings\": [{}], \"assembly\": {}}}",
+        escape_json(status),
+        write_list(diagnostics),
+        write_list(warnings),
+        assembly
+    );
+}
@@ -0,0 +1,70 @@
+use std::{fmt, ops::Range};
+
+// Place in a source file, lines and columns count from 1 and columns count bytes
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Position {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    pub fn start(file: &str) -> Self {
+        Self {
+            file: file.to_owned(),
+            line: 1,
+            column: 1,
+        }
+    }
+
+    pub fn new_line(&mut self) {
+        self.line += 1;
+        self.column = 1;
+    }
+
+    pub fn next_column(&mut self) {
+        self.column += 1;
+    }
+}
+
+// Written as file:line:column, the prefix of every diagnostic
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}:{}:{}", self.file, self.line, self.column);
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Span {
+    pub position: Position,
+    // Indices into the tokens of the program
+    pub tokens: Range<usize>,
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}", self.position);
+    }
+}
+
+// A value with the place it was written at
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Spanned<T> {
+    pub position: Position,
+    pub value: T,
+}
+
+impl<T> Spanned<T> {
+    pub fn new(position: Position, value: T) -> Self {
+        return Self { position, value };
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Spanned<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}: {}", self.position, self.value);
+    }
+}
@@ -0,0 +1,421 @@
+use std::ops::Range;
+
+use crate::{
+    lexer::{Edition, Lexer, Token, TokenType},
+    parser::{Parser, Program},
+    span::Position,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TriviaKind {
+    Whitespace,
+    // The byte order mark some editors start a file with
+    ByteOrderMark,
+    // The `#!` line at the top of a script, with its line break
+    Shebang,
+    // The `#edition` line at the top of a file
+    Pragma,
+}
+
+// Source text between tokens that the parser skips
+#[derive(Debug, Clone)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub text: String,
+}
+
+// A token as written, with the trivia before it
+#[derive(Debug, Clone)]
+pub struct SyntaxToken {
+    pub token: Token,
+    pub text: String,
+    pub leading: Vec<Trivia>,
+    // Byte range of the text in the source it was parsed from
+    pub range: Range<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SyntaxKind {
+    File,
+    Include,
+    IncludeAsm,
+    Struct,
+    Constant,
+    StaticAssert,
+    Function,
+    // Statement of a function body, with the index of the function and of the statement in the AST
+    Statement(usize, usize),
+}
+
+#[derive(Debug, Clone)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+#[derive(Debug, Clone)]
+pub struct SyntaxNode {
+    pub kind: SyntaxKind,
+    pub children: Vec<SyntaxElement>,
+}
+
+// Concrete syntax tree of a single file. Printing it gives back the source byte for byte,
+// so tools can rewrite some nodes and keep the layout of everything else.
+#[derive(Debug, Clone)]
+pub struct SyntaxTree {
+    pub filename: String,
+    pub edition: Edition,
+    pub root: SyntaxNode,
+    // Trivia after the last token
+    pub trailing: Vec<Trivia>,
+}
+
+impl SyntaxNode {
+    pub fn get_tokens(&self) -> Vec<&SyntaxToken> {
+        let mut tokens: Vec<&SyntaxToken> = Vec::new();
+
+        for child in self.children.iter() {
+            match child {
+                SyntaxElement::Node(node) => tokens.extend(node.get_tokens()),
+                SyntaxElement::Token(token) => tokens.push(token),
+            }
+        }
+
+        return tokens;
+    }
+
+    // Byte range from the first to the last token, without the leading trivia
+    pub fn get_range(&self) -> Option<Range<usize>> {
+        let tokens = self.get_tokens();
+
+        return Some(tokens.first()?.range.start..tokens.last()?.range.end);
+    }
+
+    pub fn get_tokens_mut(&mut self) -> Vec<&mut SyntaxToken> {
+        let mut tokens: Vec<&mut SyntaxToken> = Vec::new();
+
+        for child in self.children.iter_mut() {
+            match child {
+                SyntaxElement::Node(node) => tokens.extend(node.get_tokens_mut()),
+                SyntaxElement::Token(token) => tokens.push(token),
+            }
+        }
+
+        return tokens;
+    }
+
+    // Source of the node from its first token on, without the trivia before it
+    pub fn get_text(&self) -> String {
+        let mut text = String::new();
+
+        self.write_source(&mut text);
+
+        let leading: usize = match self.get_tokens().first() {
+            Some(token) => token.leading.iter().map(|trivia| trivia.text.len()).sum(),
+            None => 0,
+        };
+
+        return text[leading..].to_owned();
+    }
+
+    pub fn find(&self, kind: SyntaxKind) -> Option<&SyntaxNode> {
+        if self.kind == kind {
+            return Some(self);
+        }
+
+        return self.children.iter().find_map(|child| match child {
+            SyntaxElement::Node(node) => node.find(kind),
+            SyntaxElement::Token(_) => None,
+        });
+    }
+
+    fn write_source(&self, source: &mut String) {
+        for child in self.children.iter() {
+            match child {
+                SyntaxElement::Node(node) => node.write_source(source),
+                SyntaxElement::Token(token) => {
+                    for trivia in token.leading.iter() {
+                        source.push_str(&trivia.text);
+                    }
+
+                    source.push_str(&token.text);
+                }
+            }
+        }
+    }
+}
+
+impl SyntaxTree {
+    // Tokens grouped into top level items, statements are only known once linked to the AST
+    pub fn parse(filename: &str, source: &str, edition: Edition) -> Self {
+        let mut tree = Self {
+            filename: filename.to_owned(),
+            edition,
+            root: SyntaxNode {
+                kind: SyntaxKind::File,
+                children: Vec::new(),
+            },
+            trailing: Vec::new(),
+        };
+
+        // The lexer needs at least one character
+        if source.len() == 0 {
+            return tree;
+        }
+
+        let mut lexer = Lexer::from_source(filename, source.as_bytes().to_vec(), edition);
+
+        let mut items: Vec<SyntaxElement> = Vec::new();
+        let mut item: Vec<SyntaxElement> = Vec::new();
+        let mut depth = 0;
+        let mut end = 0;
+
+        while let Some((token, range)) = lexer.next_with_range() {
+            let closes = match token.token_type {
+                TokenType::LeftPar | TokenType::LeftBrace | TokenType::LeftBracket => {
+                    depth += 1;
+                    false
+                }
+                TokenType::RightPar | TokenType::RightBracket => {
+                    depth -= 1;
+                    false
+                }
+                TokenType::RightBrace => {
+                    depth -= 1;
+                    depth == 0
+                }
+                TokenType::Semicolon => depth == 0,
+                _ => false,
+            };
+
+            item.push(SyntaxElement::Token(SyntaxToken {
+                leading: get_trivia(source, end..range.start),
+                text: source[range.clone()].to_owned(),
+                token,
+                range: range.clone(),
+            }));
+
+            end = range.end;
+
+            if closes {
+                items.push(SyntaxElement::Node(SyntaxNode {
+                    kind: get_item_kind(&item),
+                    children: item,
+                }));
+                item = Vec::new();
+            }
+        }
+
+        // Unterminated last item, kept so nothing is lost
+        if item.len() > 0 {
+            items.push(SyntaxElement::Node(SyntaxNode {
+                kind: get_item_kind(&item),
+                children: item,
+            }));
+        }
+
+        tree.root.children = items;
+        tree.trailing = get_trivia(source, end..source.len());
+
+        return tree;
+    }
+
+    // Wraps the tokens of every statement of the file in a node pointing to the statement
+    // of the program parsed from the tree
+    pub fn link(&mut self, program: &Program) {
+        for index in 0..program.functions.len() {
+            self.link_function(program, index);
+        }
+    }
+
+    pub fn link_function(&mut self, program: &Program, index: usize) {
+        let function = &program.functions[index];
+
+        let body = match &function.body {
+            Some(body) if function.position.file == self.filename => body,
+            _ => return,
+        };
+
+        // The item holding the name of the function
+        let node = self.root.children.iter_mut().find_map(|item| match item {
+            SyntaxElement::Node(node)
+                if node
+                    .get_tokens()
+                    .iter()
+                    .any(|token| is_at(&token.token.position, &function.position)) =>
+            {
+                Some(node)
+            }
+            _ => None,
+        });
+
+        let node = match node {
+            Some(node) => node,
+            None => return,
+        };
+
+        // Back to front so the token indices of earlier statements stay valid
+        for (statement, span) in body.spans.iter().enumerate().rev() {
+            let first = match program.tokens.get(span.tokens.start) {
+                Some(token) => &token.position,
+                None => continue,
+            };
+            let last = match span.tokens.end.checked_sub(1) {
+                Some(last) => &program.tokens[last].position,
+                None => continue,
+            };
+
+            let find = |position: &Position| {
+                node.children.iter().position(|child| match child {
+                    SyntaxElement::Token(token) => is_at(&token.token.position, position),
+                    SyntaxElement::Node(_) => false,
+                })
+            };
+
+            if let (Some(start), Some(end)) = (find(first), find(last)) {
+                let children = node.children.drain(start..=end).collect();
+
+                node.children.insert(
+                    start,
+                    SyntaxElement::Node(SyntaxNode {
+                        kind: SyntaxKind::Statement(index, statement),
+                        children,
+                    }),
+                );
+            }
+        }
+    }
+
+    // Edition of the tokens of this file, which a pragma can override
+    pub fn get_file_edition(&self) -> Edition {
+        let pragma = self.root.get_tokens().first().and_then(|token| {
+            token
+                .leading
+                .iter()
+                .find(|trivia| trivia.kind == TriviaKind::Pragma)
+        });
+
+        return match pragma {
+            Some(pragma) => Edition::from_name(pragma.text.trim_start_matches("#edition").trim())
+                .unwrap_or(self.edition),
+            None => self.edition,
+        };
+    }
+
+    // Program of the source the tree prints, with the statements of the file in its nodes
+    pub fn to_program(&self) -> Program {
+        let mut parser =
+            Parser::from_source(&self.filename, self.to_source().into_bytes(), self.edition);
+        parser.generate_tokens();
+
+        return parser.generate_program();
+    }
+
+    pub fn to_source(&self) -> String {
+        let mut source = String::new();
+
+        self.root.write_source(&mut source);
+
+        for trivia in self.trailing.iter() {
+            source.push_str(&trivia.text);
+        }
+
+        return source;
+    }
+
+    // Leading trivia of the next token, or the end of the file
+    pub fn get_following_trivia(&self, token: &SyntaxToken) -> &[Trivia] {
+        let tokens = self.root.get_tokens();
+
+        let next = tokens
+            .iter()
+            .position(|other| other.range == token.range)
+            .and_then(|index| tokens.get(index + 1).copied());
+
+        return match next {
+            Some(next) => &next.leading,
+            None => &self.trailing,
+        };
+    }
+
+    pub fn find_statement(&self, function: usize, statement: usize) -> Option<&SyntaxNode> {
+        return self.root.find(SyntaxKind::Statement(function, statement));
+    }
+}
+
+fn is_at(a: &Position, b: &Position) -> bool {
+    return a.line == b.line && a.column == b.column;
+}
+
+fn get_item_kind(tokens: &[SyntaxElement]) -> SyntaxKind {
+    // pub and extern come before the keyword of a function
+    let keyword = tokens.iter().find_map(|element| match element {
+        SyntaxElement::Token(SyntaxToken {
+            token:
+                Token {
+                    token_type: TokenType::Pub | TokenType::Extern,
+                    ..
+                },
+            ..
+        }) => None,
+        SyntaxElement::Token(token) => Some(&token.token.token_type),
+        SyntaxElement::Node(_) => None,
+    });
+
+    return match keyword {
+        Some(TokenType::Include) => SyntaxKind::Include,
+        Some(TokenType::IncludeAsm) => SyntaxKind::IncludeAsm,
+        Some(TokenType::Struct) => SyntaxKind::Struct,
+        Some(TokenType::Const) => SyntaxKind::Constant,
+        Some(TokenType::StaticAssert) => SyntaxKind::StaticAssert,
+        _ => SyntaxKind::Function,
+    };
+}
+
+fn get_trivia(source: &str, range: Range<usize>) -> Vec<Trivia> {
+    let mut text = &source[range.clone()];
+    let mut trivia: Vec<Trivia> = Vec::new();
+
+    // The lexer only skips these at the very start of the file, in this order
+    if range.start == 0 {
+        if let Some(rest) = text.strip_prefix('\u{feff}') {
+            trivia.push(Trivia {
+                kind: TriviaKind::ByteOrderMark,
+                text: String::from('\u{feff}'),
+            });
+
+            text = rest;
+        }
+
+        if text.starts_with("#!") {
+            let end = text.find('\n').map_or(text.len(), |end| end + 1);
+
+            trivia.push(Trivia {
+                kind: TriviaKind::Shebang,
+                text: text[..end].to_owned(),
+            });
+
+            text = &text[end..];
+        }
+
+        if text.starts_with("#edition") {
+            let end = text.find('\n').unwrap_or(text.len());
+
+            trivia.push(Trivia {
+                kind: TriviaKind::Pragma,
+                text: text[..end].to_owned(),
+            });
+
+            text = &text[end..];
+        }
+    }
+
+    if text.len() > 0 {
+        trivia.push(Trivia {
+            kind: TriviaKind::Whitespace,
+            text: text.to_owned(),
+        });
+    }
+
+    return trivia;
+}
@@ -0,0 +1,132 @@
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum Target {
+    /// Executables and shared libraries for Linux, exiting with a system call
+    #[default]
+    #[value(name = "linux-x86_64")]
+    Linux,
+    /// Multiboot kernel without an operating system, also written as a flat binary
+    #[value(name = "baremetal-x86_64")]
+    Baremetal,
+}
+
+impl Target {
+    pub fn get_name(&self) -> &str {
+        return match self {
+            Target::Linux => "linux-x86_64",
+            Target::Baremetal => "baremetal-x86_64",
+        };
+    }
+
+    // Whether the program can make system calls, which write and printf need
+    pub fn has_os(&self) -> bool {
+        return *self == Target::Linux;
+    }
+}
+
+// Where the multiboot loader places the image, the flat binary starts with the first section
+pub const LOAD_ADDRESS: usize = 0x100000;
+
+// Multiboot header with the load addresses in it, so a flat binary boots as well as the ELF.
+// The loader enters _start in 32 bit protected mode, the stub maps the first GiB with 2 MiB
+// pages, enables SSE for the float code and switches to long mode, main is called after it.
+// Interrupts stay disabled the whole time.
+pub const BOOT_STUB: &str = "
+section .multiboot align=4
+ez_multiboot:
+	dd 0x1badb002
+	dd 0x10000
+	dd -(0x1badb002 + 0x10000)
+	dd ez_multiboot
+	dd ez_image_start
+	dd ez_load_end
+	dd ez_bss_end
+	dd _start
+section .data
+ez_boot_gdt:
+	dq 0x0
+	dq 0x00af9a000000ffff
+	dq 0x00cf92000000ffff
+ez_boot_gdt_pointer:
+	dw 0x17
+	dd ez_boot_gdt
+section .bss
+	alignb 4096
+ez_boot_pml4 resb 4096
+ez_boot_pdpt resb 4096
+ez_boot_pd resb 4096
+ez_boot_stack resb 0x4000
+ez_boot_stack_top:
+section .text
+	global _start
+bits 32
+_start:
+	cli
+	mov esp, ez_boot_stack_top
+	mov eax, ez_boot_pdpt
+	or eax, 0x3
+	mov [ez_boot_pml4], eax
+	mov eax, ez_boot_pd
+	or eax, 0x3
+	mov [ez_boot_pdpt], eax
+	xor ecx, ecx
+.boot_map:
+	mov eax, ecx
+	shl eax, 0x15
+	or eax, 0x83
+	mov [ez_boot_pd + ecx * 8], eax
+	inc ecx
+	cmp ecx, 0x200
+	jne .boot_map
+	mov eax, ez_boot_pml4
+	mov cr3, eax
+	mov eax, cr4
+	or eax, 0x620
+	mov cr4, eax
+	mov ecx, 0xc0000080
+	rdmsr
+	or eax, 0x100
+	wrmsr
+	mov eax, cr0
+	and eax, 0xfffffffb
+	or eax, 0x80000003
+	mov cr0, eax
+	lgdt [ez_boot_gdt_pointer]
+	push 0x8
+	push ez_boot_long
+	retf
+bits 64
+ez_boot_long:
+	mov ax, 0x10
+	mov ds, ax
+	mov es, ax
+	mov ss, ax
+	lea rsp, [rel ez_boot_stack_top]";
+
+// Once main and the destructors return there is nothing left to return to
+pub const BOOT_HALT: &str = "
+.boot_halt:
+	hlt
+	jmp .boot_halt";
+
+// Default layout for ld, the symbols are read by the multiboot header
+pub fn write_linker_script() -> String {
+    return format!(
+        "ENTRY(_start)
+SECTIONS
+{{
+  . = {:#x};
+  ez_image_start = .;
+  .multiboot : {{ KEEP(*(.multiboot)) }}
+  .text : {{ *(.text .text.*) }}
+  .rodata : {{ *(.rodata .rodata.*) }}
+  .data : {{ *(.data .data.*) }}
+  ez_load_end = .;
+  .bss : {{ *(.bss .bss.*) *(COMMON) }}
+  ez_bss_end = .;
+}}
+",
+        LOAD_ADDRESS
+    );
+}
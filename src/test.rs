@@ -0,0 +1,583 @@
+// Differential tests of the integer operators. Random expressions of arithmetic and calls
+// are written both in ez and in C, the ez program is compiled and interpreted, the C one is
+// built with cc, and all of them must agree with the value the constant folder computes.
+// A failing run prints the seed that generates the same programs again.
+
+use std::{
+    env,
+    fs::{create_dir_all, write},
+    panic,
+    process::{Command, ExitCode},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use clap::Parser;
+use ezlang::{
+    compiler::{Compiler, CompilerOptions},
+    constants::{compute, wrap},
+    diagnostics::get_panic_message,
+    gas::AsmSyntax,
+    interpreter::{Interpreter, InterpreterOptions},
+    lexer::{BinaryOperator, Edition},
+    lint::{Lint, LintConfig, LintLevel},
+    parser::{FunctionId, Parser as SourceParser, Type},
+};
+
+const TYPES: [Type; 8] = [
+    Type::U8,
+    Type::U16,
+    Type::U32,
+    Type::U64,
+    Type::I8,
+    Type::I16,
+    Type::I32,
+    Type::I64,
+];
+
+const ARGUMENTS: [&str; 3] = ["a", "b", "c"];
+
+// Literals and casts never need parentheses around them
+const ATOM: u8 = u8::MAX;
+
+/// Check the integer operators of ez against C on random expressions
+#[derive(Parser)]
+#[command(about)]
+struct Cli {
+    /// Programs to generate, each computing a single expression
+    #[arg(long, default_value_t = 100)]
+    programs: usize,
+
+    /// Levels of operations each expression may nest
+    #[arg(long, default_value_t = 4)]
+    depth: usize,
+
+    /// Operators to generate, any of + - * / & | ^
+    #[arg(long, default_value = "+-*/&|^")]
+    operators: String,
+
+    /// Seed of the generator, taken from the clock when missing
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Directory the programs and executables are written to
+    #[arg(long, default_value = "target/difftest")]
+    directory: String,
+
+    /// C compiler building the reference programs
+    #[arg(long, default_value = "cc")]
+    cc: String,
+
+    /// Assembler to build the ez programs with
+    #[arg(long, value_enum, default_value_t)]
+    asm_syntax: AsmSyntax,
+}
+
+// xorshift64*, good enough to pick operators and values and the same on every machine
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+
+        return self.0.wrapping_mul(0x2545f4914f6cdd1d);
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        return (self.next() % bound as u64) as usize;
+    }
+
+    // Limits of the type and values next to them turn up more often than the rest
+    fn value(&mut self, value_type: &Type) -> u64 {
+        let bits = value_type.get_size() as u32 * 8;
+
+        let value = match self.below(6) {
+            0 => 0,
+            1 => 1,
+            2 => u64::MAX,
+            3 => 1 << (bits - 1),
+            4 => (1 << (bits - 1)) - 1,
+            _ => self.next(),
+        };
+
+        return wrap(value, value_type);
+    }
+}
+
+// Expression written in both languages, with the value the constant folder gives it
+struct Generated {
+    ez: String,
+    c: String,
+    value: u64,
+    // Of the outermost operator, to only write the parentheses ez needs
+    precedence: u8,
+}
+
+struct Case {
+    arguments: Vec<(Type, u64)>,
+    return_type: Type,
+    expression: Generated,
+}
+
+struct Generator {
+    random: Random,
+    operators: Vec<BinaryOperator>,
+    arguments: Vec<(Type, u64)>,
+}
+
+impl Generator {
+    fn next_case(&mut self, depth: usize) -> Case {
+        self.arguments = ARGUMENTS
+            .iter()
+            .map(|_| {
+                let argument_type = TYPES[self.random.below(TYPES.len())].clone();
+                let value = self.random.value(&argument_type);
+
+                return (argument_type, value);
+            })
+            .collect();
+
+        let return_type = TYPES[self.random.below(TYPES.len())].clone();
+        let expression = self.next_expression(&return_type, depth);
+
+        return Case {
+            arguments: self.arguments.clone(),
+            return_type,
+            expression,
+        };
+    }
+
+    fn next_expression(&mut self, expression_type: &Type, depth: usize) -> Generated {
+        return match (depth, self.random.below(8)) {
+            (0, _) | (_, 0) => self.next_leaf(expression_type),
+            (_, 1) => {
+                let operand_type = TYPES[self.random.below(TYPES.len())].clone();
+                let operand = self.next_expression(&operand_type, depth - 1);
+
+                get_cast(expression_type, operand)
+            }
+            (_, 2) => {
+                let operand = self.next_expression(expression_type, depth - 1);
+
+                get_call(expression_type, operand)
+            }
+            _ => self.next_binary(expression_type, depth),
+        };
+    }
+
+    fn next_leaf(&mut self, leaf_type: &Type) -> Generated {
+        let argument = self.random.below(ARGUMENTS.len() + 1);
+
+        let (argument_type, value) = match self.arguments.get(argument) {
+            Some(argument) => argument.clone(),
+            None => return get_literal(leaf_type, self.random.value(leaf_type)),
+        };
+
+        let generated = Generated {
+            ez: ARGUMENTS[argument].to_owned(),
+            c: ARGUMENTS[argument].to_owned(),
+            value,
+            precedence: ATOM,
+        };
+
+        if argument_type == *leaf_type {
+            return generated;
+        }
+
+        return get_cast(leaf_type, generated);
+    }
+
+    fn next_binary(&mut self, operation_type: &Type, depth: usize) -> Generated {
+        let operator = self.operators[self.random.below(self.operators.len())].clone();
+
+        let left = self.next_expression(operation_type, depth - 1);
+        let mut right = self.next_expression(operation_type, depth - 1);
+
+        // Division by zero and overflowing divisions have no value to compare, in C they are
+        // undefined behavior
+        let value = match compute(&operator, operation_type, left.value, right.value) {
+            Ok(value) => value,
+            Err(_) => {
+                right = get_literal(operation_type, 1);
+                compute(&operator, operation_type, left.value, 1).expect("Unreachable")
+            }
+        };
+
+        let precedence = operator.get_precedence();
+
+        // Operators of the same precedence group from the left
+        let left_ez = match left.precedence < precedence {
+            true => format!("({})", left.ez),
+            false => left.ez,
+        };
+
+        let right_ez = match right.precedence <= precedence {
+            true => format!("({})", right.ez),
+            false => right.ez,
+        };
+
+        // Operations on 64 bits, truncated like ez does, have no undefined overflow in C
+        let wide = match (&operator, operation_type.is_signed()) {
+            (BinaryOperator::Div, true) => "int64_t",
+            _ => "uint64_t",
+        };
+
+        return Generated {
+            ez: format!("{} {} {}", left_ez, operator.get_symbol(), right_ez),
+            c: format!(
+                "(({})(({}){} {} ({}){}))",
+                get_c_type(operation_type),
+                wide,
+                left.c,
+                operator.get_symbol(),
+                wide,
+                right.c
+            ),
+            value,
+            precedence,
+        };
+    }
+}
+
+fn get_cast(cast_type: &Type, operand: Generated) -> Generated {
+    return Generated {
+        ez: format!("@cast({}, {})", cast_type, operand.ez),
+        c: format!("(({}){})", get_c_type(cast_type), operand.c),
+        value: wrap(operand.value, cast_type),
+        precedence: ATOM,
+    };
+}
+
+// Calls inside operations, their result has to survive the operands computed after them
+fn get_call(call_type: &Type, operand: Generated) -> Generated {
+    let argument = get_cast(&Type::I64, operand);
+
+    let call = Generated {
+        ez: format!("@pass({})", argument.ez),
+        c: format!("pass({})", argument.c),
+        value: argument.value,
+        precedence: ATOM,
+    };
+
+    return get_cast(call_type, call);
+}
+
+// Negative values are written as a subtraction, ez has no negative literals
+fn get_literal(literal_type: &Type, value: u64) -> Generated {
+    let literal = match literal_type.is_signed() && (value as i64) < 0 {
+        true => format!("0 - {}", (value as i64).unsigned_abs()),
+        false => value.to_string(),
+    };
+
+    return Generated {
+        ez: format!("@cast({}, {})", literal_type, literal),
+        c: format!("(({})UINT64_C({}))", get_c_type(literal_type), value),
+        value,
+        precedence: ATOM,
+    };
+}
+
+fn get_c_type(integer_type: &Type) -> String {
+    return match integer_type.is_signed() {
+        true => format!("int{}_t", integer_type.get_size() * 8),
+        false => format!("uint{}_t", integer_type.get_size() * 8),
+    };
+}
+
+// Values are printed as ez prints them, every integer as a signed 64 bit one
+fn format_value(value: u64, value_type: &Type) -> String {
+    return (wrap(value, value_type) as i64).to_string();
+}
+
+fn write_ez(case: &Case) -> String {
+    let arguments: Vec<String> = case
+        .arguments
+        .iter()
+        .zip(ARGUMENTS)
+        .map(|((argument_type, _), name)| format!("{}: {}", name, argument_type))
+        .collect();
+
+    let values: Vec<String> = case
+        .arguments
+        .iter()
+        .map(|(argument_type, value)| get_literal(argument_type, *value).ez)
+        .collect();
+
+    return format!(
+        "fn pass: (value: i64) -> i64 {{
+    return value;
+}}
+
+fn expression: ({}) -> {} {{
+    return {};
+}}
+
+fn main: () {{
+    var value: {} = @expression({});
+    @printf(\"{{}}\\n\", value);
+    return 0;
+}}
+",
+        arguments.join(", "),
+        case.return_type,
+        case.expression.ez,
+        case.return_type,
+        values.join(", ")
+    );
+}
+
+fn write_c(case: &Case) -> String {
+    let arguments: Vec<String> = case
+        .arguments
+        .iter()
+        .zip(ARGUMENTS)
+        .map(|((argument_type, _), name)| format!("{} {}", get_c_type(argument_type), name))
+        .collect();
+
+    let values: Vec<String> = case
+        .arguments
+        .iter()
+        .map(|(argument_type, value)| get_literal(argument_type, *value).c)
+        .collect();
+
+    return format!(
+        "#include <stdint.h>
+#include <stdio.h>
+
+static int64_t pass(int64_t value) {{
+    return value;
+}}
+
+static {} expression({}) {{
+    return {};
+}}
+
+int main(void) {{
+    printf(\"%lld\\n\", (long long)(int64_t)(uint64_t)expression({}));
+    return 0;
+}}
+",
+        get_c_type(&case.return_type),
+        arguments.join(", "),
+        case.expression.c,
+        values.join(", ")
+    );
+}
+
+fn run_executable(path: &str) -> Result<String, String> {
+    let output = match Command::new(path).output() {
+        Ok(output) => output,
+        Err(error) => return Err(format!("can not run {}: {}", path, error)),
+    };
+
+    if !output.status.success() {
+        return Err(format!("{} exited with {}", path, output.status));
+    }
+
+    return Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned());
+}
+
+fn compile_ez(filename: &str, asm_syntax: AsmSyntax) -> Result<(), String> {
+    // Generated expressions nest as deep as asked, which is what the lint warns about
+    let mut lints = LintConfig::default();
+    lints.set_level(Lint::DeepNesting, LintLevel::Allow);
+
+    let options = CompilerOptions {
+        asm_syntax,
+        lints,
+        ..Default::default()
+    };
+
+    let filename = filename.to_owned();
+
+    let result = panic::catch_unwind(move || {
+        let mut compiler = Compiler::from_file(&filename, options);
+        return compiler.compile();
+    });
+
+    return match result {
+        Ok(result) => result,
+        Err(payload) => Err(get_panic_message(&*payload).unwrap_or_default()),
+    };
+}
+
+fn interpret_ez(filename: &str, case: &Case) -> Result<String, String> {
+    let filename = filename.to_owned();
+
+    let program = match panic::catch_unwind(move || {
+        let mut parser = SourceParser::from_file(&filename, Edition::English);
+        parser.generate_tokens();
+
+        return parser.generate_program();
+    }) {
+        Ok(program) => program,
+        Err(payload) => return Err(get_panic_message(&*payload).unwrap_or_default()),
+    };
+
+    let id = program
+        .functions
+        .iter()
+        .position(|function| function.label == "expression")
+        .map(FunctionId)
+        .expect("Unreachable");
+
+    let options = InterpreterOptions {
+        max_steps: 1 << 20,
+        max_depth: 16,
+        max_memory: 1 << 20,
+        trace: false,
+    };
+
+    let arguments = case.arguments.iter().map(|(_, value)| *value).collect();
+
+    return Interpreter::new(&program, options)
+        .call_function(id, arguments)
+        .map(|value| format_value(value, &case.return_type));
+}
+
+fn compile_c(cc: &str, filename: &str, executable: &str) -> Result<(), String> {
+    let output = match Command::new(cc).args(["-o", executable, filename]).output() {
+        Ok(output) => output,
+        Err(error) => return Err(format!("can not run {}: {}", cc, error)),
+    };
+
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_owned());
+    }
+
+    return Ok(());
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let operators: Vec<BinaryOperator> = cli
+        .operators
+        .chars()
+        .filter(|symbol| !symbol.is_whitespace())
+        .map(|symbol| match symbol {
+            '+' => Some(BinaryOperator::Add),
+            '-' => Some(BinaryOperator::Sub),
+            '*' => Some(BinaryOperator::Mul),
+            '/' => Some(BinaryOperator::Div),
+            '&' => Some(BinaryOperator::BitwiseAnd),
+            '|' => Some(BinaryOperator::BitwiseOr),
+            '^' => Some(BinaryOperator::BitwiseXor),
+            _ => None,
+        })
+        .collect::<Option<_>>()
+        .unwrap_or_default();
+
+    if operators.is_empty() {
+        eprintln!("error: --operators takes any of + - * / & | ^.");
+        return ExitCode::from(64);
+    }
+
+    let seed = cli.seed.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(1, |elapsed| elapsed.as_nanos() as u64)
+    });
+
+    // The compiler writes its outputs to the current directory
+    if let Err(error) =
+        create_dir_all(&cli.directory).and_then(|_| env::set_current_dir(&cli.directory))
+    {
+        eprintln!("error: can not use {}: {}", cli.directory, error);
+        return ExitCode::from(2);
+    }
+
+    // Failures are reported with the expression, not as panics
+    panic::set_hook(Box::new(|_| {}));
+
+    let mut generator = Generator {
+        // xorshift never leaves zero
+        random: Random(seed.max(1)),
+        operators,
+        arguments: Vec::new(),
+    };
+
+    let mut failed = 0;
+
+    for program in 0..cli.programs {
+        let case = generator.next_case(cli.depth);
+
+        let stem = format!("program_{}", program);
+        let ez_filename = format!("{}.ez", stem);
+        let c_filename = format!("{}.c", stem);
+        let c_executable = format!("{}_c", stem);
+
+        if let Err(error) =
+            write(&ez_filename, write_ez(&case)).and_then(|_| write(&c_filename, write_c(&case)))
+        {
+            eprintln!("error: can not write {}: {}", stem, error);
+            return ExitCode::from(2);
+        }
+
+        let folded = format_value(case.expression.value, &case.return_type);
+
+        let reference = match compile_c(&cli.cc, &c_filename, &c_executable) {
+            Ok(()) => run_executable(&format!("./{}", c_executable)),
+            Err(error) => Err(error),
+        };
+
+        let compiled = compile_ez(&ez_filename, cli.asm_syntax)
+            .and_then(|_| run_executable(&format!("./{}", stem)));
+
+        let interpreted = interpret_ez(&ez_filename, &case);
+
+        let results = [
+            ("C", reference),
+            ("compiled", compiled),
+            ("interpreted", interpreted),
+        ];
+
+        let agree = results
+            .iter()
+            .all(|(_, result)| result.as_ref() == Ok(&folded));
+
+        if agree {
+            continue;
+        }
+
+        failed += 1;
+
+        let arguments: Vec<String> = case
+            .arguments
+            .iter()
+            .zip(ARGUMENTS)
+            .map(|((argument_type, value), name)| {
+                format!(
+                    "{}: {} = {}",
+                    name,
+                    argument_type,
+                    format_value(*value, argument_type)
+                )
+            })
+            .collect();
+
+        println!("{}/{}.ez: {}", cli.directory, stem, case.expression.ez);
+        println!("  {} -> {}", arguments.join(", "), case.return_type);
+        println!("  folded: {}", folded);
+
+        for (name, result) in results.iter() {
+            match result {
+                Ok(value) => println!("  {}: {}", name, value),
+                Err(error) => println!("  {}: failed, {}", name, error),
+            }
+        }
+    }
+
+    println!(
+        "{} of {} programs agree with C, seed {}",
+        cli.programs - failed,
+        cli.programs,
+        seed
+    );
+
+    return match failed {
+        0 => ExitCode::SUCCESS,
+        _ => ExitCode::FAILURE,
+    };
+}
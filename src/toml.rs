@@ -0,0 +1,303 @@
+use std::{iter::Peekable, str::Chars};
+
+// The part of TOML that ez.toml uses: tables, arrays of tables and keys set to strings,
+// integers, booleans, arrays or inline tables of them, each on a single line. Comments start with # outside
+// of strings.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+    Array(Vec<Value>),
+    // Like `{ path = "../util" }`
+    Table(Vec<(String, Value)>),
+}
+
+impl Value {
+    pub fn get_kind(&self) -> &'static str {
+        return match self {
+            Self::String(_) => "a string",
+            Self::Integer(_) => "an integer",
+            Self::Boolean(_) => "a boolean",
+            Self::Array(_) => "an array",
+            Self::Table(_) => "a table",
+        };
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Table {
+    // Empty for the keys before the first header
+    pub name: String,
+    pub entries: Vec<(String, Value)>,
+    // Line of the header, for diagnostics
+    pub line: usize,
+    // Line of each entry, for diagnostics about its key
+    pub lines: Vec<usize>,
+}
+
+impl Table {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        return self
+            .entries
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value);
+    }
+
+    // Line of the key, or of the header when it is not set
+    pub fn get_line(&self, key: &str) -> usize {
+        return self
+            .entries
+            .iter()
+            .zip(self.lines.iter())
+            .find(|((name, _), _)| name == key)
+            .map_or(self.line, |(_, line)| *line);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Document {
+    // In the order they are written, each `[[name]]` adds another table with the name
+    pub tables: Vec<Table>,
+}
+
+impl Document {
+    // Errors are prefixed with the file and line like compile errors
+    pub fn parse(filename: &str, source: &str) -> Result<Self, String> {
+        let mut tables = vec![Table {
+            name: String::new(),
+            entries: Vec::new(),
+            line: 1,
+            lines: Vec::new(),
+        }];
+        let mut headers: Vec<String> = Vec::new();
+
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let error = |message: &str| format!("{}:{}: {}", filename, line_number, message);
+
+            let mut characters = line.chars().peekable();
+            skip_whitespace(&mut characters);
+
+            match characters.peek() {
+                None | Some('#') => continue,
+                Some('[') => {
+                    characters.next();
+
+                    let array = characters.next_if_eq(&'[').is_some();
+                    let name = read_key(&mut characters).map_err(|message| error(&message))?;
+
+                    let closing = match array {
+                        true => "]]",
+                        false => "]",
+                    };
+
+                    for expected in closing.chars() {
+                        if characters.next() != Some(expected) {
+                            return Err(error(&format!("Expected `{}` after {}.", closing, name)));
+                        }
+                    }
+
+                    expect_end(&mut characters).map_err(|message| error(&message))?;
+
+                    if !array && headers.contains(&name) {
+                        return Err(error(&format!("Table {} is declared twice.", name)));
+                    }
+
+                    headers.push(name.clone());
+                    tables.push(Table {
+                        name,
+                        entries: Vec::new(),
+                        line: line_number,
+                        lines: Vec::new(),
+                    });
+                }
+                Some(_) => {
+                    let key = read_key(&mut characters).map_err(|message| error(&message))?;
+
+                    skip_whitespace(&mut characters);
+
+                    if characters.next() != Some('=') {
+                        return Err(error(&format!("Expected `=` after {}.", key)));
+                    }
+
+                    let value = read_value(&mut characters).map_err(|message| error(&message))?;
+
+                    expect_end(&mut characters).map_err(|message| error(&message))?;
+
+                    let table = tables.last_mut().expect("Unreachable");
+
+                    if table.get(&key).is_some() {
+                        return Err(error(&format!("Key {} is set twice.", key)));
+                    }
+
+                    table.entries.push((key, value));
+                    table.lines.push(line_number);
+                }
+            }
+        }
+
+        return Ok(Self { tables });
+    }
+
+    // The first table with the name, the keys before any header are the table ""
+    pub fn get_table(&self, name: &str) -> Option<&Table> {
+        return self.tables.iter().find(|table| table.name == name);
+    }
+
+    // Every table declared with `[[name]]`
+    pub fn get_tables<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Table> {
+        return self.tables.iter().filter(move |table| table.name == name);
+    }
+}
+
+fn skip_whitespace(characters: &mut Peekable<Chars>) {
+    while characters
+        .next_if(|character| *character == ' ' || *character == '\t')
+        .is_some()
+    {}
+}
+
+// Only whitespace and a comment may follow a header or a value
+fn expect_end(characters: &mut Peekable<Chars>) -> Result<(), String> {
+    skip_whitespace(characters);
+
+    return match characters.peek() {
+        None | Some('#') => Ok(()),
+        Some(character) => Err(format!("Unexpected `{}`.", character)),
+    };
+}
+
+// Bare keys like `name` or `magic-numbers`, or quoted ones
+fn read_key(characters: &mut Peekable<Chars>) -> Result<String, String> {
+    skip_whitespace(characters);
+
+    if characters.peek() == Some(&'"') {
+        return read_string(characters);
+    }
+
+    let mut key = String::new();
+
+    while let Some(character) = characters.next_if(|character| {
+        character.is_ascii_alphanumeric() || *character == '_' || *character == '-'
+    }) {
+        key.push(character);
+    }
+
+    if key.is_empty() {
+        return Err(String::from("Expected a key."));
+    }
+
+    skip_whitespace(characters);
+
+    return Ok(key);
+}
+
+fn read_value(characters: &mut Peekable<Chars>) -> Result<Value, String> {
+    skip_whitespace(characters);
+
+    return match characters.peek() {
+        Some('"') => Ok(Value::String(read_string(characters)?)),
+        Some('[') => {
+            characters.next();
+
+            let mut values: Vec<Value> = Vec::new();
+
+            loop {
+                skip_whitespace(characters);
+
+                if characters.next_if_eq(&']').is_some() {
+                    break;
+                }
+
+                values.push(read_value(characters)?);
+
+                skip_whitespace(characters);
+
+                match characters.next() {
+                    Some(',') => {}
+                    Some(']') => break,
+                    _ => return Err(String::from("Expected `,` or `]` in the array.")),
+                }
+            }
+
+            Ok(Value::Array(values))
+        }
+        Some('{') => {
+            characters.next();
+
+            let mut entries: Vec<(String, Value)> = Vec::new();
+
+            loop {
+                skip_whitespace(characters);
+
+                if characters.next_if_eq(&'}').is_some() {
+                    break;
+                }
+
+                let key = read_key(characters)?;
+
+                if characters.next() != Some('=') {
+                    return Err(format!("Expected `=` after {}.", key));
+                }
+
+                if entries.iter().any(|(name, _)| *name == key) {
+                    return Err(format!("Key {} is set twice.", key));
+                }
+
+                entries.push((key, read_value(characters)?));
+
+                skip_whitespace(characters);
+
+                match characters.next() {
+                    Some(',') => {}
+                    Some('}') => break,
+                    _ => return Err(String::from("Expected `,` or `}` in the table.")),
+                }
+            }
+
+            Ok(Value::Table(entries))
+        }
+        Some(_) => {
+            let mut word = String::new();
+
+            while let Some(character) = characters.next_if(|character| {
+                character.is_ascii_alphanumeric() || *character == '_' || *character == '-'
+            }) {
+                word.push(character);
+            }
+
+            match word.as_str() {
+                "true" => Ok(Value::Boolean(true)),
+                "false" => Ok(Value::Boolean(false)),
+                _ => match word.replace('_', "").parse::<i64>() {
+                    Ok(value) => Ok(Value::Integer(value)),
+                    Err(_) => Err(format!("Invalid value `{}`.", word)),
+                },
+            }
+        }
+        None => Err(String::from("Expected a value.")),
+    };
+}
+
+fn read_string(characters: &mut Peekable<Chars>) -> Result<String, String> {
+    characters.next();
+
+    let mut string = String::new();
+
+    loop {
+        match characters.next() {
+            Some('"') => return Ok(string),
+            Some('\\') => match characters.next() {
+                Some('"') => string.push('"'),
+                Some('\\') => string.push('\\'),
+                Some('n') => string.push('\n'),
+                Some('t') => string.push('\t'),
+                _ => return Err(String::from("Invalid escape in string.")),
+            },
+            Some(character) => string.push(character),
+            None => return Err(String::from("Unterminated string.")),
+        }
+    }
+}
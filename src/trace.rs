@@ -0,0 +1,211 @@
+use crate::{
+    lexer::BinaryOperator,
+    parser::{Expression, Function, Intrinsic, Program, Statement, Type},
+};
+
+impl BinaryOperator {
+    pub fn get_symbol(&self) -> &str {
+        return match self {
+            BinaryOperator::Add => "+",
+            BinaryOperator::Sub => "-",
+            BinaryOperator::Mul => "*",
+            BinaryOperator::Div => "/",
+            BinaryOperator::BitwiseAnd => "&",
+            BinaryOperator::BitwiseOr => "|",
+            BinaryOperator::BitwiseXor => "^",
+        };
+    }
+}
+
+impl Intrinsic {
+    pub fn get_name(&self) -> &str {
+        return match self {
+            Intrinsic::Write => "write",
+            Intrinsic::ToStr => "to_str",
+            Intrinsic::ParseInt => "parse_int",
+            Intrinsic::ToF64 => "to_f64",
+            Intrinsic::ToInt => "to_int",
+            Intrinsic::DivMod => "divmod",
+            Intrinsic::PortIn => "port_in",
+            Intrinsic::PortOut => "port_out",
+            Intrinsic::StackAlloc => "stack_alloc",
+            Intrinsic::Memset => "memset",
+            Intrinsic::Memcpy => "memcpy",
+            Intrinsic::Memcmp => "memcmp",
+            Intrinsic::Trap => "trap",
+            Intrinsic::Unreachable => "unreachable",
+            Intrinsic::Random => "rand",
+            Intrinsic::Seed => "seed",
+            Intrinsic::Time => "time",
+            Intrinsic::ClockNs => "clock_ns",
+            Intrinsic::SleepMs => "sleep_ms",
+        };
+    }
+}
+
+// Name of a called function as written from the calling module
+fn describe_function(function: &Function, caller: &Function) -> String {
+    return match function.module == caller.module {
+        true => function.name.clone(),
+        false => format!("{}::{}", function.module, function.name),
+    };
+}
+
+// Source like text of a statement, as it was parsed
+pub fn describe_statement(statement: &Statement, function: &Function, program: &Program) -> String {
+    let label = |index: &usize| &function.locals.get(*index).expect("Unreachable").label;
+
+    return match statement {
+        Statement::Declare(index) => format!(
+            "var {}: {};",
+            label(index),
+            function.locals.get(*index).expect("Unreachable").local_type
+        ),
+        Statement::Assign(index, expression) => format!(
+            "{} = {};",
+            label(index),
+            describe_expression(expression, function, program)
+        ),
+        Statement::Store(target, expression) => format!(
+            "{} = {};",
+            describe_expression(target, function, program),
+            describe_expression(expression, function, program)
+        ),
+        Statement::Destructure(indices, expression) => {
+            let labels: Vec<String> = indices
+                .iter()
+                .map(|index| label(index).to_string())
+                .collect();
+
+            format!(
+                "var ({}) = {};",
+                labels.join(", "),
+                describe_expression(expression, function, program)
+            )
+        }
+        Statement::Return(expression) => format!(
+            "return {};",
+            describe_expression(expression, function, program)
+        ),
+        Statement::Call(expression) => {
+            format!("{};", describe_expression(expression, function, program))
+        }
+        Statement::Label(label) => format!("{}:", label),
+        Statement::Goto(label) => format!("goto {};", label),
+    };
+}
+
+pub fn describe_expression(
+    expression: &Expression,
+    function: &Function,
+    program: &Program,
+) -> String {
+    let describe_all = |expressions: &Vec<Expression>| {
+        return expressions
+            .iter()
+            .map(|expression| describe_expression(expression, function, program))
+            .collect::<Vec<String>>()
+            .join(", ");
+    };
+
+    // Nested binaries keep the grouping the parser gave them
+    let describe_operand = |expression: &Expression| match expression {
+        Expression::Binary(_) => {
+            format!("({})", describe_expression(expression, function, program))
+        }
+        expression => describe_expression(expression, function, program),
+    };
+
+    return match expression {
+        Expression::NumberLiteral(number) => number.to_string(),
+        Expression::FloatLiteral(number) => format!("{:?}", number),
+        Expression::StringLiteral(index) => {
+            format!("\"{}\"", program.strings[*index].escape_debug())
+        }
+        Expression::Binary(binary_expression) => format!(
+            "{} {} {}",
+            describe_operand(&binary_expression.left),
+            binary_expression.operator.get_symbol(),
+            describe_operand(&binary_expression.right)
+        ),
+        Expression::Local(index) => function
+            .locals
+            .get(*index)
+            .expect("Unreachable")
+            .label
+            .to_string(),
+        Expression::Call(index, expressions) => format!(
+            "@{}({})",
+            describe_function(&program.functions[*index], function),
+            describe_all(expressions)
+        ),
+        Expression::Intrinsic(intrinsic, expressions) => {
+            format!("@{}({})", intrinsic.get_name(), describe_all(expressions))
+        }
+        Expression::Cast(cast_type, expression) => {
+            format!("{} as {}", describe_operand(expression), cast_type)
+        }
+        Expression::Index(base, index, _) => format!(
+            "{}[{}]",
+            describe_operand(base),
+            describe_expression(index, function, program)
+        ),
+        Expression::Field(base, field) => match base.get_type(&function.locals, &program.functions)
+        {
+            Type::Struct(layout) => {
+                format!("{}.{}", describe_operand(base), layout.fields[*field].name)
+            }
+            _ => panic!("Unreachable"),
+        },
+        Expression::Address(expression) => {
+            format!(
+                "@addr({})",
+                describe_expression(expression, function, program)
+            )
+        }
+        Expression::Initializer(Type::Struct(layout), expressions) => {
+            let fields: Vec<String> = layout
+                .fields
+                .iter()
+                .zip(expressions.iter())
+                .map(|(field, expression)| {
+                    format!(
+                        "{}: {}",
+                        field.name,
+                        describe_expression(expression, function, program)
+                    )
+                })
+                .collect();
+
+            format!("{} {{ {} }}", layout.name, fields.join(", "))
+        }
+        Expression::Initializer(_, expressions) => format!("[{}]", describe_all(expressions)),
+        Expression::Tuple(expressions) => format!("({})", describe_all(expressions)),
+        Expression::Format(chunks, expressions) => {
+            let mut format = String::new();
+
+            for (index, chunk) in chunks.iter().enumerate() {
+                if let Some(chunk) = chunk {
+                    format.push_str(
+                        &program.strings[*chunk]
+                            .replace('{', "{{")
+                            .replace('}', "}}"),
+                    );
+                }
+
+                if index < expressions.len() {
+                    format.push_str("{}");
+                }
+            }
+
+            match expressions.len() {
+                0 => format!("@printf(\"{}\")", format.escape_debug()),
+                _ => format!(
+                    "@printf(\"{}\", {})",
+                    format.escape_debug(),
+                    describe_all(expressions)
+                ),
+            }
+        }
+    };
+}
@@ -0,0 +1,360 @@
+use crate::{
+    dataflow::compute_liveness,
+    interner,
+    parser::{Expression, Function, FunctionId, Intrinsic, Program, Statement, Type},
+    span::Position,
+    target::Target,
+};
+
+// Checks the whole program once every file has been parsed, before any assembly is written
+// Libraries have no entry point
+pub fn validate_program(program: &Program, filename: &str, library: bool) {
+    if !library {
+        validate_entry(&program.functions, filename);
+    }
+
+    validate_labels(&program.functions);
+    validate_declarations(program);
+    validate_literal_writes(&program.functions);
+    validate_stack_allocations(&program.functions);
+    validate_aborts(&program.functions);
+    validate_gotos(&program.functions);
+}
+
+fn validate_entry(functions: &Vec<Function>, filename: &str) {
+    let entries: Vec<&Function> = functions
+        .iter()
+        .filter(|function| function.label == "main")
+        .collect();
+
+    let entry = match entries.as_slice() {
+        [entry] => entry,
+        [] => panic!(
+            "{}:1:1: error[E0014]: No entry point, declare a `fn main: ()` function.",
+            filename
+        ),
+        [_, duplicate, ..] => panic!("{}: Duplicated entry point.", duplicate.position),
+    };
+
+    if entry.body.is_none() {
+        panic!("{}: The entry point must have a body.", entry.position);
+    }
+
+    if entry.arguments.len() > 0 {
+        panic!(
+            "{}: The entry point can not take arguments.",
+            entry.position
+        );
+    }
+}
+
+// Declarations may share the label of the assembly routine they refer to
+fn validate_labels(functions: &Vec<Function>) {
+    for (index, function) in functions.iter().enumerate() {
+        let previous = functions[..index].iter().find(|previous| {
+            previous.label == function.label && (previous.body.is_some() || function.body.is_some())
+        });
+
+        if let Some(previous) = previous {
+            panic!(
+                "{}: Duplicated label {}, first declared at {}.",
+                function.position, function.label, previous.position
+            );
+        }
+    }
+}
+
+// Called declarations must be implemented by some included assembly, unless extern
+fn validate_declarations(program: &Program) {
+    let called: Vec<FunctionId> = program
+        .functions
+        .iter()
+        .flat_map(|function| function.get_calls())
+        .collect();
+
+    for (index, function) in program.functions.iter().enumerate() {
+        if function.body.is_some() || function.external || !called.contains(&FunctionId(index)) {
+            continue;
+        }
+
+        let label = format!("{}:", function.label);
+
+        let implemented = program.assembly.iter().any(|assembly| {
+            assembly
+                .source
+                .lines()
+                .any(|line| line.trim_start().starts_with(&label))
+        });
+
+        if !implemented {
+            panic!(
+                "{}: error[E0015]: Unresolved function {}, no included assembly defines the {} label.",
+                function.position,
+                function.name,
+                function.label
+            );
+        }
+    }
+}
+
+// Objects linked into executables of other packages are started by them, nothing would run
+// their hooks
+pub fn validate_library(program: &Program) {
+    let hooks = [program.get_constructors(), program.get_destructors()].concat();
+
+    if let Some(id) = hooks.first() {
+        panic!(
+            "{}: Libraries can not have constructors or destructors, call the function from main.",
+            program.functions[*id].position
+        );
+    }
+}
+
+// Writing to files and reading clocks need system calls, and only code without an operating
+// system below it may access I/O ports
+pub fn validate_target(program: &Program, target: Target) {
+    for function in program.functions.iter() {
+        let body = match &function.body {
+            Some(body) => body,
+            None => continue,
+        };
+
+        for (statement, span) in body.statements.iter().zip(body.spans.iter()) {
+            let mut expressions = statement.get_expressions();
+
+            while let Some(expression) = expressions.pop() {
+                let name = match expression {
+                    Expression::Intrinsic(intrinsic, _) => intrinsic.get_name(),
+                    Expression::Format(..) => "printf",
+                    _ => "",
+                };
+
+                let position = &span.position;
+
+                match expression {
+                    Expression::Intrinsic(
+                        Intrinsic::Write
+                        | Intrinsic::Time
+                        | Intrinsic::ClockNs
+                        | Intrinsic::SleepMs,
+                        _,
+                    )
+                    | Expression::Format(..)
+                        if !target.has_os() =>
+                    {
+                        panic!(
+                            "{}: {} needs an operating system, it can not be used with --target {}.",
+                            position,
+                            name,
+                            target.get_name()
+                        );
+                    }
+                    Expression::Intrinsic(Intrinsic::PortIn | Intrinsic::PortOut, _)
+                        if target.has_os() =>
+                    {
+                        panic!(
+                            "{}: {} accesses I/O ports, which only programs for --target {} can do.",
+                            position,
+                            name,
+                            Target::Baremetal.get_name()
+                        );
+                    }
+                    _ => {}
+                }
+
+                expressions.extend(expression.get_children());
+            }
+        }
+    }
+}
+
+// Allocations move rsp, which is only safe while nothing of the statement is pushed yet
+fn validate_stack_allocations(functions: &Vec<Function>) {
+    for function in functions.iter() {
+        let body = match &function.body {
+            Some(body) => body,
+            None => continue,
+        };
+
+        for (statement, span) in body.statements.iter().zip(body.spans.iter()) {
+            let mut expressions = match statement {
+                Statement::Assign(_, Expression::Intrinsic(Intrinsic::StackAlloc, arguments)) => {
+                    arguments.iter().collect()
+                }
+                statement => statement.get_expressions(),
+            };
+
+            while let Some(expression) = expressions.pop() {
+                if let Expression::Intrinsic(Intrinsic::StackAlloc, _) = expression {
+                    panic!(
+                        "{}: stack_alloc can only be assigned to a variable, like `var buffer = @stack_alloc(size);`.",
+                        span.position
+                    );
+                }
+
+                expressions.extend(expression.get_children());
+            }
+        }
+    }
+}
+
+// Aborts take the position of their statement for the message, and a statement of their own
+// is what ends the block for the control flow graph
+fn validate_aborts(functions: &Vec<Function>) {
+    for function in functions.iter() {
+        let body = match &function.body {
+            Some(body) => body,
+            None => continue,
+        };
+
+        for (statement, span) in body.statements.iter().zip(body.spans.iter()) {
+            let mut expressions = match statement {
+                Statement::Call(Expression::Intrinsic(_, arguments))
+                    if statement.is_diverging() =>
+                {
+                    arguments.iter().collect()
+                }
+                statement => statement.get_expressions(),
+            };
+
+            while let Some(expression) = expressions.pop() {
+                if let Expression::Intrinsic(intrinsic, _) = expression {
+                    if intrinsic.get_abort_message().is_some() {
+                        panic!(
+                            "{}: {} can only be used as a statement, like `@{}();`.",
+                            span.position,
+                            intrinsic.get_name(),
+                            intrinsic.get_name()
+                        );
+                    }
+                }
+
+                expressions.extend(expression.get_children());
+            }
+        }
+    }
+}
+
+// Labels are local to their function, so each name is declared once per function
+fn validate_gotos(functions: &Vec<Function>) {
+    for function in functions.iter() {
+        let body = match &function.body {
+            Some(body) => body,
+            None => continue,
+        };
+
+        let labels: Vec<(interner::Symbol, &Position)> = body
+            .statements
+            .iter()
+            .zip(body.spans.iter())
+            .filter_map(|(statement, span)| match statement {
+                Statement::Label(label) => Some((*label, &span.position)),
+                _ => None,
+            })
+            .collect();
+
+        for (index, (label, position)) in labels.iter().enumerate() {
+            if let Some((_, previous)) = labels[..index].iter().find(|(other, _)| other == label) {
+                panic!(
+                    "{}: Duplicated label {}, first declared at {}.",
+                    position, label, previous
+                );
+            }
+        }
+
+        for (statement, span) in body.statements.iter().zip(body.spans.iter()) {
+            if let Statement::Goto(label) = statement {
+                if !labels.iter().any(|(other, _)| other == label) {
+                    panic!(
+                        "{}: Undeclared label {}, goto only jumps to labels of function {}.",
+                        span.position, label, function.name
+                    );
+                }
+            }
+        }
+    }
+}
+
+// String literals used as pointers are read-only data, writes through them would fault.
+// Following the statements in order knows which locals hold such a pointer, up to a label
+// goto may jump to from anywhere. Locals with their address taken can change unseen and are
+// skipped.
+fn validate_literal_writes(functions: &Vec<Function>) {
+    for function in functions.iter() {
+        let body = match &function.body {
+            Some(body) => body,
+            None => continue,
+        };
+
+        let escaped = compute_liveness(function, body).escaped;
+        let mut literals = vec![false; function.locals.locals.len()];
+
+        for (statement, span) in body.statements.iter().zip(body.spans.iter()) {
+            let mut written: Vec<&Expression> = Vec::new();
+
+            if let Statement::Store(Expression::Index(base, ..), _) = statement {
+                written.push(base);
+            }
+
+            let mut expressions = statement.get_expressions();
+
+            while let Some(expression) = expressions.pop() {
+                match expression {
+                    Expression::Intrinsic(Intrinsic::ToStr, arguments) => {
+                        written.push(&arguments[1])
+                    }
+                    Expression::Intrinsic(Intrinsic::Memset | Intrinsic::Memcpy, arguments) => {
+                        written.push(&arguments[0])
+                    }
+                    _ => {}
+                }
+
+                expressions.extend(expression.get_children());
+            }
+
+            if written
+                .iter()
+                .any(|pointer| is_literal_pointer(pointer, &literals))
+            {
+                panic!(
+                    "{}: error[E0018]: Can not write through a pointer to a string literal, string literals are read-only.",
+                    span.position
+                );
+            }
+
+            match statement {
+                Statement::Declare(local) => literals[*local] = false,
+                Statement::Assign(local, expression) => {
+                    literals[*local] = !escaped[*local] && is_literal_pointer(expression, &literals)
+                }
+                Statement::Destructure(locals, _) => {
+                    for local in locals.iter() {
+                        literals[*local] = false;
+                    }
+                }
+                // Jumps may arrive from anywhere, nothing is known after a label
+                Statement::Label(_) => literals.fill(false),
+                Statement::Store(..)
+                | Statement::Return(_)
+                | Statement::Call(_)
+                | Statement::Goto(_) => {}
+            }
+        }
+    }
+}
+
+// Pointer into a string literal, offsets included
+fn is_literal_pointer(expression: &Expression, literals: &Vec<bool>) -> bool {
+    return match expression {
+        Expression::Cast(Type::Pointer(_), operand) => {
+            matches!(**operand, Expression::StringLiteral(_))
+                || is_literal_pointer(operand, literals)
+        }
+        Expression::Local(local) => literals[*local],
+        Expression::Binary(binary_expression) => {
+            is_literal_pointer(&binary_expression.left, literals)
+                || is_literal_pointer(&binary_expression.right, literals)
+        }
+        _ => false,
+    };
+}
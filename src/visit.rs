@@ -0,0 +1,140 @@
+use crate::parser::{BinaryExpression, Expression, Function, Statement};
+
+// Read only traversal of functions, statements and expressions. Every method walks into the
+// children by default, so an implementation only overrides the nodes it cares about and calls
+// the walk function of the node to keep going below it. Expressions are walked recursively,
+// the parser limits their depth.
+pub trait Visitor {
+    fn visit_function(&mut self, function: &Function) {
+        walk_function(self, function);
+    }
+
+    fn visit_statement(&mut self, statement: &Statement) {
+        walk_statement(self, statement);
+    }
+
+    fn visit_expression(&mut self, expression: &Expression) {
+        walk_expression(self, expression);
+    }
+}
+
+pub fn walk_function<V: Visitor + ?Sized>(visitor: &mut V, function: &Function) {
+    if let Some(body) = &function.body {
+        for statement in body.statements.iter() {
+            visitor.visit_statement(statement);
+        }
+    }
+}
+
+pub fn walk_statement<V: Visitor + ?Sized>(visitor: &mut V, statement: &Statement) {
+    for expression in statement.get_expressions() {
+        visitor.visit_expression(expression);
+    }
+}
+
+pub fn walk_expression<V: Visitor + ?Sized>(visitor: &mut V, expression: &Expression) {
+    for child in expression.get_children() {
+        visitor.visit_expression(child);
+    }
+}
+
+// Traversal that takes the nodes and returns their replacements. The defaults rebuild the
+// node from its folded children, so overriding fold_expression and calling rebuild_expression
+// first rewrites the tree bottom up.
+pub trait Folder {
+    fn fold_function(&mut self, function: Function) -> Function {
+        return rebuild_function(self, function);
+    }
+
+    fn fold_statement(&mut self, statement: Statement) -> Statement {
+        return rebuild_statement(self, statement);
+    }
+
+    fn fold_expression(&mut self, expression: Expression) -> Expression {
+        return rebuild_expression(self, expression);
+    }
+}
+
+pub fn rebuild_function<F: Folder + ?Sized>(folder: &mut F, mut function: Function) -> Function {
+    if let Some(body) = &mut function.body {
+        body.statements = std::mem::take(&mut body.statements)
+            .into_iter()
+            .map(|statement| folder.fold_statement(statement))
+            .collect();
+    }
+
+    return function;
+}
+
+pub fn rebuild_statement<F: Folder + ?Sized>(folder: &mut F, statement: Statement) -> Statement {
+    return match statement {
+        Statement::Declare(local) => Statement::Declare(local),
+        Statement::Assign(local, expression) => {
+            Statement::Assign(local, folder.fold_expression(expression))
+        }
+        Statement::Store(target, expression) => Statement::Store(
+            folder.fold_expression(target),
+            folder.fold_expression(expression),
+        ),
+        Statement::Destructure(locals, expression) => {
+            Statement::Destructure(locals, folder.fold_expression(expression))
+        }
+        Statement::Return(expression) => Statement::Return(folder.fold_expression(expression)),
+        Statement::Call(expression) => Statement::Call(folder.fold_expression(expression)),
+        Statement::Label(label) => Statement::Label(label),
+        Statement::Goto(label) => Statement::Goto(label),
+    };
+}
+
+pub fn rebuild_expression<F: Folder + ?Sized>(
+    folder: &mut F,
+    expression: Expression,
+) -> Expression {
+    return match expression {
+        Expression::Binary(binary_expression) => Expression::Binary(BinaryExpression {
+            operator: binary_expression.operator,
+            left: fold_boxed(folder, binary_expression.left),
+            right: fold_boxed(folder, binary_expression.right),
+        }),
+        Expression::Call(function, arguments) => {
+            Expression::Call(function, fold_all(folder, arguments))
+        }
+        Expression::Intrinsic(intrinsic, arguments) => {
+            Expression::Intrinsic(intrinsic, fold_all(folder, arguments))
+        }
+        Expression::Cast(cast_type, expression) => {
+            Expression::Cast(cast_type, fold_boxed(folder, expression))
+        }
+        Expression::Index(base, index, position) => Expression::Index(
+            fold_boxed(folder, base),
+            fold_boxed(folder, index),
+            position,
+        ),
+        Expression::Field(expression, field) => {
+            Expression::Field(fold_boxed(folder, expression), field)
+        }
+        Expression::Address(expression) => Expression::Address(fold_boxed(folder, expression)),
+        Expression::Initializer(initializer_type, values) => {
+            Expression::Initializer(initializer_type, fold_all(folder, values))
+        }
+        Expression::Tuple(values) => Expression::Tuple(fold_all(folder, values)),
+        Expression::Format(pieces, arguments) => {
+            Expression::Format(pieces, fold_all(folder, arguments))
+        }
+        leaf @ (Expression::NumberLiteral(_)
+        | Expression::FloatLiteral(_)
+        | Expression::StringLiteral(_)
+        | Expression::Local(_)) => leaf,
+    };
+}
+
+fn fold_boxed<F: Folder + ?Sized>(folder: &mut F, expression: Box<Expression>) -> Box<Expression> {
+    return Box::new(folder.fold_expression(*expression));
+}
+
+fn fold_all<F: Folder + ?Sized>(folder: &mut F, expressions: Vec<Expression>) -> Vec<Expression> {
+    return expressions
+        .into_iter()
+        .map(|expression| folder.fold_expression(expression))
+        .collect();
+}
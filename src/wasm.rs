@@ -0,0 +1,51 @@
+use std::{
+    panic,
+    sync::{Mutex, Once},
+};
+
+use wasm_bindgen::prelude::*;
+
+use crate::{compile_to_asm, diagnostics::get_panic_message};
+
+// wasm32-unknown-unknown aborts on panics instead of unwinding, so compile errors trap
+// before compile_to_asm can return them. The hook keeps the message for lastDiagnostic, which
+// is the only way a browser learns why compileToAsm failed: the error it returns is never
+// reached there, and the trap leaves the instance unusable, so create a new one after it.
+static LAST_DIAGNOSTIC: Mutex<Option<String>> = Mutex::new(None);
+
+// The hook is process wide, installed by the first call instead of replaced on every one
+static HOOK: Once = Once::new();
+
+// Assembly of the program, throwing its diagnostics joined by newlines on native builds of
+// the bindings. In the browser a compile error traps instead, and lastDiagnostic is its only
+// error channel.
+#[wasm_bindgen(js_name = compileToAsm)]
+pub fn compile_to_asm_js(source: &str) -> Result<String, JsValue> {
+    HOOK.call_once(|| {
+        panic::set_hook(Box::new(|info| {
+            if let Ok(mut last) = LAST_DIAGNOSTIC.lock() {
+                *last = get_panic_message(info.payload());
+            }
+        }));
+    });
+
+    // A diagnostic nobody read belongs to an earlier call
+    if let Ok(mut last) = LAST_DIAGNOSTIC.lock() {
+        *last = None;
+    }
+
+    return compile_to_asm(source).map_err(|diagnostics| {
+        let messages: Vec<String> = diagnostics
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect();
+
+        return JsValue::from_str(&messages.join("\n"));
+    });
+}
+
+// Message of the compile error that stopped the last call to compileToAsm
+#[wasm_bindgen(js_name = lastDiagnostic)]
+pub fn last_diagnostic() -> Option<String> {
+    return LAST_DIAGNOSTIC.lock().ok()?.take();
+}
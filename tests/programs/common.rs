@@ -0,0 +1,130 @@
+use std::{
+    fs::{create_dir_all, read_to_string, write},
+    io::Write,
+    path::PathBuf,
+    process::{Command, Output, Stdio},
+};
+
+const EZLANG: &str = env!("CARGO_BIN_EXE_ezlang");
+
+// Every program gets a directory of its own, so tests running in parallel never share files
+pub fn write_source(name: &str, source: &str) -> PathBuf {
+    let directory = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    create_dir_all(&directory).expect("Can not create the program directory");
+
+    let path = directory.join(format!("{}.ez", name));
+    write(&path, source).expect("Can not write the program");
+
+    return path;
+}
+
+pub fn build(name: &str, source: &str, arguments: &[&str]) -> Output {
+    let path = write_source(name, source);
+
+    return Command::new(EZLANG)
+        .arg("build")
+        .arg(&path)
+        .args(["--asm-syntax", "gas"])
+        .args(arguments)
+        .current_dir(path.parent().expect("Unreachable"))
+        .output()
+        .expect("Can not run ezlang");
+}
+
+pub fn run_compiled(name: &str, source: &str) -> i32 {
+    let output = build(name, source, &[]);
+
+    assert!(
+        output.status.success(),
+        "{} failed to build:\n{}",
+        name,
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let executable = PathBuf::from(env!("CARGO_TARGET_TMPDIR"))
+        .join(name)
+        .join(name);
+
+    let status = Command::new(executable)
+        .status()
+        .expect("Can not run the program");
+
+    return status.code().expect("The program was killed by a signal");
+}
+
+pub fn run_interpreted(name: &str, source: &str) -> i32 {
+    let path = write_source(name, source);
+
+    let output = Command::new(EZLANG)
+        .arg("run")
+        .arg(&path)
+        .output()
+        .expect("Can not run ezlang");
+
+    return output.status.code().expect("ezlang was killed by a signal");
+}
+
+// Compiled and interpreted, the exit code is the value of main modulo 256
+pub fn check_exit(name: &str, source: &str, expected: i32) {
+    assert_eq!(run_compiled(name, source), expected, "compiled {}", name);
+    assert_eq!(
+        run_interpreted(&format!("{}_run", name), source),
+        expected,
+        "interpreted {}",
+        name
+    );
+}
+
+// Source after `ezlang fix` applied the suggested fixes in place
+pub fn fix(name: &str, source: &str) -> String {
+    let path = write_source(name, source);
+
+    let output = Command::new(EZLANG)
+        .arg("fix")
+        .arg(&path)
+        .output()
+        .expect("Can not run ezlang");
+
+    assert!(
+        output.status.success(),
+        "{} was not fixed:\n{}",
+        name,
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    return read_to_string(&path).expect("Can not read the fixed program");
+}
+
+// Output of `ezlang debug` given the commands on its standard input
+pub fn debug(name: &str, source: &str, commands: &str) -> Output {
+    let path = write_source(name, source);
+
+    let mut child = Command::new(EZLANG)
+        .arg("debug")
+        .arg(&path)
+        .args(["--asm-syntax", "gas"])
+        .current_dir(path.parent().expect("Unreachable"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Can not run ezlang");
+
+    child
+        .stdin
+        .take()
+        .expect("Unreachable")
+        .write_all(commands.as_bytes())
+        .expect("Can not write the commands");
+
+    return child.wait_with_output().expect("Can not run ezlang");
+}
+
+// Diagnostics printed by a build that must fail
+pub fn build_error(name: &str, source: &str) -> String {
+    let output = build(name, source, &[]);
+
+    assert!(!output.status.success(), "{} built without errors", name);
+
+    return String::from_utf8_lossy(&output.stderr).into_owned();
+}
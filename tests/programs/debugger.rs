@@ -0,0 +1,27 @@
+use crate::common::debug;
+
+// The ..@ labels the debugger looks up have to survive GNU as too
+#[test]
+fn debug_with_gnu_as() {
+    let source = "
+fn main: () {
+    var a = 1;
+    return a + 2;
+}
+";
+
+    let output = debug(
+        "debug_with_gnu_as",
+        source,
+        "break main\ncontinue\nstep\ncontinue\n",
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(
+        stdout.contains("debug_with_gnu_as.ez:4:5: in main"),
+        "{}\n{}",
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(output.status.code(), Some(3));
+}
@@ -0,0 +1,224 @@
+use std::fs::write;
+
+use crate::common::{build, build_error, check_exit, fix, run_interpreted, write_source};
+
+#[test]
+fn missing_comma_between_arguments() {
+    let source = "
+fn add: (a: i64, b: i64) {
+    return a + b;
+}
+
+fn main: () {
+    var x = 1;
+    return @add(x 2);
+}
+";
+
+    let error = build_error("missing_comma_between_arguments", source);
+
+    assert!(
+        error.contains("missing_comma_between_arguments.ez:8:19: Expected `,` between arguments."),
+        "{}",
+        error
+    );
+    assert!(!error.contains("internal compiler error"), "{}", error);
+}
+
+#[test]
+fn missing_comma_after_a_call() {
+    let source = "
+fn add: (a: i64, b: i64) {
+    return a + b;
+}
+
+fn main: () {
+    return @add(@add(1, 2) (3));
+}
+";
+
+    let error = build_error("missing_comma_after_a_call", source);
+
+    assert!(
+        error.contains("missing_comma_after_a_call.ez:7:28: Expected `,` between arguments."),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn missing_commas_are_fixed() {
+    let source = "
+fn add: (a: i64, b: i64) {
+    return a + b;
+}
+
+fn main: () {
+    var x = 1;
+    return @add(@add(x
+        2) (3));
+}
+";
+
+    let fixed = fix("missing_commas_are_fixed", source);
+
+    assert!(
+        fixed.contains("    return @add(@add(x,\n        2), (3));"),
+        "{}",
+        fixed
+    );
+
+    check_exit("missing_commas_are_fixed_run", &fixed, 6);
+}
+
+#[test]
+fn operands_without_operator() {
+    let source = "
+fn main: () {
+    var x = 1;
+    return x 2;
+}
+";
+
+    let error = build_error("operands_without_operator", source);
+
+    assert!(
+        error.contains("operands_without_operator.ez:4:14: Invalid expression."),
+        "{}",
+        error
+    );
+}
+
+// Files without tokens fail with E0014 when built and when interpreted
+fn check_empty(name: &str, source: &str) {
+    let error = build_error(name, source);
+
+    assert!(
+        error.contains(&format!(
+            "{}.ez:1:1: error[E0014]: Empty source file, expected at least a `fn main: ()` function.",
+            name
+        )),
+        "{}",
+        error
+    );
+
+    assert_eq!(run_interpreted(&format!("{}_run", name), source), 1);
+}
+
+#[test]
+fn empty_file() {
+    check_empty("empty_file", "");
+}
+
+#[test]
+fn whitespace_only_file() {
+    check_empty("whitespace_only_file", "  \n\t\r\n\n");
+}
+
+// The language has no comments, these are the only lines a file can have besides code
+#[test]
+fn file_without_code() {
+    check_empty(
+        "file_without_code",
+        "\u{feff}#!/usr/bin/env ezlang\n#edition english\n\n",
+    );
+}
+
+// The byte order mark comes before the first column, so errors on the first line point right
+#[test]
+fn byte_order_mark_columns() {
+    let source = "\u{feff}fn main: () { return x; }\n";
+
+    let error = build_error("byte_order_mark_columns", source);
+
+    assert!(
+        error.contains("byte_order_mark_columns.ez:1:22: error[E0001]: Undeclared variable."),
+        "{}",
+        error
+    );
+
+    let source =
+        "\u{feff}fn add: (a: i64, b: i64) { return a + b; } fn main: () { return @add(1 2); }\n";
+
+    assert_eq!(
+        fix("byte_order_mark_fixes", source),
+        "\u{feff}fn add: (a: i64, b: i64) { return a + b; } fn main: () { return @add(1, 2); }\n"
+    );
+}
+
+fn check_manifest_error(name: &str, manifest: &str, expected: &str) {
+    let source = "
+fn main: () {
+    return 0;
+}
+";
+
+    let path = write_source(name, source);
+    let directory = path.parent().expect("Unreachable");
+    write(directory.join("ez.toml"), manifest).expect("Can not write the manifest");
+
+    let output = build(name, source, &[]);
+    let error = String::from_utf8_lossy(&output.stderr);
+
+    assert!(!output.status.success());
+    assert!(
+        error.contains(&format!(
+            "{}:{}",
+            directory.join("ez.toml").display(),
+            expected
+        )),
+        "{}",
+        error
+    );
+}
+
+// Errors about a key point at its own line, not at the header of its table
+#[test]
+fn manifest_errors_point_at_the_key() {
+    check_manifest_error(
+        "manifest_package_key",
+        "[package]\nname = \"demo\"\nopt-level = 2\n",
+        "3: opt-level must be 0 or 1.",
+    );
+    check_manifest_error(
+        "manifest_lint_key",
+        "[package]\nname = \"demo\"\n\n[lints]\ndeep-nesting = \"warn\"\nmagic-numbers = \"loud\"\n",
+        "6: Lint magic-numbers must be set to \"allow\", \"warn\" or \"deny\".",
+    );
+    check_manifest_error(
+        "manifest_binary_name",
+        "[package]\nname = \"demo\"\n\n[[bin]]\nname = \"a\"\n\n[[bin]]\nentry = \"b.ez\"\nname = \"a\"\n",
+        "9: Binary a is declared twice.",
+    );
+}
+
+fn check_literal_out_of_range(name: &str, literal: &str) {
+    let source = format!(
+        "
+fn main: () {{
+    var value = {};
+    return 0;
+}}
+",
+        literal
+    );
+
+    let error = build_error(name, &source);
+
+    assert!(
+        error.contains(&format!(
+            "{}.ez:3:17: error[E0012]: Literal out of range, it is larger than 18446744073709551615.",
+            name
+        )),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn literals_larger_than_64_bits() {
+    check_literal_out_of_range("decimal_out_of_range", "18446744073709551616");
+    check_literal_out_of_range("hexadecimal_out_of_range", "16#10000000000000000");
+    check_literal_out_of_range("binary_out_of_range", &format!("2#1{}", "0".repeat(64)));
+    check_literal_out_of_range("octal_out_of_range", "8#2000000000000000000000");
+}
@@ -0,0 +1,67 @@
+use std::{
+    fs::{create_dir_all, read_dir},
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+const EZLANG: &str = env!("CARGO_BIN_EXE_ezlang");
+
+// Whether the program can be started at all, the version it prints does not matter
+fn is_installed(program: &str) -> bool {
+    return Command::new(program)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .output()
+        .is_ok();
+}
+
+// Every example has to assemble with both assemblers, included assembly too
+fn check_examples(syntax: &str, assembler: &str) {
+    if !is_installed(assembler) {
+        eprintln!(
+            "{} is not installed, skipping the {} examples",
+            assembler, syntax
+        );
+        return;
+    }
+
+    let directory = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("examples_{}", syntax));
+    create_dir_all(&directory).expect("Can not create the examples directory");
+
+    let examples = read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/examples"))
+        .expect("Can not read the examples");
+
+    for example in examples {
+        let path = example.expect("Can not read the examples").path();
+
+        if path.extension().is_none_or(|extension| extension != "ez") {
+            continue;
+        }
+
+        let output = Command::new(EZLANG)
+            .arg("build")
+            .arg(&path)
+            .args(["--check-asm", "--asm-syntax", syntax])
+            .current_dir(&directory)
+            .output()
+            .expect("Can not run ezlang");
+
+        assert!(
+            output.status.success(),
+            "{} does not assemble with {}:\n{}",
+            path.display(),
+            assembler,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+}
+
+#[test]
+fn examples_assemble_with_nasm() {
+    check_examples("nasm", "nasm");
+}
+
+#[test]
+fn examples_assemble_with_gnu_as() {
+    check_examples("gas", "as");
+}
@@ -0,0 +1,151 @@
+use crate::common::check_exit;
+
+#[test]
+fn subtraction_is_left_associative() {
+    let source = "
+fn subtract: (a: i64, b: i64, c: i64) {
+    return a - b - c;
+}
+
+fn main: () {
+    return @subtract(10, 3, 2);
+}
+";
+
+    check_exit("subtraction_is_left_associative", source, 5);
+}
+
+#[test]
+fn operators_of_equal_precedence_apply_left_to_right() {
+    let source = "
+fn mix: (a: i64, b: i64, c: i64) {
+    return a - b + c + a * b / c;
+}
+
+fn main: () {
+    return @mix(20, 6, 4);
+}
+";
+
+    check_exit("operators_of_equal_precedence", source, 48);
+}
+
+#[test]
+fn division_of_runtime_operands() {
+    let source = "
+fn divide: (a: i64, b: i64, c: i64) {
+    return a / b / c;
+}
+
+fn main: () {
+    return @divide(100, 5, 2);
+}
+";
+
+    check_exit("division_of_runtime_operands", source, 10);
+}
+
+#[test]
+fn signed_division_truncates_toward_zero() {
+    let source = "
+fn divide: (a: i64, b: i64) {
+    return a / b;
+}
+
+fn main: () {
+    return @divide(0 - 7, 2) + 10;
+}
+";
+
+    check_exit("signed_division", source, 7);
+}
+
+#[test]
+fn unsigned_division_of_narrow_types() {
+    let source = "
+fn divide: (a: u8, b: u8) -> u8 {
+    return a / b;
+}
+
+fn main: () {
+    return @divide(250u8, 3u8) as i64;
+}
+";
+
+    check_exit("unsigned_division", source, 83);
+}
+
+#[test]
+fn division_with_the_divisor_in_either_register() {
+    let source = "
+fn divide: (a: i64, b: i64, c: i64) {
+    return (a + b) / (b - c) + a / (b * c);
+}
+
+fn main: () {
+    return @divide(90, 12, 2);
+}
+";
+
+    check_exit("division_in_either_register", source, 13);
+}
+
+#[test]
+fn operands_needing_both_registers_are_spilled() {
+    let source = "
+fn spill: (a: i64, b: i64, c: i64, d: i64, e: i64, f: i64) {
+    var x = ((a + b) * (c + d)) * ((e + f) - (a * c));
+    var y = (a * b + c * d) - (e * f - (a + b) * (c - d));
+    var z = (a - (b - (c - (d - (e - f))))) * ((a + b) * ((c + d) * (e + f)));
+    return x + y + z;
+}
+
+fn main: () {
+    return @spill(2, 3, 4, 5, 6, 7) + 1800;
+}
+";
+
+    // 225 - 21 - 1755 + 1800
+    check_exit("operands_are_spilled", source, 249);
+}
+
+#[test]
+fn spilled_operands_of_narrow_types() {
+    let source = "
+fn spill: (a: u8, b: u8, c: u8, d: u8) -> u8 {
+    return ((a + b) * (c + d)) - ((a * c) + (b * d));
+}
+
+fn main: () {
+    return @spill(200u8, 100u8, 7u8, 9u8) as i64;
+}
+";
+
+    check_exit("spilled_narrow_operands", source, 196);
+}
+
+#[test]
+fn calls_inside_expressions() {
+    let source = "
+fn twice: (a: i64) {
+    return a * 2;
+}
+
+fn add: (a: i64, b: i64) {
+    return a + b;
+}
+
+fn calls: (a: i64, b: i64, c: i64) {
+    var x = (a + b) * @twice(c) - @add(a * b, @twice(c - a)) * (b + c);
+    var y = @add(@twice(a) * (b + c), (a + b) * @twice(c)) / (a + @add(b, 1));
+    return x + y;
+}
+
+fn main: () {
+    return @calls(3, 4, 5) + 100;
+}
+";
+
+    // 70 - 144 + 124 / 8 + 100
+    check_exit("calls_inside_expressions", source, 41);
+}
@@ -0,0 +1,97 @@
+use std::{fs::write, process::Command};
+
+use crate::common::{build, build_error, write_source};
+
+// Extern functions are called like C, so an object compiled from C gets its arguments in
+// registers and may leave the high bits of a narrow result unset
+#[test]
+fn extern_functions_written_in_c() {
+    let c_source = "
+#include <stdint.h>
+
+int64_t c_combine(int64_t a, int64_t b, int64_t c, int64_t d, int64_t e, int64_t f) {
+    return a + 2 * b + 3 * c + 4 * d + 5 * e + 6 * f;
+}
+
+double c_scale(int64_t n, double x, double y) {
+    return n * x + y;
+}
+
+uint8_t c_byte(void) {
+    return 128;
+}
+";
+
+    let source = "
+extern fn c_combine: (a: i64, b: i64, c: i64, d: i64, e: i64, f: i64) -> i64;
+extern fn c_scale: (n: i64, x: f64, y: f64) -> f64;
+extern fn c_byte: () -> u8;
+
+fn main: () {
+    var combined = @c_combine(1, 2, 3, 4, 5, 6);
+    var scaled = @c_scale(3, 2.5, 0.5) as i64;
+    var byte = @c_byte() as i64;
+    return combined - 91 + scaled - 8 + byte;
+}
+";
+
+    let name = "extern_functions_written_in_c";
+    let directory = write_source(name, source)
+        .parent()
+        .expect("Unreachable")
+        .to_path_buf();
+
+    write(directory.join("c_functions.c"), c_source).expect("Can not write the C source");
+
+    let compiled = Command::new("cc")
+        .args(["-c", "-O2", "c_functions.c", "-o", "c_functions.o"])
+        .current_dir(&directory)
+        .status();
+
+    match compiled {
+        Ok(status) => assert!(status.success(), "cc failed to compile the C functions"),
+        Err(_) => {
+            eprintln!("cc is not installed, skipping the C functions");
+            return;
+        }
+    }
+
+    let output = build(name, source, &["--link", "c_functions.o"]);
+
+    assert!(
+        output.status.success(),
+        "{} failed to build:\n{}",
+        name,
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let status = Command::new(directory.join(name))
+        .status()
+        .expect("Can not run the program");
+
+    assert_eq!(status.code(), Some(128));
+}
+
+#[test]
+fn extern_functions_without_a_c_signature() {
+    let source = "
+struct Point {
+    x: i64,
+    y: i64,
+}
+
+extern fn length: (point: Point) -> i64;
+
+fn main: () {
+    return 0;
+}
+";
+
+    let error = build_error("extern_functions_without_a_c_signature", source);
+
+    assert!(
+        error.contains("extern_functions_without_a_c_signature.ez:7:11: Extern function length can not be called like C"),
+        "{}",
+        error
+    );
+}
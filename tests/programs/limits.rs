@@ -0,0 +1,276 @@
+use crate::common::{build, build_error, check_exit};
+
+fn write_chain(terms: usize) -> String {
+    return format!(
+        "
+fn add: (a: i64) {{
+    return {};
+}}
+
+fn main: () {{
+    return @add(1);
+}}
+",
+        vec!["a"; terms].join(" + ")
+    );
+}
+
+#[test]
+fn long_flat_chain_is_not_nested() {
+    check_exit("long_flat_chain", &write_chain(300), 44);
+}
+
+#[test]
+fn chain_longer_than_the_limit() {
+    let error = build_error("chain_longer_than_the_limit", &write_chain(1100));
+
+    assert!(
+        error.contains("error[E0016]: Expression too long, it chains more than 1024 operations.")
+    );
+}
+
+// Lexing stops at the limit, the invalid token after it is never reached
+#[test]
+fn tokens_over_the_limit() {
+    let source = "
+fn main: () {
+    return 0;
+}
+`
+";
+
+    let output = build("tokens_over_the_limit", source, &["--max-tokens", "8"]);
+    let error = String::from_utf8_lossy(&output.stderr);
+
+    assert!(
+        error.contains(
+            "tokens_over_the_limit.ez:3:13: error[E0017]: Program too large, it has more than 8 tokens."
+        ),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn parentheses_nested_deeper_than_the_limit() {
+    let source = format!(
+        "
+fn main: () {{
+    return {}1{};
+}}
+",
+        "(".repeat(300),
+        ")".repeat(300)
+    );
+
+    let error = build_error("parentheses_deeper_than_the_limit", &source);
+
+    assert!(error.contains("nests more than 256 levels of parentheses and calls."));
+}
+
+#[test]
+fn calls_nested_deeper_than_the_limit() {
+    let source = format!(
+        "
+fn id: (a: i64) {{
+    return a;
+}}
+
+fn main: () {{
+    return {}1{};
+}}
+",
+        "@id(".repeat(300),
+        ")".repeat(300)
+    );
+
+    let error = build_error("calls_deeper_than_the_limit", &source);
+
+    assert!(error.contains("nests more than 256 levels of parentheses and calls."));
+}
+
+// Synthetic inputs nested far past the limits, deep enough to overflow the stack of a parser
+// that recursed before checking the depth
+const DEEP_NESTING: usize = 200_000;
+
+#[test]
+fn deeply_nested_parentheses() {
+    let source = format!(
+        "
+fn main: () {{
+    return {}1{};
+}}
+",
+        "(".repeat(DEEP_NESTING),
+        ")".repeat(DEEP_NESTING)
+    );
+
+    let error = build_error("deeply_nested_parentheses", &source);
+
+    assert!(
+        error.contains("error[E0016]: Expression too complex, it nests more than 256 levels"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn deeply_nested_pointer_types() {
+    let source = format!(
+        "
+fn main: () {{
+    var pointer: {}i64;
+    return 0;
+}}
+",
+        "*".repeat(DEEP_NESTING)
+    );
+
+    let error = build_error("deeply_nested_pointer_types", &source);
+
+    assert!(
+        error.contains("Type too complex, it nests more than 64 pointers and arrays."),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn deeply_nested_array_types() {
+    let source = format!(
+        "
+fn main: () {{
+    var array: {}i64{};
+    return 0;
+}}
+",
+        "[".repeat(DEEP_NESTING),
+        "; 1]".repeat(DEEP_NESTING)
+    );
+
+    let error = build_error("deeply_nested_array_types", &source);
+
+    assert!(
+        error.contains("Type too complex, it nests more than 64 pointers and arrays."),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn types_nested_up_to_the_limit() {
+    let source = format!(
+        "
+fn main: () {{
+    var array: [[[i64; 2]; 2]; 2];
+    array[1][1][1] = 5;
+    var pointer: {pointer}i64 = 0 as {pointer}i64;
+    return array[1][1][1] + (pointer as i64);
+}}
+",
+        pointer = "*".repeat(64)
+    );
+
+    check_exit("types_nested_up_to_the_limit", &source, 5);
+}
+
+#[test]
+fn array_types_larger_than_the_address_range() {
+    let source = "
+struct Big {
+    data: [u64; 4611686018427387904],
+}
+
+fn main: () {
+    return @sizeof(Big);
+}
+";
+
+    let error = build_error("array_types_too_large", source);
+
+    assert!(
+        error.contains(
+            "array_types_too_large.ez:3:11: Type too large, it takes more than 1073741824 bytes."
+        ),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn array_lengths_too_large_or_negative() {
+    let source = "
+fn main: () {
+    var a: [u8; 18446744073709551615];
+    return 0;
+}
+";
+
+    let error = build_error("array_length_too_large", source);
+
+    assert!(
+        error.contains("array_length_too_large.ez:3:12: Array length is too large."),
+        "{}",
+        error
+    );
+
+    let source = "
+fn main: () {
+    var a: [u8; 0 - 1];
+    return 0;
+}
+";
+
+    let error = build_error("array_length_negative", source);
+
+    assert!(
+        error.contains("array_length_negative.ez:3:12: Array lengths can not be negative."),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn structs_larger_than_the_address_range() {
+    let source = "
+struct Big {
+    first: [u8; 1000000000],
+    second: [u8; 1000000000],
+}
+
+fn main: () {
+    return @sizeof(Big);
+}
+";
+
+    let error = build_error("structs_too_large", source);
+
+    assert!(
+        error.contains(
+            "structs_too_large.ez:4:5: Type too large, it takes more than 1073741824 bytes."
+        ),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn frames_larger_than_the_address_range() {
+    let source = "
+fn main: () {
+    var first: [u8; 1000000000];
+    var second: [u8; 1000000000];
+    return 0;
+}
+";
+
+    let error = build_error("frames_too_large", source);
+
+    assert!(
+        error.contains(
+            "error[E0017]: Function main too large, its variables take more than 1073741824 bytes."
+        ),
+        "{}",
+        error
+    );
+}
@@ -0,0 +1,35 @@
+use crate::common::build;
+
+#[test]
+fn flat_chains_do_not_nest() {
+    let source = format!(
+        "
+fn main: () {{
+    var a = 1;
+    return {};
+}}
+",
+        vec!["a"; 20].join(" + ")
+    );
+
+    let output = build("flat_chains_do_not_nest", &source, &[]);
+
+    assert!(output.status.success());
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("deep-nesting"));
+}
+
+#[test]
+fn nested_operations_are_reported() {
+    let source = "
+fn main: () {
+    var a = 1;
+    return a + (a * (a + (a * (a + (a * (a + (a * (a + a))))))));
+}
+";
+
+    let output = build("nested_operations_are_reported", source, &[]);
+
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("warning[deep-nesting]: Expression nests 10 levels, more than 8."));
+}
@@ -0,0 +1,14 @@
+// Programs checked end to end, run with `cargo test`. Each one is compiled with the ezlang
+// binary and assembled with GNU as, so only binutils is needed besides cargo, and interpreted
+// with `ezlang run`. Both must exit with the value main returns.
+mod common;
+mod debugger;
+mod diagnostics;
+mod examples;
+mod expressions;
+mod interop;
+mod limits;
+mod lints;
+mod reproducible;
+mod strings;
+mod wasm;
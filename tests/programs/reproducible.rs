@@ -0,0 +1,67 @@
+use std::{fs::read, path::PathBuf};
+
+use crate::common::build;
+
+// Strings, statics and several functions, which codegen emits as tables of the whole program
+const SOURCE: &str = "
+struct Point {
+    x: i64,
+    y: i64,
+}
+
+const LIMIT = 10;
+
+fn count: () {
+    var calls: i64 @static;
+    calls = calls + 1;
+    return calls;
+}
+
+fn greet: (name: *u8, length: i64) {
+    @write(1, name, length);
+    @write(1, \"\\n\", 1);
+    return 0;
+}
+
+fn main: () {
+    var point = Point { x: 3, y: 4 };
+    @greet(\"one\" as *u8, 3);
+    @greet(\"two\" as *u8, 3);
+    @greet(\"three\" as *u8, 5);
+    @printf(\"{} {} {}\\n\", point.x, point.y, 1.5);
+    @count();
+    return @count() + LIMIT;
+}
+";
+
+// Assembly and executable of a build with --reproducible
+fn build_outputs(name: &str) -> (Vec<u8>, Vec<u8>) {
+    let output = build(name, SOURCE, &["--reproducible"]);
+
+    assert!(
+        output.status.success(),
+        "{} failed to build:\n{}",
+        name,
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let directory = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+
+    let assembly = read(directory.join(format!("{}.s", name))).expect("Can not read the assembly");
+    let executable = read(directory.join(name)).expect("Can not read the executable");
+
+    return (assembly, executable);
+}
+
+#[test]
+fn builds_are_byte_for_byte_identical() {
+    // Every build runs in a process of its own, with hash maps seeded differently
+    let (first_assembly, first_executable) = build_outputs("reproducible_build");
+    let (second_assembly, second_executable) = build_outputs("reproducible_build");
+
+    assert!(first_assembly == second_assembly, "the assembly differs");
+    assert!(
+        first_executable == second_executable,
+        "the executable differs"
+    );
+}
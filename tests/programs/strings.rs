@@ -0,0 +1,85 @@
+use ezlang::{
+    compile_to_asm,
+    compiler::{Compiler, CompilerOptions},
+    gas::AsmSyntax,
+};
+
+use crate::common::build_error;
+
+// Every escape the lexer knows, each one encoded to its bytes in the string table
+const SOURCE: &str = "
+fn main: () {
+    @write(1, \"a\\n\\t\\\\\\\"\\0\\x41\\u{e9}z\", 10);
+    return 0;
+}
+";
+
+const BYTES: &str = "0x61, 0xa, 0x9, 0x5c, 0x22, 0x0, 0x41, 0xc3, 0xa9, 0x7a, 0x0";
+
+#[test]
+fn escapes_in_nasm() {
+    let assembly = match compile_to_asm(SOURCE) {
+        Ok(assembly) => assembly,
+        Err(diagnostics) => panic!("{}", diagnostics[0].message),
+    };
+
+    assert!(assembly.contains(&format!("db {}", BYTES)), "{}", assembly);
+}
+
+#[test]
+fn escapes_in_gas() {
+    let options = CompilerOptions {
+        asm_syntax: AsmSyntax::Gas,
+        ..Default::default()
+    };
+
+    let mut compiler = Compiler::from_source("input.ez", SOURCE.as_bytes().to_vec(), options);
+    compiler.generate();
+
+    let assembly = compiler.get_assembly();
+
+    assert!(
+        assembly.contains(&format!(".byte {}", BYTES)),
+        "{}",
+        assembly
+    );
+}
+
+fn check_escape_error(name: &str, escape: &str, expected: &str) {
+    let source = format!(
+        "
+fn main: () {{
+    @write(1, \"{}\", 1);
+    return 0;
+}}
+",
+        escape
+    );
+
+    let error = build_error(name, &source);
+
+    assert!(
+        error.contains(&format!("{}.ez:3:16: {}", name, expected)),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn unknown_escape() {
+    check_escape_error("unknown_escape", "\\q", "Unknown escape sequence \\q");
+}
+
+#[test]
+fn invalid_escapes() {
+    check_escape_error(
+        "short_hexadecimal_escape",
+        "\\x4",
+        "Expected two hexadecimal digits after \\x",
+    );
+    check_escape_error(
+        "invalid_unicode_escape",
+        "\\u{110000}",
+        "Invalid unicode character \\u{110000}",
+    );
+}
@@ -0,0 +1,52 @@
+use std::{
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+const TARGET: &str = "wasm32-unknown-unknown";
+
+// Whether rustup installed the standard library of the target, nothing builds for it without
+fn is_target_installed() -> bool {
+    let output = Command::new("rustc")
+        .args(["--print", "target-libdir", "--target", TARGET])
+        .stderr(Stdio::null())
+        .output();
+
+    return match output {
+        Ok(output) => PathBuf::from(String::from_utf8_lossy(&output.stdout).trim()).is_dir(),
+        Err(_) => false,
+    };
+}
+
+// Nothing else runs the bindings on the target they exist for, where panics abort
+#[test]
+fn bindings_build_for_wasm() {
+    if !is_target_installed() {
+        eprintln!("{} is not installed, skipping the wasm build", TARGET);
+        return;
+    }
+
+    let output = Command::new(env!("CARGO"))
+        .args([
+            "build",
+            "--lib",
+            "--no-default-features",
+            "--features",
+            "wasm",
+        ])
+        .args(["--target", TARGET])
+        .env(
+            "CARGO_TARGET_DIR",
+            PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("wasm"),
+        )
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("Can not run cargo");
+
+    assert!(
+        output.status.success(),
+        "The bindings do not build for {}:\n{}",
+        TARGET,
+        String::from_utf8_lossy(&output.stderr)
+    );
+}